@@ -51,6 +51,9 @@ pub fn make_dummy_item() -> syn::Item {
     syn::Item::Verbatim(TokenStream2::new())
 }
 
+// This must stay in sync with MAX_HOOK_INSTANCES in riri_mod_tools_rt::hook_instance!
+pub const MAX_HOOK_INSTANCES: usize = 8;
+
 pub struct Reloaded2CSharpHook {
     is_user_defined_init: bool
 }
@@ -67,40 +70,127 @@ impl Reloaded2CSharpHook {
     pub fn make_vtbl_ptr_string(s: &str) -> String { format!("__VTBL_PTR_{}", s) }
     pub fn make_user_cb_string(s: &str) -> String { format!("__USER_{}", s) }
     pub fn make_user_set_string(s: &str) -> String { format!("__USER_SET_{}", s) }
+    pub fn make_user_remove_string(s: &str) -> String { format!("__USER_REMOVE_{}", s) }
+    pub fn make_user_remove_set_string(s: &str) -> String { format!("__USER_SET_REMOVE_{}", s) }
+    pub fn make_hook_create_string(s: &str) -> String { format!("__HOOK_CREATE_{}", s) }
+    pub fn make_hook_toggle_string(s: &str) -> String { format!("__HOOK_TOGGLE_{}", s) }
+    pub fn make_hook_toggle_set_string(s: &str) -> String { format!("__HOOK_SET_TOGGLE_{}", s) }
     pub fn get_target_abi() -> TokenStream2 { quote! { extern "C" }}
 
     pub(crate) fn is_user_defined(&self) -> bool { self.is_user_defined_init }
 }
 
 impl Reloaded2CSharpHook {
-    fn create_set_function(&mut self, ty: &TokenStream2, set_name: &str, hooked_name: &syn::Ident) -> TokenStream2 {
+    // symbols registers addresses with the crash handler after the value is set
+    fn create_set_function(&mut self, ty: &TokenStream2, set_name: &str, hooked_name: &syn::Ident, symbols: TokenStream2) -> TokenStream2 {
         let set_cell_fn_name = syn::Ident::new(Self::make_hook_set_string(set_name).as_str(), hooked_name.span());
         let fn_target_abi = Self::get_target_abi(); 
         quote! {
             #[no_mangle]
             #[doc(hidden)]
+            #[allow(clippy::unnecessary_cast)]
             pub unsafe #fn_target_abi fn #set_cell_fn_name(cb: #ty) {
                 let _ = #hooked_name.set(cb);
+                #symbols
             }
         }
     }
 
-    fn create_user_set_function(&mut self, set_name: &str, hooked_name: &syn::Ident) -> TokenStream2 {
-        let set_cell_fn_name = syn::Ident::new(Self::make_user_set_string(set_name).as_str(), hooked_name.span());
+    fn register_symbol(name: &str, address: TokenStream2) -> TokenStream2 {
+        quote! { riri_mod_tools_rt::crash_handler::register_symbol(#name, #address as usize); }
+    }
+
+    fn create_user_set_function(&mut self, set_cell_fn_name: &str, ty: &TokenStream2, hooked_name: &syn::Ident) -> TokenStream2 {
+        let set_cell_fn_name = syn::Ident::new(set_cell_fn_name, hooked_name.span());
         let fn_target_abi = Self::get_target_abi();
         quote! {
             #[no_mangle]
             #[doc(hidden)]
-            pub unsafe #fn_target_abi fn #set_cell_fn_name(cb: extern "C" fn (usize) -> ()) {
+            pub unsafe #fn_target_abi fn #set_cell_fn_name(cb: #ty) {
                 let _ = #hooked_name.set(cb);
             }
         }
     }
 
-    fn replace_original_function_unchecked(expr: &syn::ExprMacro, fn_name: &syn::Ident) -> Option<syn::Result<syn::Expr>> {
+    // Each instance of a user defined hook has its own entry point, which marks the instance as
+    // current so that original_function! calls that instance's original function. create_hook!
+    // calls this function.
+    fn create_user_create_function(&mut self, f: &syn::ItemFn, fn_ty: &TokenStream2, fn_args_tk: &[TokenStream2],
+        fn_ret_tk: &TokenStream2, fn_name_upper: &str, og_name: &syn::Ident) -> TokenStream2 {
+        let create_fn_name = syn::Ident::new(Self::make_hook_create_string(fn_name_upper).as_str(), f.span());
+        let user_name = syn::Ident::new(Self::make_user_cb_string(fn_name_upper).as_str(), f.span());
+        let remove_name = syn::Ident::new(Self::make_user_remove_string(fn_name_upper).as_str(), f.span());
+        let fn_ident = &f.sig.ident;
+        let fn_name = fn_ident.to_string();
+        let arg_names: Vec<syn::Ident> = (0..fn_args_tk.len()).map(|i| format_ident!("__riri_arg{}", i)).collect();
+        let entries = (0..MAX_HOOK_INSTANCES).map(|i| {
+            let slot = proc_macro2::Literal::usize_unsuffixed(i);
+            quote! {{
+                #[allow(unused_unsafe)]
+                extern "C" fn entry(#(#arg_names: #fn_args_tk),*) -> #fn_ret_tk {
+                    riri_mod_tools_rt::hook_instance::enter(&#og_name, #slot, || unsafe { #fn_ident(#(#arg_names),*) })
+                }
+                entry
+            }}
+        });
+        quote! {
+            #[doc(hidden)]
+            #[allow(non_snake_case)]
+            pub fn #create_fn_name(address: usize) -> Option<riri_mod_tools_rt::hook_instance::HookHandle> {
+                const ENTRIES: [#fn_ty; riri_mod_tools_rt::hook_instance::MAX_HOOK_INSTANCES] = [#(#entries),*];
+                riri_mod_tools_rt::hook_instance::create_hook(#fn_name, address, &#og_name,
+                    &ENTRIES.map(|e| e as usize), #user_name.get(), #remove_name.get())
+            }
+        }
+    }
+
+    // The C# host sets a callback which enables or disables the hook's IHook, which lets Rust
+    // code toggle the hook with {hook_name}::enable() and {hook_name}::disable()
+    fn create_toggle_functions(&mut self, fn_ident: &syn::Ident, fn_name_upper: &str) -> TokenStream2 {
+        let toggle_name = syn::Ident::new(Self::make_hook_toggle_string(fn_name_upper).as_str(), fn_ident.span());
+        let toggle_set_name = syn::Ident::new(Self::make_hook_toggle_set_string(fn_name_upper).as_str(), fn_ident.span());
+        let fn_target_abi = Self::get_target_abi();
+        let fn_name = fn_ident.to_string();
+        let enable_doc = format!("Enable the `{}` hook. Returns false if the hook hasn't been created.", fn_name);
+        let disable_doc = format!("Disable the `{}` hook, so the original function runs without calling the hook. \
+            Returns false if the hook hasn't been created.", fn_name);
+        quote! {
+            #[doc(hidden)]
+            pub static #toggle_name: ::std::sync::OnceLock<extern "C" fn (u8) -> u8> = ::std::sync::OnceLock::new();
+            #[no_mangle]
+            #[doc(hidden)]
+            pub unsafe #fn_target_abi fn #toggle_set_name(cb: extern "C" fn (u8) -> u8) {
+                let _ = #toggle_name.set(cb);
+            }
+            #[allow(non_snake_case)]
+            pub mod #fn_ident {
+                #[doc = #enable_doc]
+                pub fn enable() -> bool {
+                    riri_mod_tools_rt::hook_registry::toggle_hook(#fn_name, super::#toggle_name.get(), true)
+                }
+                #[doc = #disable_doc]
+                pub fn disable() -> bool {
+                    riri_mod_tools_rt::hook_registry::toggle_hook(#fn_name, super::#toggle_name.get(), false)
+                }
+                pub fn is_enabled() -> bool {
+                    riri_mod_tools_rt::hook_registry::is_hook_enabled(#fn_name)
+                }
+            }
+        }
+    }
+
+    // If the original function isn't set (an optional hook wasn't found, but the payload was
+    // called from Rust), log an error and return the return type's FallbackValue instead of
+    // panicking
+    fn replace_original_function_unchecked(expr: &syn::ExprMacro, fn_name: &syn::Ident, hook_name: &str) -> Option<syn::Result<syn::Expr>> {
         if expr.mac.path.is_ident("original_function") {
             let arg_tokens = &expr.mac.tokens;
-            Some(Ok(syn::Expr::parse.parse2(quote! { (#fn_name.get().unwrap())(#arg_tokens) }).unwrap()))
+            Some(Ok(syn::Expr::parse.parse2(quote! {
+                match #fn_name.get() {
+                    Some(__riri_og) => __riri_og(#arg_tokens),
+                    None => riri_mod_tools_rt::hook_registry::missing_original(#hook_name)
+                }
+            }).unwrap()))
         } else {
             None
         }
@@ -127,11 +217,11 @@ impl Reloaded2CSharpHook {
         }
     }
 
-    fn traverse_expression(expr: &mut syn::Expr, fn_name: &syn::Ident) {
+    fn traverse_expression(expr: &mut syn::Expr, fn_name: &syn::Ident, hook_name: &str) {
         match expr {
             // Direct invocation
             syn::Expr::Macro(m) => {
-                if let Some(r) = Self::replace_original_function_unchecked(&m, fn_name) {
+                if let Some(r) = Self::replace_original_function_unchecked(&m, fn_name, hook_name) {
                     if let Ok(v) = r { *expr = v }
                 } 
                 /* 
@@ -141,77 +231,77 @@ impl Reloaded2CSharpHook {
                 */
             },
             // blocked scope
-            syn::Expr::Block(b) => Self::traverse_statements(&mut b.block.stmts, fn_name),
+            syn::Expr::Block(b) => Self::traverse_statements(&mut b.block.stmts, fn_name, hook_name),
             // unsafe blocked scope
-            syn::Expr::Unsafe(u) => Self::traverse_statements(&mut u.block.stmts, fn_name),
+            syn::Expr::Unsafe(u) => Self::traverse_statements(&mut u.block.stmts, fn_name, hook_name),
             // binary operations (e.g arithmetic)
             syn::Expr::Binary(b) => {
-                Self::traverse_expression(&mut b.left, fn_name);
-                Self::traverse_expression(&mut b.right, fn_name);
+                Self::traverse_expression(&mut b.left, fn_name, hook_name);
+                Self::traverse_expression(&mut b.right, fn_name, hook_name);
             },
             // Cast operation
-            syn::Expr::Cast(c) => Self::traverse_expression(&mut c.expr, fn_name),
+            syn::Expr::Cast(c) => Self::traverse_expression(&mut c.expr, fn_name, hook_name),
             // Parenthesized expressions
-            syn::Expr::Paren(p) => Self::traverse_expression(&mut p.expr, fn_name),
+            syn::Expr::Paren(p) => Self::traverse_expression(&mut p.expr, fn_name, hook_name),
             syn::Expr::Tuple(t) => {
                 for elem in &mut t.elems {
-                    Self::traverse_expression(elem, fn_name);
+                    Self::traverse_expression(elem, fn_name, hook_name);
                 }
             },
             // Unary operation (e.g dereferencing)
-            syn::Expr::Unary(u) => Self::traverse_expression(&mut u.expr, fn_name),
+            syn::Expr::Unary(u) => Self::traverse_expression(&mut u.expr, fn_name, hook_name),
             // Address-of
-            syn::Expr::RawAddr(r) => Self::traverse_expression(&mut r.expr, fn_name),
+            syn::Expr::RawAddr(r) => Self::traverse_expression(&mut r.expr, fn_name, hook_name),
             // Reference
-            syn::Expr::Reference(r) => Self::traverse_expression(&mut r.expr, fn_name),
+            syn::Expr::Reference(r) => Self::traverse_expression(&mut r.expr, fn_name, hook_name),
             // If-then-else
             syn::Expr::If(i) => {
-                Self::traverse_expression(&mut i.cond, fn_name);
-                Self::traverse_statements(&mut i.then_branch.stmts, fn_name);
+                Self::traverse_expression(&mut i.cond, fn_name, hook_name);
+                Self::traverse_statements(&mut i.then_branch.stmts, fn_name, hook_name);
                 if i.else_branch.is_some() {
-                    Self::traverse_expression(i.else_branch.as_mut().unwrap().1.as_mut(), fn_name);
+                    Self::traverse_expression(i.else_branch.as_mut().unwrap().1.as_mut(), fn_name, hook_name);
                 }
             },
             // For loop
             syn::Expr::ForLoop(f) => {
-                Self::traverse_expression(&mut f.expr, fn_name);
-                Self::traverse_statements(&mut f.body.stmts, fn_name);
+                Self::traverse_expression(&mut f.expr, fn_name, hook_name);
+                Self::traverse_statements(&mut f.body.stmts, fn_name, hook_name);
             },
             // Conditinless loop
             syn::Expr::Loop(l) => {
-                Self::traverse_statements(&mut l.body.stmts, fn_name);
+                Self::traverse_statements(&mut l.body.stmts, fn_name, hook_name);
             },
             // While loop
             syn::Expr::While(w) => {
-                Self::traverse_expression(&mut w.cond, fn_name);
-                Self::traverse_statements(&mut w.body.stmts, fn_name);
+                Self::traverse_expression(&mut w.cond, fn_name, hook_name);
+                Self::traverse_statements(&mut w.body.stmts, fn_name, hook_name);
             },
             // Function call
             syn::Expr::Call(c) => {
                 for arg in &mut c.args {
-                    Self::traverse_expression(arg, fn_name);
+                    Self::traverse_expression(arg, fn_name, hook_name);
                 }
             },
             // Method call
             syn::Expr::MethodCall(m) => {
-                Self::traverse_expression(&mut m.receiver, fn_name);
+                Self::traverse_expression(&mut m.receiver, fn_name, hook_name);
                 for arg in &mut m.args {
-                    Self::traverse_expression(arg, fn_name);
+                    Self::traverse_expression(arg, fn_name, hook_name);
                 }
             },
             // Match
             syn::Expr::Match(m) => {
-                Self::traverse_expression(&mut m.expr, fn_name);
+                Self::traverse_expression(&mut m.expr, fn_name, hook_name);
                 for arm in &mut m.arms {
                     if arm.guard.is_some() {
-                        Self::traverse_expression(arm.guard.as_mut().unwrap().1.as_mut(), fn_name);
+                        Self::traverse_expression(arm.guard.as_mut().unwrap().1.as_mut(), fn_name, hook_name);
                     }
-                    Self::traverse_expression(arm.body.as_mut(), fn_name);
+                    Self::traverse_expression(arm.body.as_mut(), fn_name, hook_name);
                 }
             },
             // Closure
             syn::Expr::Closure(c) => {
-                Self::traverse_expression(&mut c.body, fn_name);
+                Self::traverse_expression(&mut c.body, fn_name, hook_name);
             },
             _ => ()
         }
@@ -219,26 +309,81 @@ impl Reloaded2CSharpHook {
 
     // Search statement list for invocations of original_function! and replace it with our hooked
     // pointer
-    pub(crate) fn traverse_statements(stmts: &mut Vec<syn::Stmt>, fn_name: &syn::Ident) {
+    pub(crate) fn traverse_statements(stmts: &mut Vec<syn::Stmt>, fn_name: &syn::Ident, hook_name: &str) {
         for stmt in stmts {
             match stmt {
                 syn::Stmt::Local(l) => {
                     match &mut l.init {
                         Some(v) => {
-                            Self::traverse_expression(&mut v.expr, fn_name);
+                            Self::traverse_expression(&mut v.expr, fn_name, hook_name);
                             if v.diverge.is_some() {
-                                Self::traverse_expression(v.diverge.as_mut().unwrap().1.as_mut(), fn_name);
+                                Self::traverse_expression(v.diverge.as_mut().unwrap().1.as_mut(), fn_name, hook_name);
                             }
                         },
                         None => continue
                     }
                 },
-                syn::Stmt::Expr(e, _) => Self::traverse_expression(e, fn_name),
+                syn::Stmt::Expr(e, _) => Self::traverse_expression(e, fn_name, hook_name),
                 _ => continue
             }
         }
     }
 
+    // Run the function body inside of catch_panic so that panics don't unwind into the caller.
+    // When a panic is caught, call the original function (if there is one) or return the return
+    // type's FallbackValue depending on the panic policy. Hooks skip straight to the original
+    // function if a required hook failed. Arguments that aren't bound to a name are renamed so
+    // that they can be passed to the original function, and are cloned before the body runs since
+    // the body can move them.
+    pub(crate) fn guard_function_body(f: &mut syn::ItemFn, original: Option<&syn::Ident>) -> syn::Result<()> {
+        let fn_name = f.sig.ident.to_string();
+        let mut arg_names: Vec<syn::Ident> = vec![];
+        let mut arg_bindings: Vec<TokenStream2> = vec![];
+        for (i, fn_arg) in f.sig.inputs.iter_mut().enumerate() {
+            let arg = match fn_arg {
+                syn::FnArg::Typed(t) => t,
+                syn::FnArg::Receiver(r) => return Err(syn::Error::new(r.span(), "Self argument isn't supported for hookable functions"))
+            };
+            match arg.pat.as_ref() {
+                syn::Pat::Ident(p) if p.by_ref.is_none() && p.subpat.is_none() => arg_names.push(p.ident.clone()),
+                pat => {
+                    let name = format_ident!("__riri_arg{}", i);
+                    arg_bindings.push(quote! { let #pat = #name; });
+                    *arg.pat = syn::Pat::parse_single.parse2(quote! { #name })?;
+                    arg_names.push(name);
+                }
+            }
+        }
+        let (call_original, bypass) = match original {
+            Some(og) => {
+                let og_arg_names: Vec<syn::Ident> = (0..arg_names.len()).map(|i| format_ident!("__riri_og_arg{}", i)).collect();
+                (quote! {
+                    Err(riri_mod_tools_rt::panic_guard::PanicPolicy::CallOriginal) if #og.get().is_some() => (#og.get().unwrap())(#(#og_arg_names),*),
+                }, quote! {
+                    // A required hook is missing, so act as if this hook doesn't exist
+                    if riri_mod_tools_rt::hook_registry::required_hook_failed() {
+                        if let Some(__riri_og) = #og.get() { return __riri_og(#(#arg_names),*); }
+                    }
+                    #(let #og_arg_names = ::std::clone::Clone::clone(&#arg_names);)*
+                })
+            },
+            None => (quote! {}, quote! {})
+        };
+        let block = &f.block;
+        *f.block = syn::parse2(quote! {{
+            #bypass
+            match riri_mod_tools_rt::panic_guard::catch_panic(#fn_name, || {
+                #(#arg_bindings)*
+                #block
+            }) {
+                Ok(v) => v,
+                #call_original
+                Err(_) => riri_mod_tools_rt::panic_guard::FallbackValue::fallback_value()
+            }
+        }})?;
+        Ok(())
+    }
+
     fn get_type_name(ty: &syn::Type) -> syn::Result<TokenStream2> {
         match ty {
             syn::Type::Path(p) => Ok(p.path.to_token_stream()),
@@ -286,27 +431,57 @@ impl HookFramework for Reloaded2CSharpHook {
             syn::ReturnType::Type(_, t) => t.to_token_stream()
         };
         let fn_ty = quote! { extern "C" fn (#(#fn_args_tk),*) -> #fn_ret_tk };
-        let fn_og_tk = quote! {
-            #[doc(hidden)]
-            pub static #ptr_fn_name: ::std::sync::OnceLock<#fn_ty> = ::std::sync::OnceLock::new();
+        // User defined hooks can have multiple instances, which each have their own original
+        // function
+        let fn_og_tk = match self.is_user_defined_init {
+            true => quote! {
+                #[doc(hidden)]
+                pub static #ptr_fn_name: riri_mod_tools_rt::hook_instance::HookInstances<#fn_ty> = riri_mod_tools_rt::hook_instance::HookInstances::new();
+            },
+            false => quote! {
+                #[doc(hidden)]
+                pub static #ptr_fn_name: ::std::sync::OnceLock<#fn_ty> = ::std::sync::OnceLock::new();
+            }
         };
-        let fn_set_og_tk = self.create_set_function(&fn_ty, &fn_name_upper, &ptr_fn_name);
-        Self::traverse_statements(&mut f.block.stmts, &ptr_fn_name);
+        let fn_ident = &f.sig.ident;
+        let fn_name = fn_ident.to_string();
+        let fn_symbols = [
+            Self::register_symbol(&fn_name, quote! { #fn_ident as *const () }),
+            Self::register_symbol(&format!("{} (original)", fn_name), quote! { cb })
+        ];
+        let fn_set_og_tk = self.create_set_function(&fn_ty, &fn_name_upper, &ptr_fn_name, quote! { #(#fn_symbols)* });
+        let fn_toggle_tk = self.create_toggle_functions(fn_ident, &fn_name_upper);
+        Self::traverse_statements(&mut f.block.stmts, &ptr_fn_name, &fn_name);
+        Self::guard_function_body(f, Some(&ptr_fn_name))?;
         let fk = f.to_token_stream();
 
         // If this function is not hooked at init, but instead by user code (within Rust)
         // create another global to store the function pointer to call a hook on it
         let user_tk = if self.is_user_defined_init {
             let user_fn_name = syn::Ident::new(Self::make_user_cb_string(&fn_name_upper).as_str(), f.span());
-            let user_glb_ty = quote! { extern "C" fn (usize) -> () };
+            let user_glb_ty = quote! { extern "C" fn (usize, usize, u32, *mut usize) -> u8 };
             let user_glb_tk = quote! {
                 #[doc(hidden)]
                 pub static #user_fn_name: ::std::sync::OnceLock<#user_glb_ty> = ::std::sync::OnceLock::new();
             };
-            let user_set_tk = self.create_user_set_function(&fn_name_upper, &user_fn_name);
+            let user_set_tk = self.create_user_set_function(
+                &Self::make_user_set_string(&fn_name_upper), &user_glb_ty, &user_fn_name);
+            let remove_fn_name = syn::Ident::new(Self::make_user_remove_string(&fn_name_upper).as_str(), f.span());
+            let remove_glb_ty = quote! { extern "C" fn (u32) -> u8 };
+            let remove_glb_tk = quote! {
+                #[doc(hidden)]
+                pub static #remove_fn_name: ::std::sync::OnceLock<#remove_glb_ty> = ::std::sync::OnceLock::new();
+            };
+            let remove_set_tk = self.create_user_set_function(
+                &Self::make_user_remove_set_string(&fn_name_upper), &remove_glb_ty, &remove_fn_name);
+            let create_tk = self.create_user_create_function(
+                f, &fn_ty, &fn_args_tk, &fn_ret_tk, &fn_name_upper, &ptr_fn_name);
             quote! {
                 #user_glb_tk
                 #user_set_tk
+                #remove_glb_tk
+                #remove_set_tk
+                #create_tk
             }
         } else { quote! {} };
         Ok(quote! {
@@ -315,6 +490,7 @@ impl HookFramework for Reloaded2CSharpHook {
             #user_tk
             #[no_mangle]
             #fk // ItemFn
+            #fn_toggle_tk
         })
     }
     fn codegen_rust_static(&mut self, s: &mut StaticVarHook) -> syn::Result<TokenStream2> {
@@ -323,7 +499,8 @@ impl HookFramework for Reloaded2CSharpHook {
         let type_ident = Self::get_type_name(&s.ty)?;
         let global_static = quote! { pub static #name_ident: ::std::sync::OnceLock<#type_ident> = ::std::sync::OnceLock::new(); };
         // expose function to Reloaded-II to set pointer
-        let set_static_fn = self.create_set_function(&type_ident, name_ident.to_string().as_str(), name_ident);
+        let static_symbol = Self::register_symbol(&name_ident.to_string(), quote! { cb });
+        let set_static_fn = self.create_set_function(&type_ident, name_ident.to_string().as_str(), name_ident, static_symbol);
         Ok(quote! {
             #global_static
             #set_static_fn
@@ -340,7 +517,8 @@ impl HookFramework for Reloaded2CSharpHook {
         let vtable_ty = syn::Ident::new("usize", c.span());
         let class_vtbl_store = quote! { pub static #class_vtbl_name: ::std::sync::OnceLock<#vtable_ty> = ::std::sync::OnceLock::new(); };
         // expose function to Reloaded-II to set vtable pointer
-        let class_vtbl_fn = self.create_set_function(&vtable_ty.to_token_stream(), &class_vtbl_str, &class_vtbl_name);
+        let class_vtbl_symbol = Self::register_symbol(&format!("{} vtable", class_name), quote! { cb });
+        let class_vtbl_fn = self.create_set_function(&vtable_ty.to_token_stream(), &class_vtbl_str, &class_vtbl_name, class_vtbl_symbol);
         // add cpp pointer
         match &mut c.fields {
             syn::Fields::Named(n) => {
@@ -95,6 +95,15 @@ impl TryFrom<&str> for CallingConvention {
     }
 }
 
+impl CallingConvention {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            CallingConvention::Microsoft => "microsoft",
+            CallingConvention::Unknown => "unknown"
+        }
+    }
+}
+
 impl HookInfoParam for CallingConvention {
     fn get_param(e: &syn::ExprAssign) -> syn::Result<Self> where Self: Sized {
         if let syn::Expr::Lit(l) = e.right.borrow() {
@@ -126,6 +135,15 @@ impl TryFrom<&str> for RyoTuneSharedScan {
     }
 }
 
+impl RyoTuneSharedScan {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            RyoTuneSharedScan::Produce => "producer",
+            RyoTuneSharedScan::Consume => "consumer"
+        }
+    }
+}
+
 impl HookInfoParam for RyoTuneSharedScan {
     fn get_param(e: &syn::ExprAssign) -> syn::Result<Self> where Self: Sized {
         if let syn::Expr::Lit(l) = e.right.borrow() {
@@ -140,6 +158,23 @@ impl HookInfoParam for RyoTuneSharedScan {
     }
 }
 
+// A true or false parameter, such as required or allow_multiple
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HookFlag(bool);
+
+impl HookInfoParam for HookFlag {
+    fn get_param(e: &syn::ExprAssign) -> syn::Result<Self> where Self: Sized {
+        if let syn::Expr::Lit(l) = e.right.borrow() {
+            if let syn::Lit::Bool(b) = &l.lit {
+                return Ok(Self(b.value));
+            }
+        }
+        let name = HookParseTools::get_parameter_name(e)?;
+        Err(syn::Error::new(e.span(), format!("Invalid value for {} - should be true or false",
+            name.to_token_stream())))
+    }
+}
+
 trait OffsetBuilder {
     fn from_expr_call(entry: &syn::ExprCall) -> syn::Result<Self> where Self : Sized;
 }
@@ -173,7 +208,9 @@ pub struct DynamicOffset {
     pub sig: String,
     pub resolve_type: Option<String>,
     pub call_conv: CallingConvention,
-    pub shared_scan: Option<RyoTuneSharedScan>
+    pub shared_scan: Option<RyoTuneSharedScan>,
+    pub required: bool,
+    pub allow_multiple: bool
 }
 
 impl OffsetBuilder for DynamicOffset {
@@ -183,6 +220,8 @@ impl OffsetBuilder for DynamicOffset {
         let mut resolve_type: Option<ResolveType> = None;
         let mut call_conv: Option<CallingConvention> = None; // optional, default to microsoft
         let mut shared_scan: Option<RyoTuneSharedScan> = None; // optional (None = not shared)
+        let mut required: Option<HookFlag> = None; // optional, default to true
+        let mut allow_multiple: Option<HookFlag> = None; // optional, default to false
         for arg in &entry.args {
             if let syn::Expr::Assign(v) = arg {
                 let carg = HookParseTools::get_parameter_name(v)?;
@@ -198,6 +237,12 @@ impl OffsetBuilder for DynamicOffset {
                 // shared_scan = "producer"
                 } else if carg.is_ident("shared_scan") {
                     HookParseTools::get_single_param(v, &mut shared_scan, carg)?;
+                // required = false
+                } else if carg.is_ident("required") {
+                    HookParseTools::get_single_param(v, &mut required, carg)?;
+                // allow_multiple = true
+                } else if carg.is_ident("allow_multiple") {
+                    HookParseTools::get_single_param(v, &mut allow_multiple, carg)?;
                 } else {
                     return Err(syn::Error::new(arg.span(), "Unsupported argument name"))
                 }
@@ -225,7 +270,9 @@ impl OffsetBuilder for DynamicOffset {
             sig,
             resolve_type: if resolve_type.is_some() { Some(resolve_type.unwrap().0) } else { None },
             call_conv: call_conv.unwrap(),
-            shared_scan
+            shared_scan,
+            required: required.is_none_or(|r| r.0),
+            allow_multiple: allow_multiple.is_some_and(|r| r.0)
         })
     }
 }
@@ -253,6 +300,28 @@ impl HookEntry {
     }
 }
 
+// Written in the same syntax that HookEntry::new reads, leaving out arguments that are set to
+// their default value
+impl Display for HookEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let d = match self {
+            Self::Static(s) => return write!(f, "static_offset(0x{:x})", s.0),
+            Self::Delayed => return write!(f, "user_defined()"),
+            Self::Dyn(d) => d
+        };
+        let mut args = vec![];
+        if !d.sig.is_empty() { args.push(format!("signature = \"{}\"", d.sig)); }
+        if let Some(r) = &d.resolve_type { args.push(format!("resolve_type = {}", r)); }
+        if d.call_conv != CallingConvention::Microsoft {
+            args.push(format!("calling_convention = \"{}\"", d.call_conv.get_name()));
+        }
+        if let Some(s) = &d.shared_scan { args.push(format!("shared_scan = \"{}\"", s.get_name())); }
+        if !d.required { args.push("required = false".to_owned()); }
+        if d.allow_multiple { args.push("allow_multiple = true".to_owned()); }
+        write!(f, "dynamic_offset({})", args.join(", "))
+    }
+}
+
 #[derive(Debug)]
 pub enum HookConditional {
     // single entry
@@ -312,6 +381,17 @@ impl HookInfoMultipleCaseCollector {
         Ok(Self { arms, has_default_arm: false })
     }
 
+    /// Write a match arm for an entry in the same syntax that [`Self::collect_patterns`] reads.
+    /// Single entries ([`HookConditional::None`]) are written without a pattern.
+    pub fn format_arm(conditional: &HookConditional, entry: &HookEntry) -> String {
+        match conditional {
+            HookConditional::None => entry.to_string(),
+            HookConditional::HashNamed(name) => format!("{} => {},", name, entry),
+            HookConditional::HashNum(v) => format!("{} => {},", v, entry),
+            HookConditional::Default => format!("_ => {},", entry)
+        }
+    }
+
     pub fn collect_patterns(&mut self) -> syn::Result<Vec<(HookConditional, HookEntry)>> {
         let mut patterns: Vec<(HookConditional, HookEntry)> = vec![];
         for arm in &self.arms {
@@ -130,10 +130,37 @@ pub fn riri_hook_fn_impl(input: TokenStream2, annotated_item: TokenStream2) -> T
     transformed
 }
 
+struct HookFunctionBuildItemsToggle {
+    toggle_global: syn::ItemStatic,
+    set_toggle_function: syn::ItemFn,
+    toggle_module: syn::ItemMod
+}
+
+impl Parse for HookFunctionBuildItemsToggle {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            toggle_global: input.parse()?,
+            set_toggle_function: input.parse()?,
+            toggle_module: input.parse()?
+        })
+    }
+}
+
+impl HookFunctionBuildItemsToggle {
+    fn into_items(self) -> Vec<syn::Item> {
+        vec![
+            syn::Item::Static(self.toggle_global),
+            syn::Item::Fn(self.set_toggle_function),
+            syn::Item::Mod(self.toggle_module)
+        ]
+    }
+}
+
 struct HookFunctionBuildItemsBase {
     original_function_ptr: syn::ItemStatic,
     set_original_function: syn::ItemFn,
-    hooked_function: syn::ItemFn
+    hooked_function: syn::ItemFn,
+    toggle: HookFunctionBuildItemsToggle
 }
 
 impl Parse for HookFunctionBuildItemsBase {
@@ -142,16 +169,19 @@ impl Parse for HookFunctionBuildItemsBase {
             original_function_ptr: input.parse()?,
             set_original_function: input.parse()?,
             hooked_function: input.parse()?,
+            toggle: input.parse()?
         })
     }
 }
 impl HookFunctionBuildItemsBase {
-    fn to_items(self) -> Vec<syn::Item> {
-        vec![
+    fn into_items(self) -> Vec<syn::Item> {
+        let mut items = vec![
             syn::Item::Static(self.original_function_ptr),
             syn::Item::Fn(self.set_original_function),
             syn::Item::Fn(self.hooked_function)
-        ]
+        ];
+        items.extend(self.toggle.into_items());
+        items
     }
 }
 
@@ -161,8 +191,12 @@ struct HookFunctionBuildItemsUser {
 
     user_global: syn::ItemStatic,
     user_function: syn::ItemFn,
+    user_remove_global: syn::ItemStatic,
+    user_remove_function: syn::ItemFn,
+    create_function: syn::ItemFn,
 
     hooked_function: syn::ItemFn,
+    toggle: HookFunctionBuildItemsToggle
 }
 
 impl Parse for HookFunctionBuildItemsUser {
@@ -172,23 +206,33 @@ impl Parse for HookFunctionBuildItemsUser {
             set_original_function: input.parse()?,
             user_global: input.parse()?,
             user_function: input.parse()?,
-            hooked_function: input.parse()?
+            user_remove_global: input.parse()?,
+            user_remove_function: input.parse()?,
+            create_function: input.parse()?,
+            hooked_function: input.parse()?,
+            toggle: input.parse()?
         })
     }
 }
 
 impl HookFunctionBuildItemsUser {
-    fn to_items(self) -> Vec<syn::Item> {
-        vec![
+    fn into_items(self) -> Vec<syn::Item> {
+        let mut items = vec![
             // base
             syn::Item::Static(self.original_function_ptr),
             syn::Item::Fn(self.set_original_function),
             // user
             syn::Item::Static(self.user_global),
             syn::Item::Fn(self.user_function),
+            syn::Item::Static(self.user_remove_global),
+            syn::Item::Fn(self.user_remove_function),
+            syn::Item::Fn(self.create_function),
             // back to base
             syn::Item::Fn(self.hooked_function),
-        ]
+        ];
+        // toggle
+        items.extend(self.toggle.into_items());
+        items
     }
 }
 
@@ -196,8 +240,8 @@ pub struct HookFunctionBuildItems;
 impl HookFunctionBuildItems {
     pub fn get_items_from_tokens(input: TokenStream2, has_user: bool) -> syn::Result<Vec<syn::Item>> {
         match has_user {
-            true => Ok(HookFunctionBuildItemsUser::parse.parse2(input)?.to_items()),
-            false => Ok(HookFunctionBuildItemsBase::parse.parse2(input)?.to_items()),
+            true => Ok(HookFunctionBuildItemsUser::parse.parse2(input)?.into_items()),
+            false => Ok(HookFunctionBuildItemsBase::parse.parse2(input)?.into_items()),
         }
     }
 }
@@ -259,7 +303,7 @@ pub fn riri_hook_fn_build(input: TokenStream2, annotated_item: syn::ItemFn) -> s
     Ok(HookBuildScriptResult {
         name: target.get_name(),
         items: HookFunctionBuildItems::get_items_from_tokens(transformed, transformer.is_user_defined())?,
-        // items: HookFunctionBuildScriptItems::parse.parse2(transformed)?.to_items(),
+        // items: HookFunctionBuildScriptItems::parse.parse2(transformed)?.into_items(),
         args
     })
 }
@@ -308,7 +352,7 @@ impl Parse for HookStaticBuildScriptItems {
     }
 }
 impl HookStaticBuildScriptItems {
-    fn to_items(self) -> Vec<syn::Item> {
+    fn into_items(self) -> Vec<syn::Item> {
         vec! [
             syn::Item::Static(self.global_static),
             syn::Item::Fn(self.set_static_fn)
@@ -322,7 +366,7 @@ pub fn riri_hook_static_build(input: TokenStream2, annotated_item: syn::ItemMacr
     let transformed = transformer.codegen_rust(&mut target)?;
     Ok(HookBuildScriptResult {
         name: target.get_name(),
-        items: HookStaticBuildScriptItems::parse.parse2(transformed)?.to_items(),
+        items: HookStaticBuildScriptItems::parse.parse2(transformed)?.into_items(),
         args
     })
 }
@@ -403,7 +447,7 @@ pub fn riri_hook_inline_fn_build(input: TokenStream2, annotated_item: syn::ItemF
     Ok(HookBuildScriptResult {
         name: target.get_name(),
         items: HookFunctionBuildItems::get_items_from_tokens(transformed, transformer.is_user_defined())?,
-        // items: HookFunctionBuildScriptItems::parse.parse2(transformed)?.to_items(),
+        // items: HookFunctionBuildScriptItems::parse.parse2(transformed)?.into_items(),
         args
     })
 }
@@ -429,11 +473,11 @@ impl CreateHookParameters {
     pub fn as_tokens(&self) -> TokenStream2 {
         let address_resolve = self.var.clone();
         let cb_leaf = self.path.segments.last().unwrap().ident.to_string().to_ascii_uppercase();
-        let cb_leaf = Reloaded2CSharpHook::make_user_cb_string(&cb_leaf);
+        let cb_leaf = Reloaded2CSharpHook::make_hook_create_string(&cb_leaf);
         let mut cb_path = self.path.clone();
         cb_path.segments.last_mut().unwrap().ident = syn::Ident::new(&cb_leaf, Span2::call_site());
         quote! {
-            (#cb_path.get().unwrap())(#address_resolve)
+            #cb_path(#address_resolve)
         }
     }
 }
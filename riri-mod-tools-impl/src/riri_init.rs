@@ -15,12 +15,14 @@ use quote::{quote, ToTokens};
 
 fn get_transformed_function(func: ItemFn) -> (String, TokenStream2) {
     let name = syn::Ident::new(func.sig.ident.to_string().as_ref(), Span::call_site());
+    let name_str = name.to_string();
     let block = &func.block;
     let fn_target_abi = quote! { extern "C" };
+    // Panics are logged by catch_panic, and there's nothing else to do for init functions
     let tokens = quote! {
         #[no_mangle]
         pub unsafe #fn_target_abi fn #name() {
-            #block
+            let _ = riri_mod_tools_rt::panic_guard::catch_panic(#name_str, || #block);
         }
     };
     (name.to_string(), tokens)
@@ -76,7 +78,7 @@ fn get_transformed_function_mod_loading(func: &ItemFn) -> (String, TokenStream2)
          #[no_mangle]
         pub unsafe #fn_target_abi fn #link_func(ptr: isize) {
             let mod_config = riri_mod_tools_rt::reloaded::r#mod::interfaces::IModConfig::new_unchecked(riri_mod_tools_rt::system::Object::new_unchecked(ptr as usize));
-            #body_func(mod_config);
+            let _ = riri_mod_tools_rt::panic_guard::catch_panic(#inner_name, || #body_func(mod_config));
         }
     };
     (link_func.to_string(), link_tokens)
@@ -161,8 +161,9 @@ pub fn riri_init_fn(input: TokenStream, annotated_item: TokenStream) -> TokenStr
     riri_init::riri_init_fn_impl(input.into(), annotated_item.into()).into()
 }
 
-/// When inside a hooked function, this call is replaced with either a raw function call if they
-/// share the same calling convention or to Reloaded's OriginalFunction if they don't
+/// Hook the function at an address (a `usize` variable) with a `user_defined` hook function, e.g
+/// `create_hook!(ptr, print_log)`. Returns an `Option<HookHandle>`, which can remove the hook.
+/// The same hook function can be used on multiple addresses.
 #[proc_macro]
 pub fn create_hook(input: TokenStream) -> TokenStream {
     riri_hook::create_hook_impl(input.into()).into()
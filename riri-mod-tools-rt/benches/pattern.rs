@@ -0,0 +1,85 @@
+//! Signature scanning over an executable-sized buffer. The buffer is generated with a similar
+//! byte distribution to x86-64 code so that common bytes produce as many candidate matches as
+//! they would in a real executable.
+//!
+//! ```text
+//! cargo bench -p riri-mod-tools-rt --no-default-features --bench pattern
+//! ```
+
+use criterion::{ criterion_group, criterion_main, BenchmarkId, Criterion, Throughput };
+use riri_mod_tools_rt::pattern::{ Pattern, ScanBackend };
+use riri_mod_tools_rt::sigscan_batch::BatchScanner;
+
+const BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+// Common instruction bytes, with the most common ones repeated so they show up more often
+const COMMON: &[u8] = &[
+    0x00, 0x00, 0x00, 0x00, 0xFF, 0xCC, 0xCC, 0x48, 0x48, 0x48, 0x8B, 0x8B, 0x89, 0x0F, 0x24,
+    0x4C, 0x44, 0xE8, 0x83, 0x85, 0xC0, 0x8D, 0x49, 0x4D, 0x41, 0x45, 0x01, 0x74, 0x75, 0x33
+];
+
+// Signatures in the style that mods use: a function prologue with the start of its body
+const PATTERNS: &[&str] = &[
+    // Starts with the most common bytes in code
+    "48 89 5C 24 ?? 57 48 83 EC 20 48 8B F9 E8 ?? ?? ?? ?? 48 8B D8",
+    // Common bytes broken up by wildcards
+    "48 8B C4 48 89 58 ?? 48 89 68 ?? 48 89 70 ?? 57 41 56",
+    // Contains rare bytes
+    "40 53 48 83 EC 20 B9 37 13 00 00 E8 ?? ?? ?? ?? 66 0F 6F 05"
+];
+
+fn get_buffer() -> Vec<u8> {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let mut data: Vec<u8> = (0..BUFFER_SIZE).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        match state % 4 {
+            0 => (state >> 8) as u8,
+            _ => COMMON[(state >> 8) as usize % COMMON.len()]
+        }
+    }).collect();
+    // Place each signature near the end, so the whole buffer is scanned
+    for (i, text) in PATTERNS.iter().enumerate() {
+        let pattern = Pattern::new(text).unwrap();
+        let offset = BUFFER_SIZE - 0x1000 * (i + 1);
+        data[offset..offset + pattern.len()].copy_from_slice(pattern.get_bytes());
+    }
+    data
+}
+
+fn scan_single(c: &mut Criterion) {
+    let data = get_buffer();
+    let mut group = c.benchmark_group("find");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(20);
+    for (i, text) in PATTERNS.iter().enumerate() {
+        let pattern = Pattern::new(text).unwrap();
+        for backend in [ScanBackend::Scalar, ScanBackend::Sse2, ScanBackend::Avx2] {
+            if !backend.is_supported() { continue; }
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", backend), i), &pattern, |b, p| {
+                b.iter(|| p.find_with(&data, backend))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn scan_batch(c: &mut Criterion) {
+    let data = get_buffer();
+    let mut scanner = BatchScanner::new();
+    // Most mods resolve dozens of signatures at startup
+    for _ in 0..(64 / PATTERNS.len()) {
+        for text in PATTERNS {
+            scanner.add(Pattern::new(text).unwrap());
+        }
+    }
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(10);
+    group.bench_function(format!("{}_patterns", scanner.len()), |b| b.iter(|| scanner.scan(&data)));
+    group.finish();
+}
+
+criterion_group!(benches, scan_single, scan_batch);
+criterion_main!(benches);
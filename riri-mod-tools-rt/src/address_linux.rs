@@ -4,12 +4,18 @@ use std::mem::MaybeUninit;
 use libc;
 use crate::protection::PageProtection;
 
-#[cfg(feature = "reloaded")]
+#[cfg(all(feature = "reloaded", not(feature = "testing")))]
 #[link(name = "riri_mod_runtime_reloaded", kind = "dylib")]
 unsafe extern "C" {
     pub(crate) fn get_executable_hash_ex() -> u64;
 }
 
+// testing::Host stands in for the Reloaded runtime
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub(crate) unsafe fn get_executable_hash_ex() -> u64 {
+    crate::testing::get_executable_hash_ex()
+}
+
 #[derive(Debug)]
 pub struct ProcessModule {
     owner: *const libc::dl_phdr_info,
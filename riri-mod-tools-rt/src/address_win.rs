@@ -16,12 +16,18 @@ use windows::Win32::{
     }
 };
 
-#[cfg(feature = "reloaded")]
+#[cfg(all(feature = "reloaded", not(feature = "testing")))]
 #[link(name = "riri_mod_runtime_reloaded", kind = "raw-dylib")]
 unsafe extern "C" {
     pub(crate) fn get_executable_hash_ex() -> u64;
 }
 
+// testing::Host stands in for the Reloaded runtime
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub(crate) unsafe fn get_executable_hash_ex() -> u64 {
+    crate::testing::get_executable_hash_ex()
+}
+
 // Based on .NET's System.Diagnostics.Process:
 // https://learn.microsoft.com/en-us/dotnet/api/system.diagnostics.process
 
@@ -0,0 +1,465 @@
+//! Writes a crash report into the mod directory when the process crashes. The report contains
+//! the faulting address, the registers at the time of the crash, the nearest registered symbol
+//! (hooks and statics register themselves as they're resolved), module-relative offsets and the
+//! last lines that were logged.
+//!
+//! The handler is opt-in and should be installed during initialization:
+//!
+//! ```ignore
+//! #[riri_init_fn()]
+//! fn init() {
+//!     CrashHandlerBuilder::new().log_lines(200).install().unwrap();
+//! }
+//! ```
+//!
+//! On Windows, this uses an unhandled exception filter, so exceptions that the game handles
+//! itself aren't reported. On Linux, a signal handler is installed for `SIGSEGV`, `SIGBUS`,
+//! `SIGILL`, `SIGFPE` and `SIGABRT`. In both cases the previous handler runs after the report
+//! has been written.
+//!
+//! Writing the report allocates and does file IO, which isn't safe in every situation that a
+//! crash can happen in (such as a crash inside of the allocator), but it works well enough for
+//! crashes in game code and hooks.
+
+use std::error::Error;
+use std::fmt::{ Display, Formatter, Write as _ };
+use std::io;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex, OnceLock, PoisonError, RwLock };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::{ SystemTime, UNIX_EPOCH };
+use crate::log_sink::{ self, RingBufferSink };
+
+pub const DEFAULT_LOG_LINES: usize = 100;
+
+static CRASH_HANDLER: OnceLock<CrashHandler> = OnceLock::new();
+static INSTALL_LOCK: Mutex<()> = Mutex::new(());
+static SYMBOLS: RwLock<Vec<CrashSymbol>> = RwLock::new(vec![]);
+// Only report the first crash if several threads crash at once
+static REPORTING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrashHandlerError {
+    AlreadyInstalled,
+    InstallFailed(String)
+}
+
+impl Display for CrashHandlerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyInstalled => write!(f, "The crash handler has already been installed"),
+            Self::InstallFailed(e) => write!(f, "Failed to install the crash handler: {}", e)
+        }
+    }
+}
+
+impl Error for CrashHandlerError {}
+
+/// A named address that crash addresses are compared against. Generated hook and static set
+/// functions register their addresses here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashSymbol {
+    name: String,
+    address: usize
+}
+
+impl CrashSymbol {
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_address(&self) -> usize { self.address }
+}
+
+/// Register a symbol to show in crash reports. Registering a name again replaces its address.
+pub fn register_symbol<S: Into<String>>(name: S, address: usize) {
+    let name = name.into();
+    let mut symbols = match SYMBOLS.write() {
+        Ok(v) => v,
+        Err(e) => e.into_inner()
+    };
+    match symbols.iter_mut().find(|s| s.name == name) {
+        Some(s) => s.address = address,
+        None => symbols.push(CrashSymbol { name, address })
+    }
+}
+
+/// Get a copy of the registered symbols, sorted by address.
+pub fn get_symbols() -> Vec<CrashSymbol> {
+    let mut symbols = match SYMBOLS.read() {
+        Ok(v) => v.clone(),
+        Err(e) => e.into_inner().clone()
+    };
+    symbols.sort_by_key(|s| s.address);
+    symbols
+}
+
+/// Find the closest symbol at or before `address`, returning it with the offset from it.
+pub fn find_nearest_symbol(address: usize) -> Option<(CrashSymbol, usize)> {
+    get_symbols().into_iter()
+        .filter(|s| s.address != 0 && s.address <= address)
+        .max_by_key(|s| s.address)
+        .map(|s| { let offset = address - s.address; (s, offset) })
+}
+
+/// The module containing an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleAddress {
+    name: String,
+    base_address: usize
+}
+
+impl ModuleAddress {
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_base_address(&self) -> usize { self.base_address }
+}
+
+/// Find which loaded module (executable or shared library) contains `address`.
+#[cfg(target_os = "linux")]
+pub fn find_module(address: usize) -> Option<ModuleAddress> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    if address == 0 || unsafe { libc::dladdr(address as *const libc::c_void, &mut info) } == 0 {
+        return None;
+    }
+    let path = match info.dli_fname.is_null() {
+        true => String::new(),
+        false => unsafe { std::ffi::CStr::from_ptr(info.dli_fname) }.to_string_lossy().into_owned()
+    };
+    Some(ModuleAddress { name: get_file_name(&path), base_address: info.dli_fbase as usize })
+}
+
+/// Find which loaded module (executable or DLL) contains `address`.
+#[cfg(target_os = "windows")]
+pub fn find_module(address: usize) -> Option<ModuleAddress> {
+    use windows::Win32::{ Foundation::HMODULE, System::{ Memory, ProcessStatus, Threading } };
+    let mut info: Memory::MEMORY_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+    if address == 0 || unsafe { Memory::VirtualQuery(Some(address as *const std::ffi::c_void),
+        &mut info, size_of::<Memory::MEMORY_BASIC_INFORMATION>()) } == 0 || info.Type != Memory::MEM_IMAGE {
+        return None;
+    }
+    let mut buf = [0u8; 260];
+    let len = unsafe { ProcessStatus::GetModuleFileNameExA(Some(Threading::GetCurrentProcess()),
+        Some(HMODULE(info.AllocationBase)), &mut buf) };
+    let path = String::from_utf8_lossy(&buf[..len as usize]);
+    Some(ModuleAddress { name: get_file_name(&path), base_address: info.AllocationBase as usize })
+}
+
+fn get_file_name(path: &str) -> String {
+    match path.rsplit(['/', '\\']).next() {
+        Some(v) if !v.is_empty() => v.to_owned(),
+        _ => "unknown".to_owned()
+    }
+}
+
+/// Format an address as `module+0xoffset`, or just the address if it's not inside a module.
+pub fn format_module_offset(address: usize) -> String {
+    match find_module(address) {
+        Some(m) => format!("{}+0x{:x}", m.name, address - m.base_address),
+        None => format!("0x{:x}", address)
+    }
+}
+
+/// The state of the process at the time of a crash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashContext {
+    /// The signal or exception name, e.g `SIGSEGV` or `EXCEPTION_ACCESS_VIOLATION`.
+    pub description: String,
+    /// The signal number or exception code.
+    pub code: u32,
+    /// The address that was accessed for access violations, or the instruction pointer
+    /// otherwise.
+    pub fault_address: usize,
+    pub instruction_pointer: usize,
+    pub registers: Vec<(&'static str, u64)>
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGILL => "SIGILL",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGABRT => "SIGABRT",
+        _ => "UNKNOWN"
+    }
+}
+
+pub fn get_exception_name(code: u32) -> &'static str {
+    match code {
+        0xc0000005 => "EXCEPTION_ACCESS_VIOLATION",
+        0xc000001d => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xc0000094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0xc0000096 => "EXCEPTION_PRIV_INSTRUCTION",
+        0xc00000fd => "EXCEPTION_STACK_OVERFLOW",
+        0xc0000409 => "STATUS_STACK_BUFFER_OVERRUN",
+        0x80000003 => "EXCEPTION_BREAKPOINT",
+        _ => "UNKNOWN"
+    }
+}
+
+/// Builds the crash handler. The report directory is resolved when the handler is installed
+/// since the mod loader can't be called into safely during a crash.
+#[derive(Debug)]
+pub struct CrashHandlerBuilder {
+    directory: Option<PathBuf>,
+    log_lines: usize
+}
+
+impl Default for CrashHandlerBuilder {
+    fn default() -> Self {
+        Self { directory: None, log_lines: DEFAULT_LOG_LINES }
+    }
+}
+
+impl CrashHandlerBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    /// The directory that reports are written to. Defaults to the mod directory.
+    pub fn directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.directory = Some(path.into());
+        self
+    }
+
+    /// The number of log lines to include in the report. This adds a [`RingBufferSink`] to
+    /// the logger, so set this to 0 to skip it.
+    pub fn log_lines(mut self, count: usize) -> Self {
+        self.log_lines = count;
+        self
+    }
+
+    /// Create the handler without installing it. The log sink isn't added to the logger until
+    /// the handler is installed.
+    pub(crate) fn build(self) -> CrashHandler {
+        let directory = self.directory.unwrap_or_else(get_mod_directory);
+        let log = match self.log_lines {
+            0 => None,
+            n => Some(Arc::new(RingBufferSink::new(n)))
+        };
+        CrashHandler { directory, log }
+    }
+
+    /// Install the handler for the rest of the program. Nothing is stored if the platform
+    /// handler can't be installed, so this can be called again.
+    pub fn install(self) -> Result<&'static CrashHandler, CrashHandlerError> {
+        let _lock = INSTALL_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        if CRASH_HANDLER.get().is_some() {
+            return Err(CrashHandlerError::AlreadyInstalled);
+        }
+        let handler = self.build();
+        unsafe { install_platform_handler() }?;
+        if let Some(log) = &handler.log {
+            log_sink::add_log_sink(log.clone());
+        }
+        Ok(CRASH_HANDLER.get_or_init(|| handler))
+    }
+}
+
+fn get_mod_directory() -> PathBuf {
+    #[cfg(feature = "reloaded")]
+    return PathBuf::from(String::from(crate::mod_loader_data::get_directory_for_mod()));
+    #[cfg(not(feature = "reloaded"))]
+    return crate::standalone::get_directory_for_mod();
+}
+
+pub struct CrashHandler {
+    directory: PathBuf,
+    log: Option<Arc<RingBufferSink>>
+}
+
+impl CrashHandler {
+    /// Get the installed crash handler, if there is one.
+    pub fn get() -> Option<&'static CrashHandler> {
+        CRASH_HANDLER.get()
+    }
+
+    pub fn get_directory(&self) -> &Path { &self.directory }
+
+    /// The buffer of recent log lines that's included in reports.
+    pub fn get_log(&self) -> Option<&RingBufferSink> { self.log.as_deref() }
+
+    /// Create the text of a crash report.
+    pub fn format_report(&self, context: &CrashContext) -> String {
+        let mut out = String::new();
+        let time = SystemTime::now();
+        let _ = writeln!(out, "Crash report ({})", log_sink::format_timestamp(time));
+        let _ = writeln!(out, "{} ({:#x})", context.description, context.code);
+        let _ = writeln!(out, "Fault address: 0x{:x} ({})", context.fault_address,
+            format_module_offset(context.fault_address));
+        let _ = writeln!(out, "Instruction pointer: 0x{:x} ({})", context.instruction_pointer,
+            format_module_offset(context.instruction_pointer));
+        match find_nearest_symbol(context.instruction_pointer) {
+            Some((s, offset)) => { let _ = writeln!(out, "Nearest symbol: {}+0x{:x}", s.name, offset); },
+            None => { let _ = writeln!(out, "Nearest symbol: none"); }
+        };
+        let _ = writeln!(out, "\nRegisters:");
+        for (name, value) in &context.registers {
+            let _ = writeln!(out, "    {:<6} 0x{:016x}", name, value);
+        }
+        let _ = writeln!(out, "\nSymbols:");
+        for s in get_symbols() {
+            let _ = writeln!(out, "    0x{:016x} {} ({})", s.address, s.name, format_module_offset(s.address));
+        }
+        if let Some(log) = &self.log {
+            let _ = writeln!(out, "\nLast {} log lines:", log.get_capacity());
+            // The crash may have happened while the buffer was locked
+            match log.try_get_lines() {
+                Some(lines) => lines.iter().for_each(|l| { let _ = writeln!(out, "{}", l); }),
+                None => { let _ = writeln!(out, "(log was in use)"); }
+            }
+        }
+        out
+    }
+
+    /// Write a crash report into the report directory, returning the path of the report.
+    pub fn write_report(&self, context: &CrashContext) -> io::Result<PathBuf> {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let path = self.directory.join(format!("crash_{}.txt", seconds));
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(&path, self.format_report(context))?;
+        Ok(path)
+    }
+}
+
+fn report_crash(context: &CrashContext) {
+    if REPORTING.swap(true, Ordering::AcqRel) { return; }
+    if let Some(handler) = CrashHandler::get() {
+        if let Err(e) = handler.write_report(context) {
+            eprintln!("Failed to write crash report to {}: {}", handler.directory.display(), e);
+        }
+    }
+    log_sink::flush_log_sinks();
+}
+
+#[cfg(target_os = "linux")]
+const HANDLED_SIGNALS: [libc::c_int; 5] = [libc::SIGSEGV, libc::SIGBUS, libc::SIGILL, libc::SIGFPE, libc::SIGABRT];
+
+#[cfg(target_os = "linux")]
+struct PreviousAction(libc::c_int, libc::sigaction);
+// sigaction contains a raw function pointer for the handler
+#[cfg(target_os = "linux")]
+unsafe impl Send for PreviousAction {}
+#[cfg(target_os = "linux")]
+unsafe impl Sync for PreviousAction {}
+
+#[cfg(target_os = "linux")]
+static PREVIOUS_ACTIONS: OnceLock<Vec<PreviousAction>> = OnceLock::new();
+
+#[cfg(target_os = "linux")]
+impl CrashContext {
+    unsafe fn from_signal(signal: libc::c_int, info: *const libc::siginfo_t, context: *const libc::c_void) -> Self {
+        let mut out = Self {
+            description: get_signal_name(signal).to_owned(),
+            code: signal as u32,
+            fault_address: 0,
+            instruction_pointer: 0,
+            registers: vec![]
+        };
+        if !info.is_null() {
+            out.fault_address = (*info).si_addr() as usize;
+        }
+        #[cfg(target_arch = "x86_64")]
+        if !context.is_null() {
+            let gregs = &(*(context as *const libc::ucontext_t)).uc_mcontext.gregs;
+            let names = [
+                ("rax", libc::REG_RAX), ("rbx", libc::REG_RBX), ("rcx", libc::REG_RCX), ("rdx", libc::REG_RDX),
+                ("rsi", libc::REG_RSI), ("rdi", libc::REG_RDI), ("rbp", libc::REG_RBP), ("rsp", libc::REG_RSP),
+                ("r8", libc::REG_R8), ("r9", libc::REG_R9), ("r10", libc::REG_R10), ("r11", libc::REG_R11),
+                ("r12", libc::REG_R12), ("r13", libc::REG_R13), ("r14", libc::REG_R14), ("r15", libc::REG_R15),
+                ("rip", libc::REG_RIP), ("eflags", libc::REG_EFL)
+            ];
+            out.registers = names.iter().map(|(n, i)| (*n, gregs[*i as usize] as u64)).collect();
+            out.instruction_pointer = gregs[libc::REG_RIP as usize] as usize;
+        }
+        if out.fault_address == 0 {
+            out.fault_address = out.instruction_pointer;
+        }
+        out
+    }
+}
+
+#[cfg(target_os = "linux")]
+extern "C" fn signal_handler(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    report_crash(&unsafe { CrashContext::from_signal(signal, info, context) });
+    // Restore the previous handler. Returning retries the faulting instruction, which then goes
+    // to the previous handler, but signals sent by kill() or abort() need to be raised again.
+    if let Some(previous) = PREVIOUS_ACTIONS.get().and_then(|p| p.iter().find(|a| a.0 == signal)) {
+        unsafe { libc::sigaction(signal, &previous.1, std::ptr::null_mut()) };
+    }
+    if info.is_null() || unsafe { (*info).si_code } <= 0 {
+        unsafe { libc::raise(signal) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn install_platform_handler() -> Result<(), CrashHandlerError> {
+    let mut action: libc::sigaction = std::mem::zeroed();
+    action.sa_sigaction = signal_handler as *const () as usize;
+    action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+    libc::sigemptyset(&mut action.sa_mask);
+    let mut previous: Vec<PreviousAction> = Vec::with_capacity(HANDLED_SIGNALS.len());
+    for signal in HANDLED_SIGNALS {
+        let mut old: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(signal, &action, &mut old) != 0 {
+            let error = io::Error::last_os_error().to_string();
+            // Put back the handlers that were already replaced
+            for p in &previous {
+                libc::sigaction(p.0, &p.1, std::ptr::null_mut());
+            }
+            return Err(CrashHandlerError::InstallFailed(error));
+        }
+        previous.push(PreviousAction(signal, old));
+    }
+    let _ = PREVIOUS_ACTIONS.set(previous);
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+static PREVIOUS_FILTER: OnceLock<windows::Win32::System::Diagnostics::Debug::LPTOP_LEVEL_EXCEPTION_FILTER> = OnceLock::new();
+
+#[cfg(target_os = "windows")]
+impl CrashContext {
+    unsafe fn from_exception(info: &windows::Win32::System::Diagnostics::Debug::EXCEPTION_POINTERS) -> Self {
+        let record = &*info.ExceptionRecord;
+        let code = record.ExceptionCode.0 as u32;
+        let mut out = Self {
+            description: get_exception_name(code).to_owned(),
+            code,
+            // Access violations store the address that was accessed in the second parameter
+            fault_address: match code == 0xc0000005 && record.NumberParameters >= 2 {
+                true => record.ExceptionInformation[1],
+                false => record.ExceptionAddress as usize
+            },
+            instruction_pointer: record.ExceptionAddress as usize,
+            registers: vec![]
+        };
+        #[cfg(target_arch = "x86_64")]
+        if !info.ContextRecord.is_null() {
+            let c = &*info.ContextRecord;
+            out.registers = vec![
+                ("rax", c.Rax), ("rbx", c.Rbx), ("rcx", c.Rcx), ("rdx", c.Rdx),
+                ("rsi", c.Rsi), ("rdi", c.Rdi), ("rbp", c.Rbp), ("rsp", c.Rsp),
+                ("r8", c.R8), ("r9", c.R9), ("r10", c.R10), ("r11", c.R11),
+                ("r12", c.R12), ("r13", c.R13), ("r14", c.R14), ("r15", c.R15),
+                ("rip", c.Rip), ("eflags", c.EFlags as u64)
+            ];
+        }
+        out
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe extern "system" fn exception_filter(info: *const windows::Win32::System::Diagnostics::Debug::EXCEPTION_POINTERS) -> i32 {
+    if !info.is_null() {
+        report_crash(&CrashContext::from_exception(&*info));
+    }
+    match PREVIOUS_FILTER.get() {
+        Some(Some(previous)) => previous(info),
+        _ => 0 // EXCEPTION_CONTINUE_SEARCH
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn install_platform_handler() -> Result<(), CrashHandlerError> {
+    use windows::Win32::System::Diagnostics::Debug;
+    let previous = Debug::SetUnhandledExceptionFilter(Some(exception_filter));
+    let _ = PREVIOUS_FILTER.set(previous);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+#![cfg(test)]
+use crate::crash_handler::{ self, CrashContext, CrashHandlerBuilder };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// Symbols are shared with every other test, so these are placed far above any real address
+const SYMBOL_BASE: usize = 0xfff0_0000_0000_0000;
+
+#[test]
+fn crash_nearest_symbol() -> ReturnType {
+    crash_handler::register_symbol("nearest_test_a", SYMBOL_BASE);
+    crash_handler::register_symbol("nearest_test_b", SYMBOL_BASE + 0x800);
+    let nearest = |address| crash_handler::find_nearest_symbol(address)
+        .map(|(s, offset)| (s.get_name().to_owned(), offset));
+    assert_eq!(nearest(SYMBOL_BASE), Some(("nearest_test_a".to_owned(), 0)));
+    assert_eq!(nearest(SYMBOL_BASE + 0x7ff), Some(("nearest_test_a".to_owned(), 0x7ff)));
+    assert_eq!(nearest(SYMBOL_BASE + 0x820), Some(("nearest_test_b".to_owned(), 0x20)));
+    // Registering a name again moves it
+    crash_handler::register_symbol("nearest_test_a", SYMBOL_BASE + 0x1000);
+    assert!(nearest(SYMBOL_BASE + 0x10).is_none_or(|(n, _)| n != "nearest_test_a"));
+    assert_eq!(nearest(SYMBOL_BASE + 0x1010), Some(("nearest_test_a".to_owned(), 0x10)));
+    // Unresolved symbols are never the nearest
+    crash_handler::register_symbol("nearest_test_null", 0);
+    assert!(crash_handler::find_nearest_symbol(0).is_none_or(|(s, _)| s.get_name() != "nearest_test_null"));
+    let symbols = crash_handler::get_symbols();
+    assert!(symbols.windows(2).all(|w| w[0].get_address() <= w[1].get_address()));
+    Ok(())
+}
+
+#[test]
+fn crash_format_report() -> ReturnType {
+    use crate::log_sink::{ LogRecord, LogSink };
+    use crate::logger::LogLevel;
+    crash_handler::register_symbol("report_test", SYMBOL_BASE + 0x10_0000);
+    let handler = CrashHandlerBuilder::new().directory("crash_reports").log_lines(2).build();
+    assert_eq!(handler.get_directory(), std::path::Path::new("crash_reports"));
+    let log = handler.get_log().unwrap();
+    for text in ["first", "second", "third"] {
+        log.write(&LogRecord {
+            time: std::time::UNIX_EPOCH,
+            ..LogRecord::new(text, LogLevel::Error, LogLevel::Error.get_log_color(), true, true)
+        });
+    }
+    let report = handler.format_report(&CrashContext {
+        description: "SIGSEGV".to_owned(),
+        code: 11,
+        fault_address: 0x10,
+        instruction_pointer: SYMBOL_BASE + 0x10_0024,
+        registers: vec![("rax", 0x1234), ("rip", (SYMBOL_BASE + 0x10_0024) as u64)]
+    });
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(lines[0].starts_with("Crash report ("));
+    assert_eq!(lines[1..6], [
+        "SIGSEGV (0xb)",
+        "Fault address: 0x10 (0x10)",
+        "Instruction pointer: 0xfff0000000100024 (0xfff0000000100024)",
+        "Nearest symbol: report_test+0x24",
+        ""
+    ]);
+    assert!(report.contains("\nRegisters:\n    rax    0x0000000000001234\n    rip    0xfff0000000100024\n"));
+    assert!(report.contains("\n    0xfff0000000100000 report_test (0xfff0000000100000)\n"));
+    assert!(report.ends_with("\nLast 2 log lines:\n1970-01-01 00:00:00.000 [ERROR] second\n1970-01-01 00:00:00.000 [ERROR] third\n"));
+    // Without a log buffer, the log section is left out
+    let handler = CrashHandlerBuilder::new().directory("crash_reports").log_lines(0).build();
+    assert!(handler.get_log().is_none());
+    let report = handler.format_report(&CrashContext {
+        description: "EXCEPTION_ACCESS_VIOLATION".to_owned(),
+        code: 0xc0000005,
+        fault_address: 0,
+        instruction_pointer: 0,
+        registers: vec![]
+    });
+    assert!(report.contains("EXCEPTION_ACCESS_VIOLATION (0xc0000005)\n"));
+    assert!(!report.contains("log lines:"));
+    Ok(())
+}
+
+// Crashing kills the test process, so the test runs itself again in a child process which
+// installs the handler and crashes.
+#[cfg(all(target_os = "linux", feature = "reloaded", feature = "testing"))]
+#[test]
+fn crash_report_from_host() -> ReturnType {
+    use crate::crash_handler::{ self, CrashHandlerBuilder };
+    use crate::testing::Host;
+    use std::os::unix::process::ExitStatusExt;
+    if std::env::var_os("RIRI_CRASH_HANDLER_CHILD").is_some() {
+        Host::install();
+        CrashHandlerBuilder::new().log_lines(10).install()?;
+        crash_handler::register_symbol("crash_report_from_host", crash_report_from_host as *const () as usize);
+        crate::logln!(Information, "about to crash");
+        unsafe { std::ptr::read_volatile(0x10 as *const u8) };
+        return Ok(());
+    }
+    let child = std::process::Command::new(std::env::current_exe()?)
+        .args(["--exact", "crash_handler_tests::crash_report_from_host", "--test-threads=1"])
+        .env("RIRI_CRASH_HANDLER_CHILD", "1")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    let directory = std::env::temp_dir().join(format!("riri-mod-tools-host-{}", child.id())).join("mod");
+    let status = child.wait_with_output()?.status;
+    assert_eq!(status.signal(), Some(libc::SIGSEGV));
+    let report = std::fs::read_dir(&directory)?
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_name().to_string_lossy().starts_with("crash_"))
+        .map(|e| std::fs::read_to_string(e.path()))
+        .unwrap()?;
+    assert!(report.contains("SIGSEGV (0xb)"));
+    assert!(report.contains("Fault address: 0x10 (0x10)"));
+    assert!(report.contains("crash_report_from_host (riri_mod_tools_rt"));
+    assert!(report.contains("rip    0x"));
+    assert!(report.contains("about to crash"));
+    let _ = std::fs::remove_dir_all(directory.parent().unwrap());
+    crash_handler::register_symbol("crash_test_a", 0x1000);
+    crash_handler::register_symbol("crash_test_b", 0x1800);
+    let (symbol, offset) = crash_handler::find_nearest_symbol(0x1820).unwrap();
+    assert_eq!((symbol.get_name(), offset), ("crash_test_b", 0x20));
+    Ok(())
+}
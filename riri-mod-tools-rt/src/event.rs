@@ -0,0 +1,205 @@
+//! Attach Rust closures to C# events and create C# delegates from Rust closures.
+//!
+//! Closures are boxed and handed to C# as an opaque context pointer. The C# side wraps that
+//! context in a delegate of the correct type, which calls back into `invoke_rust_closure` with
+//! a GCHandle for each argument. Once the C# delegate is garbage collected, the context is
+//! returned through `free_rust_closure` and the closure is dropped.
+//!
+//! Event and delegate type hashes are generated the same way as method hashes: the xxh3 of the
+//! event name (e.g `"OnWrite".to_xxh3()`) and the xxh3 of the Rust type path for delegate types
+//! (e.g `crate::system::Action`). [`hash_name`] can be used to calculate these at runtime.
+//! Delegates can be created for `Action`, `EventHandler` and the non-generic delegate types in
+//! the game's assemblies. Constructed generic types such as `Func<int>` aren't registered.
+//!
+//! # Safety
+//! The `set_*` functions are called once by the generated C# while the mod is loading. The
+//! function pointers they're given must stay valid for the rest of the program.
+
+use std::sync::{Mutex, OnceLock, TryLockError};
+use crate::interop::InteropError;
+use crate::system::Object;
+
+/// The signature of a Rust closure that can be called from C#. Each argument passed into the
+/// delegate is given as an owned [`Object`]. For delegates which return a value, the closure
+/// must return `Some` with an object of that type, otherwise `None` is returned as `null`.
+pub type RustClosure = Box<dyn FnMut(Vec<Object>) -> Option<Object> + Send>;
+
+type ClosureContext = Mutex<RustClosure>;
+
+type SetSubscribeEvent = unsafe extern "C" fn(usize, u64, usize) -> usize;
+static SUBSCRIBE_EVENT: OnceLock<SetSubscribeEvent> = OnceLock::new();
+
+/// # Safety
+/// See the module documentation.
+#[no_mangle]
+pub unsafe extern "C" fn set_subscribe_event(cb: SetSubscribeEvent) {
+    SUBSCRIBE_EVENT.set(cb).unwrap();
+}
+
+/// # Safety
+/// `object` must be a valid GCHandle and `context` must come from `into_context`. C# takes
+/// ownership of `context` if a subscription handle is returned.
+pub unsafe fn subscribe_event(object: usize, event_hash: u64, context: usize) -> usize {
+    SUBSCRIBE_EVENT.get().unwrap()(object, event_hash, context)
+}
+
+type SetUnsubscribeEvent = unsafe extern "C" fn(usize);
+static UNSUBSCRIBE_EVENT: OnceLock<SetUnsubscribeEvent> = OnceLock::new();
+
+/// # Safety
+/// See the module documentation.
+#[no_mangle]
+pub unsafe extern "C" fn set_unsubscribe_event(cb: SetUnsubscribeEvent) {
+    UNSUBSCRIBE_EVENT.set(cb).unwrap();
+}
+
+/// # Safety
+/// `subscription` must be a handle returned by `subscribe_event` that hasn't been unsubscribed.
+pub unsafe fn unsubscribe_event(subscription: usize) {
+    UNSUBSCRIBE_EVENT.get().unwrap()(subscription)
+}
+
+type SetCreateDelegate = unsafe extern "C" fn(u64, usize) -> usize;
+static CREATE_DELEGATE: OnceLock<SetCreateDelegate> = OnceLock::new();
+
+/// # Safety
+/// See the module documentation.
+#[no_mangle]
+pub unsafe extern "C" fn set_create_delegate(cb: SetCreateDelegate) {
+    CREATE_DELEGATE.set(cb).unwrap();
+}
+
+/// # Safety
+/// `context` must come from `into_context`. C# takes ownership of it if a delegate is returned.
+pub unsafe fn create_delegate(type_hash: u64, context: usize) -> usize {
+    CREATE_DELEGATE.get().unwrap()(type_hash, context)
+}
+
+/// Called by a C# delegate created from a Rust closure. Ownership of each GCHandle in `args` is
+/// transferred to Rust, and ownership of the returned GCHandle (or 0 for null) is transferred
+/// back to C#. If the closure panics, the panic is logged and null is returned instead of
+/// unwinding into C#. If the closure is already running (e.g an `OnWrite` subscription that
+/// logs raises its own event), null is returned instead of calling it again.
+///
+/// # Safety
+/// `context` must come from `into_context` and not have been freed yet. `args` must point to
+/// `count` GCHandles.
+#[no_mangle]
+pub unsafe extern "C" fn invoke_rust_closure(context: usize, args: *const usize, count: usize) -> usize {
+    let args = match count {
+        0 => vec![],
+        _ => std::slice::from_raw_parts(args, count).iter()
+            .map(|a| Object::new_unchecked(*a)).collect()
+    };
+    let closure = &*(context as *const ClosureContext);
+    let result = crate::panic_guard::catch_panic("invoke_rust_closure", || {
+        // Calling the closure while it's still running would deadlock, since it's locked on the
+        // same thread. This can't log, since the log could raise the same event again.
+        match closure.try_lock() {
+            Ok(mut f) => f(args),
+            Err(TryLockError::Poisoned(e)) => e.into_inner()(args),
+            Err(TryLockError::WouldBlock) => None
+        }
+    });
+    match result.ok().flatten() {
+        Some(v) => {
+            let handle = *v;
+            std::mem::forget(v);
+            handle
+        },
+        None => 0
+    }
+}
+
+/// Called by C# once the delegate holding this closure has been garbage collected.
+///
+/// # Safety
+/// `context` must come from `into_context`, and must not be used after this.
+#[no_mangle]
+pub unsafe extern "C" fn free_rust_closure(context: usize) {
+    drop(Box::from_raw(context as *mut ClosureContext));
+}
+
+fn into_context(f: RustClosure) -> usize {
+    Box::into_raw(Box::new(Mutex::new(f))) as usize
+}
+
+/// Get the hash used to identify an event name or delegate type path.
+pub fn hash_name(name: &str) -> u64 {
+    twox_hash::XxHash3_64::oneshot(name.as_bytes())
+}
+
+/// A handle to a Rust closure subscribed to a C# event. The closure is removed from the event
+/// when this is dropped, so it must be kept alive for as long as the subscription is needed.
+#[derive(Debug)]
+pub struct EventSubscription(usize);
+
+impl EventSubscription {
+    /// Subscribe a closure to the event on `object` with the given event hash.
+    pub fn new<F>(object: &Object, event_hash: u64, f: F) -> Result<Self, InteropError>
+    where F: FnMut(Vec<Object>) -> Option<Object> + Send + 'static {
+        let context = into_context(Box::new(f));
+        match unsafe { subscribe_event(**object, event_hash, context) } {
+            0 => {
+                // C# only takes ownership of the closure once the event is found
+                unsafe { free_rust_closure(context) };
+                Err(InteropError::CouldNotSubscribeToEvent)
+            },
+            v => Ok(Self(v))
+        }
+    }
+
+    /// Subscribe a closure to the event on `object` with the given event name.
+    pub fn new_by_name<F>(object: &Object, name: &str, f: F) -> Result<Self, InteropError>
+    where F: FnMut(Vec<Object>) -> Option<Object> + Send + 'static {
+        Self::new(object, hash_name(name), f)
+    }
+
+    /// Remove the closure from the event. Equivalent to dropping the subscription.
+    pub fn unsubscribe(self) {}
+
+    /// Keep the closure subscribed for the rest of the program's lifetime.
+    pub fn forget(self) {
+        std::mem::forget(self)
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        unsafe { unsubscribe_event(self.0) }
+    }
+}
+
+/// A C# delegate that calls into a Rust closure. This can be passed as a parameter when
+/// invoking a C# method (see [`crate::interop::push_parameter`]).
+#[derive(Debug)]
+pub struct Delegate(Object);
+
+impl std::ops::Deref for Delegate {
+    type Target = Object;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Delegate {
+    /// Create a delegate of the type matching `type_hash` from a closure.
+    pub fn new<F>(type_hash: u64, f: F) -> Result<Self, InteropError>
+    where F: FnMut(Vec<Object>) -> Option<Object> + Send + 'static {
+        let context = into_context(Box::new(f));
+        match unsafe { create_delegate(type_hash, context) } {
+            0 => {
+                unsafe { free_rust_closure(context) };
+                Err(InteropError::CouldNotCreateDelegate)
+            },
+            v => Ok(Self(unsafe { Object::new_unchecked(v) }))
+        }
+    }
+
+    /// Create a delegate from a closure using the delegate type's Rust path
+    /// (e.g `crate::system::Action`).
+    pub fn new_by_name<F>(type_path: &str, f: F) -> Result<Self, InteropError>
+    where F: FnMut(Vec<Object>) -> Option<Object> + Send + 'static {
+        Self::new(hash_name(type_path), f)
+    }
+}
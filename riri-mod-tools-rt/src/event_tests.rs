@@ -0,0 +1,135 @@
+#![cfg(test)]
+use crate::event::{ hash_name, Delegate, EventSubscription };
+use crate::interop::{ InteropError, ObjectInitializable, ObjectValuable };
+use crate::system::{ Int32, Object };
+use crate::testing::{ Host, HostValue };
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicU32, Ordering };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn event_subscription_lifetime() -> ReturnType {
+    let host = Host::install();
+    let target = host.insert(HostValue::Null);
+    let event = hash_name("OnModLoaded");
+    let count = Arc::new(AtomicU32::new(0));
+    let count_first = count.clone();
+    let first = EventSubscription::new(&target, event, move |_| {
+        count_first.fetch_add(1, Ordering::Relaxed);
+        None
+    })?;
+    let count_second = count.clone();
+    EventSubscription::new_by_name(&target, "OnModLoaded", move |_| {
+        count_second.fetch_add(10, Ordering::Relaxed);
+        None
+    })?.forget();
+    // Every subscriber is called, and events on other objects don't reach them
+    assert_eq!(host.raise_event(&target, event, vec![]).len(), 2);
+    host.raise_event(&host.insert(HostValue::Null), event, vec![]);
+    assert_eq!(count.load(Ordering::Relaxed), 11);
+    // The closure is dropped once it's unsubscribed
+    assert_eq!(Arc::strong_count(&count), 3);
+    drop(first);
+    assert_eq!(Arc::strong_count(&count), 2);
+    host.raise_event(&target, event, vec![]);
+    assert_eq!(count.load(Ordering::Relaxed), 21);
+    Ok(())
+}
+
+#[test]
+fn event_subscription_failure() -> ReturnType {
+    let _host = Host::install();
+    let count = Arc::new(AtomicU32::new(0));
+    let count_event = count.clone();
+    // The host doesn't know about this handle, so it isn't freed
+    let target = ManuallyDrop::new(unsafe { Object::new_unchecked(usize::MAX - 1) });
+    let result = EventSubscription::new_by_name(&target, "OnWrite", move |_| {
+        count_event.fetch_add(1, Ordering::Relaxed);
+        None
+    });
+    assert_eq!(result.err(), Some(InteropError::CouldNotSubscribeToEvent));
+    // The closure is freed by Rust if C# didn't take ownership of it
+    assert_eq!(Arc::strong_count(&count), 1);
+    Ok(())
+}
+
+#[test]
+fn event_subscription_reentrant() -> ReturnType {
+    let host = Host::install();
+    let target = host.insert(HostValue::Null);
+    let event = hash_name("OnWrite");
+    let count = Arc::new(AtomicU32::new(0));
+    let (count_event, target_event) = (count.clone(), target.clone());
+    // Like an OnWrite subscription that logs, raising the event again from inside the closure
+    // skips the closure instead of deadlocking
+    let _subscription = EventSubscription::new(&target, event, move |_| {
+        count_event.fetch_add(1, Ordering::Relaxed);
+        let inner = Host::get().raise_event(&target_event, event, vec![]);
+        assert!(matches!(inner.as_slice(), [HostValue::Null]));
+        Int32::new(1).ok().map(|v| (*v).clone())
+    })?;
+    assert!(matches!(host.raise_event(&target, event, vec![]).as_slice(), [HostValue::Int32(1)]));
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+    Ok(())
+}
+
+#[test]
+fn event_delegate_invoke() -> ReturnType {
+    let host = Host::install();
+    let count = Arc::new(AtomicU32::new(0));
+    let count_delegate = count.clone();
+    let delegate = Delegate::new_by_name("crate::system::Action", move |_| {
+        count_delegate.fetch_add(1, Ordering::Relaxed);
+        None
+    })?;
+    assert!(matches!(host.invoke_delegate(&delegate, vec![]), HostValue::Null));
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+    // Delegate types from the game's assemblies can return values
+    host.register_delegate_type("crate::riri_event_test::Double");
+    let delegate = Delegate::new_by_name("crate::riri_event_test::Double", |args| {
+        let value = unsafe { Int32::new_unchecked(args.into_iter().next()?) };
+        Int32::new(value.value().ok()? * 2).ok().map(|v| (*v).clone())
+    })?;
+    assert!(matches!(host.invoke_delegate(&delegate, vec![HostValue::Int32(21)]), HostValue::Int32(42)));
+    // Returning None passes null back to C#
+    assert!(matches!(host.invoke_delegate(&delegate, vec![]), HostValue::Null));
+    Ok(())
+}
+
+#[test]
+fn event_delegate_unregistered_type() -> ReturnType {
+    let _host = Host::install();
+    let count = Arc::new(AtomicU32::new(0));
+    let count_delegate = count.clone();
+    // C# only registers non-generic delegate types, so constructed generic types aren't found
+    let result = Delegate::new_by_name("crate::system::Func<crate::system::Int32>", move |_| {
+        count_delegate.fetch_add(1, Ordering::Relaxed);
+        None
+    });
+    assert_eq!(result.err(), Some(InteropError::CouldNotCreateDelegate));
+    assert_eq!(Arc::strong_count(&count), 1);
+    Ok(())
+}
+
+#[test]
+fn event_delegate_panic() -> ReturnType {
+    let host = Host::install();
+    let calls = Arc::new(AtomicU32::new(0));
+    let calls_delegate = calls.clone();
+    host.register_delegate_type("crate::riri_event_test::GetValue");
+    let delegate = Delegate::new_by_name("crate::riri_event_test::GetValue", move |_| {
+        if calls_delegate.fetch_add(1, Ordering::Relaxed) == 0 {
+            panic!("delegate panic test");
+        }
+        Int32::new(5).ok().map(|v| (*v).clone())
+    })?;
+    // The panic is logged and null is returned instead of unwinding into C#
+    assert!(matches!(host.invoke_delegate(&delegate, vec![]), HostValue::Null));
+    assert!(host.get_log().iter().any(|e| e.text.starts_with("panicked: delegate panic test")
+        && e.text.contains("function=invoke_rust_closure")));
+    // The closure can still be called after it panicked
+    assert!(matches!(host.invoke_delegate(&delegate, vec![]), HostValue::Int32(5)));
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    Ok(())
+}
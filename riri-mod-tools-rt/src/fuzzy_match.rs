@@ -0,0 +1,301 @@
+//! Finds where a signature that no longer matches has most likely moved to. After a game update,
+//! the function a signature was written for usually still exists with a few bytes changed, such
+//! as a struct offset or a register. Every position in the executable's sections is scored by a
+//! weighted Hamming distance against the signature. The signature is decoded into instructions
+//! first, and its prefix, opcode and ModRM bytes count for more than displacements and
+//! immediates, which are what usually changes between builds.
+//!
+//! The closest positions are then decoded from their own start. Bytes that are relative operands
+//! or relocated at that position don't count against it, and positions where the signature's
+//! instructions don't line up with the decoded ones are penalized. A new signature is generated
+//! for each of the remaining positions with [`SignatureGenerator`].
+//!
+//! When a hook's signature isn't found in game, [`log_closest_matches`] logs the best candidates
+//! and their suggested signatures. Searching the whole executable takes a while, so like the
+//! ambiguous signature checks, this only happens in debug builds or while the signature cache is
+//! being regenerated.
+
+use crate::instruction_decoder;
+use crate::pattern::Pattern;
+use crate::pe_image::{ ImageSections, PeImage };
+use crate::signature_generator::SignatureGenerator;
+use std::ffi::{ c_char, CStr };
+use std::fmt::{ Display, Formatter };
+use std::ops::Range;
+use std::sync::{
+    atomic::{ AtomicUsize, Ordering },
+    Mutex,
+    PoisonError
+};
+
+// The headers are mapped as one page at the start of the image
+const MAPPED_HEADER_SIZE: usize = 0x1000;
+
+/// A position that's similar to a signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    offset: usize,
+    similarity: f32,
+    suggestion: Option<Pattern>
+}
+
+impl FuzzyMatch {
+    pub fn get_offset(&self) -> usize { self.offset }
+    /// How closely the bytes at the offset match the signature, from 0 to 1.
+    pub fn get_similarity(&self) -> f32 { self.similarity }
+    /// A signature that only matches this offset, if one could be generated.
+    pub fn get_suggestion(&self) -> Option<&Pattern> { self.suggestion.as_ref() }
+}
+
+impl Display for FuzzyMatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:x} ({:.0}% similar)", self.offset, self.similarity * 100.0)?;
+        match &self.suggestion {
+            Some(p) => write!(f, ", suggested signature: {}", p),
+            None => write!(f, ", no unique signature")
+        }
+    }
+}
+
+// The fixed bytes of a signature and how much each of them counts towards the distance
+#[derive(Debug)]
+struct WeightedSignature {
+    // (offset, byte, weight), with the heaviest bytes first so that scanning stops sooner
+    fixed: Vec<(usize, u8, u32)>,
+    // Where each instruction in the signature starts
+    instructions: Vec<usize>,
+    total: u32,
+    len: usize
+}
+
+/// Searches an executable for the positions closest to a signature.
+#[derive(Debug, Clone)]
+pub struct FuzzyMatcher<'a> {
+    sections: ImageSections<'a>,
+    relocations: &'a [Range<usize>],
+    max_results: usize,
+    min_similarity: f32,
+    threads: usize
+}
+
+impl<'a> FuzzyMatcher<'a> {
+    const DEFAULT_MAX_RESULTS: usize = 3;
+    const DEFAULT_MIN_SIMILARITY: f32 = 0.6;
+    const CHUNK_SIZE: usize = 0x100000;
+    // Bytes that describe what an instruction does, compared to the values it operates on
+    const OPCODE_WEIGHT: u32 = 3;
+    const OPERAND_WEIGHT: u32 = 1;
+    // Closest positions to keep from each chunk before they're decoded
+    const CANDIDATES_PER_CHUNK: usize = 64;
+
+    /// Create a matcher for an executable mapped at `image`. Only `regions` (offsets into the
+    /// image) are searched, and bytes inside of `relocations` (sorted by offset) are ignored.
+    pub fn new(image: &'a [u8], regions: Vec<Range<usize>>, relocations: &'a [Range<usize>]) -> Self {
+        Self::from_sections(ImageSections::from_image(image, &regions), relocations)
+    }
+
+    /// Create a matcher for the sections of an executable, which don't have to be next to each
+    /// other in memory, such as the game's executable while it's running.
+    pub fn from_sections(sections: ImageSections<'a>, relocations: &'a [Range<usize>]) -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |v| v.get());
+        Self {
+            sections, relocations,
+            max_results: Self::DEFAULT_MAX_RESULTS,
+            min_similarity: Self::DEFAULT_MIN_SIMILARITY,
+            threads
+        }
+    }
+
+    pub fn from_pe_image(image: &'a PeImage) -> Self {
+        Self::from_sections(image.get_mapped_sections(), image.get_relocations())
+    }
+
+    /// Set the number of matches to return. Defaults to 3.
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results.max(1);
+        self
+    }
+
+    /// Set how similar a position has to be to the signature to be returned, from 0 to 1.
+    /// Defaults to 0.6.
+    pub fn with_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.min_similarity = min_similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the number of threads to search with. Defaults to the number of logical processors.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    fn get_weighted_signature(pattern: &Pattern) -> WeightedSignature {
+        let bytes = pattern.get_bytes();
+        let mut weights = vec![Self::OPERAND_WEIGHT; bytes.len()];
+        let mut instructions = vec![];
+        let mut pos = 0;
+        // The last instruction can be cut off by the end of the signature, so its bytes keep the
+        // operand weight
+        while let Ok(instruction) = instruction_decoder::decode(&bytes[pos..]) {
+            instructions.push(pos);
+            let operands = instruction.get_displacement().or(instruction.get_immediate())
+                .map_or(instruction.get_length(), |o| o.get_offset());
+            weights[pos..pos + operands].fill(Self::OPCODE_WEIGHT);
+            pos += instruction.get_length();
+        }
+        let mut fixed: Vec<(usize, u8, u32)> = (0..bytes.len())
+            .filter(|i| pattern.get_mask()[*i])
+            .map(|i| (i, bytes[i], weights[i]))
+            .collect();
+        fixed.sort_by_key(|(i, _, w)| (u32::MAX - w, *i));
+        let total = fixed.iter().map(|(_, _, w)| w).sum();
+        WeightedSignature { fixed, instructions, total, len: bytes.len() }
+    }
+
+    fn get_max_distance(&self, signature: &WeightedSignature) -> u32 {
+        ((1.0 - self.min_similarity) * signature.total as f32).floor() as u32
+    }
+
+    // Score every position in the sections, keeping the closest few from each chunk
+    fn get_candidates(&self, signature: &WeightedSignature) -> Vec<(u32, usize)> {
+        let max_distance = self.get_max_distance(signature);
+        // Each chunk is a range of positions within a section
+        let mut chunks: Vec<(&[u8], usize, Range<usize>)> = vec![];
+        for section in self.sections.get_sections() {
+            let last = match section.len().checked_sub(signature.len) {
+                Some(v) => v + 1,
+                None => continue
+            };
+            let offset = self.sections.get_range(section).start;
+            let mut start = 0;
+            while start < last {
+                chunks.push((section, offset, start..(start + Self::CHUNK_SIZE).min(last)));
+                start += Self::CHUNK_SIZE;
+            }
+        }
+        let candidates = Mutex::new(vec![]);
+        let next_chunk = AtomicUsize::new(0);
+        let scan_chunks = || {
+            while let Some((section, offset, chunk)) = chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed)) {
+                let mut found = vec![];
+                'position: for pos in chunk.clone() {
+                    let mut distance = 0;
+                    for (i, byte, weight) in &signature.fixed {
+                        if section[pos + i] != *byte {
+                            distance += weight;
+                            if distance > max_distance { continue 'position; }
+                        }
+                    }
+                    found.push((distance, offset + pos));
+                }
+                found.sort_unstable();
+                found.truncate(Self::CANDIDATES_PER_CHUNK);
+                candidates.lock().unwrap_or_else(PoisonError::into_inner).extend(found);
+            }
+        };
+        let threads = self.threads.min(chunks.len());
+        if threads > 1 {
+            std::thread::scope(|s| {
+                for _ in 0..threads { s.spawn(scan_chunks); }
+            });
+        } else {
+            scan_chunks();
+        }
+        candidates.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Score a candidate again using the instructions decoded from it. Returns None if there
+    // isn't a valid instruction at the candidate.
+    fn get_aligned_distance(&self, generator: &SignatureGenerator, signature: &WeightedSignature, offset: usize) -> Option<u32> {
+        let (_, mask, _) = generator.get_masked_bytes(offset, signature.len).ok()?;
+        let code = self.sections.get_bytes(offset)?;
+        let mut distance = 0;
+        for (i, byte, weight) in &signature.fixed {
+            // Relative operands and relocated values are expected to differ
+            if mask.get(*i) == Some(&false) { continue; }
+            if code.get(*i) != Some(byte) { distance += weight; }
+        }
+        let mut instructions = vec![];
+        let mut pos = 0;
+        while pos < mask.len() {
+            match instruction_decoder::decode(&code[pos..]) {
+                Ok(v) => { instructions.push(pos); pos += v.get_length(); },
+                Err(_) => break
+            }
+        }
+        for start in signature.instructions.iter().filter(|s| **s < mask.len()) {
+            if instructions.binary_search(start).is_err() { distance += Self::OPCODE_WEIGHT; }
+        }
+        Some(distance.min(signature.total))
+    }
+
+    /// Find the positions closest to `pattern`, from most to least similar, along with a new
+    /// signature for each of them.
+    pub fn find_closest(&self, pattern: &Pattern) -> Vec<FuzzyMatch> {
+        let signature = Self::get_weighted_signature(pattern);
+        let generator = SignatureGenerator::from_sections(self.sections.clone(), self.relocations);
+        let max_distance = self.get_max_distance(&signature);
+        let mut candidates: Vec<(u32, usize)> = self.get_candidates(&signature).into_iter()
+            .filter_map(|(_, o)| self.get_aligned_distance(&generator, &signature, o).map(|d| (d, o)))
+            .filter(|(d, _)| *d <= max_distance)
+            .collect();
+        candidates.sort_unstable();
+        // Positions that overlap a closer one are usually the same function shifted by a few bytes
+        let mut results: Vec<(u32, usize)> = vec![];
+        for (distance, offset) in candidates {
+            if results.len() == self.max_results { break; }
+            if results.iter().all(|(_, o)| offset.abs_diff(*o) >= signature.len) {
+                results.push((distance, offset));
+            }
+        }
+        results.into_iter().map(|(distance, offset)| FuzzyMatch {
+            offset,
+            similarity: 1.0 - distance as f32 / signature.total as f32,
+            suggestion: generator.generate(offset).ok()
+        }).collect()
+    }
+}
+
+/// Log the closest matches for a hook's signature that wasn't found in the game's executable,
+/// along with a suggested replacement signature for each. Hooks declared with `required = false`
+/// are skipped, since they're expected to be missing from some versions of the game. This only
+/// runs in debug builds or while the signature cache is being regenerated, and requires
+/// `set_current_process` to have been called.
+pub fn log_closest_matches(name: &str, pattern: &Pattern) {
+    if !crate::sigscan_batch::should_count_matches()
+    || crate::hook_registry::get_hook(name).is_some_and(|r| !r.is_required())
+    || crate::sigscan_resolver::CURRENT_PROCESS.get().is_none() {
+        return;
+    }
+    let (base, sections) = crate::sigscan_batch::get_executable_sections();
+    let sections = ImageSections::new(base, sections);
+    // SAFETY: The first page of the image has the headers, and is mapped for as long as the
+    // executable is loaded
+    let headers = unsafe { std::slice::from_raw_parts(base as *const u8, MAPPED_HEADER_SIZE) };
+    let relocations = PeImage::get_mapped_relocations(headers, &sections);
+    let start = std::time::Instant::now();
+    let matches = FuzzyMatcher::from_sections(sections, &relocations).find_closest(pattern);
+    crate::logln!(Debug, "Searched for close matches to {} in {} ms", name, start.elapsed().as_millis());
+    if matches.is_empty() {
+        crate::logln!(Warning, "Signature for {} has no close matches, the function may have been removed or rewritten. Pattern: {}", name, pattern);
+        return;
+    }
+    crate::logln!(Warning, "Signature for {} wasn't found, but {} similar locations were. Pattern: {}", name, matches.len(), pattern);
+    for m in matches {
+        crate::logln!(Warning, "    {}", m);
+    }
+}
+
+/// Called by the generated C# when a signature can't be found.
+///
+/// # Safety
+///
+/// `name` and `pattern` must be valid null terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn log_ffi_closest_matches(name: *const c_char, pattern: *const c_char) {
+    let name = CStr::from_ptr(name).to_string_lossy();
+    let text = CStr::from_ptr(pattern).to_string_lossy();
+    if let Ok(p) = Pattern::new(&text) {
+        log_closest_matches(&name, &p);
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(test)]
+use crate::fuzzy_match::FuzzyMatcher;
+use crate::pattern::Pattern;
+use crate::pe_builder::{ self, PeBuilder };
+use crate::pe_image::{ PeImage, PeImageError };
+use crate::signature_generator_tests::{ get_test_code, TEST_PROLOGUE };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// The signature was written for a function that reads a field at 0x130. In this version the field
+// has moved to 0x138, there's another function in a second code section with a similar prologue,
+// and a call with a different target.
+fn get_test_image() -> Result<PeImage, PeImageError> {
+    let mut similar = TEST_PROLOGUE;
+    similar[4] = 0x10;
+    let text = get_test_code(0x200, &[
+        (0x100, &TEST_PROLOGUE), (0x10a, &[0x8b, 0x81, 0x38, 0x01, 0, 0]), (0x110, &[0xe8, 0x11, 0x22, 0, 0]),
+        (0x115, &[0x48, 0x8b, 0x05, 0x44, 0x33, 0, 0, 0xc3])
+    ]);
+    let text2 = get_test_code(0x200, &[(0, &similar), (0xa, &[0x33, 0xc0, 0xc3]), (0x100, &[0xe8, 0x50, 0x60, 0, 0, 0xc3])]);
+    let file = PeBuilder::new().text(&text).section(".text2", &text2, pe_builder::CODE_CHARACTERISTICS).build();
+    PeImage::from_bytes(&file)
+}
+
+const SIGNATURE: &str = "48 89 5C 24 08 57 48 83 EC 20 8B 81 30 01 00 00 E8 ?? ?? ?? ?? 48 8B 05";
+
+#[test]
+fn find_closest_matches() -> ReturnType {
+    let image = get_test_image()?;
+    let pattern = Pattern::new(SIGNATURE)?;
+    let matcher = FuzzyMatcher::from_pe_image(&image);
+    let matches = matcher.find_closest(&pattern);
+    assert_eq!(matches.len(), 1);
+    // Only a displacement byte is different, which counts for less than an opcode
+    assert_eq!(matches[0].get_offset(), 0x1100);
+    assert!((matches[0].get_similarity() - 41.0 / 42.0).abs() < 1e-6);
+    assert_eq!(matches[0].get_suggestion().map(|p| p.to_string()).as_deref(),
+        Some("48 89 5C 24 08"));
+    assert_eq!(matches[0].to_string(), "0x1100 (98% similar), suggested signature: 48 89 5C 24 08");
+    // The other function only shares most of the prologue
+    let matcher = FuzzyMatcher::from_pe_image(&image).with_min_similarity(0.5);
+    let offsets: Vec<usize> = matcher.find_closest(&pattern).iter().map(|m| m.get_offset()).collect();
+    assert_eq!(offsets, [0x1100, 0x2000]);
+    let matcher = matcher.with_max_results(1);
+    assert_eq!(matcher.find_closest(&pattern).len(), 1);
+    assert!(FuzzyMatcher::from_pe_image(&image).find_closest(&Pattern::new("0F 0B 0F 0B")?).is_empty());
+    Ok(())
+}
+
+#[test]
+fn find_closest_ignores_relative_operands() -> ReturnType {
+    let image = get_test_image()?;
+    // A call target that should have been a wildcard doesn't count against the match
+    let matches = FuzzyMatcher::from_pe_image(&image).with_threads(1)
+        .find_closest(&Pattern::new("E8 10 20 00 00 C3")?);
+    assert_eq!(matches.len(), 1);
+    assert_eq!((matches[0].get_offset(), matches[0].get_similarity()), (0x2100, 1.0));
+    assert_eq!(matches[0].get_suggestion().map(|p| p.to_string()).as_deref(), Some("E8 ?? ?? ?? ?? C3"));
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! Storage for `user_defined` hooks, which can be created on more than one function with
+//! `create_hook!`. Each instance gets its own entry point, which marks the instance as current
+//! before calling the hook function, so that `original_function!` calls the original function of
+//! the target that was actually called.
+//!
+//! ```ignore
+//! let handle = create_hook!(ptr, print_log).unwrap();
+//! // ...
+//! handle.remove();
+//! ```
+
+use std::cell::Cell;
+use std::sync::{
+    atomic::{ AtomicBool, AtomicUsize, Ordering },
+    OnceLock
+};
+use crate::hook_registry::{ self, HookState };
+
+/// The most instances a `user_defined` hook can have at once. This must stay in sync with
+/// MAX_HOOK_INSTANCES in riri_mod_tools_impl::hook_codegen and the instance array in the generated
+/// C# hook bootstrap!
+pub const MAX_HOOK_INSTANCES: usize = 8;
+
+/// Creates a hook with the given entry point on the target address, storing the IHook in the
+/// given slot. The address of the original function is written into the last parameter before the
+/// hook is activated. Returns 1 if the hook was created.
+pub type CreateHookCallback = extern "C" fn(usize, usize, u32, *mut usize) -> u8;
+/// Disables the hook in the given slot. Returns 1 if the slot had a hook.
+pub type RemoveHookCallback = extern "C" fn(u32) -> u8;
+
+thread_local! {
+    // The instance table and original function of the innermost user defined hook that's running
+    static CURRENT_INSTANCE: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Original function addresses for each instance of a hook.
+#[derive(Debug)]
+pub struct HookSlots {
+    originals: [AtomicUsize; MAX_HOOK_INSTANCES],
+    used: [AtomicBool; MAX_HOOK_INSTANCES]
+}
+
+impl HookSlots {
+    const fn new() -> Self {
+        Self {
+            originals: [const { AtomicUsize::new(0) }; MAX_HOOK_INSTANCES],
+            used: [const { AtomicBool::new(false) }; MAX_HOOK_INSTANCES]
+        }
+    }
+
+    fn reserve(&self) -> Option<usize> {
+        self.used.iter().position(|u| u.compare_exchange(false, true,
+            Ordering::AcqRel, Ordering::Relaxed).is_ok())
+    }
+
+    // The original function is left in place for the next instance to overwrite. Calls that are
+    // still running on another thread don't read it again, since enter copies it when the call
+    // starts.
+    fn release(&self, slot: usize) {
+        self.used[slot].store(false, Ordering::Release);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.used.iter().all(|u| !u.load(Ordering::Acquire))
+    }
+
+    fn get_current(&self) -> Option<usize> {
+        let (table, original) = CURRENT_INSTANCE.with(|c| c.get());
+        match table == self as *const Self as usize && original != 0 {
+            true => Some(original),
+            false => None
+        }
+    }
+}
+
+/// Replaces the `OnceLock` that stores the original function for `user_defined` hooks. `get`
+/// returns the original function for the instance that's currently running, or the original
+/// function that was set by the hook bootstrap if the hook wasn't called through an instance.
+/// `F` must be a function pointer.
+#[derive(Debug)]
+pub struct HookInstances<F: Copy> {
+    default: OnceLock<F>,
+    slots: HookSlots
+}
+
+impl<F: Copy> HookInstances<F> {
+    pub const fn new() -> Self {
+        Self { default: OnceLock::new(), slots: HookSlots::new() }
+    }
+
+    pub fn get(&self) -> Option<F> {
+        const {
+            assert!(std::mem::size_of::<F>() == std::mem::size_of::<usize>(),
+                "HookInstances can only store function pointers")
+        };
+        match self.slots.get_current() {
+            // SAFETY: Only function pointers of type F are stored in slots, and F is the same size
+            // as a function pointer
+            Some(v) => Some(unsafe { std::mem::transmute_copy::<usize, F>(&v) }),
+            None => self.default.get().copied()
+        }
+    }
+
+    pub fn set(&self, value: F) -> Result<(), F> {
+        self.default.set(value)
+    }
+}
+
+impl<F: Copy> Default for HookInstances<F> {
+    fn default() -> Self { Self::new() }
+}
+
+/// Call the hook function as the instance in `slot`. Used by the entry points generated for
+/// `user_defined` hooks. The instance's original function is read once here, so a call keeps
+/// using it even if the instance is removed and its slot is reused while the call is running.
+pub fn enter<F: Copy, R, C: FnOnce() -> R>(instances: &HookInstances<F>, slot: usize, f: C) -> R {
+    struct Restore((usize, usize));
+    impl Drop for Restore {
+        fn drop(&mut self) { CURRENT_INSTANCE.with(|c| c.set(self.0)); }
+    }
+    let current = (&instances.slots as *const HookSlots as usize, instances.slots.originals[slot].load(Ordering::Acquire));
+    let _restore = Restore(CURRENT_INSTANCE.with(|c| c.replace(current)));
+    f()
+}
+
+/// Handle to a hook created with `create_hook!`. Dropping the handle leaves the hook in place.
+#[derive(Debug)]
+pub struct HookHandle {
+    name: &'static str,
+    address: usize,
+    slot: usize,
+    slots: &'static HookSlots,
+    remove: Option<RemoveHookCallback>
+}
+
+impl HookHandle {
+    pub fn get_name(&self) -> &str { self.name }
+    /// The address of the hooked function.
+    pub fn get_address(&self) -> usize { self.address }
+    pub fn get_slot(&self) -> usize { self.slot }
+
+    /// Disable the hook so that the target calls the original function directly again, and free
+    /// its slot for another instance. The hook is marked as removed in the hook registry once
+    /// every instance is removed. Returns false if the C# host couldn't find the hook.
+    pub fn remove(self) -> bool {
+        let removed = match self.remove {
+            Some(f) => f(self.slot as u32) != 0,
+            None => false
+        };
+        if removed {
+            self.slots.release(self.slot);
+            if self.slots.is_empty() {
+                hook_registry::set_hook_state(self.name, None, 0, HookState::Removed, None);
+            }
+        }
+        removed
+    }
+}
+
+/// Used by `create_hook!`. `entries` are the entry points for each slot, which are passed to the
+/// C# host as the hook function.
+pub fn create_hook<F: Copy>(
+    name: &'static str,
+    address: usize,
+    instances: &'static HookInstances<F>,
+    entries: &[usize; MAX_HOOK_INSTANCES],
+    create: Option<&CreateHookCallback>,
+    remove: Option<&RemoveHookCallback>
+) -> Option<HookHandle> {
+    let create = match create {
+        Some(v) => v,
+        None => {
+            crate::logln!(Error, "Can't create {} since the hook bootstrap hasn't been initialized", name);
+            return None;
+        }
+    };
+    let slot = match instances.slots.reserve() {
+        Some(v) => v,
+        None => {
+            crate::logln!(Error, "Can't create {} at 0x{:x}: the hook already has {} instances", name, address, MAX_HOOK_INSTANCES);
+            return None;
+        }
+    };
+    if create(address, entries[slot], slot as u32, instances.slots.originals[slot].as_ptr()) == 0 {
+        instances.slots.release(slot);
+        return None;
+    }
+    Some(HookHandle { name, address, slot, slots: &instances.slots, remove: remove.copied() })
+}
@@ -0,0 +1,64 @@
+#![cfg(test)]
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn hook_instance_slots() -> ReturnType {
+    use crate::hook_instance::{ self, HookInstances, MAX_HOOK_INSTANCES };
+    use crate::hook_registry::{ self, HookState };
+    use crate::testing::Host;
+    use std::sync::atomic::{ AtomicU8, AtomicUsize, Ordering };
+    type HookFn = extern "C" fn(u32) -> u32;
+    static INSTANCES: HookInstances<HookFn> = HookInstances::new();
+    static PAYLOADS: [AtomicUsize; MAX_HOOK_INSTANCES] = [const { AtomicUsize::new(0) }; MAX_HOOK_INSTANCES];
+    static REMOVED: AtomicU8 = AtomicU8::new(0);
+    extern "C" fn original_a(v: u32) -> u32 { v + 100 }
+    extern "C" fn original_b(v: u32) -> u32 { v + 200 }
+    extern "C" fn original_c(v: u32) -> u32 { v + 300 }
+    extern "C" fn hook(v: u32) -> u32 { (INSTANCES.get().unwrap())(v) }
+    // Pretend to be the C# host, which creates the hook and gives back the original function
+    extern "C" fn create(address: usize, payload: usize, slot: u32, original: *mut usize) -> u8 {
+        PAYLOADS[slot as usize].store(payload, Ordering::Relaxed);
+        let og: HookFn = match address {
+            0x1000 => original_a,
+            0x2000 => original_b,
+            _ => original_c
+        };
+        unsafe { *original = og as usize; }
+        hook_registry::set_hook_state("instance_hook", None, address, HookState::Hooked, None);
+        1
+    }
+    extern "C" fn remove(slot: u32) -> u8 {
+        REMOVED.store(slot as u8 + 1, Ordering::Relaxed);
+        1
+    }
+    fn entry(slot: usize, v: u32) -> u32 { hook_instance::enter(&INSTANCES, slot, || hook(v)) }
+    let _host = Host::install();
+    let entries: [usize; MAX_HOOK_INSTANCES] = std::array::from_fn(|i| 0x5000 + i);
+    let create: hook_instance::CreateHookCallback = create;
+    let remove: hook_instance::RemoveHookCallback = remove;
+    assert!(hook_instance::create_hook("instance_hook", 0x1000, &INSTANCES, &entries, None, None).is_none());
+    let a = hook_instance::create_hook("instance_hook", 0x1000, &INSTANCES, &entries, Some(&create), Some(&remove)).unwrap();
+    let b = hook_instance::create_hook("instance_hook", 0x2000, &INSTANCES, &entries, Some(&create), Some(&remove)).unwrap();
+    assert_eq!((a.get_slot(), b.get_slot(), b.get_address()), (0, 1, 0x2000));
+    assert_eq!(PAYLOADS[1].load(Ordering::Relaxed), 0x5001);
+    // Each instance calls its own original function, including when one is called inside another
+    assert_eq!(entry(a.get_slot(), 1), 101);
+    assert_eq!(entry(b.get_slot(), 1), 201);
+    assert_eq!(hook_instance::enter(&INSTANCES, 1, || entry(0, 1) + hook(2)), 101 + 202);
+    assert!(INSTANCES.get().is_none());
+    assert!(b.remove());
+    assert_eq!(REMOVED.load(Ordering::Relaxed), 2);
+    assert!(hook_registry::is_hooked("instance_hook"));
+    // The freed slot is used by the next instance, while a call that was already running in the
+    // removed instance keeps calling its own original function
+    let in_flight = hook_instance::enter(&INSTANCES, 1, || {
+        let c = hook_instance::create_hook("instance_hook", 0x3000, &INSTANCES, &entries, Some(&create), Some(&remove)).unwrap();
+        (hook(1), c)
+    });
+    let c = in_flight.1;
+    assert_eq!((in_flight.0, c.get_slot()), (201, 1));
+    assert_eq!(entry(c.get_slot(), 1), 301);
+    assert!(c.remove() && a.remove());
+    assert_eq!(hook_registry::get_hook("instance_hook").unwrap().get_state(), HookState::Removed);
+    Ok(())
+}
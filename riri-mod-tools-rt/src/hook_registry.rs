@@ -0,0 +1,327 @@
+//! Keeps track of every hook and static defined with `riri_hook_fn`, `riri_hook_inline_fn` and
+//! `riri_hook_static`. The generated C# bootstrap reports each hook as pending when it's
+//! registered, then as hooked, resolved or failed once its signature scan finishes. This can be
+//! used to check if a hook is live before relying on it:
+//!
+//! ```ignore
+//! if !hook_registry::is_hooked("fldPCMoveUpdate") {
+//!     logln!(Warning, "Free camera is unavailable");
+//! }
+//! ```
+//!
+//! A summary of the registry is logged after `OnModLoaderInitialized`.
+//!
+//! Hooks are required unless they're declared with `dynamic_offset(required = false, ...)`. If a
+//! required hook fails, every hook payload in the mod calls straight through to the original
+//! function instead, so the mod's features are disabled rather than left half working. Use
+//! [`hooks_ready`] to check this from other code paths.
+//!
+//! Function hooks can also be turned off and on again at runtime through the module generated
+//! next to each hook, e.g `fldPCMoveUpdate::disable()`. This toggles Reloaded's `IHook`, so a
+//! disabled hook costs nothing when the function is called.
+
+use std::collections::HashSet;
+use std::ffi::{ c_char, CStr };
+use std::fmt::{ Display, Formatter };
+use std::sync::{
+    atomic::{ AtomicBool, Ordering },
+    Mutex,
+    RwLock
+};
+use crate::panic_guard::FallbackValue;
+
+/// This must stay in sync with HookState in the generated C# utilities!
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookState {
+    /// Waiting for the signature scan to finish.
+    Pending,
+    /// The address of a static was found.
+    Resolved,
+    /// The signature couldn't be found.
+    Failed,
+    /// The function was found and hooked.
+    Hooked,
+    /// Every instance of a user defined hook was removed with `HookHandle::remove`.
+    Removed
+}
+
+impl TryFrom<u32> for HookState {
+    type Error = ();
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Pending),
+            1 => Ok(Self::Resolved),
+            2 => Ok(Self::Failed),
+            3 => Ok(Self::Hooked),
+            4 => Ok(Self::Removed),
+            _ => Err(())
+        }
+    }
+}
+
+impl Display for HookState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Resolved => write!(f, "resolved"),
+            Self::Failed => write!(f, "failed"),
+            Self::Hooked => write!(f, "hooked"),
+            Self::Removed => write!(f, "removed")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRecord {
+    name: String,
+    signature: Option<String>,
+    address: usize,
+    state: HookState,
+    hash: Option<u64>,
+    required: bool,
+    enabled: bool,
+    allow_multiple: bool
+}
+
+impl HookRecord {
+    pub fn get_name(&self) -> &str { &self.name }
+    /// The signature that was scanned for. This is `None` for hooks at a static offset and
+    /// user defined hooks.
+    pub fn get_signature(&self) -> Option<&str> { self.signature.as_deref() }
+    /// The address of the hooked function or static, or 0 if it hasn't been resolved.
+    pub fn get_address(&self) -> usize { self.address }
+    pub fn get_state(&self) -> HookState { self.state }
+    /// The executable hash of the arm that was chosen when the hook has different entries for
+    /// each version of the game.
+    pub fn get_hash(&self) -> Option<u64> { self.hash }
+    /// If this hook fails, the mod's hooks are disabled. User defined hooks are never required.
+    pub fn is_required(&self) -> bool { self.required }
+    /// If this hook's signature is expected to match more than once, so it isn't reported as
+    /// ambiguous.
+    pub fn allows_multiple(&self) -> bool { self.allow_multiple }
+    /// Check if the hook is live and hasn't been disabled with `{hook_name}::disable()`.
+    pub fn is_enabled(&self) -> bool { self.state == HookState::Hooked && self.enabled }
+    pub fn is_live(&self) -> bool {
+        self.state == HookState::Hooked || self.state == HookState::Resolved
+    }
+}
+
+static HOOK_REGISTRY: RwLock<Vec<HookRecord>> = RwLock::new(vec![]);
+static REQUIRED_HOOK_FAILED: AtomicBool = AtomicBool::new(false);
+
+fn update_record<F: FnOnce(&mut HookRecord)>(name: &str, f: F) {
+    let mut registry = match HOOK_REGISTRY.write() {
+        Ok(v) => v,
+        Err(e) => e.into_inner()
+    };
+    let record = match registry.iter().position(|r| r.name == name) {
+        Some(i) => &mut registry[i],
+        None => {
+            registry.push(HookRecord { name: name.to_owned(), signature: None,
+                address: 0, state: HookState::Pending, hash: None, required: true, enabled: true, allow_multiple: false });
+            registry.last_mut().unwrap()
+        }
+    };
+    f(record);
+}
+
+/// Update the state of a hook, adding it to the registry if it hasn't been seen before. A
+/// `signature` of `None` or a `hash` of `None` keeps the previous value.
+pub fn set_hook_state(name: &str, signature: Option<&str>, address: usize, state: HookState, hash: Option<u64>) {
+    let mut required = false;
+    update_record(name, |record| {
+        if let Some(s) = signature { record.signature = Some(s.to_owned()); }
+        if hash.is_some() { record.hash = hash; }
+        record.address = address;
+        record.state = state;
+        // Newly created hooks are activated straight away
+        if state == HookState::Hooked { record.enabled = true; }
+        required = record.required;
+    });
+    // Let crash reports show which hooked function the game crashed in
+    if state == HookState::Hooked && address != 0 {
+        crate::crash_handler::register_symbol(format!("{} (target)", name), address);
+    }
+    // Save the resolved address alongside the signature if the signature cache is being written
+    if (state == HookState::Hooked || state == HookState::Resolved) && address != 0 {
+        crate::signature_cache::set_resolved_address(name, address);
+    }
+    if state == HookState::Failed && required
+    && !REQUIRED_HOOK_FAILED.swap(true, Ordering::Relaxed) {
+        crate::logln!(Error, "Required hook {} couldn't be found, so this mod's hooks have been disabled. The mod may not support this version of the game.", name);
+    }
+}
+
+/// Mark a hook as required or optional. Hooks are required by default.
+pub fn set_hook_required(name: &str, required: bool) {
+    update_record(name, |record| record.required = required);
+}
+
+/// Called by the generated C# hook bootstrap. `signature` may be null, and a `hash` of 0 keeps
+/// the previous value.
+///
+/// # Safety
+///
+/// `name` and `signature` (if it's not null) must be valid null terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn set_ffi_hook_state(name: *const c_char, signature: *const c_char, address: usize, state: u32, hash: u64) -> bool {
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(v) => v,
+        Err(_) => return false
+    };
+    let signature = match signature.is_null() {
+        true => None,
+        false => CStr::from_ptr(signature).to_str().ok()
+    };
+    match state.try_into() {
+        Ok(s) => {
+            set_hook_state(name, signature, address, s, if hash != 0 { Some(hash) } else { None });
+            true
+        },
+        Err(_) => false
+    }
+}
+
+/// Callback set by the generated C# which enables (1) or disables (0) a hook. Returns 1 if the
+/// hook exists.
+pub type HookToggle = extern "C" fn(u8) -> u8;
+
+/// Used by the `enable` and `disable` functions generated for each hook. Returns false if the
+/// hook hasn't been created yet.
+pub fn toggle_hook(name: &str, toggle: Option<&HookToggle>, enabled: bool) -> bool {
+    let toggle = match toggle {
+        Some(v) if is_hooked(name) => v,
+        _ => return false
+    };
+    if toggle(enabled as u8) == 0 {
+        return false;
+    }
+    update_record(name, |record| record.enabled = enabled);
+    true
+}
+
+/// Check if a function hook is live and hasn't been disabled.
+pub fn is_hook_enabled(name: &str) -> bool {
+    get_hook(name).is_some_and(|r| r.is_enabled())
+}
+
+/// Allow a hook's signature to match more than once without being reported as ambiguous.
+pub fn set_hook_allow_multiple(name: &str, allow_multiple: bool) {
+    update_record(name, |record| record.allow_multiple = allow_multiple);
+}
+
+/// Called by the generated C# hook bootstrap for optional and user defined hooks.
+///
+/// # Safety
+///
+/// `name` must be a valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn set_ffi_hook_required(name: *const c_char, required: bool) -> bool {
+    match CStr::from_ptr(name).to_str() {
+        Ok(v) => { set_hook_required(v, required); true },
+        Err(_) => false
+    }
+}
+
+/// Called by the generated C# hook bootstrap for hooks with `allow_multiple = true`.
+///
+/// # Safety
+///
+/// `name` must be a valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn set_ffi_hook_allow_multiple(name: *const c_char, allow_multiple: bool) -> bool {
+    match CStr::from_ptr(name).to_str() {
+        Ok(v) => { set_hook_allow_multiple(v, allow_multiple); true },
+        Err(_) => false
+    }
+}
+
+pub fn get_hook(name: &str) -> Option<HookRecord> {
+    match HOOK_REGISTRY.read() {
+        Ok(v) => v.iter().find(|r| r.name == name).cloned(),
+        Err(e) => e.into_inner().iter().find(|r| r.name == name).cloned()
+    }
+}
+
+/// Get every hook in the order that they were registered.
+pub fn get_hooks() -> Vec<HookRecord> {
+    match HOOK_REGISTRY.read() {
+        Ok(v) => v.clone(),
+        Err(e) => e.into_inner().clone()
+    }
+}
+
+/// Check if a function has been hooked or a static has been resolved.
+pub fn is_hooked(name: &str) -> bool {
+    get_hook(name).is_some_and(|r| r.is_live())
+}
+
+/// Check if every required hook has been hooked or resolved. This is false while signature scans
+/// are still running, and stays false if any required hook fails.
+pub fn hooks_ready() -> bool {
+    !required_hook_failed() && get_hooks().iter().all(|r| !r.required || r.is_live())
+}
+
+/// Check if a required hook failed. The generated hook payloads check this before running.
+pub fn required_hook_failed() -> bool {
+    REQUIRED_HOOK_FAILED.load(Ordering::Relaxed)
+}
+
+/// Used by `original_function!` when the original function hasn't been set, which happens when
+/// an optional hook failed but the payload is called directly from Rust. An error is logged the
+/// first time for each hook, then the return type's [`FallbackValue`] is returned.
+#[doc(hidden)]
+pub fn missing_original<T: FallbackValue>(name: &str) -> T {
+    static REPORTED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+    let mut reported = match REPORTED.lock() {
+        Ok(v) => v,
+        Err(e) => e.into_inner()
+    };
+    if reported.get_or_insert_with(HashSet::new).insert(name.to_owned()) {
+        crate::logln!(Error, "Original function for {} isn't available, returning a default value instead", name);
+    }
+    T::fallback_value()
+}
+
+/// Format the registry as a table, starting with a line counting hooks in each state.
+pub fn format_hook_summary() -> Vec<String> {
+    format_summary(&get_hooks())
+}
+
+fn format_summary(hooks: &[HookRecord]) -> Vec<String> {
+    let count = |s: HookState| hooks.iter().filter(|r| r.state == s).count();
+    let mut out = vec![format!("{} hooks: {} hooked, {} resolved, {} failed, {} pending, {} removed", hooks.len(),
+        count(HookState::Hooked), count(HookState::Resolved), count(HookState::Failed), count(HookState::Pending),
+        count(HookState::Removed))];
+    let name_width = hooks.iter().map(|r| r.name.len()).max().unwrap_or(0).max(4);
+    out.push(format!("{:<name_width$} {:<8} {:<18} {:<16} SIGNATURE", "NAME", "STATE", "ADDRESS", "HASH"));
+    for r in hooks {
+        let hash = r.hash.map_or_else(|| "-".to_owned(), |h| format!("{:x}", h));
+        out.push(format!("{:<name_width$} {:<8} {:<18} {:<16} {}", r.name, r.state.to_string(),
+            format!("0x{:x}", r.address), hash, r.signature.as_deref().unwrap_or("-")));
+    }
+    out
+}
+
+/// Log the table from [`format_hook_summary`]. Failed hooks are logged as errors, or as warnings
+/// if they're optional.
+pub fn log_hook_summary() {
+    let hooks = get_hooks();
+    let lines = format_summary(&hooks);
+    crate::logln!(Information, "{}", lines[0]);
+    crate::logln!(Information, "{}", lines[1]);
+    for (r, line) in hooks.iter().zip(lines[2..].iter()) {
+        match r.state {
+            HookState::Failed if r.required => crate::logln!(Error, "{}", line),
+            HookState::Failed => crate::logln!(Warning, "{}", line),
+            _ => crate::logln!(Information, "{}", line)
+        }
+    }
+}
+
+/// Called by the generated C# after `OnModLoaderInitialized`.
+#[no_mangle]
+pub extern "C" fn log_ffi_hook_summary() {
+    log_hook_summary();
+}
@@ -0,0 +1,90 @@
+#![cfg(test)]
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn hook_registry_state() -> ReturnType {
+    use crate::hook_registry::{ self, HookState };
+    use crate::logger::LogLevel;
+    use crate::testing::Host;
+    use std::ffi::CString;
+    let host = Host::install();
+    let name = CString::new("registry_hook")?;
+    let sig = CString::new("48 89 5C 24 ?? 57")?;
+    unsafe {
+        assert!(hook_registry::set_ffi_hook_state(name.as_ptr(), sig.as_ptr(), 0, HookState::Pending as u32, 0x1234));
+        assert!(hook_registry::set_ffi_hook_state(name.as_ptr(), std::ptr::null(), 0x140001000, HookState::Hooked as u32, 0));
+        assert!(!hook_registry::set_ffi_hook_state(name.as_ptr(), std::ptr::null(), 0, 7, 0));
+    }
+    hook_registry::set_hook_state("registry_static", Some("E8 ?? ?? ?? ??"), 0, HookState::Failed, None);
+    let hook = hook_registry::get_hook("registry_hook").unwrap();
+    assert_eq!(hook.get_signature(), Some("48 89 5C 24 ?? 57"));
+    assert_eq!((hook.get_address(), hook.get_state(), hook.get_hash()), (0x140001000, HookState::Hooked, Some(0x1234)));
+    assert!(hook_registry::is_hooked("registry_hook"));
+    assert!(!hook_registry::is_hooked("registry_static"));
+    hook_registry::log_hook_summary();
+    let log = host.get_log();
+    let row = log.iter().find(|e| e.text.starts_with("registry_static ")).unwrap();
+    assert!(row.text.contains("failed"));
+    assert_eq!(row.color, LogLevel::Error.get_log_color());
+    assert!(log.iter().any(|e| e.text.starts_with("registry_hook ") && e.text.contains("0x140001000")));
+    Ok(())
+}
+
+#[test]
+fn hook_registry_required() -> ReturnType {
+    use crate::hook_registry::{ self, HookState };
+    use crate::logger::LogLevel;
+    use crate::testing::Host;
+    use std::ffi::CString;
+    let host = Host::install();
+    let name = CString::new("optional_hook")?;
+    unsafe { assert!(hook_registry::set_ffi_hook_required(name.as_ptr(), false)); }
+    hook_registry::set_hook_state("optional_hook", None, 0, HookState::Failed, None);
+    assert!(!hook_registry::get_hook("optional_hook").unwrap().is_required());
+    // An optional hook's original function falls back to a default value, logging only once
+    let value: u32 = hook_registry::missing_original("OPTIONAL_HOOK");
+    let _: u32 = hook_registry::missing_original("OPTIONAL_HOOK");
+    assert_eq!(value, 0);
+    hook_registry::set_hook_state("required_hook", None, 0, HookState::Failed, None);
+    assert!(hook_registry::get_hook("required_hook").unwrap().is_required());
+    assert!(hook_registry::required_hook_failed());
+    assert!(!hook_registry::hooks_ready());
+    hook_registry::log_hook_summary();
+    let log = host.get_log();
+    assert_eq!(log.iter().filter(|e| e.text.contains("Original function for OPTIONAL_HOOK")).count(), 1);
+    let row = log.iter().find(|e| e.text.starts_with("optional_hook ")).unwrap();
+    assert_eq!(row.color, LogLevel::Warning.get_log_color());
+    let row = log.iter().find(|e| e.text.starts_with("required_hook ")).unwrap();
+    assert_eq!(row.color, LogLevel::Error.get_log_color());
+    Ok(())
+}
+
+#[test]
+fn hook_registry_toggle() -> ReturnType {
+    use crate::hook_registry::{ self, HookState };
+    use crate::testing::Host;
+    use std::sync::atomic::{ AtomicU8, Ordering };
+    static HOOK_ENABLED: AtomicU8 = AtomicU8::new(1);
+    extern "C" fn toggle(enable: u8) -> u8 {
+        HOOK_ENABLED.store(enable, Ordering::Relaxed);
+        1
+    }
+    let _host = Host::install();
+    let cb: hook_registry::HookToggle = toggle;
+    // Hooks can't be toggled until they're created
+    hook_registry::set_hook_state("toggle_hook", None, 0, HookState::Pending, None);
+    assert!(!hook_registry::toggle_hook("toggle_hook", Some(&cb), false));
+    assert!(!hook_registry::is_hook_enabled("toggle_hook"));
+    hook_registry::set_hook_state("toggle_hook", None, 0x140002000, HookState::Hooked, None);
+    assert!(hook_registry::is_hook_enabled("toggle_hook"));
+    assert!(!hook_registry::toggle_hook("toggle_hook", None, false));
+    assert!(hook_registry::toggle_hook("toggle_hook", Some(&cb), false));
+    assert_eq!(HOOK_ENABLED.load(Ordering::Relaxed), 0);
+    assert!(!hook_registry::is_hook_enabled("toggle_hook"));
+    // Disabling a hook doesn't change its state
+    assert!(hook_registry::is_hooked("toggle_hook"));
+    assert!(hook_registry::toggle_hook("toggle_hook", Some(&cb), true));
+    assert_eq!(HOOK_ENABLED.load(Ordering::Relaxed), 1);
+    assert!(hook_registry::is_hook_enabled("toggle_hook"));
+    Ok(())
+}
@@ -0,0 +1,331 @@
+//! A length decoder for x86-64 instructions. It doesn't disassemble instructions into mnemonics,
+//! but finds where each part of an instruction is: prefixes, opcode, ModRM, displacement and
+//! immediate. That's enough to tell which bytes of an instruction change when the code or data
+//! it refers to moves, such as RIP-relative displacements and branch targets.
+//!
+//! Only 64-bit mode is supported. Legacy, REX, VEX and EVEX prefixes are handled, along with the
+//! one byte, `0F`, `0F 38` and `0F 3A` opcode maps.
+
+use std::error::Error;
+use std::fmt::{ Display, Formatter };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The instruction continues past the end of the data.
+    Truncated,
+    /// The opcode doesn't exist in 64-bit mode.
+    InvalidOpcode(u8),
+    /// The instruction is longer than the 15 byte limit.
+    TooLong
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "Instruction is truncated"),
+            Self::InvalidOpcode(o) => write!(f, "Opcode {:02X} is not valid in 64-bit mode", o),
+            Self::TooLong => write!(f, "Instruction is longer than 15 bytes")
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// The position and size of a displacement or immediate within an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    offset: usize,
+    size: usize
+}
+
+impl Operand {
+    /// Offset from the start of the instruction.
+    pub fn get_offset(&self) -> usize { self.offset }
+    pub fn get_size(&self) -> usize { self.size }
+    /// Read the operand as a sign extended value from the instruction's bytes.
+    pub fn read(&self, code: &[u8]) -> i64 {
+        let bytes = &code[self.offset..self.offset + self.size];
+        match self.size {
+            1 => bytes[0] as i8 as i64,
+            2 => i16::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            4 => i32::from_le_bytes(bytes.try_into().unwrap()) as i64,
+            _ => i64::from_le_bytes(bytes.try_into().unwrap())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    length: usize,
+    opcode: usize,
+    modrm: Option<usize>,
+    displacement: Option<Operand>,
+    immediate: Option<Operand>,
+    rip_relative: bool,
+    relative_branch: bool
+}
+
+impl Instruction {
+    pub fn get_length(&self) -> usize { self.length }
+    /// Offset of the opcode (the first byte after any prefixes) from the start of the instruction.
+    pub fn get_opcode_offset(&self) -> usize { self.opcode }
+    pub fn get_modrm_offset(&self) -> Option<usize> { self.modrm }
+    pub fn get_displacement(&self) -> Option<Operand> { self.displacement }
+    /// The instruction's immediate. For instructions with two immediates (`enter`), this covers
+    /// both of them.
+    pub fn get_immediate(&self) -> Option<Operand> { self.immediate }
+    /// The memory operand is relative to the end of the instruction.
+    pub fn is_rip_relative(&self) -> bool { self.rip_relative }
+    /// The immediate is a branch target relative to the end of the instruction (`call`, `jmp`,
+    /// `jcc`, `loop` and `jrcxz`).
+    pub fn is_relative_branch(&self) -> bool { self.relative_branch }
+
+    /// The operand whose value is relative to the end of the instruction, if there is one.
+    pub fn get_relative_operand(&self) -> Option<Operand> {
+        match (self.rip_relative, self.relative_branch) {
+            (true, _) => self.displacement,
+            (_, true) => self.immediate,
+            _ => None
+        }
+    }
+
+    /// Get the offset that a RIP-relative memory operand or relative branch points to, given the
+    /// offset of the instruction and its bytes.
+    pub fn get_relative_target(&self, address: usize, code: &[u8]) -> Option<usize> {
+        let operand = self.get_relative_operand()?;
+        (address + self.length).checked_add_signed(operand.read(code) as isize)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpcodeMap {
+    Primary,
+    Secondary, // 0F
+    Map38,     // 0F 38
+    Map3A,     // 0F 3A
+    Other      // VEX/EVEX maps without legacy equivalents
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Immediate {
+    None,
+    Byte,
+    Word,
+    // 16 bits with an operand size prefix, otherwise 32 bits
+    Full,
+    // 64 bits with REX.W (mov r64, imm64)
+    FullOrQuad,
+    // 64 bits, or 32 bits with an address size prefix (mov al, moffs)
+    Address,
+    // 16 bits followed by 8 bits (enter)
+    WordByte,
+    Rel8,
+    Rel32,
+    // Only for /0 and /1 (test r/m, imm in F6 and F7)
+    GroupByte,
+    GroupFull
+}
+
+/// The longest that an instruction can be. Reading this many bytes always covers a whole instruction.
+pub const MAX_INSTRUCTION_LENGTH: usize = 15;
+
+/// The most instructions that `resolve_rip_relative` decodes while looking for a RIP-relative
+/// operand before giving up.
+pub const MAX_RIP_RELATIVE_SEARCH: usize = 16;
+
+// Which one byte opcodes have a ModRM byte, one bit per opcode
+const PRIMARY_MODRM: [u32; 8] = get_opcode_bits(&[
+    0x00..=0x03, 0x08..=0x0b, 0x10..=0x13, 0x18..=0x1b, 0x20..=0x23, 0x28..=0x2b, 0x30..=0x33,
+    0x38..=0x3b, 0x63..=0x63, 0x69..=0x69, 0x6b..=0x6b, 0x80..=0x8f, 0xc0..=0xc1, 0xc6..=0xc7,
+    0xd0..=0xd3, 0xd8..=0xdf, 0xf6..=0xf7, 0xfe..=0xff
+]);
+
+// Which 0F opcodes don't have a ModRM byte
+const SECONDARY_NO_MODRM: [u32; 8] = get_opcode_bits(&[
+    0x04..=0x0b, 0x0e..=0x0e, 0x30..=0x37, 0x77..=0x77, 0x80..=0x8f, 0xa0..=0xa2, 0xa8..=0xaa,
+    0xc8..=0xcf
+]);
+
+const fn get_opcode_bits(ranges: &[std::ops::RangeInclusive<u8>]) -> [u32; 8] {
+    let mut bits = [0; 8];
+    let mut i = 0;
+    while i < ranges.len() {
+        let mut op = *ranges[i].start() as usize;
+        while op <= *ranges[i].end() as usize {
+            bits[op / 32] |= 1 << (op % 32);
+            op += 1;
+        }
+        i += 1;
+    }
+    bits
+}
+
+const fn has_opcode_bit(bits: &[u32; 8], op: u8) -> bool {
+    bits[op as usize / 32] & (1 << (op as usize % 32)) != 0
+}
+
+fn get_primary_immediate(op: u8) -> Result<Immediate, DecodeError> {
+    Ok(match op {
+        0x06 | 0x07 | 0x0e | 0x16 | 0x17 | 0x1e | 0x1f | 0x27 | 0x2f | 0x37 | 0x3f | 0x60..=0x62 |
+        0x82 | 0x9a | 0xc4 | 0xc5 | 0xce | 0xd4..=0xd6 | 0xea => return Err(DecodeError::InvalidOpcode(op)),
+        0x04 | 0x0c | 0x14 | 0x1c | 0x24 | 0x2c | 0x34 | 0x3c | 0x6a | 0x6b | 0x80 | 0x83 | 0xa8 |
+        0xb0..=0xb7 | 0xc0 | 0xc1 | 0xc6 | 0xcd | 0xe4..=0xe7 => Immediate::Byte,
+        0x05 | 0x0d | 0x15 | 0x1d | 0x25 | 0x2d | 0x35 | 0x3d | 0x68 | 0x69 | 0x81 | 0xa9 | 0xc7 => Immediate::Full,
+        0xb8..=0xbf => Immediate::FullOrQuad,
+        0xa0..=0xa3 => Immediate::Address,
+        0xc2 | 0xca => Immediate::Word,
+        0xc8 => Immediate::WordByte,
+        0x70..=0x7f | 0xe0..=0xe3 | 0xeb => Immediate::Rel8,
+        0xe8 | 0xe9 => Immediate::Rel32,
+        0xf6 => Immediate::GroupByte,
+        0xf7 => Immediate::GroupFull,
+        _ => Immediate::None
+    })
+}
+
+fn get_secondary_immediate(op: u8) -> Immediate {
+    match op {
+        0x0f | 0x70..=0x73 | 0xa4 | 0xac | 0xba | 0xc2 | 0xc4..=0xc6 => Immediate::Byte,
+        0x80..=0x8f => Immediate::Rel32,
+        _ => Immediate::None
+    }
+}
+
+// Tracks the current position while decoding
+struct Reader<'a> {
+    code: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Result<u8, DecodeError> {
+        if self.pos >= MAX_INSTRUCTION_LENGTH { return Err(DecodeError::TooLong); }
+        self.code.get(self.pos).copied().ok_or(DecodeError::Truncated)
+    }
+    fn next(&mut self) -> Result<u8, DecodeError> {
+        let v = self.peek()?;
+        self.pos += 1;
+        Ok(v)
+    }
+    fn operand(&mut self, size: usize) -> Result<Operand, DecodeError> {
+        let operand = Operand { offset: self.pos, size };
+        self.pos += size;
+        if self.pos > MAX_INSTRUCTION_LENGTH { return Err(DecodeError::TooLong); }
+        if self.pos > self.code.len() { return Err(DecodeError::Truncated); }
+        Ok(operand)
+    }
+}
+
+/// Decode the instruction at the start of `code`.
+pub fn decode(code: &[u8]) -> Result<Instruction, DecodeError> {
+    let mut reader = Reader { code, pos: 0 };
+    let mut operand_size_prefix = false;
+    let mut address_size_prefix = false;
+    let mut rex_w = false;
+    // Legacy prefixes, which can come in any order
+    loop {
+        match reader.peek()? {
+            0x66 => operand_size_prefix = true,
+            0x67 => address_size_prefix = true,
+            0xf0 | 0xf2 | 0xf3 | 0x2e | 0x36 | 0x3e | 0x26 | 0x64 | 0x65 => (),
+            _ => break
+        }
+        reader.pos += 1;
+    }
+    // REX has to come directly before the opcode
+    if reader.peek()? & 0xf0 == 0x40 {
+        rex_w = reader.next()? & 8 != 0;
+    }
+    let opcode = reader.pos;
+    let (map, op, has_modrm, immediate) = match reader.next()? {
+        // VEX and EVEX always have a ModRM, except for vzeroupper/vzeroall
+        prefix @ (0xc4 | 0xc5 | 0x62) => {
+            let map = match prefix {
+                0xc5 => 1,
+                0xc4 => reader.next()? & 0x1f,
+                _ => {
+                    let p0 = reader.next()?;
+                    reader.next()?;
+                    p0 & 7
+                }
+            };
+            // The last byte of the prefix holds the vector length and extra register operand
+            reader.next()?;
+            let op = reader.next()?;
+            match map {
+                1 => (OpcodeMap::Secondary, op, op != 0x77, match op {
+                    0x70..=0x73 | 0xc2 | 0xc4..=0xc6 => Immediate::Byte,
+                    _ => Immediate::None
+                }),
+                2 => (OpcodeMap::Map38, op, true, Immediate::None),
+                3 => (OpcodeMap::Map3A, op, true, Immediate::Byte),
+                _ => (OpcodeMap::Other, op, true, Immediate::None)
+            }
+        },
+        0x0f => match reader.next()? {
+            0x38 => (OpcodeMap::Map38, reader.next()?, true, Immediate::None),
+            0x3a => (OpcodeMap::Map3A, reader.next()?, true, Immediate::Byte),
+            op => (OpcodeMap::Secondary, op, !has_opcode_bit(&SECONDARY_NO_MODRM, op), get_secondary_immediate(op))
+        },
+        op => (OpcodeMap::Primary, op, has_opcode_bit(&PRIMARY_MODRM, op), get_primary_immediate(op)?)
+    };
+    let mut modrm_offset = None;
+    let mut displacement = None;
+    let mut rip_relative = false;
+    let mut modrm_reg = 0;
+    if has_modrm {
+        modrm_offset = Some(reader.pos);
+        let modrm = reader.next()?;
+        let (md, rm) = (modrm >> 6, modrm & 7);
+        modrm_reg = (modrm >> 3) & 7;
+        if md != 3 {
+            let mut base = rm;
+            if rm == 4 {
+                base = reader.next()? & 7;
+            }
+            let size = match (md, rm, base) {
+                (0, 5, _) => { rip_relative = true; 4 },
+                (0, 4, 5) => 4,
+                (0, _, _) => 0,
+                (1, _, _) => 1,
+                _ => 4
+            };
+            if size > 0 {
+                displacement = Some(reader.operand(size)?);
+            }
+        }
+    }
+    let immediate_size = |full| match (rex_w, operand_size_prefix) {
+        (false, true) => 2,
+        _ => full
+    };
+    let mut relative_branch = false;
+    let immediate = match immediate {
+        Immediate::None => None,
+        Immediate::Byte => Some(reader.operand(1)?),
+        Immediate::Word => Some(reader.operand(2)?),
+        Immediate::Full => Some(reader.operand(immediate_size(4))?),
+        Immediate::FullOrQuad => Some(reader.operand(immediate_size(if rex_w { 8 } else { 4 }))?),
+        Immediate::Address => Some(reader.operand(if address_size_prefix { 4 } else { 8 })?),
+        Immediate::WordByte => Some(reader.operand(3)?),
+        Immediate::Rel8 => { relative_branch = true; Some(reader.operand(1)?) },
+        // Operand size prefixes are ignored for near branches in 64-bit mode
+        Immediate::Rel32 => { relative_branch = true; Some(reader.operand(4)?) },
+        Immediate::GroupByte if modrm_reg < 2 => Some(reader.operand(1)?),
+        Immediate::GroupFull if modrm_reg < 2 => Some(reader.operand(immediate_size(4))?),
+        Immediate::GroupByte | Immediate::GroupFull => None
+    };
+    // xbegin (C7 F8) has a relative branch instead of an immediate
+    if map == OpcodeMap::Primary && op == 0xc7 && code[modrm_offset.unwrap()] == 0xf8 {
+        relative_branch = true;
+    }
+    Ok(Instruction {
+        length: reader.pos,
+        opcode,
+        modrm: modrm_offset,
+        displacement,
+        immediate,
+        rip_relative,
+        relative_branch
+    })
+}
@@ -0,0 +1,92 @@
+#![cfg(test)]
+use crate::instruction_decoder::{ decode, DecodeError };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// Length, displacement (offset, size), immediate (offset, size), RIP-relative, relative branch
+type Expected = (usize, Option<(usize, usize)>, Option<(usize, usize)>, bool, bool);
+
+const INSTRUCTIONS: &[(&[u8], Expected)] = &[
+    // ret, int3, push rdi
+    (&[0xc3], (1, None, None, false, false)),
+    (&[0xcc], (1, None, None, false, false)),
+    (&[0x57], (1, None, None, false, false)),
+    // mov rax, [rip+0x12345678]
+    (&[0x48, 0x8b, 0x05, 0x78, 0x56, 0x34, 0x12], (7, Some((3, 4)), None, true, false)),
+    // mov [rsp+8], rbx
+    (&[0x48, 0x89, 0x5c, 0x24, 0x08], (5, Some((4, 1)), None, false, false)),
+    // mov rax, [0x12345678]
+    (&[0x48, 0x8b, 0x04, 0x25, 0x78, 0x56, 0x34, 0x12], (8, Some((4, 4)), None, false, false)),
+    // sub rsp, 0x20
+    (&[0x48, 0x83, 0xec, 0x20], (4, None, Some((3, 1)), false, false)),
+    // call, jmp, jmp short, je, jne short
+    (&[0xe8, 0x10, 0, 0, 0], (5, None, Some((1, 4)), false, true)),
+    (&[0xe9, 0x10, 0, 0, 0], (5, None, Some((1, 4)), false, true)),
+    (&[0xeb, 0x05], (2, None, Some((1, 1)), false, true)),
+    (&[0x0f, 0x84, 0x10, 0, 0, 0], (6, None, Some((2, 4)), false, true)),
+    (&[0x75, 0xf0], (2, None, Some((1, 1)), false, true)),
+    // jmp [rip+0x10]
+    (&[0xff, 0x25, 0x10, 0, 0, 0], (6, Some((2, 4)), None, true, false)),
+    // mov rax, imm64 and mov ax, imm16
+    (&[0x48, 0xb8, 1, 2, 3, 4, 5, 6, 7, 8], (10, None, Some((2, 8)), false, false)),
+    (&[0x66, 0xb8, 0x34, 0x12], (4, None, Some((2, 2)), false, false)),
+    // mov dword [rip+0x10], 1
+    (&[0xc7, 0x05, 0x10, 0, 0, 0, 1, 0, 0, 0], (10, Some((2, 4)), Some((6, 4)), true, false)),
+    // cmp word [rsp+8], 0x1234
+    (&[0x66, 0x81, 0x7c, 0x24, 0x08, 0x34, 0x12], (7, Some((4, 1)), Some((5, 2)), false, false)),
+    // test byte [rip+0x10], 1 and neg al (F6 /3 has no immediate)
+    (&[0xf6, 0x05, 0x10, 0, 0, 0, 1], (7, Some((2, 4)), Some((6, 1)), true, false)),
+    (&[0xf6, 0xd8], (2, None, None, false, false)),
+    // mov al, [moffs64]
+    (&[0xa0, 1, 2, 3, 4, 5, 6, 7, 8], (9, None, Some((1, 8)), false, false)),
+    // enter 0x20, 0
+    (&[0xc8, 0x20, 0, 0], (4, None, Some((1, 3)), false, false)),
+    // endbr64, lock cmpxchg [rcx], edx
+    (&[0xf3, 0x0f, 0x1e, 0xfa], (4, None, None, false, false)),
+    (&[0xf0, 0x0f, 0xb1, 0x11], (4, None, None, false, false)),
+    // movss xmm0, [rip+0x10]
+    (&[0xf3, 0x0f, 0x10, 0x05, 0x10, 0, 0, 0], (8, Some((4, 4)), None, true, false)),
+    // pshufb xmm0, xmm1 and palignr xmm0, xmm1, 8
+    (&[0x66, 0x0f, 0x38, 0x00, 0xc1], (5, None, None, false, false)),
+    (&[0x66, 0x0f, 0x3a, 0x0f, 0xc1, 0x08], (6, None, Some((5, 1)), false, false)),
+    // vzeroupper, vmovss xmm0, [rip+0x10], vpextrb eax, xmm0, 1
+    (&[0xc5, 0xf8, 0x77], (3, None, None, false, false)),
+    (&[0xc5, 0xfa, 0x10, 0x05, 0x10, 0, 0, 0], (8, Some((4, 4)), None, true, false)),
+    (&[0xc4, 0xe3, 0x79, 0x14, 0xc0, 0x01], (6, None, Some((5, 1)), false, false)),
+    // vmovups zmm0, [rip+0x10]
+    (&[0x62, 0xf1, 0x7c, 0x48, 0x10, 0x05, 0x10, 0, 0, 0], (10, Some((6, 4)), None, true, false)),
+];
+
+#[test]
+fn decode_instruction_layout() -> ReturnType {
+    for (code, expected) in INSTRUCTIONS {
+        let instruction = decode(code)?;
+        let displacement = instruction.get_displacement().map(|o| (o.get_offset(), o.get_size()));
+        let immediate = instruction.get_immediate().map(|o| (o.get_offset(), o.get_size()));
+        assert_eq!((instruction.get_length(), displacement, immediate, instruction.is_rip_relative(),
+            instruction.is_relative_branch()), *expected, "{:02X?}", code);
+    }
+    Ok(())
+}
+
+#[test]
+fn decode_relative_target() -> ReturnType {
+    let call = [0xe8, 0x10, 0, 0, 0];
+    assert_eq!(decode(&call)?.get_relative_target(0x1000, &call), Some(0x1015));
+    let jmp = [0xeb, 0xfe];
+    assert_eq!(decode(&jmp)?.get_relative_target(0x1000, &jmp), Some(0x1000));
+    let lea = [0x48, 0x8d, 0x0d, 0xf0, 0xff, 0xff, 0xff];
+    assert_eq!(decode(&lea)?.get_relative_target(0x1000, &lea), Some(0xff7));
+    let mov = [0x48, 0x89, 0x5c, 0x24, 0x08];
+    assert_eq!(decode(&mov)?.get_relative_target(0x1000, &mov), None);
+    Ok(())
+}
+
+#[test]
+fn decode_invalid() -> ReturnType {
+    assert_eq!(decode(&[]), Err(DecodeError::Truncated));
+    assert_eq!(decode(&[0x48]), Err(DecodeError::Truncated));
+    assert_eq!(decode(&[0x48, 0x8b, 0x05, 0x10]), Err(DecodeError::Truncated));
+    assert_eq!(decode(&[0x06]), Err(DecodeError::InvalidOpcode(0x06)));
+    assert_eq!(decode(&[0x66; 16]), Err(DecodeError::TooLong));
+    Ok(())
+}
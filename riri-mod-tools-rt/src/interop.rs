@@ -1,3 +1,7 @@
+//! Callbacks into the C# host for calling methods and managing GCHandles. Each callback is a
+//! function pointer set once by the generated C# through its `set_*` function while the mod is
+//! loading, and it must stay valid for the rest of the program.
+
 use std::alloc::{alloc, dealloc, Layout};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
@@ -42,6 +46,55 @@ pub unsafe fn free_object(object: usize) {
     FREE_OBJECT.get().unwrap()(object)
 }
 
+type SetCloneObject = unsafe extern "C" fn(usize) -> usize;
+static CLONE_OBJECT: OnceLock<SetCloneObject> = OnceLock::new();
+
+/// # Safety
+/// See the module documentation.
+#[no_mangle]
+pub unsafe extern "C" fn set_clone_object(cb: SetCloneObject) {
+    _ = CLONE_OBJECT.set(cb).unwrap();
+}
+
+/// # Safety
+/// `object` must be a valid GCHandle. The returned GCHandle is owned by the caller.
+pub unsafe fn clone_object(object: usize) -> usize {
+    CLONE_OBJECT.get().unwrap()(object)
+}
+
+type SetDowngradeObject = unsafe extern "C" fn(usize) -> usize;
+static DOWNGRADE_OBJECT: OnceLock<SetDowngradeObject> = OnceLock::new();
+
+/// # Safety
+/// See the module documentation.
+#[no_mangle]
+pub unsafe extern "C" fn set_downgrade_object(cb: SetDowngradeObject) {
+    _ = DOWNGRADE_OBJECT.set(cb).unwrap();
+}
+
+/// # Safety
+/// `object` must be a valid GCHandle. The returned weak GCHandle is owned by the caller.
+pub unsafe fn downgrade_object(object: usize) -> usize {
+    DOWNGRADE_OBJECT.get().unwrap()(object)
+}
+
+type SetUpgradeObject = unsafe extern "C" fn(usize) -> usize;
+static UPGRADE_OBJECT: OnceLock<SetUpgradeObject> = OnceLock::new();
+
+/// # Safety
+/// See the module documentation.
+#[no_mangle]
+pub unsafe extern "C" fn set_upgrade_object(cb: SetUpgradeObject) {
+    _ = UPGRADE_OBJECT.set(cb).unwrap();
+}
+
+/// # Safety
+/// `object` must be a valid weak GCHandle. The returned GCHandle is owned by the caller, or 0
+/// if the object was garbage collected.
+pub unsafe fn upgrade_object(object: usize) -> usize {
+    UPGRADE_OBJECT.get().unwrap()(object)
+}
+
 type SetObjectAsU8 = unsafe extern "C" fn(usize, *mut bool) -> u8;
 static OBJECT_AS_U8: OnceLock<SetObjectAsU8> = OnceLock::new();
 
@@ -121,6 +174,11 @@ pub struct ObjectInitializer {
     size: usize
 }
 
+impl ObjectInitializer {
+    pub fn get_hash(&self) -> u64 { self.hash }
+    pub fn get_size(&self) -> usize { self.size }
+}
+
 #[derive(Debug)]
 pub struct ObjectInitHandle<T>
 where T: Copy {
@@ -174,7 +232,9 @@ where T: Copy {
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
 pub enum InteropError {
     CouldNotMakeObjectInstance,
-    CouldNotCastToValue
+    CouldNotCastToValue,
+    CouldNotSubscribeToEvent,
+    CouldNotCreateDelegate
 }
 
 impl Display for InteropError {
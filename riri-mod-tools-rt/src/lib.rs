@@ -7,12 +7,41 @@ pub mod address;
 #[path = "address_linux.rs"]
 pub mod address;
 pub mod assembly_utils;
+pub mod crash_handler; // Crash reports for segfaults and unhandled exceptions
+pub mod crash_handler_tests;
+#[cfg(feature = "reloaded")]
+pub mod event; // Rust closures as C# events/delegates (requires Reloaded runtime)
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub mod event_tests;
+pub mod fuzzy_match; // Closest matches for signatures that weren't found
+pub mod fuzzy_match_tests;
+pub mod hook_instance; // Multiple instances of user defined hooks
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub mod hook_instance_tests;
+pub mod hook_registry; // Resolution state of generated hooks and statics
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub mod hook_registry_tests;
+pub mod instruction_decoder; // x86-64 instruction lengths and operand positions
+pub mod instruction_decoder_tests;
 pub mod interleave;
 #[cfg(feature = "reloaded")]
 pub mod interop; // For C# interop (requires Reloaded runtime)
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod log_facade; // log and tracing crate backends
+#[cfg(all(feature = "testing", any(feature = "log", feature = "tracing")))]
+pub mod log_facade_tests;
+pub mod log_sink; // File and ring buffer log outputs
 pub mod logger;
+pub mod logger_tests;
 #[cfg(feature = "reloaded")]
 pub mod mod_loader_data;
+pub mod panic_guard; // Catch panics at the FFI boundary
+pub mod pattern;
+pub mod pattern_tests;
+#[cfg(feature = "testing")]
+pub mod pe_builder; // Build small executables for tests
+pub mod pe_image; // Map executables from disk to check signatures offline
+pub mod pe_image_tests;
 pub mod protection;
 #[cfg(feature = "reloaded")]
 pub mod reloaded {
@@ -20,7 +49,24 @@ pub mod reloaded {
         pub mod interfaces; // namespace Reloaded.Mod.Interfaces;
     }
 }
+pub mod signature_cache; // Cached signature offsets for UseCachedSignatures
+pub mod signature_cache_tests;
+pub mod signature_generator; // Create unique signatures for an address
+pub mod signature_generator_tests;
+pub mod sigscan_batch; // Scan for every signature in one pass
+pub mod sigscan_batch_tests;
 pub mod sigscan_resolver;
+pub mod sigscan_resolver_tests;
+#[cfg(not(feature = "reloaded"))]
+pub mod standalone; // Runtime services outside of Reloaded-II
+#[cfg(not(feature = "reloaded"))]
+pub mod standalone_tests;
 #[cfg(feature = "reloaded")]
 pub mod system; // namespace System;
+#[cfg(feature = "testing")]
+pub mod testing; // Emulates the C# host for unit tests
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub mod testing_tests;
+pub mod thunk; // Follow jumps to the functions that thunks lead to
+pub mod thunk_tests;
 pub mod vtable;
\ No newline at end of file
@@ -0,0 +1,160 @@
+//! Forward records from the `log` and `tracing` facades into the Reloaded logger, so that output
+//! from dependencies that use those crates shows up in the Reloaded console.
+//!
+//! Records are filtered using the same level and module filter as the `log!` macros (see
+//! [`crate::logger::set_log_filter`]), using the record's target as the module path.
+
+use crate::logger::{ LogField, LogLevel, LogMetadata, is_log_enabled };
+
+fn write_record(level: LogLevel, target: &str, text: String, fields: &[LogField], file: Option<&'static str>, line: Option<u32>) {
+    crate::logger::write_structured(&LogMetadata {
+        level,
+        module: target,
+        file: file.unwrap_or("unknown"),
+        line: line.unwrap_or(0),
+        show_prefix: true,
+        newline: true
+    }, format!("[{}] {}", target, text), fields);
+}
+
+#[cfg(feature = "log")]
+impl From<log::Level> for LogLevel {
+    fn from(value: log::Level) -> Self {
+        match value {
+            log::Level::Trace => Self::Verbose,
+            log::Level::Debug => Self::Debug,
+            log::Level::Info => Self::Information,
+            log::Level::Warn => Self::Warning,
+            log::Level::Error => Self::Error
+        }
+    }
+}
+
+/// A `log::Log` implementation which writes into the Reloaded logger.
+#[cfg(feature = "log")]
+#[derive(Debug)]
+pub struct ReloadedLog;
+
+#[cfg(feature = "log")]
+static RELOADED_LOG: ReloadedLog = ReloadedLog;
+
+#[cfg(feature = "log")]
+impl log::Log for ReloadedLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        is_log_enabled(metadata.level().into(), metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            write_record(record.level().into(), record.target(),
+                record.args().to_string(), &[], record.file_static(), record.line());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install [`ReloadedLog`] as the logger for the `log` crate. This fails if another logger has
+/// already been installed.
+#[cfg(feature = "log")]
+pub fn init_log() -> Result<(), log::SetLoggerError> {
+    log::set_logger(&RELOADED_LOG)?;
+    log::set_max_level(log::LevelFilter::Trace);
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+impl From<tracing::Level> for LogLevel {
+    fn from(value: tracing::Level) -> Self {
+        match value {
+            tracing::Level::TRACE => Self::Verbose,
+            tracing::Level::DEBUG => Self::Debug,
+            tracing::Level::INFO => Self::Information,
+            tracing::Level::WARN => Self::Warning,
+            tracing::Level::ERROR => Self::Error
+        }
+    }
+}
+
+/// Collects an event's message and fields, keeping the type of each field where possible.
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: Vec<LogField>
+}
+
+#[cfg(feature = "tracing")]
+impl EventVisitor {
+    fn push(&mut self, field: &tracing::field::Field, value: crate::logger::LogValue) {
+        self.fields.push(LogField::new(field.name(), value));
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for EventVisitor {
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.push(field, crate::logger::LogValue::Int(value));
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.push(field, crate::logger::LogValue::UInt(value));
+    }
+
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.push(field, crate::logger::LogValue::Float(value));
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.push(field, crate::logger::LogValue::Bool(value));
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            _ => self.push(field, crate::logger::LogValue::String(value.to_owned()))
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message.push_str(&format!("{:?}", value)),
+            _ => self.push(field, crate::logger::LogValue::String(format!("{:?}", value)))
+        }
+    }
+}
+
+/// A `tracing_subscriber` layer which writes events into the Reloaded logger.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default)]
+pub struct ReloadedLayer;
+
+#[cfg(feature = "tracing")]
+impl<S> tracing_subscriber::Layer<S> for ReloadedLayer
+where S: tracing::Subscriber {
+    // The log filter can change at any time, so the result can't be cached for each callsite
+    fn register_callsite(&self, _: &'static tracing::Metadata<'static>) -> tracing::subscriber::Interest {
+        tracing::subscriber::Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _: tracing_subscriber::layer::Context<'_, S>) -> bool {
+        is_log_enabled((*metadata.level()).into(), metadata.target())
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        write_record((*metadata.level()).into(), metadata.target(),
+            visitor.message, &visitor.fields, metadata.file(), metadata.line());
+    }
+}
+
+/// Install a subscriber containing [`ReloadedLayer`] as the global default for `tracing`. This
+/// fails if a global default has already been set. To combine this with other layers, add
+/// [`ReloadedLayer`] to your own subscriber instead.
+#[cfg(feature = "tracing")]
+pub fn init_tracing() -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
+    use tracing_subscriber::layer::SubscriberExt;
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(ReloadedLayer))
+}
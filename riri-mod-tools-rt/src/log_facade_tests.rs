@@ -0,0 +1,77 @@
+#![cfg(test)]
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[cfg(feature = "log")]
+#[test]
+fn log_facade_log_records() -> ReturnType {
+    use crate::log_facade::ReloadedLog;
+    use crate::logger::{ LogLevel, set_log_filter };
+    use crate::logger_tests::lock_log_filter;
+    use crate::testing::Host;
+    use log::Log;
+    let host = Host::install();
+    let _guard = lock_log_filter();
+    set_log_filter("riri_facade_log=verbose,riri_facade_log::quiet=error,debug".parse()?);
+    let write = |level, target, text| ReloadedLog.log(&log::Record::builder()
+        .level(level).target(target).args(format_args!("{}", text)).build());
+    write(log::Level::Trace, "riri_facade_log", "trace");
+    write(log::Level::Debug, "riri_facade_log", "debug");
+    write(log::Level::Info, "riri_facade_log", "info");
+    write(log::Level::Warn, "riri_facade_log", "warn");
+    write(log::Level::Error, "riri_facade_log", "error");
+    write(log::Level::Warn, "riri_facade_log::quiet", "quiet warn");
+    write(log::Level::Error, "riri_facade_log::quiet", "quiet error");
+    let log: Vec<_> = host.get_log().into_iter()
+        .filter(|e| e.text.starts_with("[riri_facade_log")).map(|e| (e.level, e.text)).collect();
+    assert_eq!(log, vec![
+        (LogLevel::Verbose, "[riri_facade_log] trace".to_owned()),
+        (LogLevel::Debug, "[riri_facade_log] debug".to_owned()),
+        (LogLevel::Information, "[riri_facade_log] info".to_owned()),
+        (LogLevel::Warning, "[riri_facade_log] warn".to_owned()),
+        (LogLevel::Error, "[riri_facade_log] error".to_owned()),
+        (LogLevel::Error, "[riri_facade_log::quiet] quiet error".to_owned())
+    ]);
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn log_facade_tracing_events() -> ReturnType {
+    use crate::log_facade::ReloadedLayer;
+    use crate::logger::{ LogLevel, set_log_filter };
+    use crate::logger_tests::lock_log_filter;
+    use crate::testing::Host;
+    use tracing_subscriber::layer::SubscriberExt;
+    let host = Host::install();
+    let _guard = lock_log_filter();
+    set_log_filter("riri_facade_tracing=verbose,riri_facade_tracing::quiet=error,debug".parse()?);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(ReloadedLayer), || {
+        tracing::trace!(target: "riri_facade_tracing", "trace");
+        tracing::debug!(target: "riri_facade_tracing", "debug");
+        tracing::info!(target: "riri_facade_tracing", count = 3, flag = true, name = "riri", ratio = 0.5, "info {}", 1);
+        tracing::warn!(target: "riri_facade_tracing", offset = -4, "warn");
+        tracing::error!(target: "riri_facade_tracing", "error");
+        tracing::warn!(target: "riri_facade_tracing::quiet", "quiet warn");
+        tracing::error!(target: "riri_facade_tracing::quiet", "quiet error");
+    });
+    // Changing the filter after an event was filtered out enables it again
+    set_log_filter("riri_facade_tracing::toggle=error,debug".parse()?);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(ReloadedLayer), || {
+        for level in ["error", "verbose"] {
+            set_log_filter(format!("riri_facade_tracing::toggle={},debug", level).parse().unwrap());
+            tracing::trace!(target: "riri_facade_tracing::toggle", "toggle {}", level);
+        }
+    });
+    let log: Vec<_> = host.get_log().into_iter()
+        .filter(|e| e.text.starts_with("[riri_facade_tracing")).map(|e| (e.level, e.text)).collect();
+    assert_eq!(log, vec![
+        (LogLevel::Verbose, "[riri_facade_tracing] trace".to_owned()),
+        (LogLevel::Debug, "[riri_facade_tracing] debug".to_owned()),
+        (LogLevel::Information, "[riri_facade_tracing] info 1 count=3 flag=true name=riri ratio=0.5".to_owned()),
+        (LogLevel::Warning, "[riri_facade_tracing] warn offset=-4".to_owned()),
+        (LogLevel::Error, "[riri_facade_tracing] error".to_owned()),
+        (LogLevel::Error, "[riri_facade_tracing::quiet] quiet error".to_owned()),
+        (LogLevel::Verbose, "[riri_facade_tracing::toggle] toggle verbose".to_owned())
+    ]);
+    Ok(())
+}
@@ -0,0 +1,411 @@
+//! Additional destinations for log messages which run alongside the Reloaded logger. Messages
+//! sent to Reloaded are written asynchronously, so the last few lines before a crash are often
+//! lost. Sinks are written to synchronously on the logging thread before the message is passed
+//! onto Reloaded.
+//!
+//! Sinks should be added during initialization (e.g from a `#[riri_init_fn]`):
+//!
+//! ```ignore
+//! use riri_mod_tools_rt::log_sink::{ self, FileSink, RingBufferSink };
+//! log_sink::add_log_sink(Arc::new(FileSink::in_config_directory("mymod")?));
+//! let ring = Arc::new(RingBufferSink::new(200));
+//! ring.dump_on_panic(log_sink::get_config_directory().join("mymod_panic.log"));
+//! log_sink::add_log_sink(ring);
+//! ```
+
+use std::collections::VecDeque;
+use std::fs::{ File, OpenOptions };
+use std::io::{ self, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex, PoisonError, RwLock, TryLockError };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::{ SystemTime, UNIX_EPOCH };
+use crate::logger::{ LogColor, LogField, LogLevel };
+
+/// A single call into the logger. Messages written with `log!` don't end with a newline, so
+/// consecutive records may make up a single line.
+#[derive(Debug, Clone, Copy)]
+pub struct LogRecord<'a> {
+    /// The text as it's shown in the Reloaded console, including any fields.
+    pub text: &'a str,
+    /// The message without fields.
+    pub message: &'a str,
+    /// The module that the message was logged from, if it's known.
+    pub module: Option<&'a str>,
+    pub fields: &'a [LogField],
+    pub level: LogLevel,
+    pub color: LogColor,
+    pub show_prefix: bool,
+    pub newline: bool,
+    pub time: SystemTime
+}
+
+impl<'a> LogRecord<'a> {
+    /// Create a record for a message without fields, timestamped with the current time.
+    pub fn new(text: &'a str, level: LogLevel, color: LogColor, show_prefix: bool, newline: bool) -> Self {
+        Self { text, message: text, module: None, fields: &[], level, color,
+            show_prefix, newline, time: SystemTime::now() }
+    }
+}
+
+pub trait LogSink: Send + Sync {
+    fn write(&self, record: &LogRecord);
+    fn flush(&self) {}
+}
+
+static LOG_SINKS: RwLock<Vec<Arc<dyn LogSink>>> = RwLock::new(vec![]);
+static HAS_LOG_SINKS: AtomicBool = AtomicBool::new(false);
+
+pub fn add_log_sink(sink: Arc<dyn LogSink>) {
+    LOG_SINKS.write().unwrap_or_else(PoisonError::into_inner).push(sink);
+    HAS_LOG_SINKS.store(true, Ordering::Relaxed);
+}
+
+/// Remove all sinks, flushing them first.
+pub fn clear_log_sinks() {
+    let mut sinks = LOG_SINKS.write().unwrap_or_else(PoisonError::into_inner);
+    sinks.iter().for_each(|s| s.flush());
+    sinks.clear();
+    HAS_LOG_SINKS.store(false, Ordering::Relaxed);
+}
+
+pub fn flush_log_sinks() {
+    LOG_SINKS.read().unwrap_or_else(PoisonError::into_inner).iter().for_each(|s| s.flush());
+}
+
+pub fn has_log_sinks() -> bool {
+    HAS_LOG_SINKS.load(Ordering::Relaxed)
+}
+
+pub fn write_to_sinks(record: &LogRecord) {
+    if !has_log_sinks() { return; }
+    // A sink which panics while another thread is logging shouldn't stop everything else
+    let sinks = LOG_SINKS.read().unwrap_or_else(PoisonError::into_inner);
+    sinks.iter().for_each(|s| s.write(record));
+}
+
+pub(crate) unsafe fn write_raw_to_sinks(p: *const u8, len: usize, color: LogColor, level: LogLevel, show_prefix: bool, newline: bool) {
+    if !has_log_sinks() { return; }
+    let text = String::from_utf8_lossy(std::slice::from_raw_parts(p, len));
+    write_to_sinks(&LogRecord::new(&text, level, color, show_prefix, newline));
+}
+
+/// Get the mod's config directory, which is where file sinks are stored by default.
+pub fn get_config_directory() -> PathBuf {
+    #[cfg(feature = "reloaded")]
+    return PathBuf::from(String::from(crate::mod_loader_data::get_config_directory()));
+    #[cfg(not(feature = "reloaded"))]
+    return crate::standalone::get_config_directory();
+}
+
+/// Format a time as `YYYY-MM-DD HH:MM:SS.mmm` in UTC.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}", year, month, day,
+        rem / 3600, (rem / 60) % 60, rem % 60, since_epoch.subsec_millis())
+}
+
+/// Write a record as a single line JSON object, e.g
+/// `{"time":"2025-01-01 00:00:00.000","level":"DEBUG","module":"mymod::hooks","message":"resolved","fields":{"hook":"fldPCMoveUpdate"}}`
+pub fn format_json(record: &LogRecord) -> String {
+    let mut out = format!("{{\"time\":\"{}\",\"level\":\"{}\"", format_timestamp(record.time), record.level);
+    if let Some(module) = record.module {
+        out.push_str(",\"module\":");
+        crate::logger::write_json_string(module, &mut out);
+    }
+    out.push_str(",\"message\":");
+    crate::logger::write_json_string(record.message, &mut out);
+    if !record.fields.is_empty() {
+        out.push_str(",\"fields\":{");
+        for (i, field) in record.fields.iter().enumerate() {
+            if i != 0 { out.push(','); }
+            crate::logger::write_json_string(field.key, &mut out);
+            out.push(':');
+            field.value.write_json(&mut out);
+        }
+        out.push('}');
+    }
+    out.push('}');
+    out
+}
+
+/// Text shown at the start of each line in sinks that write text (e.g `2025-01-01 00:00:00.000 [INFO] `)
+fn line_prefix(record: &LogRecord) -> String {
+    format!("{} [{}] ", format_timestamp(record.time), record.level)
+}
+
+/// How each message is written into a [`FileSink`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The same text as the Reloaded console, prefixed with the time and level.
+    #[default]
+    Text,
+    /// One JSON object per message, with fields kept separate. See [`format_json`].
+    Json
+}
+
+struct FileState {
+    file: File,
+    size: u64,
+    at_line_start: bool
+}
+
+/// Writes messages into a file, moving it to a numbered backup once it goes above the size
+/// limit (`mymod.log` -> `mymod.1.log` -> `mymod.2.log`...). Each write goes directly to the
+/// file, so everything logged before a crash is kept.
+pub struct FileSink {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    format: LogFormat,
+    state: Mutex<Option<FileState>>
+}
+
+impl FileSink {
+    pub const DEFAULT_MAX_SIZE: u64 = 0x100000;
+    pub const DEFAULT_MAX_FILES: usize = 3;
+
+    /// Create a file sink at `path`. Existing logs at that path are rotated out so that each
+    /// session starts with a new file.
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let sink = Self {
+            path: path.into(),
+            max_size: Self::DEFAULT_MAX_SIZE,
+            max_files: Self::DEFAULT_MAX_FILES,
+            format: LogFormat::Text,
+            state: Mutex::new(None)
+        };
+        if let Some(p) = sink.path.parent() {
+            std::fs::create_dir_all(p)?;
+        }
+        sink.rotate()?;
+        *sink.state.lock().unwrap_or_else(PoisonError::into_inner) = Some(sink.open()?);
+        Ok(sink)
+    }
+
+    /// Create a file sink at `{config directory}/{name}.log`.
+    pub fn in_config_directory(name: &str) -> io::Result<Self> {
+        Self::new(get_config_directory().join(format!("{}.log", name)))
+    }
+
+    /// Set the size in bytes at which the log is moved into a backup.
+    pub fn with_max_size(mut self, size: u64) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Set how many backups are kept, not including the current log.
+    pub fn with_max_files(mut self, count: usize) -> Self {
+        self.max_files = count;
+        self
+    }
+
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn get_path(&self) -> &Path { &self.path }
+
+    /// Get the path of the nth backup (0 is the current log).
+    pub fn get_backup_path(&self, index: usize) -> PathBuf {
+        if index == 0 { return self.path.clone(); }
+        let stem = self.path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let name = match self.path.extension() {
+            Some(e) => format!("{}.{}.{}", stem, index, e.to_string_lossy()),
+            None => format!("{}.{}", stem, index)
+        };
+        self.path.with_file_name(name)
+    }
+
+    fn open(&self) -> io::Result<FileState> {
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let size = file.metadata()?.len();
+        Ok(FileState { file, size, at_line_start: true })
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if !self.path.exists() { return Ok(()); }
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for i in (0..self.max_files).rev() {
+            let from = self.get_backup_path(i);
+            if from.exists() {
+                std::fs::rename(&from, self.get_backup_path(i + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_inner(&self, state: &mut Option<FileState>, record: &LogRecord) -> io::Result<()> {
+        if let Some(s) = state.as_ref() {
+            if s.at_line_start && s.size >= self.max_size {
+                *state = None;
+                self.rotate()?;
+            }
+        }
+        if state.is_none() {
+            *state = Some(self.open()?);
+        }
+        let state = state.as_mut().unwrap();
+        let line = match self.format {
+            LogFormat::Text => {
+                let mut line = String::new();
+                if state.at_line_start {
+                    line.push_str(&line_prefix(record));
+                }
+                line.push_str(record.text);
+                if record.newline {
+                    line.push('\n');
+                }
+                state.at_line_start = record.newline;
+                line
+            },
+            LogFormat::Json => format_json(record) + "\n"
+        };
+        state.file.write_all(line.as_bytes())?;
+        state.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&self, record: &LogRecord) {
+        // A panic while writing poisons the lock, but the file is still usable
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        // Logging can't report its own errors, so drop the file and try again next time
+        if self.write_inner(&mut state, record).is_err() {
+            *state = None;
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(s) = self.state.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            _ = s.file.sync_data();
+        }
+    }
+}
+
+struct RingBufferState {
+    lines: VecDeque<String>,
+    at_line_start: bool
+}
+
+/// Keeps the last `capacity` lines that were logged in memory. These can be read with
+/// [`RingBufferSink::get_lines`], or written out with [`RingBufferSink::dump`], which can be
+/// done automatically when the mod panics using [`RingBufferSink::dump_on_panic`].
+pub struct RingBufferSink {
+    capacity: usize,
+    state: Mutex<RingBufferState>
+}
+
+impl RingBufferSink {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(RingBufferState {
+                lines: VecDeque::with_capacity(capacity),
+                at_line_start: true
+            })
+        }
+    }
+
+    pub fn get_capacity(&self) -> usize { self.capacity }
+
+    /// Get a copy of the lines in the buffer, from oldest to newest.
+    pub fn get_lines(&self) -> Vec<String> {
+        self.lock().lines.iter().cloned().collect()
+    }
+
+    /// Get a copy of the lines in the buffer without waiting, returning `None` if another
+    /// thread is writing to the buffer.
+    pub fn try_get_lines(&self) -> Option<Vec<String>> {
+        match self.state.try_lock() {
+            Ok(v) => Some(v.lines.iter().cloned().collect()),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner().lines.iter().cloned().collect()),
+            Err(TryLockError::WouldBlock) => None
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.lines.clear();
+        state.at_line_start = true;
+    }
+
+    /// Write the lines in the buffer into `writer`, from oldest to newest.
+    pub fn dump<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for line in self.lock().lines.iter() {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    pub fn dump_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        self.dump(&mut file)?;
+        file.sync_data()
+    }
+
+    /// Install a panic hook which writes the panic message and the contents of the buffer into
+    /// `path`. The previous panic hook is called afterwards.
+    pub fn dump_on_panic<P: Into<PathBuf>>(self: &Arc<Self>, path: P) {
+        let sink = Arc::downgrade(self);
+        let path = path.into();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(sink) = sink.upgrade() {
+                // The panic may have come from inside the sink while it was locked
+                let state = match sink.state.try_lock() {
+                    Ok(v) => Some(v),
+                    Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+                    Err(TryLockError::WouldBlock) => None
+                };
+                let result = File::create(&path).and_then(|mut f| {
+                    for line in state.iter().flat_map(|s| s.lines.iter()) {
+                        writeln!(f, "{}", line)?;
+                    }
+                    writeln!(f, "{} [PANIC] {}", format_timestamp(SystemTime::now()), info)?;
+                    f.sync_data()
+                });
+                if let Err(e) = result {
+                    eprintln!("Failed to write log to {}: {}", path.display(), e);
+                }
+            }
+            previous(info);
+        }));
+    }
+
+    // Don't lose the log if the lock was poisoned by a panic
+    fn lock(&self) -> std::sync::MutexGuard<'_, RingBufferState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl LogSink for RingBufferSink {
+    fn write(&self, record: &LogRecord) {
+        if self.capacity == 0 { return; }
+        let mut state = self.lock();
+        if state.at_line_start || state.lines.is_empty() {
+            if state.lines.len() == self.capacity {
+                state.lines.pop_front();
+            }
+            state.lines.push_back(line_prefix(record));
+        }
+        state.lines.back_mut().unwrap().push_str(record.text);
+        state.at_line_start = record.newline;
+    }
+}
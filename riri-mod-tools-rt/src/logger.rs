@@ -3,9 +3,11 @@ use std::{
     error::Error,
     ffi::CStr,
     fmt::Display,
+    str::FromStr,
     sync::OnceLock
 };
-use std::sync::Mutex;
+use std::sync::{ Mutex, PoisonError, RwLock };
+use std::sync::atomic::{ AtomicBool, AtomicU32, Ordering };
 #[cfg(feature = "reloaded")]
 use crate::mod_loader_data::CSharpString;
 
@@ -352,7 +354,7 @@ impl TryFrom<&str> for LogColor {
 
 /// Defines levels 
 #[repr(u32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LogLevel {
     Verbose,
     Debug,
@@ -388,13 +390,412 @@ impl LogLevel {
     }
 }
 
+impl TryFrom<u32> for LogLevel {
+    type Error = LogFilterError;
+    fn try_from(value: u32) -> Result<Self, LogFilterError> {
+        match value {
+            0 => Ok(Self::Verbose),
+            1 => Ok(Self::Debug),
+            2 => Ok(Self::Information),
+            3 => Ok(Self::Warning),
+            4 => Ok(Self::Error),
+            _ => Err(LogFilterError::InvalidLevel(value.to_string()))
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = LogFilterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" | "verbose" => Ok(Self::Verbose),
+            "debug" => Ok(Self::Debug),
+            "info" | "information" => Ok(Self::Information),
+            "warn" | "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(LogFilterError::InvalidLevel(s.to_owned()))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogFilterError {
+    InvalidLevel(String),
+    InvalidDirective(String)
+}
+
+impl Display for LogFilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLevel(v) => write!(f, "'{}' is not a valid log level", v),
+            Self::InvalidDirective(v) => write!(f, "'{}' is not a valid filter directive", v)
+        }
+    }
+}
+
+impl Error for LogFilterError {}
+
+/// A set of filter directives, written as a comma separated list of `module::path=Level` and
+/// `Level` (e.g `mymod::camera=Debug,warn`). A directive applies to the module it names and
+/// all of its submodules, with the most specific directive being used. A level without a module
+/// path sets the default level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LogLevel,
+    directives: Vec<(String, LogLevel)>
+}
+
+impl LogFilter {
+    pub fn new(default: LogLevel) -> Self {
+        Self { default, directives: vec![] }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LogFilterError> {
+        let mut filter = Self::new(LogLevel::Verbose);
+        for directive in text.split(',').map(|d| d.trim()).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((path, level)) => {
+                    let path = path.trim();
+                    if path.is_empty() {
+                        return Err(LogFilterError::InvalidDirective(directive.to_owned()));
+                    }
+                    filter.directives.push((path.to_owned(), level.trim().parse()?));
+                },
+                None => filter.default = directive.parse()?
+            }
+        }
+        // Check the most specific paths first
+        filter.directives.sort_by_key(|d| std::cmp::Reverse(d.0.len()));
+        Ok(filter)
+    }
+
+    pub fn get_default(&self) -> LogLevel { self.default }
+
+    /// Get the level that applies to a module path (from `module_path!()`).
+    pub fn get_level(&self, module: &str) -> LogLevel {
+        self.directives.iter()
+            .find(|(path, _)| module.strip_prefix(path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::")))
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn get_min_level(&self) -> LogLevel {
+        self.directives.iter().map(|(_, l)| *l)
+            .fold(self.default, |a, b| if (b as u32) < (a as u32) { b } else { a })
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = LogFilterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
 pub static GLOBAL_LOG_COLOR: OnceLock<LogColor> = OnceLock::new();
-pub static GLOBAL_LOG_LEVEL: LogLevel = LogLevel::Verbose;
+/// The default log level, used for modules that aren't covered by a filter directive. Read and
+/// written through `get_log_level` and `set_log_level`.
+static GLOBAL_LOG_LEVEL: AtomicU32 = AtomicU32::new(LogLevel::Verbose as u32);
+/// The lowest level enabled by either the default level or any filter directive. This is
+/// checked first so that disabled messages only cost an atomic load.
+static MIN_LOG_LEVEL: AtomicU32 = AtomicU32::new(LogLevel::Verbose as u32);
+static HAS_LOG_DIRECTIVES: AtomicBool = AtomicBool::new(false);
+static LOG_FILTER: RwLock<Option<LogFilter>> = RwLock::new(None);
+
+/// Check if a message at the given level from the given module (from `module_path!()`) should
+/// be logged.
+#[inline]
+pub fn is_log_enabled(level: LogLevel, module: &str) -> bool {
+    let level = level as u32;
+    if level < MIN_LOG_LEVEL.load(Ordering::Relaxed) { return false; }
+    if !HAS_LOG_DIRECTIVES.load(Ordering::Relaxed) { return true; }
+    match &*LOG_FILTER.read().unwrap_or_else(PoisonError::into_inner) {
+        Some(f) => level >= f.get_level(module) as u32,
+        None => level >= GLOBAL_LOG_LEVEL.load(Ordering::Relaxed)
+    }
+}
+
+/// Get the default log level.
+pub fn get_log_level() -> LogLevel {
+    GLOBAL_LOG_LEVEL.load(Ordering::Relaxed).try_into().unwrap()
+}
+
+/// Set the default log level. This keeps any module filter directives.
+pub fn set_log_level(level: LogLevel) {
+    let mut filter = LOG_FILTER.write().unwrap_or_else(PoisonError::into_inner);
+    match &mut *filter {
+        Some(f) => {
+            f.default = level;
+            MIN_LOG_LEVEL.store(f.get_min_level() as u32, Ordering::Relaxed);
+        },
+        None => MIN_LOG_LEVEL.store(level as u32, Ordering::Relaxed)
+    };
+    GLOBAL_LOG_LEVEL.store(level as u32, Ordering::Relaxed);
+}
+
+/// Replace the current log level and filter directives with `filter`.
+pub fn set_log_filter(filter: LogFilter) {
+    let mut current = LOG_FILTER.write().unwrap_or_else(PoisonError::into_inner);
+    GLOBAL_LOG_LEVEL.store(filter.default as u32, Ordering::Relaxed);
+    MIN_LOG_LEVEL.store(filter.get_min_level() as u32, Ordering::Relaxed);
+    HAS_LOG_DIRECTIVES.store(!filter.directives.is_empty(), Ordering::Relaxed);
+    *current = Some(filter);
+}
+
+/// Get the current log level and filter directives.
+pub fn get_log_filter() -> LogFilter {
+    match &*LOG_FILTER.read().unwrap_or_else(PoisonError::into_inner) {
+        Some(f) => f.clone(),
+        None => LogFilter::new(get_log_level())
+    }
+}
+
+/// A value attached to a log message as a key-value field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Pointer(usize)
+}
+
+impl Display for LogValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Int(v) => write!(f, "{}", v),
+            Self::UInt(v) => write!(f, "{}", v),
+            Self::Float(v) => write!(f, "{}", v),
+            // Quote strings that would otherwise be hard to tell apart from the next field
+            Self::String(v) => match v.is_empty() || v.contains(|c: char| c.is_whitespace() || c == '=' || c == '"') {
+                true => write!(f, "{:?}", v),
+                false => write!(f, "{}", v)
+            },
+            Self::Pointer(v) => write!(f, "0x{:x}", v)
+        }
+    }
+}
+
+impl LogValue {
+    /// Write the value as JSON. Pointers are written as hex strings and non-finite floats as
+    /// null.
+    pub fn write_json(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Float(v) if !v.is_finite() => out.push_str("null"),
+            Self::String(v) => write_json_string(v, out),
+            Self::Pointer(v) => write_json_string(&format!("0x{:x}", v), out),
+            v => out.push_str(&v.to_string())
+        }
+    }
+}
+
+pub fn write_json_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+/// Converts a value into a [`LogValue`] for use as a field in the log macros. Types that don't
+/// implement this can be formatted into a string first (e.g `pos = format!("{:?}", pos)`).
+pub trait ToLogValue {
+    fn to_log_value(&self) -> LogValue;
+}
+
+macro_rules! impl_to_log_value {
+    ($variant:ident, $target:ty, $($ty:ty),*) => {
+        $(
+            impl ToLogValue for $ty {
+                fn to_log_value(&self) -> LogValue { LogValue::$variant(*self as $target) }
+            }
+        )*
+    };
+}
+
+impl_to_log_value!(Int, i64, i8, i16, i32, i64, isize);
+impl_to_log_value!(UInt, u64, u8, u16, u32, u64, usize);
+impl_to_log_value!(Float, f64, f32, f64);
+
+impl ToLogValue for bool {
+    fn to_log_value(&self) -> LogValue { LogValue::Bool(*self) }
+}
+
+impl ToLogValue for char {
+    fn to_log_value(&self) -> LogValue { LogValue::String(self.to_string()) }
+}
+
+impl ToLogValue for str {
+    fn to_log_value(&self) -> LogValue { LogValue::String(self.to_owned()) }
+}
+
+impl ToLogValue for String {
+    fn to_log_value(&self) -> LogValue { LogValue::String(self.clone()) }
+}
+
+impl<T> ToLogValue for *const T {
+    fn to_log_value(&self) -> LogValue { LogValue::Pointer(*self as usize) }
+}
+
+impl<T> ToLogValue for *mut T {
+    fn to_log_value(&self) -> LogValue { LogValue::Pointer(*self as usize) }
+}
+
+impl<T: ToLogValue> ToLogValue for Option<T> {
+    fn to_log_value(&self) -> LogValue {
+        self.as_ref().map_or(LogValue::Null, |v| v.to_log_value())
+    }
+}
+
+impl ToLogValue for LogValue {
+    fn to_log_value(&self) -> LogValue { self.clone() }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogField {
+    pub key: &'static str,
+    pub value: LogValue
+}
+
+impl LogField {
+    pub fn new(key: &'static str, value: LogValue) -> Self {
+        Self { key, value }
+    }
+}
+
+/// Where a structured log message came from and how it should be written.
+#[derive(Debug, Clone, Copy)]
+pub struct LogMetadata<'a> {
+    pub level: LogLevel,
+    pub module: &'a str,
+    pub file: &'static str,
+    pub line: u32,
+    pub show_prefix: bool,
+    pub newline: bool
+}
+
+/// A scoped timer which logs how long it was alive for when it's dropped, created with
+/// [`log_span!`]. Fields given to the span are included in the message, along with
+/// `elapsed_ms`.
+#[must_use = "the span logs when it's dropped, so it should be assigned to a variable"]
+pub struct LogSpan {
+    metadata: Option<LogMetadata<'static>>,
+    name: String,
+    fields: Vec<LogField>,
+    start: std::time::Instant
+}
+
+impl LogSpan {
+    pub fn new(metadata: LogMetadata<'static>, name: String, fields: Vec<LogField>) -> Self {
+        Self { metadata: Some(metadata), name, fields, start: std::time::Instant::now() }
+    }
+
+    /// A span which doesn't log anything, used when the span's level is disabled.
+    pub fn disabled() -> Self {
+        Self { metadata: None, name: String::new(), fields: vec![], start: std::time::Instant::now() }
+    }
+
+    pub fn is_enabled(&self) -> bool { self.metadata.is_some() }
+
+    pub fn elapsed(&self) -> std::time::Duration { self.start.elapsed() }
+
+    /// Add a field to the message that's logged when the span ends.
+    pub fn record<T: ToLogValue + ?Sized>(&mut self, key: &'static str, value: &T) {
+        if self.is_enabled() {
+            self.fields.push(LogField::new(key, value.to_log_value()));
+        }
+    }
+}
+
+impl Drop for LogSpan {
+    fn drop(&mut self) {
+        if let Some(metadata) = self.metadata.as_ref() {
+            let elapsed = self.start.elapsed();
+            let mut fields = std::mem::take(&mut self.fields);
+            fields.push(LogField::new("elapsed_ms", LogValue::Float(elapsed.as_secs_f64() * 1000.)));
+            write_structured(metadata, format!("{} finished in {:.3?}", self.name, elapsed), &fields);
+        }
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_fields {
+    ($($key:ident = $value:expr),+) => {{
+        #[allow(unused_imports)]
+        use $crate::logger::ToLogValue as _;
+        vec![$( $crate::logger::LogField::new(stringify!($key), ($value).to_log_value()) ),+]
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_structured {
+    ($ty:ident, $show_prefix:expr, $newline:expr, $($key:ident = $value:expr),+ ; $($fmt:tt)*) => {
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!()) {
+            $crate::logger::write_structured(&$crate::logger::LogMetadata {
+                level: $crate::logger::LogLevel::$ty,
+                module: module_path!(),
+                file: file!(),
+                line: line!(),
+                show_prefix: $show_prefix,
+                newline: $newline
+            }, format!($($fmt)*), &$crate::__log_fields!($($key = $value),+));
+        }
+    };
+}
+
+/// Start a [`LogSpan`] which logs its duration when dropped. Fields can be given before the
+/// name, in the same way as the other log macros:
+///
+/// ```ignore
+/// let _span = log_span!(Debug, count = models.len(); "load_models");
+/// // ...
+/// // logs "load_models finished in 12.345ms count=8 elapsed_ms=12.345"
+/// ```
+#[macro_export]
+macro_rules! log_span {
+    ($ty:ident, $($key:ident = $value:expr),+ ; $($fmt:tt)*) => {
+        $crate::log_span!(@create $ty, format!($($fmt)*), $crate::__log_fields!($($key = $value),+))
+    };
+    ($ty:ident, $($fmt:tt)*) => {
+        $crate::log_span!(@create $ty, format!($($fmt)*), vec![])
+    };
+    (@create $ty:ident, $name:expr, $fields:expr) => {
+        match $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!()) {
+            true => $crate::logger::LogSpan::new($crate::logger::LogMetadata {
+                level: $crate::logger::LogLevel::$ty,
+                module: module_path!(),
+                file: file!(),
+                line: line!(),
+                show_prefix: true,
+                newline: true
+            }, $name, $fields),
+            false => $crate::logger::LogSpan::disabled()
+        }
+    };
+}
 
 #[macro_export]
 macro_rules! log {
+    ($ty:ident, $($key:ident = $value:expr),+ ; $($fmt:tt)*) => {
+        $crate::__log_structured!($ty, true, false, $($key = $value),+ ; $($fmt)*)
+    };
     ($ty:ident, $($fmt:tt)*) => {
-        if $crate::logger::LogLevel::$ty as u32 >= $crate::logger::GLOBAL_LOG_LEVEL as u32 {
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!()) {
             let file = file!();
             let line = line!();
             let thread_id = $crate::address::get_thread_id();
@@ -413,8 +814,11 @@ macro_rules! log {
 
 #[macro_export]
 macro_rules! log_noprefix {
+    ($ty:ident, $($key:ident = $value:expr),+ ; $($fmt:tt)*) => {
+        $crate::__log_structured!($ty, false, false, $($key = $value),+ ; $($fmt)*)
+    };
      ($ty:ident, $($fmt:tt)*) => {
-        if $crate::logger::LogLevel::$ty as u32 >= $crate::logger::GLOBAL_LOG_LEVEL as u32 {
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!()) {
             let file = file!();
             let line = line!();
             let thread_id = $crate::address::get_thread_id();
@@ -433,8 +837,11 @@ macro_rules! log_noprefix {
 
 #[macro_export]
 macro_rules! logln {
+    ($ty:ident, $($key:ident = $value:expr),+ ; $($fmt:tt)*) => {
+        $crate::__log_structured!($ty, true, true, $($key = $value),+ ; $($fmt)*)
+    };
     ($ty:ident, $($fmt:tt)*) => {
-        if $crate::logger::LogLevel::$ty as u32 >= $crate::logger::GLOBAL_LOG_LEVEL as u32 {
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!()) {
             let file = file!();
             let line = line!();
             let thread_id = $crate::address::get_thread_id();
@@ -453,8 +860,11 @@ macro_rules! logln {
 
 #[macro_export]
 macro_rules! logln_noprefix {
+    ($ty:ident, $($key:ident = $value:expr),+ ; $($fmt:tt)*) => {
+        $crate::__log_structured!($ty, false, true, $($key = $value),+ ; $($fmt)*)
+    };
     ($ty:ident, $($fmt:tt)*) => {
-        if $crate::logger::LogLevel::$ty as u32 >= $crate::logger::GLOBAL_LOG_LEVEL as u32 {
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!()) {
             let file = file!();
             let line = line!();
             let thread_id = $crate::address::get_thread_id();
@@ -471,6 +881,54 @@ macro_rules! logln_noprefix {
     };
 }
 
+/// Log a line the first time this call site is reached while its level is enabled.
+///
+/// ```ignore
+/// log_once!(Warning, "camera mode {} isn't supported", mode);
+/// ```
+#[macro_export]
+macro_rules! log_once {
+    ($ty:ident, $($args:tt)*) => {{
+        static ONCE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!())
+            && !ONCE.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            $crate::logln!($ty, $($args)*);
+        }
+    }};
+}
+
+/// Log a line on the first call and every `n`th call after that from this call site.
+///
+/// ```ignore
+/// log_every!(60, Debug, "player position: {:?}", pos);
+/// ```
+#[macro_export]
+macro_rules! log_every {
+    ($n:expr, $ty:ident, $($args:tt)*) => {{
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!())
+            && COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed) % ($n as usize).max(1) == 0 {
+            $crate::logln!($ty, $($args)*);
+        }
+    }};
+}
+
+/// Log a line from this call site at most once per `interval` (a `Duration`).
+///
+/// ```ignore
+/// log_throttled!(Duration::from_secs(1), Debug, "camera fov: {}", fov);
+/// ```
+#[macro_export]
+macro_rules! log_throttled {
+    ($interval:expr, $ty:ident, $($args:tt)*) => {{
+        static THROTTLE: $crate::logger::LogThrottle = $crate::logger::LogThrottle::new();
+        if $crate::logger::is_log_enabled($crate::logger::LogLevel::$ty, module_path!())
+            && THROTTLE.check($interval) {
+            $crate::logln!($ty, $($args)*);
+        }
+    }};
+}
+
 pub fn transform_text(base: String, file: &'static str, line: u32, thread_id: u64) -> String {
     if cfg!(feature = "detailed-logs") {
         format!("[THREAD {}] [{}:{}] {}", thread_id, file, line, base)
@@ -500,12 +958,217 @@ pub unsafe extern "C" fn set_reloaded_logger_newline(cb: LogFn) {
 
 #[no_mangle]
 pub unsafe extern "C" fn invoke_reloaded_logger(p: *const u8, len: usize, c: LogColor, level: LogLevel, show_prefix: bool) {
-    RELOADED_LOGGER.get().unwrap()(p, len, c, level, show_prefix);
+    if !check_repeated(std::slice::from_raw_parts(p, len), c, level, show_prefix, false) { return; }
+    crate::log_sink::write_raw_to_sinks(p, len, c, level, show_prefix, false);
+    write_reloaded_logger(p, len, c, level, show_prefix, false);
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn invoke_reloaded_logger_newline(p: *const u8, len: usize, c: LogColor, level: LogLevel, show_prefix: bool) {
-    RELOADED_LOGGER_LN.get().unwrap()(p, len, c, level, show_prefix);
+    if !check_repeated(std::slice::from_raw_parts(p, len), c, level, show_prefix, true) { return; }
+    crate::log_sink::write_raw_to_sinks(p, len, c, level, show_prefix, true);
+    write_reloaded_logger(p, len, c, level, show_prefix, true);
+}
+
+#[cfg(feature = "reloaded")]
+unsafe fn write_reloaded_logger(p: *const u8, len: usize, c: LogColor, level: LogLevel, show_prefix: bool, newline: bool) {
+    match newline {
+        true => RELOADED_LOGGER_LN.get().unwrap()(p, len, c, level, show_prefix),
+        false => RELOADED_LOGGER.get().unwrap()(p, len, c, level, show_prefix)
+    }
+}
+
+// Outside of Reloaded, write to stderr unless a logger has been set
+#[cfg(not(feature = "reloaded"))]
+unsafe fn write_reloaded_logger(p: *const u8, len: usize, c: LogColor, level: LogLevel, show_prefix: bool, newline: bool) {
+    match if newline { RELOADED_LOGGER_LN.get() } else { RELOADED_LOGGER.get() } {
+        Some(f) => f(p, len, c, level, show_prefix),
+        None => crate::standalone::write_log(&String::from_utf8_lossy(
+            std::slice::from_raw_parts(p, len)), c, level, show_prefix, newline)
+    }
+}
+
+/// Write a message with key-value fields. The fields are appended onto the message for the
+/// Reloaded console (e.g `resolved hook=fldPCMoveUpdate addr=0x14a2b3c40`), while sinks
+/// receive them separately. This is called by the log macros when fields are given.
+pub fn write_structured(metadata: &LogMetadata, message: String, fields: &[LogField]) {
+    let color = metadata.level.get_log_color();
+    let text = transform_text(render_fields(&message, fields),
+        metadata.file, metadata.line, crate::address::get_thread_id());
+    if !check_repeated(text.as_bytes(), color, metadata.level, metadata.show_prefix, metadata.newline) { return; }
+    crate::log_sink::write_to_sinks(&crate::log_sink::LogRecord {
+        text: &text,
+        message: &message,
+        module: Some(metadata.module),
+        fields,
+        ..crate::log_sink::LogRecord::new(&text, metadata.level, color, metadata.show_prefix, metadata.newline)
+    });
+    unsafe { write_reloaded_logger(text.as_ptr(), text.len(), color,
+        metadata.level, metadata.show_prefix, metadata.newline) };
+}
+
+/// A line which was suppressed by [`LogDeduplicator`] because it was the same as the line
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatSummary {
+    pub repeats: usize,
+    pub color: LogColor,
+    pub level: LogLevel,
+    pub show_prefix: bool
+}
+
+impl RepeatSummary {
+    pub fn get_text(&self) -> String {
+        match self.repeats {
+            1 => "(repeated 1 time)".to_owned(),
+            n => format!("(repeated {} times)", n)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RepeatedLine {
+    text: Vec<u8>,
+    summary: RepeatSummary,
+    reported: std::time::Instant
+}
+
+/// Collapses consecutive identical lines into a single `(repeated N times)` line. This is
+/// written once a different line is logged, or when a line keeps repeating for longer than
+/// `interval`, so that messages logged every frame are still shown occasionally.
+#[derive(Debug)]
+pub struct LogDeduplicator {
+    interval: std::time::Duration,
+    last: Option<RepeatedLine>
+}
+
+impl LogDeduplicator {
+    pub fn new(interval: std::time::Duration) -> Self {
+        Self { interval, last: None }
+    }
+
+    pub fn get_interval(&self) -> std::time::Duration { self.interval }
+
+    /// Check if a line should be written, returning the summary for the previous line if
+    /// it was repeated and the summary is now due. Text without a newline is always written,
+    /// and breaks up any repeats.
+    pub fn check(&mut self, text: &[u8], color: LogColor, level: LogLevel, show_prefix: bool, newline: bool) -> (bool, Option<RepeatSummary>) {
+        if let Some(last) = self.last.as_mut() {
+            if newline && last.text == text && last.summary.level == level {
+                last.summary.repeats += 1;
+                if last.reported.elapsed() < self.interval {
+                    return (false, None);
+                }
+                let summary = last.summary;
+                last.summary.repeats = 0;
+                last.reported = std::time::Instant::now();
+                return (false, Some(summary));
+            }
+        }
+        let summary = self.flush();
+        if newline {
+            self.last = Some(RepeatedLine {
+                text: text.to_vec(),
+                summary: RepeatSummary { repeats: 0, color, level, show_prefix },
+                reported: std::time::Instant::now()
+            });
+        }
+        (true, summary)
+    }
+
+    /// Stop tracking the last line, returning a summary if it was repeated.
+    pub fn flush(&mut self) -> Option<RepeatSummary> {
+        self.last.take().map(|l| l.summary).filter(|s| s.repeats > 0)
+    }
+}
+
+static LOG_DEDUPLICATOR: Mutex<Option<LogDeduplicator>> = Mutex::new(None);
+static HAS_LOG_DEDUPLICATOR: AtomicBool = AtomicBool::new(false);
+
+/// Collapse repeated lines into `(repeated N times)` for everything written through the
+/// logger, or pass `None` to turn this off. Lines that keep repeating are summarized every
+/// `interval`.
+pub fn set_log_deduplication(interval: Option<std::time::Duration>) {
+    let summary = {
+        let mut dedup = LOG_DEDUPLICATOR.lock().unwrap_or_else(PoisonError::into_inner);
+        let summary = dedup.as_mut().and_then(|d| d.flush());
+        *dedup = interval.map(LogDeduplicator::new);
+        HAS_LOG_DEDUPLICATOR.store(dedup.is_some(), Ordering::Relaxed);
+        summary
+    };
+    if let Some(summary) = summary {
+        write_repeat_summary(&summary);
+    }
+}
+
+/// Write the repeat count for the last line if it's been repeated since it was last shown.
+pub fn flush_log_deduplication() {
+    let summary = LOG_DEDUPLICATOR.lock().unwrap_or_else(PoisonError::into_inner)
+        .as_mut().and_then(|d| d.flush());
+    if let Some(summary) = summary {
+        write_repeat_summary(&summary);
+    }
+}
+
+fn check_repeated(text: &[u8], color: LogColor, level: LogLevel, show_prefix: bool, newline: bool) -> bool {
+    if !HAS_LOG_DEDUPLICATOR.load(Ordering::Relaxed) { return true; }
+    // The lock is released before writing the summary, since sinks may log themselves
+    let (write, summary) = match LOG_DEDUPLICATOR.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+        Some(d) => d.check(text, color, level, show_prefix, newline),
+        None => (true, None)
+    };
+    if let Some(s) = summary {
+        write_repeat_summary(&s);
+    }
+    write
+}
+
+fn write_repeat_summary(summary: &RepeatSummary) {
+    let text = summary.get_text();
+    unsafe {
+        crate::log_sink::write_raw_to_sinks(text.as_ptr(), text.len(), summary.color, summary.level, summary.show_prefix, true);
+        write_reloaded_logger(text.as_ptr(), text.len(), summary.color, summary.level, summary.show_prefix, true);
+    }
+}
+
+/// Nanoseconds since the first call, used by [`LogThrottle`].
+fn get_monotonic_nanos() -> u64 {
+    static START: OnceLock<std::time::Instant> = OnceLock::new();
+    START.get_or_init(std::time::Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Tracks when a [`log_throttled!`] call site last logged.
+#[derive(Debug, Default)]
+pub struct LogThrottle(std::sync::atomic::AtomicU64);
+
+impl LogThrottle {
+    pub const fn new() -> Self {
+        Self(std::sync::atomic::AtomicU64::new(u64::MAX))
+    }
+
+    /// Returns true if nothing has been logged within `interval`, marking the current time as
+    /// the last log.
+    pub fn check(&self, interval: std::time::Duration) -> bool {
+        let now = get_monotonic_nanos();
+        let mut last = self.0.load(Ordering::Relaxed);
+        loop {
+            if last != u64::MAX && now.saturating_sub(last) < interval.as_nanos() as u64 {
+                return false;
+            }
+            match self.0.compare_exchange_weak(last, now, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(v) => last = v
+            }
+        }
+    }
+}
+
+fn render_fields(message: &str, fields: &[LogField]) -> String {
+    let mut text = message.to_owned();
+    for field in fields {
+        text.push_str(&format!(" {}={}", field.key, field.value));
+    }
+    text
 }
 
 #[no_mangle]
@@ -520,6 +1183,35 @@ pub unsafe extern "C" fn set_logger_color(name: *const i8) -> bool {
     }
 }
 
+/// Set the default log level from C#, returning false if the level is invalid.
+#[no_mangle]
+pub extern "C" fn set_logger_level(level: u32) -> bool {
+    match level.try_into() {
+        Ok(v) => {
+            set_log_level(v);
+            true
+        },
+        Err(_) => false
+    }
+}
+
+/// Set the log level and filter directives from C# (e.g `mymod::camera=Debug,warn`), returning
+/// false if the filter can't be parsed.
+///
+/// # Safety
+///
+/// `filter` must point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn set_logger_filter(filter: *const i8) -> bool {
+    match CStr::from_ptr(filter).to_str().map(|f| f.parse::<LogFilter>()) {
+        Ok(Ok(v)) => {
+            set_log_filter(v);
+            true
+        },
+        _ => false
+    }
+}
+
 #[cfg(feature = "reloaded")]
 static ON_RELOADED_LOGGER: Mutex<Vec<fn(&str)>> = Mutex::new(vec![]);
 
@@ -0,0 +1,188 @@
+#![cfg(test)]
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn log_filter_directives() -> ReturnType {
+    use crate::logger::{ LogFilter, LogLevel };
+    let filter: LogFilter = "mymod::camera=Debug, warn, mymod::camera::fov=error".parse()?;
+    assert_eq!(filter.get_default(), LogLevel::Warning);
+    assert_eq!(filter.get_level("mymod::camera"), LogLevel::Debug);
+    assert_eq!(filter.get_level("mymod::camera::orbit"), LogLevel::Debug);
+    assert_eq!(filter.get_level("mymod::camera::fov"), LogLevel::Error);
+    assert_eq!(filter.get_level("mymod::camera_old"), LogLevel::Warning);
+    assert_eq!(filter.get_level("othermod"), LogLevel::Warning);
+    Ok(())
+}
+
+#[test]
+fn log_filter_invalid() -> ReturnType {
+    use crate::logger::{ LogFilter, LogFilterError };
+    assert_eq!("mymod=loud".parse::<LogFilter>(), Err(LogFilterError::InvalidLevel("loud".to_owned())));
+    assert_eq!("=debug".parse::<LogFilter>(), Err(LogFilterError::InvalidDirective("=debug".to_owned())));
+    Ok(())
+}
+
+#[test]
+fn log_sink_timestamp() -> ReturnType {
+    use crate::log_sink::format_timestamp;
+    use std::time::{ Duration, UNIX_EPOCH };
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00.000");
+    assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_millis(1709210096789)), "2024-02-29 12:34:56.789");
+    Ok(())
+}
+
+#[test]
+fn log_sink_ring_buffer() -> ReturnType {
+    use crate::log_sink::{ LogRecord, LogSink, RingBufferSink };
+    use crate::logger::LogLevel;
+    let sink = RingBufferSink::new(2);
+    let record = |text, newline| LogRecord {
+        time: std::time::UNIX_EPOCH,
+        ..LogRecord::new(text, LogLevel::Information, LogLevel::Information.get_log_color(), true, newline)
+    };
+    sink.write(&record("first", true));
+    sink.write(&record("second ", false));
+    sink.write(&record("line", true));
+    sink.write(&record("third", true));
+    assert_eq!(sink.get_lines(), vec![
+        "1970-01-01 00:00:00.000 [INFO] second line".to_owned(),
+        "1970-01-01 00:00:00.000 [INFO] third".to_owned()
+    ]);
+    let mut dump = vec![];
+    sink.dump(&mut dump)?;
+    assert_eq!(String::from_utf8(dump)?.lines().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn log_sink_file_rotation() -> ReturnType {
+    use crate::log_sink::{ FileSink, LogRecord, LogSink };
+    use crate::logger::LogLevel;
+    let dir = std::env::temp_dir().join(format!("riri-mod-tools-sink-{}", std::process::id()));
+    let sink = FileSink::new(dir.join("test.log"))?.with_max_size(1).with_max_files(1);
+    for text in ["first", "second", "third"] {
+        sink.write(&LogRecord {
+            time: std::time::UNIX_EPOCH,
+            ..LogRecord::new(text, LogLevel::Warning, LogLevel::Warning.get_log_color(), true, true)
+        });
+    }
+    assert_eq!(std::fs::read_to_string(sink.get_path())?, "1970-01-01 00:00:00.000 [WARN] third\n");
+    assert_eq!(std::fs::read_to_string(sink.get_backup_path(1))?, "1970-01-01 00:00:00.000 [WARN] second\n");
+    assert!(!sink.get_backup_path(2).exists());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn log_structured_fields() -> ReturnType {
+    use crate::log_sink::{ format_json, LogRecord };
+    use crate::logger::{ LogField, LogLevel, LogValue, ToLogValue };
+    let fields = vec![
+        LogField::new("hook", "fldPCMoveUpdate".to_log_value()),
+        LogField::new("addr", (0x14a2b3c40usize as *const u8).to_log_value()),
+        LogField::new("name", "a \"b\"".to_log_value()),
+        LogField::new("scale", 1.5f32.to_log_value()),
+        LogField::new("index", Some(-3i32).to_log_value()),
+        LogField::new("parent", None::<u32>.to_log_value())
+    ];
+    let text = fields.iter().map(|f| format!(" {}={}", f.key, f.value)).collect::<String>();
+    assert_eq!(text, " hook=fldPCMoveUpdate addr=0x14a2b3c40 name=\"a \\\"b\\\"\" scale=1.5 index=-3 parent=null");
+    let record = LogRecord {
+        module: Some("mymod::hooks"),
+        fields: &fields,
+        time: std::time::UNIX_EPOCH,
+        ..LogRecord::new("resolved", LogLevel::Debug, LogLevel::Debug.get_log_color(), true, true)
+    };
+    assert_eq!(format_json(&record), concat!(
+        r#"{"time":"1970-01-01 00:00:00.000","level":"DEBUG","module":"mymod::hooks","message":"resolved","#,
+        r#""fields":{"hook":"fldPCMoveUpdate","addr":"0x14a2b3c40","name":"a \"b\"","scale":1.5,"index":-3,"parent":null}}"#
+    ));
+    assert_eq!(LogValue::Float(f64::NAN).to_string(), "NaN");
+    Ok(())
+}
+
+#[test]
+fn log_deduplication() -> ReturnType {
+    use crate::logger::{ LogDeduplicator, LogLevel };
+    use std::time::Duration;
+    let color = LogLevel::Information.get_log_color();
+    let mut dedup = LogDeduplicator::new(Duration::from_secs(60));
+    assert_eq!(dedup.check(b"frame", color, LogLevel::Information, true, true), (true, None));
+    assert_eq!(dedup.check(b"frame", color, LogLevel::Information, true, true), (false, None));
+    assert_eq!(dedup.check(b"frame", color, LogLevel::Information, true, true), (false, None));
+    // A different level isn't a repeat
+    let (write, summary) = dedup.check(b"frame", color, LogLevel::Warning, true, true);
+    assert!(write);
+    assert_eq!(summary.unwrap().get_text(), "(repeated 2 times)");
+    assert_eq!(dedup.check(b"other", color, LogLevel::Information, true, true), (true, None));
+    assert_eq!(dedup.check(b"other", color, LogLevel::Information, true, true), (false, None));
+    assert_eq!(dedup.flush().unwrap().get_text(), "(repeated 1 time)");
+    assert_eq!(dedup.flush(), None);
+    // Lines that keep repeating are summarized every interval
+    let mut dedup = LogDeduplicator::new(Duration::ZERO);
+    assert!(dedup.check(b"frame", color, LogLevel::Information, true, true).0);
+    let (write, summary) = dedup.check(b"frame", color, LogLevel::Information, true, true);
+    assert!(!write && summary.unwrap().repeats == 1);
+    Ok(())
+}
+
+#[test]
+fn log_throttle() -> ReturnType {
+    use crate::logger::LogThrottle;
+    use std::time::Duration;
+    let throttle = LogThrottle::new();
+    assert!(throttle.check(Duration::from_secs(60)));
+    assert!(!throttle.check(Duration::from_secs(60)));
+    assert!(throttle.check(Duration::ZERO));
+    Ok(())
+}
+
+// The log level and filter are global, so tests that change them or depend on messages below
+// Information being enabled hold this lock
+static LOG_LEVEL_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// Holds LOG_LEVEL_LOCK, and puts the previous log level and filter back before releasing it
+// (even if the test fails)
+pub(crate) struct LogFilterGuard {
+    previous: crate::logger::LogFilter,
+    _lock: std::sync::MutexGuard<'static, ()>
+}
+
+impl Drop for LogFilterGuard {
+    fn drop(&mut self) {
+        crate::logger::set_log_filter(self.previous.clone());
+    }
+}
+
+pub(crate) fn lock_log_filter() -> LogFilterGuard {
+    let lock = LOG_LEVEL_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    LogFilterGuard { previous: crate::logger::get_log_filter(), _lock: lock }
+}
+
+#[test]
+fn log_level_enabled() -> ReturnType {
+    use crate::logger::{ LogFilter, LogLevel, get_log_filter, get_log_level, is_log_enabled, set_log_filter, set_log_level };
+    let _guard = lock_log_filter();
+    // Other tests log at Information and above, so the default level doesn't go higher than Debug
+    set_log_level(LogLevel::Debug);
+    assert_eq!(get_log_level(), LogLevel::Debug);
+    assert!(!is_log_enabled(LogLevel::Verbose, "riri_level_test"));
+    assert!(is_log_enabled(LogLevel::Debug, "riri_level_test"));
+    set_log_filter("riri_level_test=error,riri_level_test::verbose=verbose,debug".parse()?);
+    assert_eq!(get_log_level(), LogLevel::Debug);
+    assert!(!is_log_enabled(LogLevel::Warning, "riri_level_test"));
+    assert!(is_log_enabled(LogLevel::Error, "riri_level_test::inner"));
+    assert!(is_log_enabled(LogLevel::Verbose, "riri_level_test::verbose"));
+    assert!(!is_log_enabled(LogLevel::Verbose, "riri_other_test"));
+    // Changing the default level keeps the module directives
+    set_log_level(LogLevel::Verbose);
+    assert_eq!(get_log_level(), LogLevel::Verbose);
+    assert!(is_log_enabled(LogLevel::Verbose, "riri_other_test"));
+    assert!(!is_log_enabled(LogLevel::Warning, "riri_level_test"));
+    set_log_filter(LogFilter::new(LogLevel::Verbose));
+    assert!(is_log_enabled(LogLevel::Verbose, "riri_level_test"));
+    let filter: LogFilter = "riri_level_test=error,info".parse()?;
+    set_log_filter(filter.clone());
+    assert_eq!(get_log_filter(), filter);
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Catches panics before they reach the C# host or the game. Unwinding out of an `extern "C"`
+//! function aborts the process without any information, so generated hook functions,
+//! `riri_init_fn`, `riri_mods_loaded_fn` and `riri_mod_loading_fn` run their body in
+//! [`catch_panic`]. The panic's message, location and backtrace are logged, then the
+//! [`PanicPolicy`] decides what happens next.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{ Cell, RefCell };
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+use std::sync::atomic::{ AtomicU32, Ordering };
+
+/// What to do after a panic has been caught and logged.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Call the original function with the hook's arguments and return its result. Functions
+    /// without an original function (such as init functions) return instead.
+    #[default]
+    CallOriginal,
+    /// Return the return type's [`FallbackValue`] (0, null, false etc.) from the hook.
+    ReturnDefault,
+    /// Abort the process after flushing log sinks.
+    Abort
+}
+
+impl TryFrom<u32> for PanicPolicy {
+    type Error = ();
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::CallOriginal),
+            1 => Ok(Self::ReturnDefault),
+            2 => Ok(Self::Abort),
+            _ => Err(())
+        }
+    }
+}
+
+/// The value that a hook returns after a panic when the [`PanicPolicy`] is
+/// [`PanicPolicy::ReturnDefault`] or there's no original function to call. This is implemented
+/// for types where zero is a valid value (numbers, `bool`, raw pointers and `Option`). Hooks that
+/// return another type need to implement this for it.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't have a value to return when a hook panics",
+    note = "implement `riri_mod_tools_rt::panic_guard::FallbackValue` for `{Self}`"
+)]
+pub trait FallbackValue {
+    fn fallback_value() -> Self;
+}
+
+macro_rules! impl_fallback_value {
+    ($value:expr; $($ty:ty),*) => {
+        $(impl FallbackValue for $ty {
+            fn fallback_value() -> Self { $value }
+        })*
+    };
+}
+
+impl_fallback_value!(0; u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_fallback_value!(0.0; f32, f64);
+impl_fallback_value!(false; bool);
+impl_fallback_value!((); ());
+
+impl<T> FallbackValue for *const T {
+    fn fallback_value() -> Self { std::ptr::null() }
+}
+
+impl<T> FallbackValue for *mut T {
+    fn fallback_value() -> Self { std::ptr::null_mut() }
+}
+
+impl<T> FallbackValue for Option<T> {
+    fn fallback_value() -> Self { None }
+}
+
+static PANIC_POLICY: AtomicU32 = AtomicU32::new(PanicPolicy::CallOriginal as u32);
+
+pub fn get_panic_policy() -> PanicPolicy {
+    PANIC_POLICY.load(Ordering::Relaxed).try_into().unwrap()
+}
+
+pub fn set_panic_policy(policy: PanicPolicy) {
+    PANIC_POLICY.store(policy as u32, Ordering::Relaxed);
+}
+
+/// Set the panic policy from C#, returning false if the policy is invalid.
+#[no_mangle]
+pub extern "C" fn set_ffi_panic_policy(policy: u32) -> bool {
+    match policy.try_into() {
+        Ok(v) => {
+            set_panic_policy(v);
+            true
+        },
+        Err(_) => false
+    }
+}
+
+struct PanicDetails {
+    location: String,
+    backtrace: Backtrace
+}
+
+thread_local! {
+    static GUARD_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static LAST_PANIC: RefCell<Option<PanicDetails>> = const { RefCell::new(None) };
+}
+
+// The location and backtrace aren't available from catch_unwind, so record them in a panic
+// hook while a guarded function is running.
+fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if GUARD_DEPTH.with(|d| d.get()) > 0 {
+                let details = PanicDetails {
+                    location: info.location().map_or_else(|| "unknown".to_owned(), |l| l.to_string()),
+                    backtrace: Backtrace::force_capture()
+                };
+                LAST_PANIC.with(|p| *p.borrow_mut() = Some(details));
+            }
+            previous(info);
+        }));
+    });
+}
+
+fn get_panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&'static str>() {
+        Some(s) => s,
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.as_str(),
+            None => "Box<dyn Any>"
+        }
+    }
+}
+
+/// Run `f`, catching any panic that happens inside of it. If it panics, the panic is logged
+/// as coming from `name` and the current [`PanicPolicy`] is returned. The process is aborted
+/// instead if the policy is [`PanicPolicy::Abort`].
+///
+/// `f` is treated as unwind safe since the alternative is aborting the process.
+pub fn catch_panic<R, F: FnOnce() -> R>(name: &str, f: F) -> Result<R, PanicPolicy> {
+    install_panic_hook();
+    GUARD_DEPTH.with(|d| d.set(d.get() + 1));
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    GUARD_DEPTH.with(|d| d.set(d.get() - 1));
+    let payload = match result {
+        Ok(v) => return Ok(v),
+        Err(e) => e
+    };
+    let policy = get_panic_policy();
+    let details = LAST_PANIC.with(|p| p.borrow_mut().take());
+    let location = details.as_ref().map_or("unknown", |d| d.location.as_str());
+    crate::logln!(Error, function = name, location = location, policy = format!("{:?}", policy);
+        "panicked: {}", get_panic_message(payload.as_ref()));
+    if let Some(d) = details {
+        crate::logln!(Error, "{}", d.backtrace);
+    }
+    if policy == PanicPolicy::Abort {
+        crate::logger::flush_log_deduplication();
+        crate::log_sink::flush_log_sinks();
+        std::process::abort();
+    }
+    Err(policy)
+}
@@ -0,0 +1,347 @@
+//! Parsing and matching for byte signatures, written using the same syntax as
+//! Reloaded.Memory.SigScan (e.g `48 8B 05 ?? ?? ?? ?? 48 85 C0`). This is used when the
+//! runtime needs to scan memory without going through the C# host.
+//!
+//! Scanning uses AVX2 or SSE2 when they're available. Two of the pattern's fixed bytes (the ones
+//! least likely to appear in x86-64 code) are compared against 32 or 16 positions at once, and
+//! only positions where both match are checked against the full pattern.
+
+use std::error::Error;
+use std::fmt::{ Display, Formatter };
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    Empty,
+    InvalidByte(String),
+    LeadingWildcard
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Pattern is empty"),
+            Self::InvalidByte(b) => write!(f, "'{}' is not a valid byte or wildcard", b),
+            Self::LeadingWildcard => write!(f, "Pattern must start with a byte")
+        }
+    }
+}
+
+impl Error for PatternError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchError {
+    NotFound,
+    /// The pattern matched more than once. Contains the offset of every match.
+    Ambiguous(Vec<usize>)
+}
+
+impl Display for MatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "Pattern wasn't found"),
+            Self::Ambiguous(v) => {
+                write!(f, "Pattern matched {} times:", v.len())?;
+                for o in v { write!(f, " 0x{:x}", o)?; }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for MatchError {}
+
+/// The instruction set used to search for candidate matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanBackend {
+    Scalar,
+    Sse2,
+    Avx2
+}
+
+impl ScanBackend {
+    /// Get the fastest backend that the CPU supports.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") { return Self::Avx2; }
+            if is_x86_feature_detected!("sse2") { return Self::Sse2; }
+        }
+        Self::Scalar
+    }
+
+    /// Check if this backend can run on the current CPU.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false
+        }
+    }
+}
+
+// Rough ranking of how often each byte appears in x86-64 code, where higher is more common.
+// Bytes that aren't listed are treated as rare.
+const fn get_byte_frequency_table() -> [u8; 256] {
+    let common: [u8; 40] = [
+        0x00, 0xFF, 0xCC, 0x48, 0x8B, 0x89, 0x0F, 0x24, 0x4C, 0x44, 0xE8, 0x83, 0x85, 0xC0,
+        0x8D, 0x49, 0x4D, 0x41, 0x45, 0x01, 0x74, 0x75, 0x33, 0x10, 0x08, 0x20, 0x28, 0x30,
+        0x40, 0x50, 0xC3, 0x90, 0x5C, 0x54, 0x6C, 0x8C, 0xEB, 0xE9, 0x84, 0x02
+    ];
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < common.len() {
+        table[common[i] as usize] = (common.len() - i) as u8;
+        i += 1;
+    }
+    table
+}
+static BYTE_FREQUENCY: [u8; 256] = get_byte_frequency_table();
+
+/// A signature made up of bytes and wildcards. Wildcards can be written as `??` or `?`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<bool>,
+    // Offsets of the two rarest fixed bytes, used to filter candidates. These are the same if
+    // there's only one fixed byte.
+    anchors: (usize, usize)
+}
+
+impl Pattern {
+    pub fn new(text: &str) -> Result<Self, PatternError> {
+        let mut bytes = vec![];
+        let mut mask = vec![];
+        for token in text.trim_end_matches('\0').split_whitespace() {
+            match token {
+                "?" | "??" => {
+                    bytes.push(0);
+                    mask.push(false);
+                },
+                _ => {
+                    let byte = match token.len() {
+                        2 => u8::from_str_radix(token, 16).ok(),
+                        _ => None
+                    }.ok_or_else(|| PatternError::InvalidByte(token.to_owned()))?;
+                    bytes.push(byte);
+                    mask.push(true);
+                }
+            }
+        }
+        Self::from_masked(bytes, mask)
+    }
+
+    /// Create a pattern from its bytes and which of them are fixed. Bytes where the mask is
+    /// false are wildcards.
+    pub fn from_masked(bytes: Vec<u8>, mask: Vec<bool>) -> Result<Self, PatternError> {
+        assert_eq!(bytes.len(), mask.len(), "Pattern bytes and mask should be the same length");
+        match mask.first() {
+            None => Err(PatternError::Empty),
+            Some(false) => Err(PatternError::LeadingWildcard),
+            Some(true) => {
+                let anchors = Self::get_anchors(&bytes, &mask);
+                Ok(Self { bytes, mask, anchors })
+            }
+        }
+    }
+
+    fn get_anchors(bytes: &[u8], mask: &[bool]) -> (usize, usize) {
+        let mut fixed: Vec<usize> = (0..bytes.len()).filter(|i| mask[*i]).collect();
+        fixed.sort_by_key(|i| BYTE_FREQUENCY[bytes[*i] as usize]);
+        let first = fixed[0];
+        // Prefer a second byte with a different value, since the same value at two offsets
+        // filters out less
+        let second = fixed.iter().skip(1).find(|i| bytes[**i] != bytes[first])
+            .or(fixed.get(1)).copied().unwrap_or(first);
+        (first, second)
+    }
+
+    /// The number of bytes that the pattern covers, including wildcards.
+    pub fn len(&self) -> usize { self.bytes.len() }
+
+    pub fn is_empty(&self) -> bool { self.bytes.is_empty() }
+
+    pub fn get_bytes(&self) -> &[u8] { &self.bytes }
+
+    /// For each byte, true if the byte must match or false if it's a wildcard.
+    pub fn get_mask(&self) -> &[bool] { &self.mask }
+
+    /// Check if the pattern matches the start of `data`.
+    pub fn matches_at(&self, data: &[u8]) -> bool {
+        data.len() >= self.len() && self.bytes.iter().zip(self.mask.iter()).zip(data.iter())
+            .all(|((b, m), d)| !*m || *b == *d)
+    }
+
+    /// Find the offset of the first match in `data`.
+    pub fn find(&self, data: &[u8]) -> Option<usize> {
+        self.find_with(data, ScanBackend::detect())
+    }
+
+    pub fn find_with(&self, data: &[u8], backend: ScanBackend) -> Option<usize> {
+        let mut found = None;
+        self.scan(data, backend, &mut |i| { found = Some(i); false });
+        found
+    }
+
+    /// Find the offset of every match in `data`, in ascending order. Matches can overlap.
+    pub fn find_all(&self, data: &[u8]) -> Vec<usize> {
+        self.find_all_with(data, ScanBackend::detect())
+    }
+
+    pub fn find_all_with(&self, data: &[u8], backend: ScanBackend) -> Vec<usize> {
+        let mut found = vec![];
+        self.scan(data, backend, &mut |i| { found.push(i); true });
+        found
+    }
+
+    /// Find the offset of the only match in `data`, returning every offset if there's more than
+    /// one.
+    pub fn find_unique(&self, data: &[u8]) -> Result<usize, MatchError> {
+        let found = self.find_all(data);
+        match found.len() {
+            0 => Err(MatchError::NotFound),
+            1 => Ok(found[0]),
+            _ => Err(MatchError::Ambiguous(found))
+        }
+    }
+
+    /// Check if the pattern matches exactly once in `data`. This stops scanning at the second
+    /// match.
+    pub fn is_unique(&self, data: &[u8]) -> bool {
+        let mut count = 0;
+        self.scan(data, ScanBackend::detect(), &mut |_| { count += 1; count < 2 });
+        count == 1
+    }
+
+    // Call on_match for each match until it returns false. Unsupported backends fall back to the
+    // scalar scanner.
+    fn scan(&self, data: &[u8], backend: ScanBackend, on_match: &mut dyn FnMut(usize) -> bool) {
+        if data.len() < self.len() { return; }
+        #[cfg(target_arch = "x86_64")]
+        {
+            if backend == ScanBackend::Avx2 && backend.is_supported() {
+                // SAFETY: AVX2 support was checked
+                return unsafe { simd::scan_avx2(self, data, on_match) };
+            }
+            if backend != ScanBackend::Scalar && ScanBackend::Sse2.is_supported() {
+                // SAFETY: SSE2 support was checked
+                return unsafe { simd::scan_sse2(self, data, on_match) };
+            }
+        }
+        self.scan_scalar(data, 0, on_match);
+    }
+
+    // Also used for the positions at the end of the data that don't fill a whole vector
+    fn scan_scalar(&self, data: &[u8], start: usize, on_match: &mut dyn FnMut(usize) -> bool) {
+        let (a0, a1) = self.anchors;
+        let (b0, b1) = (self.bytes[a0], self.bytes[a1]);
+        for i in start..=data.len() - self.len() {
+            if data[i + a0] == b0 && data[i + a1] == b1 && self.matches_at(&data[i..]) && !on_match(i) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+    use super::Pattern;
+
+    // Check each set bit in a candidate mask, returning false if scanning should stop
+    #[inline(always)]
+    fn check_candidates(pattern: &Pattern, data: &[u8], base: usize, mut bits: u32,
+        on_match: &mut dyn FnMut(usize) -> bool) -> bool {
+        while bits != 0 {
+            let i = base + bits.trailing_zeros() as usize;
+            if pattern.matches_at(&data[i..]) && !on_match(i) {
+                return false;
+            }
+            bits &= bits - 1;
+        }
+        true
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn scan_avx2(pattern: &Pattern, data: &[u8], on_match: &mut dyn FnMut(usize) -> bool) {
+        const WIDTH: usize = 32;
+        let (a0, a1) = pattern.anchors;
+        let b0 = _mm256_set1_epi8(pattern.bytes[a0] as i8);
+        let b1 = _mm256_set1_epi8(pattern.bytes[a1] as i8);
+        // Number of positions where the whole pattern fits
+        let positions = data.len() - pattern.len() + 1;
+        let ptr = data.as_ptr();
+        let mut i = 0;
+        while i + WIDTH <= positions {
+            // SAFETY: i + anchor + WIDTH <= positions + pattern.len() - 1 = data.len()
+            let v0 = _mm256_loadu_si256(ptr.add(i + a0) as *const __m256i);
+            let v1 = _mm256_loadu_si256(ptr.add(i + a1) as *const __m256i);
+            let eq = _mm256_and_si256(_mm256_cmpeq_epi8(v0, b0), _mm256_cmpeq_epi8(v1, b1));
+            let bits = _mm256_movemask_epi8(eq) as u32;
+            if !check_candidates(pattern, data, i, bits, on_match) { return; }
+            i += WIDTH;
+        }
+        if i < positions { pattern.scan_scalar(data, i, on_match); }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn scan_sse2(pattern: &Pattern, data: &[u8], on_match: &mut dyn FnMut(usize) -> bool) {
+        const WIDTH: usize = 16;
+        let (a0, a1) = pattern.anchors;
+        let b0 = _mm_set1_epi8(pattern.bytes[a0] as i8);
+        let b1 = _mm_set1_epi8(pattern.bytes[a1] as i8);
+        let positions = data.len() - pattern.len() + 1;
+        let ptr = data.as_ptr();
+        let mut i = 0;
+        while i + WIDTH <= positions {
+            // SAFETY: i + anchor + WIDTH <= positions + pattern.len() - 1 = data.len()
+            let v0 = _mm_loadu_si128(ptr.add(i + a0) as *const __m128i);
+            let v1 = _mm_loadu_si128(ptr.add(i + a1) as *const __m128i);
+            let eq = _mm_and_si128(_mm_cmpeq_epi8(v0, b0), _mm_cmpeq_epi8(v1, b1));
+            let bits = _mm_movemask_epi8(eq) as u32;
+            if !check_candidates(pattern, data, i, bits, on_match) { return; }
+            i += WIDTH;
+        }
+        if i < positions { pattern.scan_scalar(data, i, on_match); }
+    }
+}
+
+impl TryFrom<&str> for Pattern {
+    type Error = PatternError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (b, m)) in self.bytes.iter().zip(self.mask.iter()).enumerate() {
+            if i != 0 { write!(f, " ")?; }
+            match m {
+                true => write!(f, "{:02X}", b)?,
+                false => write!(f, "??")?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Find a pattern within `len` bytes from `start`, returning null if the pattern is invalid or
+/// can't be found.
+///
+/// # Safety
+///
+/// `start` must be valid for reads of `len` bytes.
+pub unsafe fn find_pattern_raw(text: &str, start: *const u8, len: usize) -> *const u8 {
+    let pattern = match Pattern::new(text) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null()
+    };
+    match pattern.find(std::slice::from_raw_parts(start, len)) {
+        Some(i) => start.add(i),
+        None => std::ptr::null()
+    }
+}
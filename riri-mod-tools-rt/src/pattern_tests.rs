@@ -0,0 +1,93 @@
+#![cfg(test)]
+use crate::pattern::{ MatchError, Pattern, PatternError, ScanBackend };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+const BACKENDS: [ScanBackend; 3] = [ScanBackend::Scalar, ScanBackend::Sse2, ScanBackend::Avx2];
+
+// Deterministic pseudo-random bytes so that scans have plenty of near misses
+pub(crate) fn get_test_data(len: usize) -> Vec<u8> {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    (0..len).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        [0x48, 0x8B, 0x05, 0x00, 0xFF, (state >> 32) as u8][(state % 6) as usize]
+    }).collect()
+}
+
+fn find_all_naive(pattern: &Pattern, data: &[u8]) -> Vec<usize> {
+    (0..data.len()).filter(|i| pattern.matches_at(&data[*i..])).collect()
+}
+
+#[test]
+fn pattern_parse() -> ReturnType {
+    let pattern = Pattern::new("48 8B 05 ?? ? 48 85 c0")?;
+    assert_eq!(pattern.len(), 8);
+    assert_eq!(pattern.get_bytes(), &[0x48, 0x8B, 0x05, 0, 0, 0x48, 0x85, 0xC0]);
+    assert_eq!(pattern.get_mask(), &[true, true, true, false, false, true, true, true]);
+    assert_eq!(pattern.to_string(), "48 8B 05 ?? ?? 48 85 C0");
+    assert_eq!(Pattern::new(""), Err(PatternError::Empty));
+    assert_eq!(Pattern::new("?? 48"), Err(PatternError::LeadingWildcard));
+    assert_eq!(Pattern::new("48 8G"), Err(PatternError::InvalidByte("8G".to_owned())));
+    assert_eq!(Pattern::new("48 123"), Err(PatternError::InvalidByte("123".to_owned())));
+    Ok(())
+}
+
+#[test]
+fn pattern_find_first() -> ReturnType {
+    let pattern = Pattern::new("E8 ?? ?? ?? ?? 48 8B C8")?;
+    let mut data = vec![0xCCu8; 100];
+    data[70..78].copy_from_slice(&[0xE8, 1, 2, 3, 4, 0x48, 0x8B, 0xC8]);
+    data[40..48].copy_from_slice(&[0xE8, 5, 6, 7, 8, 0x48, 0x8B, 0xC8]);
+    for backend in BACKENDS {
+        assert_eq!(pattern.find_with(&data, backend), Some(40));
+        assert_eq!(pattern.find_with(&data[41..], backend), Some(29));
+        assert_eq!(pattern.find_with(&data[..77], backend), Some(40));
+        assert_eq!(pattern.find_with(&data[41..77], backend), None);
+        assert_eq!(pattern.find_with(&data[..4], backend), None);
+    }
+    Ok(())
+}
+
+#[test]
+fn pattern_backends_match_naive() -> ReturnType {
+    let data = get_test_data(4099);
+    for text in ["48", "48 8B", "48 ?? 05", "00 FF ?? ?? 48", "05 05", "8B ?? ?? ?? ?? ?? ?? ?? ?? ?? ?? 00"] {
+        let pattern = Pattern::new(text)?;
+        let expected = find_all_naive(&pattern, &data);
+        assert!(!expected.is_empty(), "{} should match the test data", text);
+        // Check every alignment of the tail that doesn't fill a whole vector
+        for end in data.len() - 33..=data.len() {
+            let expected: Vec<usize> = expected.iter().copied()
+                .filter(|i| *i + pattern.len() <= end).collect();
+            for backend in BACKENDS {
+                assert_eq!(pattern.find_all_with(&data[..end], backend), expected,
+                    "{} with {:?} on {} bytes", text, backend, end);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn pattern_find_unique() -> ReturnType {
+    let data = [0x48, 0x8B, 0x05, 0x48, 0x8B, 0x0D, 0x48, 0x8B, 0x05];
+    assert_eq!(Pattern::new("48 8B 0D")?.find_unique(&data), Ok(3));
+    assert!(Pattern::new("48 8B 0D")?.is_unique(&data));
+    assert_eq!(Pattern::new("48 8B 05")?.find_unique(&data), Err(MatchError::Ambiguous(vec![0, 6])));
+    assert!(!Pattern::new("48 8B 05")?.is_unique(&data));
+    assert_eq!(Pattern::new("48 8B 15")?.find_unique(&data), Err(MatchError::NotFound));
+    assert!(!Pattern::new("48 8B 15")?.is_unique(&data));
+    assert_eq!(MatchError::Ambiguous(vec![0, 6]).to_string(), "Pattern matched 2 times: 0x0 0x6");
+    Ok(())
+}
+
+#[test]
+fn pattern_find_raw() -> ReturnType {
+    let data = get_test_data(256);
+    let start = data.as_ptr();
+    let expected = Pattern::new("48 8B")?.find(&data).map(|i| unsafe { start.add(i) });
+    assert_eq!(Some(unsafe { crate::pattern::find_pattern_raw("48 8B", start, data.len()) }), expected);
+    assert!(unsafe { crate::pattern::find_pattern_raw("?? 8B", start, data.len()) }.is_null());
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Builds small PE32+ executables for testing code that reads game executables offline, such as
+//! [`crate::pe_image::PeImage`].
+//!
+//! Sections are placed one after another, starting at 0x1000 in memory and 0x200 in the file.
+//! Each section's size is rounded up to a multiple of 0x200 bytes, and every section starts on
+//! its own 0x1000 byte page.
+//!
+//! ```
+//! use riri_mod_tools_rt::{ pe_builder::PeBuilder, pe_image::PeImage };
+//! let file = PeBuilder::new()
+//!     .text(&[0x48, 0x89, 0x5c, 0x24, 0x08])
+//!     .data(b"GlobalWork")
+//!     .build();
+//! let image = PeImage::from_bytes(&file).unwrap();
+//! assert_eq!(&image.get_image()[0x2000..0x200a], b"GlobalWork");
+//! ```
+
+pub const DEFAULT_IMAGE_BASE: u64 = 0x140000000;
+pub const SECTION_ALIGNMENT: u32 = 0x1000;
+pub const FILE_ALIGNMENT: u32 = 0x200;
+
+pub const CODE_CHARACTERISTICS: u32 = 0x60000020;
+pub const DATA_CHARACTERISTICS: u32 = 0xc0000040;
+
+pub const EXCEPTION_DIRECTORY: usize = 3;
+pub const RELOCATION_DIRECTORY: usize = 5;
+
+const PE_HEADER: usize = 0x40;
+const OPTIONAL_HEADER: usize = PE_HEADER + 0x18;
+const SECTION_HEADERS: usize = OPTIONAL_HEADER + 0xf0;
+const SECTION_HEADER_SIZE: usize = 40;
+const MAX_SECTIONS: usize = (FILE_ALIGNMENT as usize - SECTION_HEADERS) / SECTION_HEADER_SIZE;
+
+#[derive(Debug, Clone)]
+struct PeBuilderSection {
+    name: [u8; 8],
+    data: Vec<u8>,
+    characteristics: u32
+}
+
+/// Builds a PE32+ executable. See the module documentation for the layout.
+#[derive(Debug, Clone)]
+pub struct PeBuilder {
+    image_base: u64,
+    sections: Vec<PeBuilderSection>,
+    directories: [(u32, u32); 16]
+}
+
+impl Default for PeBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+impl PeBuilder {
+    pub fn new() -> Self {
+        Self { image_base: DEFAULT_IMAGE_BASE, sections: vec![], directories: [(0, 0); 16] }
+    }
+
+    pub fn image_base(mut self, base: u64) -> Self {
+        self.image_base = base;
+        self
+    }
+
+    /// Add a section. Names longer than 8 bytes are cut off.
+    pub fn section(mut self, name: &str, data: &[u8], characteristics: u32) -> Self {
+        assert!(self.sections.len() < MAX_SECTIONS, "PeBuilder: Can't add more than {} sections", MAX_SECTIONS);
+        let mut section_name = [0; 8];
+        let len = name.len().min(8);
+        section_name[..len].copy_from_slice(&name.as_bytes()[..len]);
+        self.sections.push(PeBuilderSection { name: section_name, data: data.to_vec(), characteristics });
+        self
+    }
+
+    /// Add an executable `.text` section.
+    pub fn text(self, data: &[u8]) -> Self {
+        self.section(".text", data, CODE_CHARACTERISTICS)
+    }
+
+    /// Add a writable `.data` section.
+    pub fn data(self, data: &[u8]) -> Self {
+        self.section(".data", data, DATA_CHARACTERISTICS)
+    }
+
+    /// Set the address and size of a data directory, such as [`RELOCATION_DIRECTORY`].
+    pub fn directory(mut self, index: usize, address: u32, size: u32) -> Self {
+        self.directories[index] = (address, size);
+        self
+    }
+
+    // The virtual address, file offset and size of each section
+    fn get_layout(&self) -> Vec<(u32, u32, u32)> {
+        let mut address = SECTION_ALIGNMENT;
+        let mut offset = FILE_ALIGNMENT;
+        self.sections.iter().map(|s| {
+            let size = (s.data.len().max(1) as u32).next_multiple_of(FILE_ALIGNMENT);
+            let layout = (address, offset, size);
+            address += size.next_multiple_of(SECTION_ALIGNMENT);
+            offset += size;
+            layout
+        }).collect()
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let layout = self.get_layout();
+        let (image_size, file_size) = layout.last().map_or((SECTION_ALIGNMENT, FILE_ALIGNMENT),
+            |(a, o, s)| (a + s.next_multiple_of(SECTION_ALIGNMENT), o + s));
+        let mut file = vec![0u8; file_size as usize];
+        let mut write = |offset: usize, bytes: &[u8]| file[offset..offset + bytes.len()].copy_from_slice(bytes);
+        write(0, b"MZ");
+        write(0x3c, &(PE_HEADER as u32).to_le_bytes());
+        write(PE_HEADER, b"PE\0\0");
+        write(PE_HEADER + 4, &0x8664u16.to_le_bytes());
+        write(PE_HEADER + 6, &(self.sections.len() as u16).to_le_bytes());
+        write(PE_HEADER + 0x14, &0xf0u16.to_le_bytes());
+        write(OPTIONAL_HEADER, &0x20bu16.to_le_bytes());
+        write(OPTIONAL_HEADER + 0x18, &self.image_base.to_le_bytes());
+        write(OPTIONAL_HEADER + 0x20, &SECTION_ALIGNMENT.to_le_bytes());
+        write(OPTIONAL_HEADER + 0x24, &FILE_ALIGNMENT.to_le_bytes());
+        write(OPTIONAL_HEADER + 0x38, &image_size.to_le_bytes());
+        write(OPTIONAL_HEADER + 0x3c, &FILE_ALIGNMENT.to_le_bytes());
+        write(OPTIONAL_HEADER + 0x6c, &(self.directories.len() as u32).to_le_bytes());
+        for (i, (address, size)) in self.directories.iter().enumerate() {
+            write(OPTIONAL_HEADER + 0x70 + i * 8, &address.to_le_bytes());
+            write(OPTIONAL_HEADER + 0x74 + i * 8, &size.to_le_bytes());
+        }
+        for (i, (section, (address, offset, size))) in self.sections.iter().zip(layout).enumerate() {
+            let header = SECTION_HEADERS + i * SECTION_HEADER_SIZE;
+            write(header, &section.name);
+            write(header + 8, &size.to_le_bytes());
+            write(header + 12, &address.to_le_bytes());
+            write(header + 16, &size.to_le_bytes());
+            write(header + 20, &offset.to_le_bytes());
+            write(header + 36, &section.characteristics.to_le_bytes());
+            write(offset as usize, &section.data);
+        }
+        file
+    }
+}
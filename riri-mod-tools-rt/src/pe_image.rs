@@ -0,0 +1,440 @@
+//! Maps a PE executable from disk into memory the same way that the Windows loader lays it out,
+//! so that signatures and resolvers can be checked against a game version without launching it.
+//! This works on any platform, since the file is only read and never executed.
+//!
+//! Only what's needed for scanning is done: the headers and each section are copied to their
+//! virtual address. Relocations and imports are left as they are in the file, so absolute
+//! addresses are relative to the image base from the optional header.
+//!
+//! Offsets used by [`PeImage`] are relative to the start of the image, the same as the offsets
+//! that the signature scanner returns in game.
+
+use crate::instruction_decoder::{ self, Instruction, MAX_INSTRUCTION_LENGTH, MAX_RIP_RELATIVE_SEARCH };
+use crate::pattern::Pattern;
+use crate::sigscan_batch::BatchScanner;
+use crate::thunk::{ self, ThunkMemory, ThunkResolution };
+use std::error::Error;
+use std::fmt::{ Display, Formatter };
+use std::ops::Range;
+use std::path::Path;
+use twox_hash::XxHash3_64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeImageError {
+    Io(String),
+    /// The file doesn't start with a DOS header or doesn't point to an NT header.
+    NotPe,
+    /// A header or section points past the end of the file.
+    Truncated
+}
+
+impl Display for PeImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::NotPe => write!(f, "File is not a PE executable"),
+            Self::Truncated => write!(f, "PE executable is truncated")
+        }
+    }
+}
+
+impl Error for PeImageError {}
+
+impl From<std::io::Error> for PeImageError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The resolver pointed outside of the image.
+    OutOfBounds,
+    /// The resolver is a function defined by the mod, which can't be run outside of the game.
+    UserDefined(String)
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBounds => write!(f, "Resolved address is outside of the executable"),
+            Self::UserDefined(n) => write!(f, "{} is defined by the mod and can't be run offline", n)
+        }
+    }
+}
+
+impl Error for ResolveError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeSection {
+    name: String,
+    virtual_address: usize,
+    size: usize,
+    characteristics: u32
+}
+
+impl PeSection {
+    const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+
+    pub fn get_name(&self) -> &str { &self.name }
+    /// Offset of the section from the start of the image.
+    pub fn get_virtual_address(&self) -> usize { self.virtual_address }
+    /// Size of the section's data in the file, which is also what gets scanned in game.
+    pub fn get_size(&self) -> usize { self.size }
+    pub fn get_characteristics(&self) -> u32 { self.characteristics }
+    pub fn is_executable(&self) -> bool { self.characteristics & Self::IMAGE_SCN_MEM_EXECUTE != 0 }
+}
+
+/// The sections of a mapped executable, which don't have to be next to each other in memory. The
+/// space between sections is never read, since it may not be mapped in game. Offsets are relative
+/// to the address that the image starts at.
+#[derive(Debug, Clone)]
+pub struct ImageSections<'a> {
+    base: usize,
+    // Sorted by address
+    sections: Vec<&'a [u8]>
+}
+
+impl<'a> ImageSections<'a> {
+    /// Create a view of the sections of an image that starts at `base`.
+    pub fn new(base: usize, mut sections: Vec<&'a [u8]>) -> Self {
+        sections.sort_by_key(|s| s.as_ptr() as usize);
+        Self { base, sections }
+    }
+
+    /// Create a view of `regions` (offsets into the image) of an image that's mapped as one
+    /// slice.
+    pub fn from_image(image: &'a [u8], regions: &[Range<usize>]) -> Self {
+        Self::new(image.as_ptr() as usize, regions.iter().map(|r| &image[r.clone()]).collect())
+    }
+
+    pub fn get_base(&self) -> usize { self.base }
+    pub fn get_sections(&self) -> &[&'a [u8]] { &self.sections }
+
+    /// The offsets covered by a section.
+    pub fn get_range(&self, section: &[u8]) -> Range<usize> {
+        let start = section.as_ptr() as usize - self.base;
+        start..start + section.len()
+    }
+
+    /// The offsets covered by every section.
+    pub fn get_ranges(&self) -> Vec<Range<usize>> {
+        self.sections.iter().map(|s| self.get_range(s)).collect()
+    }
+
+    /// The offsets covered by the section that contains `offset`.
+    pub fn get_section(&self, offset: usize) -> Option<Range<usize>> {
+        self.sections.iter().map(|s| self.get_range(s)).find(|r| r.contains(&offset))
+    }
+
+    /// The bytes from `offset` until the end of its section.
+    pub fn get_bytes(&self, offset: usize) -> Option<&'a [u8]> {
+        self.sections.iter().find_map(|s| {
+            let range = self.get_range(s);
+            range.contains(&offset).then(|| &s[offset - range.start..])
+        })
+    }
+
+    /// The bytes in `range`, if they're all inside of one section.
+    pub fn get(&self, range: Range<usize>) -> Option<&'a [u8]> {
+        self.get_bytes(range.start).and_then(|b| b.get(..range.end.checked_sub(range.start)?))
+    }
+}
+
+/// A PE executable that's been mapped into memory.
+#[derive(Debug, Clone)]
+pub struct PeImage {
+    image: Vec<u8>,
+    image_base: u64,
+    sections: Vec<PeSection>,
+    // Bytes that the loader patches when the image isn't loaded at its preferred address,
+    // sorted by offset
+    relocations: Vec<Range<usize>>,
+    // Functions from the exception directory, sorted by offset
+    functions: Vec<Range<usize>>,
+    hash: u64
+}
+
+// Reads little endian values from the file, failing if they're past the end of it
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], PeImageError> {
+    offset.checked_add(N).and_then(|end| data.get(offset..end))
+        .map(|v| v.try_into().unwrap()).ok_or(PeImageError::Truncated)
+}
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, PeImageError> { Ok(u16::from_le_bytes(read_bytes(data, offset)?)) }
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, PeImageError> { Ok(u32::from_le_bytes(read_bytes(data, offset)?)) }
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, PeImageError> { Ok(u64::from_le_bytes(read_bytes(data, offset)?)) }
+
+impl PeImage {
+    const PE32_MAGIC: u16 = 0x10b;
+    const PE32_PLUS_MAGIC: u16 = 0x20b;
+    const SECTION_HEADER_SIZE: usize = 40;
+    const EXCEPTION_DIRECTORY: usize = 3;
+    const BASE_RELOCATION_DIRECTORY: usize = 5;
+    const RUNTIME_FUNCTION_SIZE: usize = 12;
+    const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+    const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+    // Returns the offsets of the NT header and the data directories, and the image base. The
+    // headers are at the start of both the file and the mapped image.
+    fn get_headers(data: &[u8]) -> Result<(usize, usize, u64), PeImageError> {
+        if data.get(..2) != Some(b"MZ") { return Err(PeImageError::NotPe); }
+        let nt_header = read_u32(data, 0x3c)? as usize;
+        if read_bytes::<4>(data, nt_header)? != *b"PE\0\0" { return Err(PeImageError::NotPe); }
+        let optional_header = nt_header + 24;
+        // Data directories start after the image base, which is 4 bytes larger in PE32+
+        match read_u16(data, optional_header)? {
+            Self::PE32_PLUS_MAGIC => Ok((nt_header, optional_header + 108, read_u64(data, optional_header + 24)?)),
+            Self::PE32_MAGIC => Ok((nt_header, optional_header + 92, read_u32(data, optional_header + 28)? as u64)),
+            _ => Err(PeImageError::NotPe)
+        }
+    }
+
+    // The address and size of a data directory, if the image has it
+    fn get_data_directory(data: &[u8], data_directories: usize, index: usize) -> Result<Option<(usize, usize)>, PeImageError> {
+        if read_u32(data, data_directories)? as usize <= index {
+            return Ok(None);
+        }
+        let directory = data_directories + 4 + index * 8;
+        Ok(Some((read_u32(data, directory)? as usize, read_u32(data, directory + 4)? as usize)))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PeImageError> {
+        let (nt_header, data_directories, image_base) = Self::get_headers(data)?;
+        let section_count = read_u16(data, nt_header + 6)? as usize;
+        let optional_header_size = read_u16(data, nt_header + 20)? as usize;
+        let optional_header = nt_header + 24;
+        let image_size = read_u32(data, optional_header + 56)? as usize;
+        let header_size = (read_u32(data, optional_header + 60)? as usize).min(data.len()).min(image_size);
+        let mut image = vec![0; image_size];
+        image[..header_size].copy_from_slice(&data[..header_size]);
+        let mut sections = Vec::with_capacity(section_count);
+        for i in 0..section_count {
+            let header = optional_header + optional_header_size + i * Self::SECTION_HEADER_SIZE;
+            let name = read_bytes::<8>(data, header)?;
+            let virtual_address = read_u32(data, header + 12)? as usize;
+            let raw_size = read_u32(data, header + 16)? as usize;
+            let raw_offset = read_u32(data, header + 20)? as usize;
+            let raw = raw_offset.checked_add(raw_size).and_then(|end| data.get(raw_offset..end))
+                .ok_or(PeImageError::Truncated)?;
+            // Sections can't extend past the end of the image
+            let size = raw_size.min(image_size.saturating_sub(virtual_address));
+            if size > 0 {
+                image[virtual_address..virtual_address + size].copy_from_slice(&raw[..size]);
+            }
+            sections.push(PeSection {
+                name: String::from_utf8_lossy(&name).trim_end_matches('\0').to_owned(),
+                virtual_address,
+                size,
+                characteristics: read_u32(data, header + 36)?
+            });
+        }
+        let relocations = match Self::get_data_directory(data, data_directories, Self::BASE_RELOCATION_DIRECTORY)? {
+            Some((start, size)) => start.checked_add(size).and_then(|end| image.get(start..end))
+                .map_or_else(Vec::new, Self::get_relocations_from_table),
+            None => vec![]
+        };
+        let functions = match Self::get_data_directory(data, data_directories, Self::EXCEPTION_DIRECTORY)? {
+            Some((start, size)) => Self::get_functions_from_image(&image, start, size),
+            None => vec![]
+        };
+        Ok(Self { image, image_base, sections, relocations, functions, hash: XxHash3_64::oneshot(data) })
+    }
+
+    // The base relocation table is made of blocks for each 4 KB page, each with a list of
+    // offsets into the page and the type of value to patch there
+    fn get_relocations_from_table(table: &[u8]) -> Vec<Range<usize>> {
+        let mut relocations = vec![];
+        let mut block = 0;
+        while let (Ok(page), Ok(block_size)) = (read_u32(table, block), read_u32(table, block + 4)) {
+            let block_size = block_size as usize;
+            if block_size < 8 { break; }
+            for entry in (block + 8..block + block_size).step_by(2) {
+                let entry = match read_u16(table, entry) {
+                    Ok(v) => v,
+                    Err(_) => break
+                };
+                let offset = page as usize + (entry & 0xfff) as usize;
+                match entry >> 12 {
+                    Self::IMAGE_REL_BASED_HIGHLOW => relocations.push(offset..offset + 4),
+                    Self::IMAGE_REL_BASED_DIR64 => relocations.push(offset..offset + 8),
+                    _ => ()
+                }
+            }
+            block += block_size;
+        }
+        relocations.sort_by_key(|r| r.start);
+        relocations
+    }
+
+    // The exception directory has the start and end address of every function that isn't a leaf
+    // function, followed by the address of its unwind info. Functions with chained unwind info
+    // have an entry for each of their parts.
+    fn get_functions_from_image(image: &[u8], start: usize, size: usize) -> Vec<Range<usize>> {
+        let table = match start.checked_add(size).and_then(|end| image.get(start..end)) {
+            Some(v) => v,
+            None => return vec![]
+        };
+        let mut functions: Vec<Range<usize>> = table.chunks_exact(Self::RUNTIME_FUNCTION_SIZE).filter_map(|entry| {
+            let begin = read_u32(entry, 0).ok()? as usize;
+            let end = read_u32(entry, 4).ok()? as usize;
+            (begin < end && end <= image.len()).then_some(begin..end)
+        }).collect();
+        functions.sort_by_key(|f| f.start);
+        functions.dedup_by_key(|f| f.start);
+        functions
+    }
+
+    /// Read the base relocations of an executable that's already been mapped into memory, such
+    /// as the game's executable while it's running. `headers` is the start of the image and the
+    /// relocation table is read from `sections`. Returns an empty list if the image doesn't have
+    /// any relocations or its headers are invalid.
+    pub fn get_mapped_relocations(headers: &[u8], sections: &ImageSections) -> Vec<Range<usize>> {
+        match Self::get_headers(headers).and_then(|(_, d, _)| Self::get_data_directory(headers, d, Self::BASE_RELOCATION_DIRECTORY)) {
+            Ok(Some((start, size))) => start.checked_add(size).and_then(|end| sections.get(start..end))
+                .map_or_else(Vec::new, Self::get_relocations_from_table),
+            _ => vec![]
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PeImageError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// The mapped image, starting from the DOS header.
+    pub fn get_image(&self) -> &[u8] { &self.image }
+    /// The preferred load address from the optional header.
+    pub fn get_image_base(&self) -> u64 { self.image_base }
+    pub fn get_sections(&self) -> &[PeSection] { &self.sections }
+    /// xxh3 hash of the file, the same value that `get_executable_hash` returns in game.
+    pub fn get_hash(&self) -> u64 { self.hash }
+    /// Ranges of bytes that are patched by base relocations, sorted by offset.
+    pub fn get_relocations(&self) -> &[Range<usize>] { &self.relocations }
+    /// Functions listed in the exception directory, sorted by offset. Leaf functions that don't
+    /// touch the stack aren't included.
+    pub fn get_functions(&self) -> &[Range<usize>] { &self.functions }
+
+    /// Find the function from the exception directory that contains `offset`.
+    pub fn get_function(&self, offset: usize) -> Option<Range<usize>> {
+        let i = self.functions.partition_point(|f| f.start <= offset).checked_sub(1)?;
+        self.functions.get(i).filter(|f| f.contains(&offset)).cloned()
+    }
+
+    /// The ranges of the image that are scanned for signatures.
+    pub fn get_section_ranges(&self) -> Vec<Range<usize>> {
+        self.sections.iter()
+            .map(|s| s.virtual_address..s.virtual_address + s.size)
+            .filter(|r| r.end <= self.image.len())
+            .collect()
+    }
+
+    /// The image's sections, which are what gets scanned for signatures.
+    pub fn get_mapped_sections(&self) -> ImageSections<'_> {
+        ImageSections::from_image(&self.image, &self.get_section_ranges())
+    }
+
+    /// Find the offset of every match of each pattern in the image's sections, the same as
+    /// [`BatchScanner::scan_executable_all`] would in game.
+    pub fn scan_all(&self, scanner: &BatchScanner) -> Vec<Vec<usize>> {
+        let sections = self.get_mapped_sections();
+        scanner.scan_regions(sections.get_base(), sections.get_sections(), true)
+    }
+
+    /// Find the offset of every match of a pattern in the image's sections.
+    pub fn find_all(&self, pattern: &Pattern) -> Vec<usize> {
+        let mut scanner = BatchScanner::new();
+        scanner.add(pattern.clone());
+        self.scan_all(&scanner).remove(0)
+    }
+
+    fn in_bounds(&self, ofs: usize) -> Result<usize, ResolveError> {
+        match ofs < self.image.len() {
+            true => Ok(ofs),
+            false => Err(ResolveError::OutOfBounds)
+        }
+    }
+
+    // Dereference a 32-bit displacement relative to the end of itself
+    fn deref_int_relative(&self, ofs: usize) -> Result<usize, ResolveError> {
+        let rel = read_bytes::<4>(&self.image, ofs).map_err(|_| ResolveError::OutOfBounds)?;
+        let target = (ofs + 4).checked_add_signed(i32::from_le_bytes(rel) as isize)
+            .ok_or(ResolveError::OutOfBounds)?;
+        self.in_bounds(target)
+    }
+
+    /// Equivalent to `resolve_thunks`. Follows jump instructions from `ofs` until it reaches an
+    /// instruction that isn't a jump, and reports which kinds of thunk were followed. Indirect
+    /// jumps through pointers that don't point into the image, such as imports that are only
+    /// bound when the game is loaded, aren't followed.
+    pub fn resolve_thunks(&self, ofs: usize) -> Result<ThunkResolution, ResolveError> {
+        thunk::follow_thunks(self, ofs).ok_or(ResolveError::OutOfBounds)
+    }
+
+    /// Equivalent to `get_address_may_thunk`. Follows jump instructions from `ofs` until it
+    /// reaches an instruction that isn't a jump.
+    pub fn get_address_may_thunk(&self, ofs: usize) -> Result<usize, ResolveError> {
+        self.resolve_thunks(ofs).map(|v| v.get_address())
+    }
+
+    // Decode the instruction at ofs along with its bytes
+    fn decode_instruction(&self, ofs: usize) -> Option<(Instruction, &[u8])> {
+        let code = self.image.get(ofs..)?;
+        let code = &code[..code.len().min(MAX_INSTRUCTION_LENGTH)];
+        instruction_decoder::decode(code).ok().map(|v| (v, code))
+    }
+
+    /// Equivalent to `resolve_rip_relative`. Resolves the relative operand at `operand_index`,
+    /// counting from the instruction at `ofs`.
+    pub fn resolve_rip_relative(&self, mut ofs: usize, operand_index: usize) -> Result<usize, ResolveError> {
+        let mut remaining = operand_index;
+        for _ in 0..MAX_RIP_RELATIVE_SEARCH {
+            let (instruction, code) = self.decode_instruction(ofs).ok_or(ResolveError::OutOfBounds)?;
+            if let Some(target) = instruction.get_relative_target(ofs, code) {
+                if remaining == 0 { return self.in_bounds(target); }
+                remaining -= 1;
+            }
+            ofs += instruction.get_length();
+        }
+        Err(ResolveError::OutOfBounds)
+    }
+
+    /// Equivalent to the `get_indirect_address_*` resolvers. `operand` is the position of the
+    /// near pointer within the instruction at `ofs` (1 for short, 2 for short2, 3 for long and 4
+    /// for long4). If the decoded instruction's relative operand is at that position, the target
+    /// is relative to the end of the instruction, otherwise it's relative to the end of the near
+    /// pointer.
+    pub fn get_indirect_address(&self, ofs: usize, operand: usize) -> Result<usize, ResolveError> {
+        let decoded = self.decode_instruction(ofs).and_then(|(v, _)| v.get_relative_operand());
+        match decoded {
+            Some(v) if v.get_offset() == operand => self.resolve_rip_relative(ofs, 0),
+            _ => self.deref_int_relative(ofs + operand)
+        }
+    }
+
+    /// Run the resolver named by a hook's `resolve_type` on the offset that its signature was
+    /// found at. Hooks without a `resolve_type` use `get_address_may_thunk`.
+    pub fn resolve(&self, resolve_type: Option<&str>, ofs: usize) -> Result<usize, ResolveError> {
+        match resolve_type.unwrap_or("get_address_may_thunk") {
+            "get_address" => self.in_bounds(ofs),
+            "get_address_may_thunk" => self.get_address_may_thunk(ofs),
+            "get_indirect_address_short" => self.get_indirect_address(ofs, 1),
+            "get_indirect_address_short2" => self.get_indirect_address(ofs, 2),
+            "get_indirect_address_long" => self.get_indirect_address(ofs, 3),
+            "get_indirect_address_long4" => self.get_indirect_address(ofs, 4),
+            v => Err(ResolveError::UserDefined(v.to_owned()))
+        }
+    }
+}
+
+impl ThunkMemory for PeImage {
+    fn read(&self, address: usize, buf: &mut [u8]) -> bool {
+        match address.checked_add(buf.len()).and_then(|end| self.image.get(address..end)) {
+            Some(v) => { buf.copy_from_slice(v); true },
+            None => false
+        }
+    }
+    fn read_pointer(&self, address: usize) -> Option<usize> {
+        let abs = read_u64(&self.image, address).ok()?;
+        abs.checked_sub(self.image_base).map(|v| v as usize).filter(|v| *v < self.image.len())
+    }
+    fn in_bounds(&self, address: usize) -> bool { address < self.image.len() }
+}
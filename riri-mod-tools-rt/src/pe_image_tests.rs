@@ -0,0 +1,170 @@
+#![cfg(test)]
+use crate::pattern::Pattern;
+use crate::pe_builder::{ self, PeBuilder };
+use crate::pe_image::{ ImageSections, PeImage, PeImageError, ResolveError };
+use crate::thunk::ThunkKind;
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+const IMAGE_BASE: u64 = 0x140000000;
+
+// PE32+ executable with a .text section at 0x1000 and a .data section at 0x2000, each 0x200
+// bytes long
+fn get_test_executable(text: &[u8], data: &[u8]) -> Vec<u8> {
+    PeBuilder::new().image_base(IMAGE_BASE).text(text).data(data).build()
+}
+
+fn get_test_code() -> Vec<u8> {
+    let mut text = vec![0xccu8; 0x200];
+    // 0x1010: jmp 0x1040
+    text[0x10..0x15].copy_from_slice(&[0xe9, 0x2b, 0, 0, 0]);
+    // 0x1040: jmp short 0x1044
+    text[0x40..0x42].copy_from_slice(&[0xeb, 0x02]);
+    // 0x1044: mov [rsp+8], rbx
+    text[0x44..0x49].copy_from_slice(&[0x48, 0x89, 0x5c, 0x24, 0x08]);
+    // 0x1080: mov rax, [0x2010]
+    text[0x80..0x87].copy_from_slice(&[0x48, 0x8b, 0x05, 0x89, 0x0f, 0, 0]);
+    // 0x10c0: jmp [rip+0] with the absolute address of 0x1044 after it
+    text[0xc0..0xc6].copy_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+    text[0xc6..0xce].copy_from_slice(&(IMAGE_BASE + 0x1044).to_le_bytes());
+    // 0x1100: jmp short 0x1100
+    text[0x100..0x102].copy_from_slice(&[0xeb, 0xfe]);
+    // 0x1110: mov rax, [rip+0x7fffffff]
+    text[0x110..0x117].copy_from_slice(&[0x48, 0x8b, 0x05, 0xff, 0xff, 0xff, 0x7f]);
+    // 0x1140: cmp dword [0x2010], 1; call 0x1044
+    text[0x140..0x147].copy_from_slice(&[0x83, 0x3d, 0xc9, 0x0e, 0, 0, 0x01]);
+    text[0x147..0x14c].copy_from_slice(&[0xe8, 0xf8, 0xfe, 0xff, 0xff]);
+    text
+}
+
+#[test]
+fn pe_image_map_sections() -> ReturnType {
+    let file = get_test_executable(&get_test_code(), b"GlobalWork");
+    let image = PeImage::from_bytes(&file)?;
+    assert_eq!(image.get_image_base(), IMAGE_BASE);
+    assert_eq!(image.get_image().len(), 0x3000);
+    assert_eq!(image.get_hash(), twox_hash::XxHash3_64::oneshot(&file));
+    let sections = image.get_sections();
+    assert_eq!(sections.len(), 2);
+    assert_eq!((sections[0].get_name(), sections[0].get_virtual_address(), sections[0].get_size()), (".text", 0x1000, 0x200));
+    assert!(sections[0].is_executable());
+    assert_eq!((sections[1].get_name(), sections[1].get_virtual_address()), (".data", 0x2000));
+    assert!(!sections[1].is_executable());
+    assert_eq!(&image.get_image()[0x1044..0x1049], &[0x48, 0x89, 0x5c, 0x24, 0x08]);
+    assert_eq!(&image.get_image()[0x2000..0x200a], b"GlobalWork");
+    // Headers are mapped, but the gap between them and the first section isn't
+    assert_eq!(&image.get_image()[..2], b"MZ");
+    assert!(image.get_image()[0x200..0x1000].iter().all(|b| *b == 0));
+    Ok(())
+}
+
+#[test]
+fn pe_image_invalid() -> ReturnType {
+    let file = get_test_executable(&get_test_code(), &[]);
+    assert_eq!(PeImage::from_bytes(&file[..0x400]).unwrap_err(), PeImageError::Truncated);
+    assert_eq!(PeImage::from_bytes(&file[..0x20]).unwrap_err(), PeImageError::Truncated);
+    assert_eq!(PeImage::from_bytes(b"\x7fELF").unwrap_err(), PeImageError::NotPe);
+    let mut no_nt_header = file.clone();
+    no_nt_header[0x40] = b'N';
+    assert_eq!(PeImage::from_bytes(&no_nt_header).unwrap_err(), PeImageError::NotPe);
+    Ok(())
+}
+
+#[test]
+fn pe_image_relocations() -> ReturnType {
+    // One block for the page at 0x1000 with a 64-bit and a 32-bit relocation, then padding
+    let mut relocations = vec![];
+    relocations.extend_from_slice(&0x1000u32.to_le_bytes());
+    relocations.extend_from_slice(&16u32.to_le_bytes());
+    for entry in [(10 << 12) | 0xc6, (3 << 12) | 0x20, 0, 0u16] {
+        relocations.extend_from_slice(&entry.to_le_bytes());
+    }
+    let mut data = vec![0; 0x100];
+    data.extend_from_slice(&relocations);
+    let file = PeBuilder::new().image_base(IMAGE_BASE).text(&get_test_code()).data(&data)
+        .directory(pe_builder::RELOCATION_DIRECTORY, 0x2100, 16).build();
+    let image = PeImage::from_bytes(&file)?;
+    assert_eq!(image.get_relocations(), &[0x1020..0x1024, 0x10c6..0x10ce]);
+    // The same table can be read from the image once it's mapped, with the headers and each
+    // section in separate slices
+    let headers = &image.get_image()[..0x1000];
+    let sections = image.get_mapped_sections();
+    assert_eq!(sections.get_ranges(), [0x1000..0x1200, 0x2000..0x2200]);
+    assert_eq!(PeImage::get_mapped_relocations(headers, &sections), image.get_relocations());
+    assert!(PeImage::get_mapped_relocations(&image.get_image()[0x1000..], &sections).is_empty());
+    // The table has to be inside of a section
+    let text = ImageSections::new(sections.get_base(), vec![sections.get_sections()[0]]);
+    assert!(PeImage::get_mapped_relocations(headers, &text).is_empty());
+    // Executables without a relocation directory have no relocations
+    assert!(PeImage::from_bytes(&get_test_executable(&get_test_code(), &[]))?.get_relocations().is_empty());
+    Ok(())
+}
+
+#[test]
+fn pe_image_functions() -> ReturnType {
+    let mut data = vec![0; 0x180];
+    // Exception directory entries aren't always sorted
+    for (begin, end) in [(0x1040u32, 0x1050u32), (0x1010, 0x1015), (0x1080, 0x1087)] {
+        data.extend_from_slice(&begin.to_le_bytes());
+        data.extend_from_slice(&end.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+    }
+    let file = PeBuilder::new().image_base(IMAGE_BASE).text(&get_test_code()).data(&data)
+        .directory(pe_builder::EXCEPTION_DIRECTORY, 0x2180, 36).build();
+    let image = PeImage::from_bytes(&file)?;
+    assert_eq!(image.get_functions(), &[0x1010..0x1015, 0x1040..0x1050, 0x1080..0x1087]);
+    assert_eq!(image.get_function(0x1044), Some(0x1040..0x1050));
+    assert_eq!(image.get_function(0x1010), Some(0x1010..0x1015));
+    assert_eq!(image.get_function(0x1015), None);
+    assert_eq!(image.get_function(0x1000), None);
+    Ok(())
+}
+
+#[test]
+fn pe_image_scan() -> ReturnType {
+    let image = PeImage::from_bytes(&get_test_executable(&get_test_code(), b"\x48\x8b\x05"))?;
+    assert_eq!(image.find_all(&Pattern::new("48 89 5C 24 08")?), vec![0x1044]);
+    // The .data section is scanned too
+    assert_eq!(image.find_all(&Pattern::new("48 8B 05")?), vec![0x1080, 0x1110, 0x2000]);
+    // Headers aren't part of any section
+    assert_eq!(image.find_all(&Pattern::new("4D 5A")?), vec![]);
+    Ok(())
+}
+
+#[test]
+fn pe_image_resolve() -> ReturnType {
+    let image = PeImage::from_bytes(&get_test_executable(&get_test_code(), &[]))?;
+    assert_eq!(image.resolve(Some("get_address"), 0x1010), Ok(0x1010));
+    assert_eq!(image.resolve(None, 0x1010), Ok(0x1044));
+    assert_eq!(image.resolve(Some("get_address_may_thunk"), 0x10c0), Ok(0x1044));
+    assert_eq!(image.resolve_thunks(0x10c0)?.get_thunks(), [ThunkKind::Indirect]);
+    assert_eq!(image.resolve_thunks(0x1010)?.get_thunks(), [ThunkKind::NearJump, ThunkKind::ShortJump]);
+    assert_eq!(image.resolve(None, 0x1044), Ok(0x1044));
+    // Jumps that never reach a function stop after a while
+    assert_eq!(image.resolve(None, 0x1100), Ok(0x1100));
+    assert!(image.resolve_thunks(0x1100)?.is_limited());
+    assert_eq!(image.resolve(Some("get_indirect_address_long"), 0x1080), Ok(0x2010));
+    assert_eq!(image.resolve(Some("get_indirect_address_long"), 0x1110), Err(ResolveError::OutOfBounds));
+    assert_eq!(image.resolve(Some("get_address"), 0x3000), Err(ResolveError::OutOfBounds));
+    // The displacement is relative to the end of the instruction, after the immediate
+    assert_eq!(image.resolve(Some("get_indirect_address_short2"), 0x1140), Ok(0x2010));
+    assert_eq!(image.resolve(Some("get_player_data"), 0x1080), Err(ResolveError::UserDefined("get_player_data".to_owned())));
+    Ok(())
+}
+
+#[test]
+fn pe_image_resolve_rip_relative() -> ReturnType {
+    let image = PeImage::from_bytes(&get_test_executable(&get_test_code(), &[]))?;
+    assert_eq!(image.resolve_rip_relative(0x1140, 0), Ok(0x2010));
+    assert_eq!(image.resolve_rip_relative(0x1140, 1), Ok(0x1044));
+    // Instructions without a relative operand are skipped
+    assert_eq!(image.resolve_rip_relative(0x107f, 0), Ok(0x2010));
+    assert_eq!(image.resolve_rip_relative(0x1140, 2), Err(ResolveError::OutOfBounds));
+    assert_eq!(image.resolve_rip_relative(0x1110, 0), Err(ResolveError::OutOfBounds));
+    // The old resolvers still read from a fixed position when there isn't an instruction there
+    assert_eq!(image.get_indirect_address(0x1082, 1), Ok(0x2010));
+    // The near pointer is always read from the resolver's position, even if the instruction has a
+    // relative operand somewhere else
+    assert_eq!(image.get_indirect_address(0x1140, 2), Ok(0x2010));
+    assert_eq!(image.get_indirect_address(0x1140, 1), Err(ResolveError::OutOfBounds));
+    Ok(())
+}
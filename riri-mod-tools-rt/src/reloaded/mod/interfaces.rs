@@ -2,7 +2,7 @@ use std::ops::Deref;
 use crate::interop::{ ObjectHash, ObjectInitializable, ObjectValuable };
 use crate::system::Object;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IModConfig(Object);
 
 impl Deref for IModConfig {
@@ -0,0 +1,387 @@
+//! Reads and writes the signature cache used by `UseCachedSignatures`. The cache stores where
+//! each signature was found, so that scanning can be skipped on the next launch as long as the
+//! executable and the list of active mods (including their versions) haven't changed.
+//!
+//! All strings except hook names are stored as their xxh3 hash. The file is laid out as follows
+//! (see `misc/signature_cache.hexpat`):
+//!
+//! ```text
+//! u64 ExecutableHash
+//! u32 Version
+//! u32 ModCount
+//! ModEntry[ModCount]:
+//!     u64 ModId
+//!     u64 ModVersion
+//! u64 SignatureCount
+//! SignatureEntry[SignatureCount]:
+//!     u64 Pattern
+//!     u64 Offset       // Where the signature was found, relative to the executable
+//!     u64 Resolved     // The address after the resolve function, relative to the executable,
+//!                      // or u64::MAX if it wasn't resolved or is outside of the executable
+//!     u32 NameLength
+//!     u8  Name[NameLength]
+//! u64 Checksum         // xxh3 of everything before this
+//! ```
+//!
+//! The generated C# opens the cache with [`open_ffi_signature_cache`] before registering hooks,
+//! then saves it with [`save_ffi_signature_cache`] after `OnModLoaderInitialized` if it had to be
+//! regenerated.
+
+use std::error::Error;
+use std::ffi::{ c_char, CStr };
+use std::fmt::{ Display, Formatter };
+use std::path::{ Path, PathBuf };
+use std::sync::{ Mutex, PoisonError };
+use twox_hash::XxHash3_64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureCacheError {
+    Io(String),
+    /// The file ended before the data it describes.
+    Truncated,
+    /// The cache was written by an older or newer version of riri-mod-tools.
+    UnsupportedVersion(u32),
+    ChecksumMismatch,
+    InvalidName
+}
+
+impl Display for SignatureCacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Truncated => write!(f, "Signature cache is truncated"),
+            Self::UnsupportedVersion(v) => write!(f, "Signature cache version {} is not supported (expected {})", v, SignatureCache::VERSION),
+            Self::ChecksumMismatch => write!(f, "Signature cache checksum doesn't match its contents"),
+            Self::InvalidName => write!(f, "Signature cache contains a hook name that isn't valid UTF-8")
+        }
+    }
+}
+
+impl Error for SignatureCacheError {}
+
+impl From<std::io::Error> for SignatureCacheError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value.to_string())
+    }
+}
+
+/// A mod that was active when the cache was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedMod {
+    id: u64,
+    version: u64
+}
+
+impl CachedMod {
+    pub fn new(id: &str, version: &str) -> Self {
+        Self::from_hashes(XxHash3_64::oneshot(id.as_bytes()), XxHash3_64::oneshot(version.as_bytes()))
+    }
+    pub fn from_hashes(id: u64, version: u64) -> Self { Self { id, version } }
+    pub fn get_id_hash(&self) -> u64 { self.id }
+    pub fn get_version_hash(&self) -> u64 { self.version }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedSignature {
+    name: String,
+    pattern: u64,
+    offset: u64,
+    resolved: Option<u64>
+}
+
+impl CachedSignature {
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_pattern_hash(&self) -> u64 { self.pattern }
+    /// Where the signature was found, relative to the start of the executable.
+    pub fn get_offset(&self) -> u64 { self.offset }
+    /// The address returned by the hook's resolve function, relative to the start of the
+    /// executable.
+    pub fn get_resolved(&self) -> Option<u64> { self.resolved }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureCache {
+    executable_hash: u64,
+    mods: Vec<CachedMod>,
+    signatures: Vec<CachedSignature>
+}
+
+// Reads little endian values from the cache, failing if there aren't enough bytes left
+struct CacheReader<'a>(&'a [u8]);
+
+impl<'a> CacheReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SignatureCacheError> {
+        if self.0.len() < len { return Err(SignatureCacheError::Truncated); }
+        let (v, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(v)
+    }
+    fn read_u32(&mut self) -> Result<u32, SignatureCacheError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn read_u64(&mut self) -> Result<u64, SignatureCacheError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl SignatureCache {
+    pub const VERSION: u32 = 1;
+    const NOT_RESOLVED: u64 = u64::MAX;
+
+    pub fn new(executable_hash: u64, mods: Vec<CachedMod>) -> Self {
+        Self { executable_hash, mods, signatures: vec![] }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SignatureCacheError> {
+        let mut reader = CacheReader(data);
+        let executable_hash = reader.read_u64()?;
+        let version = reader.read_u32()?;
+        if version != Self::VERSION {
+            return Err(SignatureCacheError::UnsupportedVersion(version));
+        }
+        if data.len() < 20 { return Err(SignatureCacheError::Truncated); }
+        let (body, checksum) = data.split_at(data.len() - 8);
+        if XxHash3_64::oneshot(body) != u64::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(SignatureCacheError::ChecksumMismatch);
+        }
+        reader.0 = &body[12..];
+        let mod_count = reader.read_u32()? as usize;
+        let mut mods = Vec::with_capacity(mod_count.min(reader.0.len() / 16));
+        for _ in 0..mod_count {
+            mods.push(CachedMod::from_hashes(reader.read_u64()?, reader.read_u64()?));
+        }
+        let sig_count = reader.read_u64()? as usize;
+        let mut signatures = Vec::with_capacity(sig_count.min(reader.0.len() / 28));
+        for _ in 0..sig_count {
+            let (pattern, offset, resolved) = (reader.read_u64()?, reader.read_u64()?, reader.read_u64()?);
+            let name_len = reader.read_u32()? as usize;
+            let name = std::str::from_utf8(reader.take(name_len)?)
+                .map_err(|_| SignatureCacheError::InvalidName)?.to_owned();
+            let resolved = match resolved {
+                Self::NOT_RESOLVED => None,
+                v => Some(v)
+            };
+            signatures.push(CachedSignature { name, pattern, offset, resolved });
+        }
+        Ok(Self { executable_hash, mods, signatures })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend_from_slice(&self.executable_hash.to_le_bytes());
+        out.extend_from_slice(&Self::VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.mods.len() as u32).to_le_bytes());
+        for m in &self.mods {
+            out.extend_from_slice(&m.id.to_le_bytes());
+            out.extend_from_slice(&m.version.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.signatures.len() as u64).to_le_bytes());
+        for s in &self.signatures {
+            out.extend_from_slice(&s.pattern.to_le_bytes());
+            out.extend_from_slice(&s.offset.to_le_bytes());
+            out.extend_from_slice(&s.resolved.unwrap_or(Self::NOT_RESOLVED).to_le_bytes());
+            out.extend_from_slice(&(s.name.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.name.as_bytes());
+        }
+        let checksum = XxHash3_64::oneshot(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SignatureCacheError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SignatureCacheError> {
+        Ok(std::fs::write(path, self.to_bytes())?)
+    }
+
+    pub fn get_executable_hash(&self) -> u64 { self.executable_hash }
+    pub fn get_mods(&self) -> &[CachedMod] { &self.mods }
+    pub fn get_signatures(&self) -> &[CachedSignature] { &self.signatures }
+
+    /// Check if the cache was written for the same executable and the same mods, in the same
+    /// order and with the same versions.
+    pub fn is_valid_for(&self, executable_hash: u64, mods: &[CachedMod]) -> bool {
+        self.executable_hash == executable_hash && self.mods == mods
+    }
+
+    pub fn get(&self, pattern: &str) -> Option<&CachedSignature> {
+        let hash = XxHash3_64::oneshot(pattern.as_bytes());
+        self.signatures.iter().find(|s| s.pattern == hash)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&CachedSignature> {
+        self.signatures.iter().find(|s| s.name == name)
+    }
+
+    /// Add where a signature was found, replacing the previous entry for the same hook.
+    pub fn insert(&mut self, name: &str, pattern: &str, offset: u64) {
+        let entry = CachedSignature { name: name.to_owned(), pattern: XxHash3_64::oneshot(pattern.as_bytes()),
+            offset, resolved: None };
+        match self.signatures.iter().position(|s| s.name == name) {
+            Some(i) => self.signatures[i] = entry,
+            None => self.signatures.push(entry)
+        }
+    }
+
+    /// Set the resolved address of a hook, relative to the start of the executable. Returns
+    /// false if the hook isn't in the cache.
+    pub fn set_resolved(&mut self, name: &str, resolved: Option<u64>) -> bool {
+        match self.signatures.iter_mut().find(|s| s.name == name) {
+            Some(s) => { s.resolved = resolved; true },
+            None => false
+        }
+    }
+
+    /// Check if a hook resolved to the same address as when the cache was written. Hooks that
+    /// aren't in the cache or weren't resolved when it was written always match.
+    pub fn matches_resolved(&self, name: &str, resolved: Option<u64>) -> bool {
+        match self.get_by_name(name).and_then(|s| s.resolved) {
+            Some(v) => resolved == Some(v),
+            None => true
+        }
+    }
+}
+
+struct ActiveCache {
+    cache: SignatureCache,
+    path: PathBuf,
+    // True if the cache was loaded from disk, false if it's being regenerated
+    loaded: bool,
+    // A hook resolved to a different address than the loaded cache has, so it's removed when
+    // it would be saved
+    stale: bool
+}
+
+static ACTIVE_CACHE: Mutex<Option<ActiveCache>> = Mutex::new(None);
+
+fn with_active_cache<R, F: FnOnce(&mut ActiveCache) -> R>(f: F) -> Option<R> {
+    ACTIVE_CACHE.lock().unwrap_or_else(PoisonError::into_inner).as_mut().map(f)
+}
+
+/// Check if a signature cache is open and being regenerated.
+pub(crate) fn is_regenerating() -> bool {
+    with_active_cache(|c| !c.loaded).unwrap_or(false)
+}
+
+/// Called by the hook registry when a hook or static is resolved, so that the resolved address
+/// is saved alongside its signature. If the cache was loaded from disk, the address is checked
+/// against the cached one instead, and the cache is regenerated on the next launch if they're
+/// different (e.g a resolve function changed).
+pub(crate) fn set_resolved_address(name: &str, address: usize) {
+    let process = match crate::sigscan_resolver::CURRENT_PROCESS.get() {
+        Some(v) => v,
+        None => return
+    };
+    let base = process.get_executable_address();
+    let resolved = match address.checked_sub(base) {
+        Some(v) if v < process.get_executable_size() => Some(v as u64),
+        _ => None
+    };
+    let stale = with_active_cache(|c| match c.loaded {
+        true => {
+            let stale = !c.cache.matches_resolved(name, resolved);
+            c.stale |= stale;
+            stale
+        },
+        false => {
+            c.cache.set_resolved(name, resolved);
+            false
+        }
+    }).unwrap_or(false);
+    if stale {
+        crate::logln!(Warning, "{} resolved to a different address than the cached one, the signature cache will be \
+            regenerated on the next launch", name);
+    }
+}
+
+/// Load the signature cache at `path`, or start a new one if it doesn't exist or was written for
+/// a different executable or set of mods. `mods` contains the xxh3 hash of each active mod's ID
+/// and version, one after the other. Returns true if the cached offsets can be used.
+///
+/// # Safety
+///
+/// `path` must be a valid null terminated string, and `mods` must be valid for reads of
+/// `mod_count * 2` values.
+#[no_mangle]
+pub unsafe extern "C" fn open_ffi_signature_cache(path: *const c_char, mods: *const u64, mod_count: u32) -> bool {
+    let path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+    let mods: Vec<CachedMod> = std::slice::from_raw_parts(mods, mod_count as usize * 2)
+        .chunks_exact(2).map(|m| CachedMod::from_hashes(m[0], m[1])).collect();
+    let executable_hash = crate::sigscan_resolver::get_executable_hash();
+    let cache = match SignatureCache::load(&path) {
+        Ok(c) if c.is_valid_for(executable_hash, &mods) => Some(c),
+        Ok(_) => {
+            crate::logln!(Information, "Executable or active mods have changed, regenerating signature cache...");
+            None
+        },
+        Err(SignatureCacheError::Io(_)) if !path.exists() => {
+            crate::logln!(Information, "Signature cache does not exist yet, generating...");
+            None
+        },
+        Err(e) => {
+            crate::logln!(Warning, "Couldn't read signature cache: {}, regenerating...", e);
+            None
+        }
+    };
+    let loaded = cache.is_some();
+    let cache = cache.unwrap_or_else(|| SignatureCache::new(executable_hash, mods));
+    *ACTIVE_CACHE.lock().unwrap_or_else(PoisonError::into_inner) = Some(ActiveCache { cache, path, loaded, stale: false });
+    loaded
+}
+
+/// Get the offset where a signature was found from the loaded cache. Returns false if there's no
+/// loaded cache or the signature isn't in it.
+///
+/// # Safety
+///
+/// `pattern` must be a valid null terminated string and `offset` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn get_ffi_cached_signature(pattern: *const c_char, offset: *mut usize) -> bool {
+    let pattern = CStr::from_ptr(pattern).to_string_lossy();
+    let found = with_active_cache(|c| match c.loaded {
+        true => c.cache.get(&pattern).map(|s| s.get_offset() as usize),
+        false => None
+    }).flatten();
+    match found {
+        Some(v) => { *offset = v; true },
+        None => false
+    }
+}
+
+/// Add where a signature was found while the cache is being regenerated.
+///
+/// # Safety
+///
+/// `name` and `pattern` must be valid null terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn add_ffi_cached_signature(name: *const c_char, pattern: *const c_char, offset: usize) {
+    let name = CStr::from_ptr(name).to_string_lossy();
+    let pattern = CStr::from_ptr(pattern).to_string_lossy();
+    with_active_cache(|c| if !c.loaded { c.cache.insert(&name, &pattern, offset as u64); });
+}
+
+/// Write the cache to disk if it was regenerated, or remove it if a hook resolved to a different
+/// address than the loaded cache has. Returns false if the cache couldn't be written or removed.
+#[no_mangle]
+pub extern "C" fn save_ffi_signature_cache() -> bool {
+    let cache = ACTIVE_CACHE.lock().unwrap_or_else(PoisonError::into_inner).take();
+    match cache {
+        Some(c) if !c.loaded => match c.cache.save(&c.path) {
+            Ok(_) => true,
+            Err(e) => {
+                crate::logln!(Error, "Couldn't write signature cache: {}", e);
+                false
+            }
+        },
+        Some(c) if c.stale => match std::fs::remove_file(&c.path) {
+            Ok(_) => true,
+            Err(e) => {
+                crate::logln!(Error, "Couldn't remove stale signature cache: {}", e);
+                false
+            }
+        },
+        _ => true
+    }
+}
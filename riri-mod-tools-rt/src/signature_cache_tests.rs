@@ -0,0 +1,77 @@
+#![cfg(test)]
+use crate::signature_cache::{ CachedMod, SignatureCache, SignatureCacheError };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+fn get_test_cache() -> SignatureCache {
+    let mods = vec![CachedMod::new("p3rpc.modmenu", "1.0.0"), CachedMod::new("p3rpc.camera", "0.2.1")];
+    let mut cache = SignatureCache::new(0xdeadbeef, mods);
+    cache.insert("fldPCMoveUpdate", "48 89 5C 24 ?? 57 48 83 EC 20", 0x4b1230);
+    cache.insert("GlobalWork", "48 8B 05 ?? ?? ?? ??", 0x10a00);
+    cache.set_resolved("fldPCMoveUpdate", Some(0x4b1230));
+    cache
+}
+
+#[test]
+fn signature_cache_round_trip() -> ReturnType {
+    let cache = get_test_cache();
+    let read = SignatureCache::from_bytes(&cache.to_bytes())?;
+    assert_eq!(read, cache);
+    let sig = read.get("48 89 5C 24 ?? 57 48 83 EC 20").unwrap();
+    assert_eq!(sig.get_name(), "fldPCMoveUpdate");
+    assert_eq!(sig.get_offset(), 0x4b1230);
+    assert_eq!(sig.get_resolved(), Some(0x4b1230));
+    assert_eq!(read.get_by_name("GlobalWork").unwrap().get_resolved(), None);
+    assert!(read.get("48 8B 0D").is_none());
+    Ok(())
+}
+
+#[test]
+fn signature_cache_insert_replaces() -> ReturnType {
+    let mut cache = get_test_cache();
+    cache.insert("GlobalWork", "48 8B 0D ?? ?? ?? ??", 0x20b00);
+    assert_eq!(cache.get_signatures().len(), 2);
+    assert!(cache.get("48 8B 05 ?? ?? ?? ??").is_none());
+    assert_eq!(cache.get("48 8B 0D ?? ?? ?? ??").unwrap().get_offset(), 0x20b00);
+    assert!(!cache.set_resolved("Missing", Some(0)));
+    assert!(cache.matches_resolved("fldPCMoveUpdate", Some(0x4b1230)));
+    assert!(!cache.matches_resolved("fldPCMoveUpdate", Some(0x4b1234)));
+    assert!(!cache.matches_resolved("fldPCMoveUpdate", None));
+    // Hooks that weren't resolved when the cache was written can't be checked
+    assert!(cache.matches_resolved("GlobalWork", Some(0x10a00)));
+    assert!(cache.matches_resolved("Missing", None));
+    Ok(())
+}
+
+#[test]
+fn signature_cache_validation() -> ReturnType {
+    let cache = get_test_cache();
+    let mods = cache.get_mods().to_vec();
+    assert!(cache.is_valid_for(0xdeadbeef, &mods));
+    assert!(!cache.is_valid_for(0xcafebabe, &mods));
+    assert!(!cache.is_valid_for(0xdeadbeef, &mods[..1]));
+    assert!(!cache.is_valid_for(0xdeadbeef, &[mods[1], mods[0]]));
+    assert!(!cache.is_valid_for(0xdeadbeef, &[mods[0], CachedMod::new("p3rpc.camera", "0.2.2")]));
+
+    let bytes = cache.to_bytes();
+    let mut corrupted = bytes.clone();
+    corrupted[20] ^= 1;
+    assert_eq!(SignatureCache::from_bytes(&corrupted), Err(SignatureCacheError::ChecksumMismatch));
+    let mut old_version = bytes.clone();
+    old_version[8..12].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(SignatureCache::from_bytes(&old_version), Err(SignatureCacheError::UnsupportedVersion(0)));
+    assert_eq!(SignatureCache::from_bytes(&bytes[..10]), Err(SignatureCacheError::Truncated));
+    assert_eq!(SignatureCache::from_bytes(&bytes[..bytes.len() - 1]), Err(SignatureCacheError::ChecksumMismatch));
+    Ok(())
+}
+
+#[test]
+fn signature_cache_file() -> ReturnType {
+    let path = std::env::temp_dir().join(format!("riri_signature_cache_{}", std::process::id()));
+    let cache = get_test_cache();
+    cache.save(&path)?;
+    let read = SignatureCache::load(&path);
+    std::fs::remove_file(&path)?;
+    assert_eq!(read?, cache);
+    assert!(matches!(SignatureCache::load(&path), Err(SignatureCacheError::Io(_))));
+    Ok(())
+}
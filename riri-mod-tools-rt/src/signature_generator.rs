@@ -0,0 +1,161 @@
+//! Creates the shortest signature that only matches one address in an executable. Instructions
+//! are decoded from the address so that bytes which change whenever code or data moves are
+//! replaced with wildcards: RIP-relative displacements, call and jump targets, and anything
+//! patched by a base relocation. Everything else is kept, and the signature grows one byte at a
+//! time until no other position in the executable's sections matches it.
+//!
+//! The result is a [`Pattern`], which displays in the format that `dynamic_offset` expects:
+//!
+//! ```ignore
+//! let image = PeImage::load("METAPHOR.exe")?;
+//! let pattern = SignatureGenerator::from_pe_image(&image).generate(0x4b1230)?;
+//! println!("dynamic_offset(signature = \"{}\")", pattern);
+//! ```
+
+use crate::instruction_decoder::{ self, DecodeError };
+use crate::pattern::Pattern;
+use crate::pe_image::{ ImageSections, PeImage };
+use crate::sigscan_batch::BatchScanner;
+use std::error::Error;
+use std::fmt::{ Display, Formatter };
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureGeneratorError {
+    /// The address isn't inside of any of the sections that get scanned.
+    OutOfBounds,
+    /// The address is inside of a value patched by a base relocation, so it can't be the start
+    /// of a signature.
+    Relocated,
+    /// The instruction at this offset couldn't be decoded.
+    InvalidInstruction(usize, DecodeError),
+    /// The signature still matched at these offsets after reaching the maximum length.
+    NotUnique(Vec<usize>)
+}
+
+impl Display for SignatureGeneratorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBounds => write!(f, "Address is outside of the executable's sections"),
+            Self::Relocated => write!(f, "Address is inside of a relocated value"),
+            Self::InvalidInstruction(o, e) => write!(f, "Couldn't decode instruction at 0x{:x}: {}", o, e),
+            Self::NotUnique(m) => {
+                let offsets: Vec<String> = m.iter().map(|o| format!("0x{:x}", o)).collect();
+                write!(f, "Signature is still ambiguous at its maximum length, it also matches {}", offsets.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for SignatureGeneratorError {}
+
+#[derive(Debug, Clone)]
+pub struct SignatureGenerator<'a> {
+    sections: ImageSections<'a>,
+    relocations: &'a [Range<usize>],
+    max_length: usize
+}
+
+impl<'a> SignatureGenerator<'a> {
+    const DEFAULT_MAX_LENGTH: usize = 128;
+
+    /// Create a generator for an executable mapped at `image`. Signatures are made unique within
+    /// `regions`, which are offsets into the image (usually its sections), and bytes inside of
+    /// `relocations` (sorted by offset) are always wildcards.
+    pub fn new(image: &'a [u8], regions: Vec<Range<usize>>, relocations: &'a [Range<usize>]) -> Self {
+        Self::from_sections(ImageSections::from_image(image, &regions), relocations)
+    }
+
+    /// Create a generator for the sections of an executable, which don't have to be next to each
+    /// other in memory, such as the game's executable while it's running.
+    pub fn from_sections(sections: ImageSections<'a>, relocations: &'a [Range<usize>]) -> Self {
+        Self { sections, relocations, max_length: Self::DEFAULT_MAX_LENGTH }
+    }
+
+    pub fn from_pe_image(image: &'a PeImage) -> Self {
+        Self::from_sections(image.get_mapped_sections(), image.get_relocations())
+    }
+
+    /// Set the longest signature that will be generated, in bytes. Defaults to 128.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length.max(1);
+        self
+    }
+
+    fn is_relocated(&self, offset: usize) -> bool {
+        let i = self.relocations.partition_point(|r| r.end <= offset);
+        self.relocations.get(i).is_some_and(|r| r.contains(&offset))
+    }
+
+    /// Decode instructions from `offset` until at least `len` bytes are covered (or the end of
+    /// the section is reached), returning the bytes, which of them should be kept and the length
+    /// of the first instruction.
+    pub fn get_masked_bytes(&self, offset: usize, len: usize) -> Result<(Vec<u8>, Vec<bool>, usize), SignatureGeneratorError> {
+        let section = self.sections.get_bytes(offset).ok_or(SignatureGeneratorError::OutOfBounds)?;
+        let mut bytes = vec![];
+        let mut mask = vec![];
+        let mut first_length = 0;
+        let mut pos = offset;
+        while pos - offset < len && pos - offset < section.len() {
+            let code = &section[pos - offset..];
+            let instruction = match instruction_decoder::decode(code) {
+                Ok(v) => v,
+                // Later instructions can run into data, so only the first one has to decode
+                Err(e) if pos == offset => return Err(SignatureGeneratorError::InvalidInstruction(pos, e)),
+                Err(_) => break
+            };
+            let relative = instruction.get_relative_operand()
+                .map(|o| o.get_offset()..o.get_offset() + o.get_size());
+            for (i, byte) in code[..instruction.get_length()].iter().enumerate() {
+                bytes.push(*byte);
+                mask.push(!relative.as_ref().is_some_and(|r| r.contains(&i)) && !self.is_relocated(pos + i));
+            }
+            if first_length == 0 { first_length = instruction.get_length(); }
+            pos += instruction.get_length();
+        }
+        Ok((bytes, mask, first_length))
+    }
+
+    // Find every position in the scanned regions that matches the pattern
+    fn find_all(&self, pattern: Pattern) -> Vec<usize> {
+        let mut scanner = BatchScanner::new();
+        scanner.add(pattern);
+        scanner.scan_regions(self.sections.get_base(), self.sections.get_sections(), true).remove(0)
+    }
+
+    // Trailing wildcards don't make a signature any more unique
+    fn get_pattern(bytes: &[u8], mask: &[bool]) -> Pattern {
+        let len = mask.iter().rposition(|m| *m).map_or(0, |p| p + 1);
+        Pattern::from_masked(bytes[..len].to_vec(), mask[..len].to_vec()).unwrap()
+    }
+
+    /// Generate the shortest signature that matches `offset` and nothing else in the scanned
+    /// regions.
+    pub fn generate(&self, offset: usize) -> Result<Pattern, SignatureGeneratorError> {
+        let (bytes, mask, first_length) = self.get_masked_bytes(offset, self.max_length)?;
+        if !mask[0] { return Err(SignatureGeneratorError::Relocated); }
+        let max_length = bytes.len().min(self.max_length);
+        let mut candidates = self.find_all(Self::get_pattern(&bytes[..first_length], &mask[..first_length]));
+        if candidates == [offset] {
+            // Matching on part of the first instruction might already be enough
+            for len in 1..first_length {
+                if mask[len - 1] && self.find_all(Self::get_pattern(&bytes[..len], &mask[..len])) == [offset] {
+                    return Ok(Self::get_pattern(&bytes[..len], &mask[..len]));
+                }
+            }
+            return Ok(Self::get_pattern(&bytes[..first_length], &mask[..first_length]));
+        }
+        // Extend the signature from the candidates that are left, which is much faster than
+        // scanning the executable again for each byte
+        for i in first_length..max_length {
+            if !mask[i] { continue; }
+            candidates.retain(|c| *c == offset
+                || self.sections.get_bytes(*c).and_then(|b| b.get(i)) == Some(&bytes[i]));
+            if candidates.len() == 1 {
+                return Ok(Self::get_pattern(&bytes[..=i], &mask[..=i]));
+            }
+        }
+        candidates.retain(|c| *c != offset);
+        Err(SignatureGeneratorError::NotUnique(candidates))
+    }
+}
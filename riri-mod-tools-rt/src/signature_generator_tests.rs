@@ -0,0 +1,75 @@
+#![cfg(test)]
+use crate::instruction_decoder::DecodeError;
+use crate::pe_builder::{ self, PeBuilder };
+use crate::pe_image::{ PeImage, PeImageError };
+use crate::signature_generator::{ SignatureGenerator, SignatureGeneratorError };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// mov [rsp+8], rbx; push rdi; sub rsp, 0x20
+pub(crate) const TEST_PROLOGUE: [u8; 10] = [0x48, 0x89, 0x5c, 0x24, 0x08, 0x57, 0x48, 0x83, 0xec, 0x20];
+
+// A code section of `size` bytes filled with int3, with each piece of code placed at its offset
+pub(crate) fn get_test_code(size: usize, code: &[(usize, &[u8])]) -> Vec<u8> {
+    let mut text = vec![0xccu8; size];
+    for (offset, bytes) in code {
+        text[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+    }
+    text
+}
+
+// .text has two functions with the same prologue that differ after a call, a function whose
+// first byte is unique and two functions that only differ in a relocated constant. A second code
+// section has two identical functions, and signatures can't continue from one section into the
+// next.
+fn get_test_image() -> Result<PeImage, PeImageError> {
+    let mov_absolute = |offset: u64, last: u8| [&[0x48, 0xb8][..], &(pe_builder::DEFAULT_IMAGE_BASE + offset).to_le_bytes(), &[last]].concat();
+    let text = get_test_code(0x600, &[
+        (0x100, &TEST_PROLOGUE), (0x10a, &[0xe8, 0x10, 0x20, 0, 0]), (0x10f, &[0x48, 0x8b, 0x05, 0x30, 0x40, 0, 0]),
+        (0x200, &TEST_PROLOGUE), (0x20a, &[0xe8, 0x50, 0x60, 0, 0]), (0x20f, &[0x48, 0x8b, 0x0d, 0x30, 0x40, 0, 0]),
+        (0x300, &[0xb9, 0x78, 0x56, 0x34, 0x12]),
+        (0x400, &mov_absolute(0x1400, 0xc3)), (0x500, &mov_absolute(0x1500, 0x90))
+    ]);
+    let identical = get_test_code(0x200, &[(0, &[0x31, 0xc0, 0xff, 0xc0]), (0x100, &[0x31, 0xc0, 0xff, 0xc0])]);
+    // One base relocation block for the page at 0x1000, with both of the absolute addresses
+    let mut relocations = vec![];
+    relocations.extend_from_slice(&0x1000u32.to_le_bytes());
+    relocations.extend_from_slice(&12u32.to_le_bytes());
+    for entry in [(10u16 << 12) | 0x402, (10 << 12) | 0x502] {
+        relocations.extend_from_slice(&entry.to_le_bytes());
+    }
+    let file = PeBuilder::new().text(&text).section(".text2", &identical, pe_builder::CODE_CHARACTERISTICS)
+        .data(&relocations).directory(pe_builder::RELOCATION_DIRECTORY, 0x3000, 12).build();
+    PeImage::from_bytes(&file)
+}
+
+#[test]
+fn generate_signature() -> ReturnType {
+    let image = get_test_image()?;
+    assert_eq!(image.get_relocations(), &[0x1402..0x140a, 0x1502..0x150a]);
+    let generator = SignatureGenerator::from_pe_image(&image);
+    // Call targets and RIP-relative displacements are wildcards
+    assert_eq!(generator.generate(0x1100)?.to_string(), "48 89 5C 24 08 57 48 83 EC 20 E8 ?? ?? ?? ?? 48 8B 05");
+    assert_eq!(generator.generate(0x1200)?.to_string(), "48 89 5C 24 08 57 48 83 EC 20 E8 ?? ?? ?? ?? 48 8B 0D");
+    // Shorter than the first instruction
+    assert_eq!(generator.generate(0x1300)?.to_string(), "B9");
+    // Relocated values are wildcards
+    assert_eq!(generator.generate(0x1400)?.to_string(), "48 B8 ?? ?? ?? ?? ?? ?? ?? ?? C3");
+    let (_, mask, first_length) = generator.get_masked_bytes(0x1400, 1)?;
+    assert_eq!(first_length, 10);
+    assert_eq!(mask, [true, true, false, false, false, false, false, false, false, false]);
+    Ok(())
+}
+
+#[test]
+fn generate_signature_errors() -> ReturnType {
+    let image = get_test_image()?;
+    let generator = SignatureGenerator::from_pe_image(&image).with_max_length(32);
+    assert_eq!(generator.generate(0x2000), Err(SignatureGeneratorError::NotUnique(vec![0x2100])));
+    assert_eq!(generator.generate(0x1800), Err(SignatureGeneratorError::OutOfBounds));
+    assert_eq!(generator.generate(0x1404), Err(SignatureGeneratorError::Relocated));
+    let mut invalid = image.get_image().to_vec();
+    invalid[0x1300] = 0x06;
+    let generator = SignatureGenerator::new(&invalid, image.get_section_ranges(), image.get_relocations());
+    assert_eq!(generator.generate(0x1300), Err(SignatureGeneratorError::InvalidInstruction(0x1300, DecodeError::InvalidOpcode(0x06))));
+    Ok(())
+}
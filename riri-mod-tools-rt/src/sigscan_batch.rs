@@ -0,0 +1,255 @@
+//! Scans for many signatures in one pass over the executable. Rather than scanning the whole
+//! executable once per signature, each section is split into chunks that are handed out to a
+//! pool of threads, and every signature is matched against a chunk while it's still in cache.
+//!
+//! The generated C# uses this when `UseBatchedSignatureScan` is set in `package.toml`. Each call
+//! to `SigScan` is queued, then every queued signature is scanned for with [`scan_ffi_signatures`]
+//! after the hooks are registered, and the results are passed to the same resolve functions that
+//! the startup scanner would have called.
+//!
+//! In debug builds and when the signature cache is being regenerated, every match is counted
+//! instead, and signatures that match more than once are reported with all of their offsets.
+
+use crate::pattern::Pattern;
+use std::ffi::{ c_char, CStr };
+use std::sync::{
+    atomic::{ AtomicUsize, Ordering },
+    Mutex,
+    PoisonError
+};
+
+/// A list of patterns to scan for at the same time.
+#[derive(Debug, Clone)]
+pub struct BatchScanner {
+    patterns: Vec<Pattern>,
+    threads: usize,
+    chunk_size: usize
+}
+
+impl BatchScanner {
+    const DEFAULT_CHUNK_SIZE: usize = 0x100000;
+
+    pub fn new() -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |v| v.get());
+        Self { patterns: vec![], threads, chunk_size: Self::DEFAULT_CHUNK_SIZE }
+    }
+
+    /// Set the number of threads to scan with. Defaults to the number of logical processors.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Set the number of bytes that each thread scans at a time. Defaults to 1 MB.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Add a pattern to the batch, returning its index in the results.
+    pub fn add(&mut self, pattern: Pattern) -> usize {
+        self.patterns.push(pattern);
+        self.patterns.len() - 1
+    }
+
+    pub fn len(&self) -> usize { self.patterns.len() }
+
+    pub fn is_empty(&self) -> bool { self.patterns.is_empty() }
+
+    pub fn get_patterns(&self) -> &[Pattern] { &self.patterns }
+
+    /// Find the offset of the first match of each pattern in `data`, in the order that the
+    /// patterns were added.
+    pub fn scan(&self, data: &[u8]) -> Vec<Option<usize>> {
+        Self::get_first(self.scan_regions(data.as_ptr() as usize, &[data], false))
+    }
+
+    /// Find the offset of every match of each pattern in `data`, in ascending order.
+    pub fn scan_all(&self, data: &[u8]) -> Vec<Vec<usize>> {
+        self.scan_regions(data.as_ptr() as usize, &[data], true)
+    }
+
+    /// Find the offset of the first match of each pattern in the executable's sections, relative
+    /// to the start of the executable. This requires `set_current_process` to have been called.
+    pub fn scan_executable(&self) -> Vec<Option<usize>> {
+        Self::get_first(self.scan_executable_inner(false))
+    }
+
+    /// Like [`BatchScanner::scan_executable`], but finds every match of each pattern.
+    pub fn scan_executable_all(&self) -> Vec<Vec<usize>> {
+        self.scan_executable_inner(true)
+    }
+
+    fn get_first(matches: Vec<Vec<usize>>) -> Vec<Option<usize>> {
+        matches.into_iter().map(|m| m.first().copied()).collect()
+    }
+
+    fn scan_executable_inner(&self, all: bool) -> Vec<Vec<usize>> {
+        let (base, sections) = get_executable_sections();
+        self.scan_regions(base, &sections, all)
+    }
+
+    // Offsets are relative to base. Regions must be in ascending order of address so that
+    // chunks are handed out in order, which lets patterns that already have an earlier match
+    // skip the rest of the data when only the first match is needed.
+    pub(crate) fn scan_regions(&self, base: usize, regions: &[&[u8]], all: bool) -> Vec<Vec<usize>> {
+        let mut chunks = vec![];
+        for region in regions {
+            let mut start = 0;
+            while start < region.len() {
+                chunks.push((*region, start));
+                start += self.chunk_size;
+            }
+        }
+        let first: Vec<AtomicUsize> = self.patterns.iter().map(|_| AtomicUsize::new(usize::MAX)).collect();
+        let matches: Vec<Mutex<Vec<usize>>> = self.patterns.iter().map(|_| Mutex::new(vec![])).collect();
+        let next_chunk = AtomicUsize::new(0);
+        let scan_chunks = || {
+            loop {
+                let (region, start) = match chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed)) {
+                    Some(v) => *v,
+                    None => return
+                };
+                let offset = region.as_ptr() as usize - base + start;
+                for (i, pattern) in self.patterns.iter().enumerate() {
+                    if !all && first[i].load(Ordering::Relaxed) < offset { continue; }
+                    // Extend the chunk so that matches starting at the end of it aren't cut off
+                    let end = (start + self.chunk_size + pattern.len() - 1).min(region.len());
+                    if all {
+                        let found = pattern.find_all(&region[start..end]);
+                        if !found.is_empty() {
+                            matches[i].lock().unwrap_or_else(PoisonError::into_inner).extend(found.into_iter().map(|m| offset + m));
+                        }
+                    } else if let Some(m) = pattern.find(&region[start..end]) {
+                        first[i].fetch_min(offset + m, Ordering::Relaxed);
+                    }
+                }
+            }
+        };
+        let threads = self.threads.min(chunks.len());
+        if threads > 1 {
+            std::thread::scope(|s| {
+                for _ in 0..threads { s.spawn(scan_chunks); }
+            });
+        } else {
+            scan_chunks();
+        }
+        match all {
+            true => matches.into_iter().map(|m| {
+                let mut m = m.into_inner().unwrap_or_else(PoisonError::into_inner);
+                m.sort_unstable();
+                m
+            }).collect(),
+            false => first.into_iter().map(|f| match f.into_inner() {
+                usize::MAX => vec![],
+                v => vec![v]
+            }).collect()
+        }
+    }
+}
+
+impl Default for BatchScanner {
+    fn default() -> Self { Self::new() }
+}
+
+/// Get the executable's address and its sections in ascending order of address, clamped to the
+/// executable's memory. This requires `set_current_process` to have been called.
+pub(crate) fn get_executable_sections() -> (usize, Vec<&'static [u8]>) {
+    let process = crate::sigscan_resolver::CURRENT_PROCESS.get().unwrap();
+    let base = process.get_executable_address();
+    let end = base + process.get_executable_size();
+    let mut sections: Vec<&[u8]> = process.get_memory_sections().iter().filter_map(|s| {
+        let start = (s.get_virtual_address() as usize).max(base);
+        let size = (s.get_virtual_address() as usize + s.get_size()).min(end).checked_sub(start)?;
+        // SAFETY: Sections are mapped for as long as the executable is loaded
+        Some(unsafe { std::slice::from_raw_parts(start as *const u8, size) })
+    }).collect();
+    sections.sort_by_key(|s| s.as_ptr() as usize);
+    (base, sections)
+}
+
+// Every match is counted in debug builds and when the signature cache is being regenerated, so
+// that ambiguous signatures are noticed before their offsets get cached. Missing signatures are
+// only searched for close matches at the same times.
+pub(crate) fn should_count_matches() -> bool {
+    cfg!(debug_assertions) || crate::signature_cache::is_regenerating()
+}
+
+/// Log every offset that a hook's signature matched if it matched more than once, unless the hook
+/// was declared with `allow_multiple = true`. Returns false if the signature is ambiguous.
+pub fn check_signature_matches(name: &str, pattern: &str, matches: &[usize]) -> bool {
+    if matches.len() < 2 || crate::hook_registry::get_hook(name).is_some_and(|r| r.allows_multiple()) {
+        return true;
+    }
+    let offsets: Vec<String> = matches.iter().map(|m| format!("0x{:x}", m)).collect();
+    crate::logln!(Warning, "Signature for {} matched {} times, using the first match. Use allow_multiple = true if this is expected. Pattern: {}, Offsets: {}",
+        name, matches.len(), pattern, offsets.join(", "));
+    false
+}
+
+/// Called by the generated C# after every hook has been registered. Writes the offset of each
+/// signature relative to the start of the executable into `offsets`, or -1 if the signature is
+/// invalid or couldn't be found. Returns the number of signatures that were found.
+///
+/// # Safety
+///
+/// `names` and `patterns` must each point to `count` valid null terminated strings, and `offsets`
+/// must be valid for writes of `count` values.
+#[no_mangle]
+pub unsafe extern "C" fn scan_ffi_signatures(names: *const *const c_char, patterns: *const *const c_char, count: usize, offsets: *mut isize) -> usize {
+    let mut scanner = BatchScanner::new();
+    // Index of each valid pattern within the batch
+    let mut indices = Vec::with_capacity(count);
+    for i in 0..count {
+        let text = CStr::from_ptr(*patterns.add(i)).to_string_lossy();
+        match Pattern::new(&text) {
+            Ok(p) => indices.push(Some(scanner.add(p))),
+            Err(e) => {
+                crate::logln!(Error, "Signature '{}' is invalid: {}", text, e);
+                indices.push(None);
+            }
+        }
+    }
+    let start = std::time::Instant::now();
+    let count_matches = should_count_matches();
+    let results = scanner.scan_executable_inner(count_matches);
+    crate::logln!(Debug, "Scanned for {} signatures in {} ms", scanner.len(), start.elapsed().as_millis());
+    let offsets = std::slice::from_raw_parts_mut(offsets, count);
+    let mut found = 0;
+    for (i, (offset, index)) in offsets.iter_mut().zip(indices).enumerate() {
+        let matches = match index {
+            Some(v) => &results[v][..],
+            None => &[]
+        };
+        if count_matches {
+            let name = CStr::from_ptr(*names.add(i)).to_string_lossy();
+            let pattern = CStr::from_ptr(*patterns.add(i)).to_string_lossy();
+            check_signature_matches(&name, &pattern, matches);
+        }
+        *offset = match matches.first() {
+            Some(v) => { found += 1; *v as isize },
+            None => -1
+        };
+    }
+    found
+}
+
+/// Called by the generated C# when the startup scanner finds a signature. In debug builds or
+/// while the signature cache is being regenerated, this scans the executable again to check that
+/// the signature only matches once. Returns false if the signature is ambiguous.
+///
+/// # Safety
+///
+/// `name` and `pattern` must be valid null terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn check_ffi_signature_matches(name: *const c_char, pattern: *const c_char) -> bool {
+    if !should_count_matches() { return true; }
+    let name = CStr::from_ptr(name).to_string_lossy();
+    let text = CStr::from_ptr(pattern).to_string_lossy();
+    let mut scanner = BatchScanner::new();
+    match Pattern::new(&text) {
+        Ok(p) => scanner.add(p),
+        Err(_) => return true
+    };
+    check_signature_matches(&name, &text, &scanner.scan_executable_all()[0])
+}
@@ -0,0 +1,57 @@
+#![cfg(test)]
+use crate::pattern::Pattern;
+use crate::pattern_tests::get_test_data;
+use crate::sigscan_batch::BatchScanner;
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn batch_scan_matches_single() -> ReturnType {
+    let data = get_test_data(10000);
+    let texts = ["48 8B 05", "00 FF ?? ?? 48", "8B ?? ?? ?? ?? ?? ?? ?? ?? ?? ?? 00", "05 05 05 05 05 05 05 05 05"];
+    // Small chunks so that matches cross chunk boundaries
+    for (threads, chunk_size) in [(1, 7), (4, 7), (4, 64), (16, 0x100000)] {
+        let mut scanner = BatchScanner::new().with_threads(threads).with_chunk_size(chunk_size);
+        for text in texts { scanner.add(Pattern::new(text)?); }
+        let expected: Vec<Option<usize>> = scanner.get_patterns().iter().map(|p| p.find(&data)).collect();
+        assert_eq!(scanner.scan(&data), expected, "{} threads with {} byte chunks", threads, chunk_size);
+    }
+    assert_eq!(BatchScanner::new().scan(&data), vec![]);
+    Ok(())
+}
+
+#[test]
+fn batch_scan_all_matches_single() -> ReturnType {
+    let data = get_test_data(10000);
+    let texts = ["48 8B 05", "00 FF ?? ?? 48", "05 05 05 05 05 05 05 05 05"];
+    for (threads, chunk_size) in [(1, 7), (4, 7), (4, 64), (16, 0x100000)] {
+        let mut scanner = BatchScanner::new().with_threads(threads).with_chunk_size(chunk_size);
+        for text in texts { scanner.add(Pattern::new(text)?); }
+        let expected: Vec<Vec<usize>> = scanner.get_patterns().iter().map(|p| p.find_all(&data)).collect();
+        assert!(expected[0].len() > 1);
+        assert_eq!(scanner.scan_all(&data), expected, "{} threads with {} byte chunks", threads, chunk_size);
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+#[test]
+fn batch_ambiguous_signature() -> ReturnType {
+    use crate::hook_registry;
+    use crate::logger::LogLevel;
+    use crate::sigscan_batch::check_signature_matches;
+    use crate::testing::Host;
+    use std::ffi::CString;
+    let host = Host::install();
+    assert!(check_signature_matches("unique_hook", "48 8B 05", &[0x1000]));
+    assert!(!check_signature_matches("ambiguous_hook", "48 8B 05", &[0x1000, 0x2400, 0x3800]));
+    let name = CString::new("multiple_hook")?;
+    unsafe { assert!(hook_registry::set_ffi_hook_allow_multiple(name.as_ptr(), true)); }
+    assert!(hook_registry::get_hook("multiple_hook").unwrap().allows_multiple());
+    assert!(check_signature_matches("multiple_hook", "48 8B 05", &[0x1000, 0x2400]));
+    let log = host.get_log();
+    let entry = log.iter().find(|e| e.text.starts_with("Signature for ambiguous_hook matched 3 times")).unwrap();
+    assert!(entry.text.ends_with("Offsets: 0x1000, 0x2400, 0x3800"));
+    assert_eq!(entry.color, LogLevel::Warning.get_log_color());
+    assert!(!log.iter().any(|e| e.text.contains("unique_hook") || e.text.contains("multiple_hook")));
+    Ok(())
+}
@@ -7,8 +7,13 @@
 //! will be returned.
 //! Currently, only the main module (executable) is supported.
 
-use crate::address::ProcessInfo;
+use crate::{
+    address::ProcessInfo,
+    instruction_decoder::{ self, Instruction, MAX_INSTRUCTION_LENGTH, MAX_RIP_RELATIVE_SEARCH },
+    thunk::{ self, ThunkMemory, ThunkResolution }
+};
 use std::{
+    marker::PhantomData,
     ptr::NonNull,
     sync::OnceLock
 };
@@ -67,50 +72,32 @@ unsafe fn deref_int_relative_pointer_unchecked(p: *mut u8) -> *mut u8 {
 }
 
 
-#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
-unsafe fn deref_instruction_pointer_short<B>(p: *mut u8) -> CanNull 
-where B: PointerBounds {
-    try_deref_instruction_pointer::<B>(p.offset((*p.add(1) as i8 + 2) as isize))
-}
-
-#[cfg(target_arch = "x86_64")]
-unsafe fn deref_instruction_pointer_near<B>(p: *mut u8) -> CanNull 
-where B: PointerBounds { 
-    try_deref_instruction_pointer::<B>(deref_int_relative_pointer_unchecked(p))
-}
-
-unsafe fn deref_long_absolute_pointer_unchecked(p: *mut u8) -> *mut u8 {
-    let abs = std::ptr::read_unaligned::<i64>(p as *const i64);
-    abs as *mut u8
-}
+// Reads thunks from the current process's memory. Only created by unsafe resolvers, which
+// require the addresses they're given to be readable.
+struct ProcessMemory<B>(PhantomData<B>);
 
-#[cfg(target_arch = "x86_64")]
-unsafe fn deref_instruction_pointer_long<B>(p: *mut u8) -> CanNull 
+impl<B> ThunkMemory for ProcessMemory<B>
 where B: PointerBounds {
-    try_deref_instruction_pointer::<B>(deref_long_absolute_pointer_unchecked(p))    
+    fn read(&self, address: usize, buf: &mut [u8]) -> bool {
+        unsafe { std::ptr::copy_nonoverlapping(address as *const u8, buf.as_mut_ptr(), buf.len()) };
+        true
+    }
+    fn read_pointer(&self, address: usize) -> Option<usize> {
+        Some(unsafe { std::ptr::read_unaligned(address as *const usize) })
+    }
+    fn in_bounds(&self, address: usize) -> bool {
+        unsafe { B::in_bounds(address as *mut u8) }
+    }
 }
 
-#[cfg(target_arch = "x86_64")]
-unsafe fn try_deref_instruction_pointer<B>(p: *mut u8) -> CanNull 
-where B: PointerBounds {
-    // If the pointer is outside of the executable, return a null pointer
-    if !B::in_bounds(p) { return None; }
-    match *p {
-        0xeb => deref_instruction_pointer_short::<B>(p),
-        0xe9 => deref_instruction_pointer_near::<B>(p.add(1)),
-        0xff => {
-            // *4 (Jump near, absolute indirect)
-            // *5 (Jump far, absolute indirect)
-            let second_byte = *p.add(1) & 0xf;
-            if second_byte == 5 {
-                // 2 bytes for instruction + 4 bytes for ????
-                deref_instruction_pointer_long::<B>(p.add(6))
-            } else {
-                NonNull::new(p)
-            }
-        },
-        _ => NonNull::new(p)
-    }
+/// Follow jumps from `addr` until reaching an instruction that isn't a jump, and report which
+/// kinds of thunk were followed. `get_address_may_thunk` and `get_address_may_thunk_absolute`
+/// return the address from this. See the `thunk` module for the jumps that are recognized.
+///
+/// # Safety
+/// `addr` and any address that a jump leads to must be readable.
+pub unsafe fn resolve_thunks(addr: *mut u8) -> Option<ThunkResolution> {
+    thunk::follow_thunks(&ProcessMemory::<PointerUnbounded>(PhantomData), addr as usize)
 }
 
 /// Get an absolute executable address given an offset. Assumes that the sigscanner library 
@@ -134,15 +121,68 @@ pub unsafe extern "C" fn get_address(ofs: usize) -> NonNull<u8> {
 /// reaches a non-jump. As of Reloaded-II's hooking library, hooks crash on thunked functions, so
 /// this provides a convenient method to work around that. If resolve_type is not specified in
 /// riri_hook, this resolver is used by default. Additionally, #[cpp_class] uses this when
-/// hooking functions from a vtable pointer.
+/// hooking functions from a vtable pointer. Use `resolve_thunks` to see which jumps were
+/// followed.
 #[no_mangle]
 pub unsafe extern "C" fn get_address_may_thunk(ofs: usize) -> CanNull {
-    try_deref_instruction_pointer::<PointerUnbounded>(base_address().add(ofs))
+    get_address_may_thunk_absolute(base_address().add(ofs) as usize)
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn get_address_may_thunk_absolute(addr: usize) -> CanNull {
-    try_deref_instruction_pointer::<PointerUnbounded>(addr as *mut u8)
+    resolve_thunks(addr as *mut u8).and_then(|v| NonNull::new(v.get_address() as *mut u8))
+}
+
+// Decode the instruction at p. The slice covers the longest possible instruction, so it can
+// extend past the end of the instruction.
+unsafe fn decode_instruction<'a>(p: *mut u8) -> Option<(Instruction, &'a [u8])> {
+    let code = std::slice::from_raw_parts(p as *const u8, MAX_INSTRUCTION_LENGTH);
+    instruction_decoder::decode(code).ok().map(|v| (v, code))
+}
+
+/// Resolve a RIP-relative memory operand or a relative branch target using the instruction
+/// decoder, so the position of the displacement doesn't need to be known ahead of time. Prefixes
+/// and immediates after the displacement are accounted for. `operand_index` selects which relative
+/// operand to resolve, starting with the first instruction at `addr`. A signature for
+/// `mov rax, [rip+x]; call y` could resolve `x` with an index of 0 and `y` with an index of 1.
+/// Instructions without a relative operand are skipped. Returns None if the operand isn't within
+/// the next 16 instructions or an instruction couldn't be decoded.
+///
+/// # Safety
+/// `addr` must point to readable code, with at least 15 readable bytes after each instruction
+/// that's decoded.
+#[no_mangle]
+pub unsafe extern "C" fn resolve_rip_relative(addr: *mut u8, operand_index: usize) -> CanNull {
+    let mut p = addr;
+    let mut remaining = operand_index;
+    for _ in 0..MAX_RIP_RELATIVE_SEARCH {
+        let (instruction, code) = decode_instruction(p)?;
+        if let Some(operand) = instruction.get_relative_operand() {
+            if remaining == 0 {
+                let target = p.add(instruction.get_length()).offset(operand.read(code) as isize);
+                return match PointerUnbounded::in_bounds(target) {
+                    true => NonNull::new(target),
+                    false => None
+                };
+            }
+            remaining -= 1;
+        }
+        p = p.add(instruction.get_length());
+    }
+    None
+}
+
+// The get_indirect_address_* resolvers read the near pointer at a fixed position within the
+// instruction at p. If the decoded instruction's relative operand is at that position, the target
+// is relative to the end of the instruction so that immediates after it are accounted for.
+// Otherwise it's relative to the end of the near pointer, which keeps signatures that don't start
+// at an instruction working.
+unsafe fn get_indirect_address_at(p: *mut u8, operand: usize) -> CanNull {
+    let decoded = decode_instruction(p).and_then(|(v, _)| v.get_relative_operand());
+    match decoded {
+        Some(v) if v.get_offset() == operand => resolve_rip_relative(p, 0),
+        _ => deref_int_relative_pointer::<PointerUnbounded>(p.add(operand))
+    }
 }
 
 /// Function to dereference an arbitrary near pointer, where the pointee is an address within the
@@ -150,12 +190,15 @@ pub unsafe extern "C" fn get_address_may_thunk_absolute(addr: usize) -> CanNull
 /// the second byte of the target instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_short(ofs: usize) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(base_address().add(ofs + 1))
+    get_indirect_address_at(base_address().add(ofs), 1)
 }
 
+/// # Safety
+/// `addr` must point to readable code. Up to 15 bytes are read from `addr` to decode the
+/// instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_short_abs(addr: *mut u8) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(addr.add(1))
+    get_indirect_address_at(addr, 1)
 }
 
 /// Function to dereference an arbitrary near pointer, where the pointee is an address within the
@@ -163,12 +206,15 @@ pub unsafe extern "C" fn get_indirect_address_short_abs(addr: *mut u8) -> CanNul
 /// the third byte of the target instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_short2(ofs: usize) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(base_address().add(ofs + 2))
+    get_indirect_address_at(base_address().add(ofs), 2)
 }
 
+/// # Safety
+/// `addr` must point to readable code. Up to 15 bytes are read from `addr` to decode the
+/// instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_short2_abs(addr: *mut u8) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(addr.add(2))
+    get_indirect_address_at(addr, 2)
 }
 
 /// Function to dereference an arbitrary near pointer, where the pointee is an address within the
@@ -176,12 +222,15 @@ pub unsafe extern "C" fn get_indirect_address_short2_abs(addr: *mut u8) -> CanNu
 /// the fourth byte of the target instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_long(ofs: usize) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(base_address().add(ofs + 3))
+    get_indirect_address_at(base_address().add(ofs), 3)
 }
 
+/// # Safety
+/// `addr` must point to readable code. Up to 15 bytes are read from `addr` to decode the
+/// instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_long_abs(addr: *mut u8) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(addr.add(3))
+    get_indirect_address_at(addr, 3)
 }
 
 /// Function to dereference an arbitrary near pointer, where the pointee is an address within the
@@ -189,12 +238,15 @@ pub unsafe extern "C" fn get_indirect_address_long_abs(addr: *mut u8) -> CanNull
 /// the fifth byte of the target instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_long4(ofs: usize) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(base_address().add(ofs + 4))
+    get_indirect_address_at(base_address().add(ofs), 4)
 }
 
+/// # Safety
+/// `addr` must point to readable code. Up to 15 bytes are read from `addr` to decode the
+/// instruction.
 #[no_mangle]
 pub unsafe extern "C" fn get_indirect_address_long4_abs(addr: *mut u8) -> CanNull {
-    deref_int_relative_pointer::<PointerUnbounded>(addr.add(4))
+    get_indirect_address_at(addr, 4)
 }
 
 /// Get the hash of the target executable. Useful for selectively applying signatures by hash
@@ -210,5 +262,5 @@ pub unsafe extern "C" fn get_executable_hash() -> u64 {
 #[no_mangle]
 #[cfg(not(feature = "reloaded"))]
 pub unsafe extern "C" fn get_executable_hash() -> u64 {
-    u64::MAX
+    crate::standalone::get_executable_hash()
 }
\ No newline at end of file
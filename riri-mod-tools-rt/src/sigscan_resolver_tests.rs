@@ -0,0 +1,67 @@
+#![cfg(test)]
+use crate::sigscan_resolver::{
+    get_indirect_address_short_abs,
+    get_indirect_address_short2_abs,
+    get_address_may_thunk_absolute,
+    resolve_rip_relative,
+    resolve_thunks
+};
+use crate::thunk::ThunkKind;
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// 0x0: cmp dword [rip+0x10], 1; 0x7: call 0x20; 0x28: add eax, 4
+fn get_test_code() -> Vec<u8> {
+    let mut code = vec![0xccu8; 0x50];
+    code[0..7].copy_from_slice(&[0x83, 0x3d, 0x10, 0, 0, 0, 0x01]);
+    code[7..0xc].copy_from_slice(&[0xe8, 0x14, 0, 0, 0]);
+    code[0x28..0x2d].copy_from_slice(&[0x05, 0x04, 0, 0, 0]);
+    code
+}
+
+#[test]
+fn resolve_rip_relative_operands() -> ReturnType {
+    let mut code = get_test_code();
+    let p = code.as_mut_ptr();
+    unsafe {
+        // The displacement is relative to the end of the instruction, not the end of itself
+        assert_eq!(resolve_rip_relative(p, 0).map(|v| v.as_ptr()), Some(p.add(0x17)));
+        assert_eq!(resolve_rip_relative(p, 1).map(|v| v.as_ptr()), Some(p.add(0x20)));
+        assert_eq!(resolve_rip_relative(p.add(7), 0).map(|v| v.as_ptr()), Some(p.add(0x20)));
+        assert_eq!(resolve_rip_relative(p, 2), None);
+    }
+    Ok(())
+}
+
+#[test]
+fn resolve_indirect_address_wrappers() -> ReturnType {
+    let mut code = get_test_code();
+    let p = code.as_mut_ptr();
+    unsafe {
+        assert_eq!(get_indirect_address_short2_abs(p).map(|v| v.as_ptr()), Some(p.add(0x17)));
+        assert_eq!(get_indirect_address_short_abs(p.add(7)).map(|v| v.as_ptr()), Some(p.add(0x20)));
+        // The near pointer is read from the resolver's position, even if the relative operand is
+        // somewhere else
+        assert_eq!(get_indirect_address_short_abs(p).map(|v| v.as_ptr()), Some(p.wrapping_add(0x1042)));
+        // Without a relative operand, the near pointer is read from a fixed position
+        assert_eq!(get_indirect_address_short_abs(p.add(0x28)).map(|v| v.as_ptr()), Some(p.add(0x31)));
+    }
+    Ok(())
+}
+
+#[test]
+fn resolve_thunks_in_memory() -> ReturnType {
+    let mut code = get_test_code();
+    // 0x30: jmp short 0x34, 0x34: jmp [rip+0] with the address of 0x7 after it
+    code[0x30..0x32].copy_from_slice(&[0xeb, 0x02]);
+    code[0x34..0x3a].copy_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+    let p = code.as_mut_ptr();
+    unsafe {
+        std::ptr::write_unaligned(p.add(0x3a) as *mut usize, p.add(7) as usize);
+        let resolution = resolve_thunks(p.add(0x30)).unwrap();
+        assert_eq!(resolution.get_address(), p.add(7) as usize);
+        assert_eq!(resolution.get_thunks(), [ThunkKind::ShortJump, ThunkKind::Indirect]);
+        // call isn't a thunk
+        assert_eq!(get_address_may_thunk_absolute(p.add(0x30) as usize).map(|v| v.as_ptr()), Some(p.add(7)));
+    }
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Runtime services for when the crate is used outside of Reloaded-II (without the `reloaded`
+//! feature), such as from an injector or a command line tool.
+//!
+//! - Log messages are written to stderr, colored using the message's [`LogColor`].
+//! - The mod and config directories are taken from [`StandaloneBuilder`], or from the
+//!   `RIRI_MOD_DIRECTORY` and `RIRI_CONFIG_DIRECTORY` environment variables. Otherwise, the
+//!   directory containing the executable is used.
+//! - Signature scanning and executable hashing are done natively.
+//!
+//! If the standalone settings aren't installed through [`StandaloneBuilder::install`], they're
+//! read from the environment the first time they're needed.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::{ Display, Formatter };
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use std::sync::OnceLock;
+use crate::logger::{ LogColor, LogLevel };
+
+pub const MOD_DIRECTORY_VAR: &str = "RIRI_MOD_DIRECTORY";
+pub const CONFIG_DIRECTORY_VAR: &str = "RIRI_CONFIG_DIRECTORY";
+pub const LOG_PREFIX_VAR: &str = "RIRI_LOG_PREFIX";
+
+static STANDALONE: OnceLock<Standalone> = OnceLock::new();
+static EXECUTABLE_HASH: OnceLock<u64> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StandaloneError {
+    AlreadyInstalled
+}
+
+impl Display for StandaloneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyInstalled => write!(f, "Standalone settings have already been installed")
+        }
+    }
+}
+
+impl Error for StandaloneError {}
+
+#[derive(Debug)]
+pub struct Standalone {
+    mod_directory: PathBuf,
+    config_directory: PathBuf,
+    log_prefix: Option<String>,
+    log_color: bool
+}
+
+impl Standalone {
+    /// Get the installed settings, or read them from the environment if they haven't been
+    /// installed yet.
+    pub fn get() -> &'static Standalone {
+        STANDALONE.get_or_init(|| StandaloneBuilder::new().build())
+    }
+
+    pub fn get_mod_directory(&self) -> &Path { &self.mod_directory }
+    pub fn get_config_directory(&self) -> &Path { &self.config_directory }
+    pub fn get_log_prefix(&self) -> Option<&str> { self.log_prefix.as_deref() }
+}
+
+/// Configures the standalone runtime. Any setting that isn't set falls back to its
+/// environment variable.
+#[derive(Debug, Default)]
+pub struct StandaloneBuilder {
+    mod_directory: Option<PathBuf>,
+    config_directory: Option<PathBuf>,
+    log_prefix: Option<String>,
+    log_color: Option<bool>
+}
+
+impl StandaloneBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn mod_directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.mod_directory = Some(path.into());
+        self
+    }
+
+    pub fn config_directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config_directory = Some(path.into());
+        self
+    }
+
+    /// The name shown before each message that's logged with a prefix.
+    pub fn log_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.log_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Enable or disable ANSI colors in log output. By default, colors are used unless the
+    /// `NO_COLOR` environment variable is set.
+    pub fn log_color(mut self, enabled: bool) -> Self {
+        self.log_color = Some(enabled);
+        self
+    }
+
+    pub(crate) fn build(self) -> Standalone {
+        self.build_with(|name| std::env::var_os(name))
+    }
+
+    /// Build the settings, using `env` to look up environment variables for any setting that
+    /// isn't set.
+    pub(crate) fn build_with<F: Fn(&str) -> Option<OsString>>(self, env: F) -> Standalone {
+        let mod_directory = self.mod_directory
+            .or_else(|| env(MOD_DIRECTORY_VAR).map(PathBuf::from))
+            .or_else(|| std::env::current_exe().ok().and_then(|p| p.parent().map(|p| p.to_owned())))
+            .unwrap_or_default();
+        let config_directory = self.config_directory
+            .or_else(|| env(CONFIG_DIRECTORY_VAR).map(PathBuf::from))
+            .unwrap_or_else(|| mod_directory.clone());
+        Standalone {
+            mod_directory,
+            config_directory,
+            log_prefix: self.log_prefix.or_else(|| env(LOG_PREFIX_VAR).and_then(|v| v.into_string().ok())),
+            log_color: self.log_color.unwrap_or_else(|| env("NO_COLOR").is_none())
+        }
+    }
+
+    /// Use these settings for the rest of the program. This must be called before anything
+    /// that reads the settings, such as logging.
+    pub fn install(self) -> Result<&'static Standalone, StandaloneError> {
+        let mut installed = false;
+        let result = STANDALONE.get_or_init(|| {
+            installed = true;
+            self.build()
+        });
+        match installed {
+            true => Ok(result),
+            false => Err(StandaloneError::AlreadyInstalled)
+        }
+    }
+}
+
+pub fn get_directory_for_mod() -> PathBuf {
+    Standalone::get().mod_directory.clone()
+}
+
+pub fn get_config_directory() -> PathBuf {
+    Standalone::get().config_directory.clone()
+}
+
+/// Find a pattern within `len` bytes from `start`. Equivalent to
+/// `mod_loader_data::find_pattern` in Reloaded-II mods.
+///
+/// # Safety
+///
+/// `start` must be valid for reads of `len` bytes.
+pub unsafe fn find_pattern(text: &str, start: *const u8, len: u32) -> *const u8 {
+    crate::pattern::find_pattern_raw(text, start, len as usize)
+}
+
+/// Get the xxh3 hash of the current executable, calculated the same way as the Reloaded
+/// runtime. This reads the entire file the first time it's called.
+pub fn get_executable_hash() -> u64 {
+    *EXECUTABLE_HASH.get_or_init(|| {
+        std::env::current_exe().and_then(std::fs::read)
+            .map_or(u64::MAX, |v| twox_hash::XxHash3_64::oneshot(v.as_slice()))
+    })
+}
+
+/// Write a message to stderr. This is used in place of the Reloaded logger when it hasn't been
+/// set.
+pub(crate) fn write_log(text: &str, color: LogColor, level: LogLevel, show_prefix: bool, newline: bool) {
+    let settings = Standalone::get();
+    let mut stderr = std::io::stderr().lock();
+    if settings.log_color {
+        _ = write!(stderr, "\x1b[38;2;{};{};{}m", color.get_red(), color.get_green(), color.get_blue());
+    }
+    if show_prefix {
+        _ = match &settings.log_prefix {
+            Some(p) => write!(stderr, "[{}] [{}] ", p, level),
+            None => write!(stderr, "[{}] ", level)
+        };
+    }
+    _ = write!(stderr, "{}", text);
+    if settings.log_color {
+        _ = write!(stderr, "\x1b[0m");
+    }
+    if newline {
+        _ = writeln!(stderr);
+    }
+}
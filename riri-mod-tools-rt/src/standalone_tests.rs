@@ -0,0 +1,67 @@
+#![cfg(test)]
+use crate::standalone::{ self, Standalone, StandaloneBuilder, StandaloneError };
+use std::path::Path;
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn standalone_builder_settings() -> ReturnType {
+    let settings = StandaloneBuilder::new()
+        .mod_directory("mods/test")
+        .config_directory("config/test")
+        .log_prefix("test-tool")
+        .log_color(false)
+        .build();
+    assert_eq!(settings.get_mod_directory(), Path::new("mods/test"));
+    assert_eq!(settings.get_config_directory(), Path::new("config/test"));
+    assert_eq!(settings.get_log_prefix(), Some("test-tool"));
+    Ok(())
+}
+
+#[test]
+fn standalone_environment() -> ReturnType {
+    let env = |vars: &'static [(&'static str, &'static str)]| move |name: &str| vars.iter()
+        .find(|(k, _)| *k == name).map(|(_, v)| std::ffi::OsString::from(v));
+    let settings = StandaloneBuilder::new().build_with(env(&[
+        (standalone::MOD_DIRECTORY_VAR, "env/mod"),
+        (standalone::LOG_PREFIX_VAR, "env-tool")
+    ]));
+    assert_eq!(settings.get_mod_directory(), Path::new("env/mod"));
+    assert_eq!(settings.get_log_prefix(), Some("env-tool"));
+    // The config directory defaults to the mod directory
+    assert_eq!(settings.get_config_directory(), Path::new("env/mod"));
+    let vars = env(&[(standalone::MOD_DIRECTORY_VAR, "env/mod"), (standalone::CONFIG_DIRECTORY_VAR, "env/config")]);
+    assert_eq!(StandaloneBuilder::new().build_with(vars).get_config_directory(), Path::new("env/config"));
+    // Values from the builder take priority
+    assert_eq!(StandaloneBuilder::new().mod_directory("builder").build_with(vars).get_mod_directory(), Path::new("builder"));
+    // Without either, the executable's directory is used
+    let exe = std::env::current_exe()?;
+    assert_eq!(StandaloneBuilder::new().build_with(env(&[])).get_mod_directory(), exe.parent().unwrap());
+    Ok(())
+}
+
+#[test]
+fn standalone_install() -> ReturnType {
+    // Another test may have read the settings first, which installs them from the environment
+    let first = StandaloneBuilder::new().log_prefix("standalone-test").install();
+    assert_eq!(StandaloneBuilder::new().install().unwrap_err(), StandaloneError::AlreadyInstalled);
+    if let Ok(v) = first {
+        assert!(std::ptr::eq(v, Standalone::get()));
+        assert_eq!(v.get_log_prefix(), Some("standalone-test"));
+    }
+    assert_eq!(standalone::get_directory_for_mod(), Standalone::get().get_mod_directory());
+    assert_eq!(standalone::get_config_directory(), Standalone::get().get_config_directory());
+    crate::logln!(Information, "standalone log {}", 1);
+    Ok(())
+}
+
+#[test]
+fn standalone_native_services() -> ReturnType {
+    let exe = std::fs::read(std::env::current_exe()?)?;
+    assert_eq!(standalone::get_executable_hash(), twox_hash::XxHash3_64::oneshot(&exe));
+    let code = [0xcc, 0x48, 0x8b, 0x05, 0x10, 0x20, 0x30, 0x40, 0xc3];
+    unsafe {
+        assert_eq!(standalone::find_pattern("8B 05 ?? ?? ?? ?? C3", code.as_ptr(), code.len() as u32), code.as_ptr().add(2));
+        assert!(standalone::find_pattern("0F 0B", code.as_ptr(), code.len() as u32).is_null());
+    }
+    Ok(())
+}
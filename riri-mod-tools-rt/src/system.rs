@@ -22,13 +22,35 @@ use crate::interop::{InteropError, ObjectHash, ObjectInitializable, ObjectValuab
 // riri_mod_tools_rt::system::Double = 0xdd6aef94114ecd43
 // riri_mod_tools_rt::system::String = 0xd17d6432bd7c2cc9
 
+/// An owned GCHandle to a C# object. The handle is freed when this is dropped.
+///
+/// Cloning an `Object` allocates a new GCHandle to the same C# object, so each clone can be
+/// dropped independently. GCHandles can be used and freed from any thread, so `Object` is
+/// `Send` and `Sync`, allowing it to be stored in a static or shared between threads.
 #[derive(Debug)]
 pub struct Object(usize);
 
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Object>();
+    assert_send_sync::<WeakObject>();
+};
+
 impl Object {
     pub unsafe fn new_unchecked(ptr: usize) -> Self {
         Self(ptr)
     }
+
+    /// Create a weak handle to this object, which doesn't keep the C# object alive.
+    pub fn downgrade(&self) -> WeakObject {
+        WeakObject(unsafe { crate::interop::downgrade_object(**self) })
+    }
+}
+
+impl Clone for Object {
+    fn clone(&self) -> Self {
+        Self(unsafe { crate::interop::clone_object(**self) })
+    }
 }
 
 impl Deref for Object {
@@ -44,7 +66,27 @@ impl Drop for Object {
     }
 }
 
+/// A weak GCHandle to a C# object, useful for caching singletons without keeping them alive.
 #[derive(Debug)]
+pub struct WeakObject(usize);
+
+impl WeakObject {
+    /// Get a strong handle to the object, or `None` if it has already been collected.
+    pub fn upgrade(&self) -> Option<Object> {
+        match unsafe { crate::interop::upgrade_object(self.0) } {
+            0 => None,
+            v => Some(Object(v))
+        }
+    }
+}
+
+impl Drop for WeakObject {
+    fn drop(&mut self) {
+        unsafe { crate::interop::free_object(self.0) }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Int8(Object);
 
 impl Deref for Int8 {
@@ -88,7 +130,7 @@ impl ObjectInitializable for Int8 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UInt8(Object);
 
 impl Deref for UInt8 {
@@ -132,7 +174,7 @@ impl ObjectInitializable for UInt8 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Int16(Object);
 
 impl Deref for Int16 {
@@ -176,7 +218,7 @@ impl ObjectInitializable for Int16 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UInt16(Object);
 
 impl Deref for UInt16 {
@@ -220,7 +262,7 @@ impl ObjectInitializable for UInt16 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Int32(Object);
 
 impl Deref for Int32 {
@@ -264,7 +306,7 @@ impl ObjectInitializable for Int32 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UInt32(Object);
 
 impl Deref for UInt32 {
@@ -308,7 +350,7 @@ impl ObjectInitializable for UInt32 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Int64(Object);
 
 impl Deref for Int64 {
@@ -352,7 +394,7 @@ impl ObjectInitializable for Int64 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UInt64(Object);
 
 impl Deref for UInt64 {
@@ -396,7 +438,7 @@ impl ObjectInitializable for UInt64 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Bool(Object);
 
 impl Deref for Bool {
@@ -440,7 +482,7 @@ impl ObjectInitializable for Bool {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Float(Object);
 
 impl Deref for Float {
@@ -484,7 +526,7 @@ impl ObjectInitializable for Float {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Double(Object);
 
 impl Deref for Double {
@@ -530,12 +572,12 @@ impl ObjectInitializable for Double {
 
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
-struct StringData {
-    ptr: *const u8,
-    len: usize
+pub(crate) struct StringData {
+    pub(crate) ptr: *const u8,
+    pub(crate) len: usize
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct String<'a> {
     handle: Object,
     lifetime: PhantomData<&'a Object>
@@ -594,9 +636,9 @@ impl<'a> ObjectInitializable for String<'a> {
 
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
-struct ArrayData {
-    value_type: u64,
-    len: usize
+pub(crate) struct ArrayData {
+    pub(crate) value_type: u64,
+    pub(crate) len: usize
 }
 
 #[derive(Debug)]
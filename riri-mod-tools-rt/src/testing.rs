@@ -0,0 +1,720 @@
+//! An in-process stand-in for the C# side of a Reloaded-II mod, so that mod logic and hook
+//! payloads can be tested with `cargo test` without loading the mod into .NET.
+//!
+//! Every runtime facility that normally relies on a callback from the generated C# code (the
+//! logger, mod loader directories, pattern scanning and C# interop) is given a Rust
+//! implementation when [`Host::install`] is called. Since those callbacks can only be set once,
+//! the host is shared between every test in the same process. Callbacks never panic, since that
+//! would abort the whole test binary: errors such as calling a C# method that hasn't been
+//! registered are recorded in [`Host::get_errors`] and the callback returns a failure value.
+//!
+//! ```
+//! use riri_mod_tools_rt::{ logln, testing::Host };
+//! let host = Host::install();
+//! logln!(Information, "Hello from a test!");
+//! assert!(host.get_log().iter().any(|e| e.text == "Hello from a test!"));
+//! ```
+
+use std::path::{ Path, PathBuf };
+use std::sync::{ Mutex, OnceLock, PoisonError };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use crate::logger::{ LogColor, LogLevel };
+#[cfg(feature = "reloaded")]
+use std::any::Any;
+#[cfg(feature = "reloaded")]
+use std::collections::{ HashMap, HashSet };
+#[cfg(feature = "reloaded")]
+use std::sync::Arc;
+#[cfg(feature = "reloaded")]
+use crate::interop::{ ObjectHash, ObjectInitializer };
+#[cfg(feature = "reloaded")]
+use crate::system::Object;
+
+/// A message that was written to the host's logger.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub text: String,
+    pub color: LogColor,
+    pub level: LogLevel,
+    pub newline: bool,
+    pub show_prefix: bool
+}
+
+/// A value stored in the host's object store. This takes the place of a C# object that a
+/// GCHandle points to.
+#[cfg(feature = "reloaded")]
+#[derive(Debug, Clone)]
+pub enum HostValue {
+    Null,
+    Bool(bool),
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Array(Arc<Mutex<Vec<HostValue>>>),
+    Delegate(Arc<HostDelegate>),
+    Custom(Arc<dyn Any + Send + Sync>)
+}
+
+#[cfg(feature = "reloaded")]
+impl HostValue {
+    /// Get the default value for a type, matching what C# would put into a new array.
+    fn default_for_type(hash: u64) -> Self {
+        match hash {
+            crate::system::Bool::HASH => Self::Bool(false),
+            crate::system::Int8::HASH => Self::Int8(0),
+            crate::system::UInt8::HASH => Self::UInt8(0),
+            crate::system::Int16::HASH => Self::Int16(0),
+            crate::system::UInt16::HASH => Self::UInt16(0),
+            crate::system::Int32::HASH => Self::Int32(0),
+            crate::system::UInt32::HASH => Self::UInt32(0),
+            crate::system::Int64::HASH => Self::Int64(0),
+            crate::system::UInt64::HASH => Self::UInt64(0),
+            crate::system::Float::HASH => Self::Float(0.),
+            crate::system::Double::HASH => Self::Double(0.),
+            _ => Self::Null
+        }
+    }
+}
+
+/// A delegate created from a Rust closure. The closure is freed once the last reference to the
+/// delegate is dropped, in the same way that it would be once C# garbage collects it.
+#[cfg(feature = "reloaded")]
+#[derive(Debug)]
+pub struct HostDelegate {
+    type_hash: u64,
+    context: usize
+}
+
+#[cfg(feature = "reloaded")]
+impl HostDelegate {
+    pub fn get_type_hash(&self) -> u64 { self.type_hash }
+
+    /// Call the Rust closure with the given arguments.
+    pub fn invoke(&self, args: Vec<HostValue>) -> HostValue {
+        let host = Host::get();
+        let handles: Vec<usize> = args.into_iter().map(|v| host.alloc(v)).collect();
+        let result = unsafe { crate::event::invoke_rust_closure(self.context, handles.as_ptr(), handles.len()) };
+        match result {
+            0 => HostValue::Null,
+            v => {
+                let value = host.value_of(v).unwrap_or(HostValue::Null);
+                unsafe { host_free_object(v) };
+                value
+            }
+        }
+    }
+}
+
+#[cfg(feature = "reloaded")]
+impl Drop for HostDelegate {
+    fn drop(&mut self) {
+        unsafe { crate::event::free_rust_closure(self.context) }
+    }
+}
+
+/// A C# method implemented in Rust, called with the value of the object that the method was
+/// invoked on and the values of each parameter.
+#[cfg(feature = "reloaded")]
+pub type HostMethod = Box<dyn Fn(&HostValue, Vec<HostValue>) -> HostValue + Send + Sync>;
+
+#[cfg(feature = "reloaded")]
+#[derive(Debug)]
+struct HostTarget {
+    value: HostValue,
+    strong: usize
+}
+
+#[cfg(feature = "reloaded")]
+#[derive(Debug, Clone, Copy)]
+struct HostHandle {
+    target: usize,
+    weak: bool
+}
+
+#[cfg(feature = "reloaded")]
+#[derive(Default)]
+struct HostObjects {
+    next_id: usize,
+    handles: HashMap<usize, HostHandle>,
+    targets: HashMap<usize, HostTarget>,
+    singletons: HashMap<u64, usize>,
+    parameters: HashMap<(u64, u64), Vec<usize>>,
+    subscriptions: HashMap<usize, (usize, u64, Arc<HostDelegate>)>,
+    strings: HashMap<usize, Vec<u16>>
+}
+
+#[cfg(feature = "reloaded")]
+impl HostObjects {
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn add_handle(&mut self, target: usize, weak: bool) -> usize {
+        if !weak {
+            self.targets.get_mut(&target).unwrap().strong += 1;
+        }
+        let id = self.next_id();
+        self.handles.insert(id, HostHandle { target, weak });
+        id
+    }
+
+    fn add_target(&mut self, value: HostValue) -> usize {
+        let target = self.next_id();
+        self.targets.insert(target, HostTarget { value, strong: 0 });
+        self.add_handle(target, false)
+    }
+
+    fn get_target(&self, handle: usize) -> Result<usize, String> {
+        self.handles.get(&handle).map(|h| h.target)
+            .ok_or_else(|| format!("testing::Host: 0x{:x} isn't a valid object handle", handle))
+    }
+
+    fn value_of(&self, handle: usize) -> Option<HostValue> {
+        let handle = self.handles.get(&handle)?;
+        self.targets.get(&handle.target).map(|t| t.value.clone())
+    }
+
+    /// Remove a handle, returning the target's value if it's no longer reachable. This must be
+    /// dropped after the lock is released since it may own a Rust closure.
+    fn remove_handle(&mut self, handle: usize) -> Option<HostValue> {
+        let handle = self.handles.remove(&handle)?;
+        if handle.weak { return None; }
+        let target = self.targets.get_mut(&handle.target)?;
+        target.strong -= 1;
+        match target.strong {
+            0 => self.targets.remove(&handle.target).map(|t| t.value),
+            _ => None
+        }
+    }
+}
+
+/// An in-process emulation of the C# host. See the module documentation for details.
+pub struct Host {
+    directory: PathBuf,
+    mod_directory: PathBuf,
+    config_directory: PathBuf,
+    executable_hash: AtomicU64,
+    log: Mutex<Vec<LogEntry>>,
+    errors: Mutex<Vec<String>>,
+    #[cfg(feature = "reloaded")]
+    vtables: Mutex<HashMap<(String, u32), usize>>,
+    #[cfg(feature = "reloaded")]
+    objects: Mutex<HostObjects>,
+    #[cfg(feature = "reloaded")]
+    methods: Mutex<HashMap<(u64, u64), Arc<HostMethod>>>,
+    #[cfg(feature = "reloaded")]
+    delegate_types: Mutex<HashSet<u64>>
+}
+
+static HOST: OnceLock<Host> = OnceLock::new();
+
+impl Host {
+    /// Install Rust implementations for each host callback and return the host. This can be
+    /// called multiple times, with each call returning the same host. Callbacks are installed
+    /// before the host is returned to any thread, so tests can call this in parallel.
+    pub fn install() -> &'static Host {
+        HOST.get_or_init(|| {
+            let host = Self::new();
+            unsafe {
+                host.install_callbacks();
+                atexit(remove_host_directory);
+            }
+            host
+        })
+    }
+
+    /// Get the host, panicking if it hasn't been installed yet.
+    pub fn get() -> &'static Host {
+        HOST.get().expect("testing::Host::install has not been called")
+    }
+
+    fn new() -> Self {
+        let base = std::env::temp_dir().join(format!("riri-mod-tools-host-{}", std::process::id()));
+        let mod_directory = base.join("mod");
+        let config_directory = base.join("config");
+        std::fs::create_dir_all(&mod_directory).unwrap();
+        std::fs::create_dir_all(&config_directory).unwrap();
+        Self {
+            directory: base,
+            mod_directory,
+            config_directory,
+            executable_hash: AtomicU64::new(0),
+            log: Mutex::new(vec![]),
+            errors: Mutex::new(vec![]),
+            #[cfg(feature = "reloaded")]
+            vtables: Mutex::new(HashMap::new()),
+            #[cfg(feature = "reloaded")]
+            objects: Mutex::new(HostObjects::default()),
+            #[cfg(feature = "reloaded")]
+            methods: Mutex::new(HashMap::new()),
+            // The generated C# registers these along with every non-generic delegate type in the
+            // game's assemblies
+            #[cfg(feature = "reloaded")]
+            delegate_types: Mutex::new(["crate::system::Action", "crate::system::EventHandler"]
+                .into_iter().map(crate::event::hash_name).collect())
+        }
+    }
+
+    unsafe fn install_callbacks(&self) {
+        crate::logger::set_reloaded_logger(host_log);
+        crate::logger::set_reloaded_logger_newline(host_log_newline);
+        #[cfg(feature = "reloaded")]
+        {
+            crate::mod_loader_data::set_get_directory_for_mod(host_get_directory_for_mod);
+            crate::mod_loader_data::set_get_config_directory(host_get_config_directory);
+            crate::mod_loader_data::set_free_csharp_string(host_free_csharp_string);
+            crate::mod_loader_data::set_find_pattern(host_find_pattern);
+
+            crate::interop::set_push_parameter(host_push_parameter);
+            crate::interop::set_call_function(host_call_function);
+            crate::interop::set_free_object(host_free_object);
+            crate::interop::set_clone_object(host_clone_object);
+            crate::interop::set_downgrade_object(host_downgrade_object);
+            crate::interop::set_upgrade_object(host_upgrade_object);
+            crate::interop::set_object_as_u8(host_object_as_u8);
+            crate::interop::set_object_as_u16(host_object_as_u16);
+            crate::interop::set_object_as_u32(host_object_as_u32);
+            crate::interop::set_object_as_u64(host_object_as_u64);
+            crate::interop::set_object_as_string(host_object_as_string);
+            crate::interop::set_get_object_instance(host_get_object_instance);
+
+            crate::event::set_subscribe_event(host_subscribe_event);
+            crate::event::set_unsubscribe_event(host_unsubscribe_event);
+            crate::event::set_create_delegate(host_create_delegate);
+
+            self.install_array_methods();
+        }
+        crate::sigscan_resolver::set_current_process();
+    }
+
+    /// The temporary directory returned from `get_directory_for_mod`.
+    pub fn get_mod_directory(&self) -> &Path { &self.mod_directory }
+
+    /// The temporary directory returned from `get_config_directory`.
+    pub fn get_config_directory(&self) -> &Path { &self.config_directory }
+
+    /// Set the hash returned from `get_executable_hash`.
+    pub fn set_executable_hash(&self, hash: u64) {
+        self.executable_hash.store(hash, Ordering::Relaxed);
+    }
+
+    pub fn get_executable_hash(&self) -> u64 {
+        self.executable_hash.load(Ordering::Relaxed)
+    }
+
+    /// Get a copy of every message logged so far.
+    pub fn get_log(&self) -> Vec<LogEntry> {
+        self.log.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Take every message logged so far, clearing the log.
+    pub fn take_log(&self) -> Vec<LogEntry> {
+        std::mem::take(&mut *self.log.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub fn clear_log(&self) {
+        self.log.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    /// Get a copy of every error reported by a host callback so far.
+    pub fn get_errors(&self) -> Vec<String> {
+        self.errors.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Take every error reported by a host callback so far, clearing the list.
+    pub fn take_errors(&self) -> Vec<String> {
+        std::mem::take(&mut *self.errors.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn push_error(&self, error: String) {
+        self.errors.lock().unwrap_or_else(PoisonError::into_inner).push(error);
+    }
+}
+
+unsafe extern "C" {
+    fn atexit(cb: extern "C" fn()) -> std::ffi::c_int;
+}
+
+// The host lives in a static which is never dropped, so the temporary directory is removed when
+// the test process exits instead.
+extern "C" fn remove_host_directory() {
+    if let Some(host) = HOST.get() {
+        let _ = std::fs::remove_dir_all(&host.directory);
+    }
+}
+
+// Unwinding out of an extern "C" callback aborts the process, so any error or panic is recorded
+// in the host and the callback returns `failure` instead.
+fn host_callback<R>(failure: R, f: impl FnOnce(&'static Host) -> Result<R, String>) -> R {
+    let host = match HOST.get() {
+        Some(v) => v,
+        None => return failure
+    };
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(host))) {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => {
+            host.push_error(e);
+            failure
+        },
+        Err(e) => {
+            let message = e.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".to_owned());
+            host.push_error(format!("testing::Host: Callback panicked: {}", message));
+            failure
+        }
+    }
+}
+
+#[cfg(feature = "reloaded")]
+impl Host {
+    fn alloc(&self, value: HostValue) -> usize {
+        self.objects.lock().unwrap_or_else(PoisonError::into_inner).add_target(value)
+    }
+
+    fn value_of(&self, handle: usize) -> Option<HostValue> {
+        self.objects.lock().unwrap_or_else(PoisonError::into_inner).value_of(handle)
+    }
+
+    /// Register the address returned from `vtable::get_vtable_with_offset` for a class's
+    /// mangled name (e.g `TitleLayout@ui@app@@`).
+    pub fn register_vtable(&self, name: &str, offset: u32, vtable: *const u8) {
+        self.vtables.lock().unwrap_or_else(PoisonError::into_inner).insert((name.to_owned(), offset), vtable as usize);
+    }
+
+    /// Store a value in the host, returning an owned handle to it.
+    pub fn insert(&self, value: HostValue) -> Object {
+        unsafe { Object::new_unchecked(self.alloc(value)) }
+    }
+
+    /// Get the value that an object handle points to.
+    pub fn get_value(&self, object: &Object) -> Option<HostValue> {
+        self.value_of(**object)
+    }
+
+    /// Register an object to be returned when creating an instance of the given type, in the
+    /// same way that Reloaded-II singletons such as `IModConfig` are provided.
+    pub fn register_singleton(&self, type_hash: u64, value: HostValue) {
+        let mut objects = self.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        let target = objects.next_id();
+        // Singletons are held by the host, so they're never freed
+        objects.targets.insert(target, HostTarget { value, strong: 1 });
+        objects.singletons.insert(type_hash, target);
+    }
+
+    /// Register an implementation for a C# method with the given type and method hash.
+    /// Allow `event::Delegate` to create delegates of a type, identified by its Rust path (e.g
+    /// `crate::ui::OnSelect`). `Action` and `EventHandler` are registered by default.
+    pub fn register_delegate_type(&self, type_path: &str) {
+        self.delegate_types.lock().unwrap_or_else(PoisonError::into_inner).insert(crate::event::hash_name(type_path));
+    }
+
+    pub fn register_method<F>(&self, type_hash: u64, method_hash: u64, f: F)
+    where F: Fn(&HostValue, Vec<HostValue>) -> HostValue + Send + Sync + 'static {
+        self.methods.lock().unwrap_or_else(PoisonError::into_inner).insert((type_hash, method_hash), Arc::new(Box::new(f)));
+    }
+
+    fn install_array_methods(&self) {
+        type HostArray<'a> = crate::system::Array<'a, crate::system::Int32>;
+        // public int System.Array.get_Length();
+        self.register_method(HostArray::HASH, 0x45eb5d69f4b05f30, |this, _| match this {
+            HostValue::Array(v) => HostValue::Int32(v.lock().unwrap_or_else(PoisonError::into_inner).len() as i32),
+            _ => HostValue::Null
+        });
+        // public object? System.Array.GetValue(int index);
+        self.register_method(HostArray::HASH, 0x40fb3729a671e7f4, |this, args| match (this, args.first()) {
+            (HostValue::Array(v), Some(HostValue::Int32(i))) => v.lock().unwrap_or_else(PoisonError::into_inner)
+                .get(*i as usize).cloned().unwrap_or(HostValue::Null),
+            _ => HostValue::Null
+        });
+    }
+
+    /// Invoke every Rust closure subscribed to an event on the given object, returning the
+    /// result from each subscriber.
+    pub fn raise_event(&self, object: &Object, event_hash: u64, args: Vec<HostValue>) -> Vec<HostValue> {
+        let subscribers: Vec<Arc<HostDelegate>> = {
+            let objects = self.objects.lock().unwrap_or_else(PoisonError::into_inner);
+            let target = match objects.handles.get(&**object) {
+                Some(h) => h.target,
+                None => return vec![]
+            };
+            objects.subscriptions.values()
+                .filter(|(t, h, _)| *t == target && *h == event_hash)
+                .map(|(_, _, d)| d.clone()).collect()
+        };
+        subscribers.iter().map(|d| d.invoke(args.clone())).collect()
+    }
+
+    /// Invoke a delegate created through `event::Delegate`.
+    pub fn invoke_delegate(&self, delegate: &Object, args: Vec<HostValue>) -> HostValue {
+        match self.value_of(**delegate) {
+            Some(HostValue::Delegate(d)) => d.invoke(args),
+            _ => HostValue::Null
+        }
+    }
+
+    /// The number of C# objects that are still reachable from a strong handle.
+    pub fn get_live_object_count(&self) -> usize {
+        let objects = self.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        objects.targets.len() - objects.singletons.len()
+    }
+
+    fn alloc_string(&self, value: &str) -> *const u16 {
+        let text: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
+        let p = text.as_ptr();
+        self.objects.lock().unwrap_or_else(PoisonError::into_inner).strings.insert(p as usize, text);
+        p
+    }
+}
+
+unsafe fn host_write_log(p: *const u8, len: usize, color: LogColor, level: LogLevel, show_prefix: bool, newline: bool) {
+    let text = String::from_utf8_lossy(std::slice::from_raw_parts(p, len)).into_owned();
+    host_callback((), |host| {
+        host.log.lock().unwrap_or_else(PoisonError::into_inner).push(LogEntry { text, color, level, newline, show_prefix });
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn host_log(p: *const u8, len: usize, color: LogColor, level: LogLevel, show_prefix: bool) {
+    host_write_log(p, len, color, level, show_prefix, false)
+}
+
+unsafe extern "C" fn host_log_newline(p: *const u8, len: usize, color: LogColor, level: LogLevel, show_prefix: bool) {
+    host_write_log(p, len, color, level, show_prefix, true)
+}
+
+/// Used in place of the Reloaded runtime's executable hash while testing.
+#[cfg(feature = "reloaded")]
+pub(crate) fn get_executable_hash_ex() -> u64 {
+    HOST.get().map_or(0, |h| h.get_executable_hash())
+}
+
+/// Used in place of the Reloaded runtime's RTTI lookup while testing.
+#[cfg(feature = "reloaded")]
+pub(crate) fn get_vtable_rtti(name: &str, offset: u32) -> *const u8 {
+    let name = name.trim_end_matches('\0');
+    HOST.get().and_then(|h| h.vtables.lock().unwrap_or_else(PoisonError::into_inner).get(&(name.to_owned(), offset)).copied())
+        .map_or(std::ptr::null(), |v| v as *const u8)
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_get_directory_for_mod() -> *const u16 {
+    host_callback(std::ptr::null(), |host| Ok(host.alloc_string(&host.mod_directory.to_string_lossy())))
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_get_config_directory() -> *const u16 {
+    host_callback(std::ptr::null(), |host| Ok(host.alloc_string(&host.config_directory.to_string_lossy())))
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_free_csharp_string(p: *const u16) {
+    host_callback((), |host| {
+        host.objects.lock().unwrap_or_else(PoisonError::into_inner).strings.remove(&(p as usize));
+        Ok(())
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_find_pattern(text: *const u8, start: *const u8, len: u32) -> *const u8 {
+    crate::pattern::find_pattern_raw(&std::ffi::CStr::from_ptr(text as _).to_string_lossy(), start, len as usize)
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_push_parameter(type_hash: u64, method_hash: u64, object: usize) {
+    host_callback((), |host| {
+        let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        objects.parameters.entry((type_hash, method_hash)).or_default().push(object);
+        Ok(())
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_call_function(type_hash: u64, method_hash: u64, object: usize) -> usize {
+    host_callback(0, |host| {
+        let (this, args) = {
+            let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+            let params = objects.parameters.remove(&(type_hash, method_hash)).unwrap_or_default();
+            let args: Vec<HostValue> = params.into_iter()
+                .map(|p| objects.value_of(p).unwrap_or(HostValue::Null)).collect();
+            (objects.value_of(object).unwrap_or(HostValue::Null), args)
+        };
+        let method = host.methods.lock().unwrap_or_else(PoisonError::into_inner).get(&(type_hash, method_hash)).cloned()
+            .ok_or_else(|| format!("testing::Host: No method registered for type 0x{:x}, method 0x{:x}", type_hash, method_hash))?;
+        Ok(host.alloc(method(&this, args)))
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_free_object(object: usize) {
+    host_callback((), |host| {
+        let value = host.objects.lock().unwrap_or_else(PoisonError::into_inner).remove_handle(object);
+        drop(value);
+        Ok(())
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_clone_object(object: usize) -> usize {
+    host_callback(0, |host| {
+        let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        let target = objects.get_target(object)?;
+        Ok(objects.add_handle(target, false))
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_downgrade_object(object: usize) -> usize {
+    host_callback(0, |host| {
+        let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        let target = objects.get_target(object)?;
+        Ok(objects.add_handle(target, true))
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_upgrade_object(object: usize) -> usize {
+    host_callback(0, |host| {
+        let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        let target = objects.get_target(object)?;
+        Ok(match objects.targets.contains_key(&target) {
+            true => objects.add_handle(target, false),
+            false => 0
+        })
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe fn host_object_as<T>(object: usize, success: *mut bool, f: fn(HostValue) -> Option<T>) -> Option<T> {
+    let result = host_callback(None, |host| Ok(host.value_of(object).and_then(f)));
+    if result.is_none() { *success = false; }
+    result
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_object_as_u8(object: usize, success: *mut bool) -> u8 {
+    host_object_as(object, success, |v| match v {
+        HostValue::UInt8(v) => Some(v),
+        HostValue::Int8(v) => Some(v as u8),
+        HostValue::Bool(v) => Some(v as u8),
+        _ => None
+    }).unwrap_or_default()
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_object_as_u16(object: usize, success: *mut bool) -> u16 {
+    host_object_as(object, success, |v| match v {
+        HostValue::UInt16(v) => Some(v),
+        HostValue::Int16(v) => Some(v as u16),
+        _ => None
+    }).unwrap_or_default()
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_object_as_u32(object: usize, success: *mut bool) -> u32 {
+    host_object_as(object, success, |v| match v {
+        HostValue::UInt32(v) => Some(v),
+        HostValue::Int32(v) => Some(v as u32),
+        HostValue::Float(v) => Some(v.to_bits()),
+        _ => None
+    }).unwrap_or_default()
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_object_as_u64(object: usize, success: *mut bool) -> u64 {
+    host_object_as(object, success, |v| match v {
+        HostValue::UInt64(v) => Some(v),
+        HostValue::Int64(v) => Some(v as u64),
+        HostValue::Double(v) => Some(v.to_bits()),
+        _ => None
+    }).unwrap_or_default()
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_object_as_string(object: usize, success: *mut bool) -> usize {
+    match host_object_as(object, success, |v| match v {
+        HostValue::String(v) => Some(v),
+        _ => None
+    }) {
+        Some(v) => host_callback(0, |host| Ok(host.alloc_string(&v) as usize)),
+        None => 0
+    }
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_get_object_instance(initializer: *const ObjectInitializer) -> usize {
+    unsafe fn read<T: Copy>(initializer: *const ObjectInitializer) -> T {
+        *(initializer.add(1) as *const T)
+    }
+    host_callback(0, |host| {
+        let hash = (*initializer).get_hash();
+        {
+            let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(target) = objects.singletons.get(&hash).copied() {
+                return Ok(objects.add_handle(target, false));
+            }
+        }
+        let value = match hash {
+            crate::system::Bool::HASH => HostValue::Bool(read(initializer)),
+            crate::system::Int8::HASH => HostValue::Int8(read(initializer)),
+            crate::system::UInt8::HASH => HostValue::UInt8(read(initializer)),
+            crate::system::Int16::HASH => HostValue::Int16(read(initializer)),
+            crate::system::UInt16::HASH => HostValue::UInt16(read(initializer)),
+            crate::system::Int32::HASH => HostValue::Int32(read(initializer)),
+            crate::system::UInt32::HASH => HostValue::UInt32(read(initializer)),
+            crate::system::Int64::HASH => HostValue::Int64(read(initializer)),
+            crate::system::UInt64::HASH => HostValue::UInt64(read(initializer)),
+            crate::system::Float::HASH => HostValue::Float(read(initializer)),
+            crate::system::Double::HASH => HostValue::Double(read(initializer)),
+            crate::system::String::HASH => {
+                let data: crate::system::StringData = read(initializer);
+                HostValue::String(String::from_utf8_lossy(std::slice::from_raw_parts(data.ptr, data.len)).into_owned())
+            },
+            crate::system::Array::<()>::HASH => {
+                let data: crate::system::ArrayData = read(initializer);
+                HostValue::Array(Arc::new(Mutex::new(vec![HostValue::default_for_type(data.value_type); data.len])))
+            },
+            _ => return Err(format!("testing::Host: Can't create an object of type 0x{:x}", hash))
+        };
+        Ok(host.alloc(value))
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_subscribe_event(object: usize, event_hash: u64, context: usize) -> usize {
+    host_callback(0, |host| {
+        let mut objects = host.objects.lock().unwrap_or_else(PoisonError::into_inner);
+        let target = objects.get_target(object)?;
+        let id = objects.next_id();
+        objects.subscriptions.insert(id, (target, event_hash, Arc::new(HostDelegate { type_hash: 0, context })));
+        Ok(id)
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_unsubscribe_event(subscription: usize) {
+    host_callback((), |host| {
+        let removed = host.objects.lock().unwrap_or_else(PoisonError::into_inner).subscriptions.remove(&subscription);
+        drop(removed);
+        Ok(())
+    })
+}
+
+#[cfg(feature = "reloaded")]
+unsafe extern "C" fn host_create_delegate(type_hash: u64, context: usize) -> usize {
+    host_callback(0, |host| {
+        if !host.delegate_types.lock().unwrap_or_else(PoisonError::into_inner).contains(&type_hash) {
+            return Err(format!("testing::Host: Delegate type 0x{:x} isn't registered", type_hash));
+        }
+        Ok(host.alloc(HostValue::Delegate(Arc::new(HostDelegate { type_hash, context }))))
+    })
+}
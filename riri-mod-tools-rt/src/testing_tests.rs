@@ -0,0 +1,174 @@
+#![cfg(test)]
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn host_captures_log() -> ReturnType {
+    use crate::logger::LogLevel;
+    use crate::testing::Host;
+    let host = Host::install();
+    crate::logln!(Warning, "captured {}", 1);
+    let entry = host.get_log().into_iter().find(|e| e.text == "captured 1").unwrap();
+    assert!(entry.newline && entry.show_prefix);
+    assert_eq!(entry.color, LogLevel::Warning.get_log_color());
+    Ok(())
+}
+
+#[test]
+fn host_install_in_parallel() -> ReturnType {
+    use crate::testing::Host;
+    // Every thread can use the runtime as soon as install returns
+    let threads: Vec<_> = (0..8).map(|i| std::thread::spawn(move || {
+        let host = Host::install();
+        crate::logln!(Information, "parallel install {}", i);
+        host.get_mod_directory().to_owned()
+    })).collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), Host::get().get_mod_directory());
+    }
+    let log = Host::get().get_log();
+    assert!((0..8).all(|i| log.iter().any(|e| e.text == format!("parallel install {}", i))));
+    Ok(())
+}
+
+#[test]
+fn host_captures_structured_log() -> ReturnType {
+    use crate::testing::Host;
+    let host = Host::install();
+    let addr = 0x14a2b3c40usize as *const u8;
+    crate::logln!(Information, hook = "fldPCMoveUpdate", addr = addr; "resolved {}", "structured");
+    assert!(host.get_log().iter().any(|e| e.text == "resolved structured hook=fldPCMoveUpdate addr=0x14a2b3c40"));
+    {
+        let mut span = crate::log_span!(Information, count = 2; "span_{}", "test");
+        span.record("extra", &true);
+    }
+    assert!(host.get_log().iter().any(|e| e.text.starts_with("span_test finished in ")
+        && e.text.contains(" count=2 extra=true elapsed_ms=")));
+    Ok(())
+}
+
+#[test]
+fn host_rate_limited_log() -> ReturnType {
+    use crate::testing::Host;
+    let host = Host::install();
+    for i in 0..10 {
+        crate::log_once!(Information, "once test {}", i);
+        crate::log_every!(4, Information, "every test {}", i);
+        crate::log_throttled!(std::time::Duration::from_secs(60), Information, "throttled test {}", i);
+    }
+    let count = |prefix: &str| host.get_log().iter().filter(|e| e.text.starts_with(prefix)).count();
+    assert_eq!(count("once test "), 1);
+    assert_eq!(count("every test "), 3);
+    assert_eq!(count("throttled test "), 1);
+    assert!(host.get_log().iter().any(|e| e.text == "every test 8"));
+    Ok(())
+}
+
+#[test]
+fn host_logs_caught_panic() -> ReturnType {
+    use crate::panic_guard::{ catch_panic, PanicPolicy };
+    use crate::testing::Host;
+    let host = Host::install();
+    assert_eq!(catch_panic("guarded_ok", || 5), Ok(5));
+    assert_eq!(catch_panic("guarded_panic", || -> u32 { panic!("panic test {}", 1) }), Err(PanicPolicy::CallOriginal));
+    let entry = host.get_log().into_iter().find(|e| e.text.starts_with("panicked: panic test 1")).unwrap();
+    assert!(entry.text.contains("function=guarded_panic location=riri-mod-tools-rt/src/testing_tests.rs:"));
+    Ok(())
+}
+
+#[test]
+fn host_mod_directories() -> ReturnType {
+    use crate::testing::Host;
+    let host = Host::install();
+    let mod_dir: String = crate::mod_loader_data::get_directory_for_mod().into();
+    assert_eq!(std::path::Path::new(&mod_dir), host.get_mod_directory());
+    let data = [0x12u8, 0x48, 0x8b, 0x05, 0x90];
+    let found = crate::mod_loader_data::find_pattern("48 ?? 05", data.as_ptr(), data.len() as u32);
+    assert_eq!(found, unsafe { data.as_ptr().add(1) });
+    Ok(())
+}
+
+#[test]
+fn host_object_interop() -> ReturnType {
+    use crate::interop::{ ObjectInitializable, ObjectValuable };
+    use crate::system::{ Array, Int32, String };
+    use crate::testing::{ Host, HostValue };
+    let host = Host::install();
+    assert_eq!(Int32::new(42)?.value()?, 42);
+    assert_eq!(String::new("riri")?.value()?, "riri");
+    let value = Int32::new(7)?;
+    let weak = value.downgrade();
+    let clone = value.clone();
+    drop(value);
+    assert!(weak.upgrade().is_some());
+    drop(clone);
+    assert!(weak.upgrade().is_none());
+    let array = host.insert(HostValue::Array(std::sync::Arc::new(std::sync::Mutex::new(
+        vec![HostValue::Int32(1), HostValue::Int32(2)]))));
+    let array = unsafe { Array::<Int32>::new_unchecked(array) };
+    assert_eq!(array.value()?, vec![1, 2]);
+    Ok(())
+}
+
+#[test]
+fn host_events_and_delegates() -> ReturnType {
+    use std::sync::Arc;
+    use std::sync::atomic::{ AtomicU32, Ordering };
+    use crate::event::{ Delegate, EventSubscription };
+    use crate::interop::ObjectInitializable;
+    use crate::system::Int32;
+    use crate::testing::{ Host, HostValue };
+    let host = Host::install();
+    let count = Arc::new(AtomicU32::new(0));
+    let target = host.insert(HostValue::Null);
+    let count_event = count.clone();
+    let subscription = EventSubscription::new_by_name(&target, "OnWrite", move |args| {
+        count_event.fetch_add(args.len() as u32, Ordering::Relaxed);
+        None
+    })?;
+    let event = crate::event::hash_name("OnWrite");
+    host.raise_event(&target, event, vec![HostValue::Int32(1), HostValue::Int32(2)]);
+    subscription.unsubscribe();
+    host.raise_event(&target, event, vec![HostValue::Int32(1)]);
+    assert_eq!(count.load(Ordering::Relaxed), 2);
+    host.register_delegate_type("crate::riri_host_test::GetValue");
+    let delegate = Delegate::new_by_name("crate::riri_host_test::GetValue", |_| {
+        Int32::new(5).ok().map(|v| (*v).clone())
+    })?;
+    assert!(matches!(host.invoke_delegate(&delegate, vec![]), HostValue::Int32(5)));
+    assert!(Delegate::new_by_name("crate::riri_host_test::Unregistered", |_| None).is_err());
+    Ok(())
+}
+
+#[test]
+fn host_callback_errors() -> ReturnType {
+    use crate::testing::Host;
+    let host = Host::install();
+    let result = unsafe { crate::interop::call_function(0x5249524900000001, 0x5249524900000002, 0) };
+    assert_eq!(result, 0);
+    assert_eq!(unsafe { crate::interop::clone_object(usize::MAX) }, 0);
+    let errors = host.get_errors();
+    assert!(errors.iter().any(|e| e == "testing::Host: No method registered for type 0x5249524900000001, method 0x5249524900000002"));
+    assert!(errors.iter().any(|e| e == &format!("testing::Host: 0x{:x} isn't a valid object handle", usize::MAX)));
+    Ok(())
+}
+
+// The host's temporary directory is removed when the process exits, so this is checked from a
+// child process.
+#[test]
+fn host_removes_directory() -> ReturnType {
+    use crate::testing::Host;
+    if std::env::var_os("RIRI_HOST_DIRECTORY_CHILD").is_some() {
+        assert!(Host::install().get_mod_directory().exists());
+        return Ok(());
+    }
+    let child = std::process::Command::new(std::env::current_exe()?)
+        .args(["--exact", "testing_tests::host_removes_directory", "--test-threads=1"])
+        .env("RIRI_HOST_DIRECTORY_CHILD", "1")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    let directory = std::env::temp_dir().join(format!("riri-mod-tools-host-{}", child.id()));
+    assert!(child.wait_with_output()?.status.success());
+    assert!(!directory.exists());
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Follows thunks, which are functions made of a single jump to another function, until reaching
+//! the function that they lead to. This is used by `get_address_may_thunk` in game and by
+//! [`PeImage`](crate::pe_image::PeImage) offline, which read memory through [`ThunkMemory`].
+//!
+//! The following jumps are recognized:
+//! - `jmp rel8` and `jmp rel32`, including chains of them
+//! - `jmp [rip+disp]` (`FF 25`), which jumps through a pointer such as an import address table
+//!   entry
+//! - tables of `jmp rel32` created by incremental linking
+//! - `mov edi, edi` hot-patch prologues, either patched with a `jmp short` to the jump in the 5
+//!   bytes before the function or followed by a jump
+//!
+//! At most [`MAX_THUNK_HOPS`] jumps are followed, so jumps that loop back on themselves end.
+
+use std::fmt::{ Display, Formatter };
+
+/// The most jumps that will be followed from one address.
+pub const MAX_THUNK_HOPS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThunkKind {
+    /// `jmp rel8`
+    ShortJump,
+    /// `jmp rel32`
+    NearJump,
+    /// `jmp [rip+disp]` through a pointer, such as an import address table entry.
+    Indirect,
+    /// A `jmp rel32` in an incremental linking table.
+    IncrementalLink,
+    /// A `mov edi, edi` hot-patch prologue which jumps elsewhere.
+    HotPatch
+}
+
+impl Display for ThunkKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ShortJump => write!(f, "short jump"),
+            Self::NearJump => write!(f, "near jump"),
+            Self::Indirect => write!(f, "indirect jump"),
+            Self::IncrementalLink => write!(f, "incremental linking jump"),
+            Self::HotPatch => write!(f, "hot-patch jump")
+        }
+    }
+}
+
+/// Memory that thunks are read from. Addresses can be absolute or offsets into an image, as long
+/// as pointers read by `read_pointer` are converted into the same kind of address.
+pub trait ThunkMemory {
+    /// Fill `buf` with the bytes at `address`. Returns false if they can't be read.
+    fn read(&self, address: usize, buf: &mut [u8]) -> bool;
+    /// Read the pointer at `address` which an indirect jump goes through. Returns None if it
+    /// can't be followed, such as an import that's only bound when the game is loaded.
+    fn read_pointer(&self, address: usize) -> Option<usize>;
+    /// Whether a jump is allowed to go to `address`.
+    fn in_bounds(&self, address: usize) -> bool;
+}
+
+/// The function that a thunk leads to, and the jumps that were followed to get there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThunkResolution {
+    address: usize,
+    thunks: Vec<ThunkKind>,
+    limited: bool
+}
+
+impl ThunkResolution {
+    pub fn get_address(&self) -> usize { self.address }
+    /// Each jump that was followed, in order. This is empty if the address wasn't a thunk.
+    pub fn get_thunks(&self) -> &[ThunkKind] { &self.thunks }
+    /// Following jumps stopped after `MAX_THUNK_HOPS`, so the address is still a jump.
+    pub fn is_limited(&self) -> bool { self.limited }
+}
+
+fn read_array<M: ThunkMemory, const N: usize>(memory: &M, address: usize) -> Option<[u8; N]> {
+    let mut buf = [0; N];
+    memory.read(address, &mut buf).then_some(buf)
+}
+
+fn read_rel32<M: ThunkMemory>(memory: &M, address: usize) -> Option<isize> {
+    read_array::<M, 4>(memory, address).map(|v| i32::from_le_bytes(v) as isize)
+}
+
+fn is_near_jump<M: ThunkMemory>(memory: &M, address: usize) -> bool {
+    read_array::<M, 1>(memory, address) == Some([0xe9])
+}
+
+// jmp [rip+disp] where the displacement starts at `operand`
+fn get_indirect_jump<M: ThunkMemory>(memory: &M, address: usize, operand: usize) -> Option<(ThunkKind, usize)> {
+    let slot = (address + operand + 4).checked_add_signed(read_rel32(memory, address + operand)?)?;
+    Some((ThunkKind::Indirect, memory.read_pointer(slot)?))
+}
+
+// The kind and target of the jump at address, or None if it isn't a jump that can be followed
+fn get_jump<M: ThunkMemory>(memory: &M, address: usize) -> Option<(ThunkKind, usize)> {
+    match read_array::<M, 3>(memory, address)? {
+        [0xeb, rel, _] => {
+            let target = (address + 2).checked_add_signed(rel as i8 as isize)?;
+            // A patched hot-patch prologue jumps back to a near jump in the padding before it
+            match target + 5 == address && is_near_jump(memory, target) {
+                true => Some((ThunkKind::HotPatch, target)),
+                false => Some((ThunkKind::ShortJump, target))
+            }
+        },
+        [0xe9, ..] => {
+            let target = (address + 5).checked_add_signed(read_rel32(memory, address + 1)?)?;
+            // Incremental linking tables are made of jumps next to each other
+            let table = is_near_jump(memory, address + 5)
+                || address.checked_sub(5).is_some_and(|v| is_near_jump(memory, v));
+            match table {
+                true => Some((ThunkKind::IncrementalLink, target)),
+                false => Some((ThunkKind::NearJump, target))
+            }
+        },
+        [0xff, 0x25, _] => get_indirect_jump(memory, address, 2),
+        [0x48, 0xff, 0x25] => get_indirect_jump(memory, address, 3),
+        // mov edi, edi is a two byte nop, so skip it if there's a jump after it
+        [0x8b, 0xff, _] => get_jump(memory, address + 2).map(|_| (ThunkKind::HotPatch, address + 2)),
+        _ => None
+    }
+}
+
+/// Follow jumps from `address` until reaching an instruction that isn't a jump. Returns None if
+/// a jump goes to an address that isn't in bounds.
+pub fn follow_thunks<M: ThunkMemory>(memory: &M, address: usize) -> Option<ThunkResolution> {
+    if !memory.in_bounds(address) { return None; }
+    let mut resolution = ThunkResolution { address, thunks: vec![], limited: false };
+    while let Some((kind, target)) = get_jump(memory, resolution.address) {
+        if resolution.thunks.len() == MAX_THUNK_HOPS {
+            resolution.limited = true;
+            break;
+        }
+        if !memory.in_bounds(target) { return None; }
+        resolution.thunks.push(kind);
+        resolution.address = target;
+    }
+    Some(resolution)
+}
@@ -0,0 +1,110 @@
+#![cfg(test)]
+use crate::thunk::{ follow_thunks, ThunkKind, ThunkMemory, MAX_THUNK_HOPS };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+const BASE: u64 = 0x140000000;
+
+// Pointers are stored as absolute addresses from BASE
+struct TestMemory(Vec<u8>);
+
+impl ThunkMemory for TestMemory {
+    fn read(&self, address: usize, buf: &mut [u8]) -> bool {
+        match self.0.get(address..address + buf.len()) {
+            Some(v) => { buf.copy_from_slice(v); true },
+            None => false
+        }
+    }
+    fn read_pointer(&self, address: usize) -> Option<usize> {
+        let abs = u64::from_le_bytes(self.0.get(address..address + 8)?.try_into().unwrap());
+        abs.checked_sub(BASE).map(|v| v as usize).filter(|v| *v < self.0.len())
+    }
+    fn in_bounds(&self, address: usize) -> bool { address < self.0.len() }
+}
+
+fn jmp_rel32(memory: &mut [u8], address: usize, target: usize) {
+    memory[address] = 0xe9;
+    memory[address + 1..address + 5].copy_from_slice(&((target as i32) - (address as i32 + 5)).to_le_bytes());
+}
+
+// 0x300 and 0x380 are functions, everything else jumps to them
+fn get_test_memory() -> TestMemory {
+    let mut memory = vec![0xccu8; 0x400];
+    // 0x10: jmp short 0x20, 0x20: jmp 0x300
+    memory[0x10..0x12].copy_from_slice(&[0xeb, 0x0e]);
+    jmp_rel32(&mut memory, 0x20, 0x300);
+    // 0x40: jmp [rip+0x1a] through a pointer at 0x60, 0x48: the same with a REX prefix
+    memory[0x40..0x46].copy_from_slice(&[0xff, 0x25, 0x1a, 0, 0, 0]);
+    memory[0x48..0x4f].copy_from_slice(&[0x48, 0xff, 0x25, 0x11, 0, 0, 0]);
+    memory[0x60..0x68].copy_from_slice(&(BASE + 0x300).to_le_bytes());
+    // 0x70: jmp [rip+0x12] through an unbound import at 0x88, 0x78: call [rip+0xa]
+    memory[0x70..0x76].copy_from_slice(&[0xff, 0x25, 0x12, 0, 0, 0]);
+    memory[0x78..0x7e].copy_from_slice(&[0xff, 0x15, 0x0a, 0, 0, 0]);
+    memory[0x88..0x90].copy_from_slice(&0x5040u64.to_le_bytes());
+    // 0x100: incremental linking table
+    jmp_rel32(&mut memory, 0x100, 0x300);
+    jmp_rel32(&mut memory, 0x105, 0x380);
+    jmp_rel32(&mut memory, 0x10a, 0x300);
+    // 0x1fb: jmp 0x380, 0x200: patched hot-patch prologue
+    jmp_rel32(&mut memory, 0x1fb, 0x380);
+    memory[0x200..0x202].copy_from_slice(&[0xeb, 0xf9]);
+    // 0x220: mov edi, edi followed by a jump, 0x240: mov edi, edi; push rbp
+    memory[0x220..0x222].copy_from_slice(&[0x8b, 0xff]);
+    jmp_rel32(&mut memory, 0x222, 0x300);
+    memory[0x240..0x243].copy_from_slice(&[0x8b, 0xff, 0x55]);
+    // 0x260: jmp short 0x260, 0x270: jmp past the end
+    memory[0x260..0x262].copy_from_slice(&[0xeb, 0xfe]);
+    jmp_rel32(&mut memory, 0x270, 0x1000);
+    // 0x300: push rbp, 0x380: sub rsp, 0x28
+    memory[0x300] = 0x55;
+    memory[0x380..0x384].copy_from_slice(&[0x48, 0x83, 0xec, 0x28]);
+    TestMemory(memory)
+}
+
+#[test]
+fn follow_jump_chains() -> ReturnType {
+    let memory = get_test_memory();
+    let resolution = follow_thunks(&memory, 0x10).unwrap();
+    assert_eq!(resolution.get_address(), 0x300);
+    assert_eq!(resolution.get_thunks(), [ThunkKind::ShortJump, ThunkKind::NearJump]);
+    assert!(!resolution.is_limited());
+    let resolution = follow_thunks(&memory, 0x300).unwrap();
+    assert_eq!((resolution.get_address(), resolution.get_thunks()), (0x300, &[][..]));
+    // Jumps that never reach a function stop after a while
+    let resolution = follow_thunks(&memory, 0x260).unwrap();
+    assert_eq!(resolution.get_address(), 0x260);
+    assert_eq!(resolution.get_thunks().len(), MAX_THUNK_HOPS);
+    assert!(resolution.is_limited());
+    assert_eq!(follow_thunks(&memory, 0x270), None);
+    assert_eq!(follow_thunks(&memory, 0x1000), None);
+    Ok(())
+}
+
+#[test]
+fn follow_indirect_jumps() -> ReturnType {
+    let memory = get_test_memory();
+    let resolution = follow_thunks(&memory, 0x40).unwrap();
+    assert_eq!((resolution.get_address(), resolution.get_thunks()), (0x300, &[ThunkKind::Indirect][..]));
+    assert_eq!(follow_thunks(&memory, 0x48).unwrap().get_address(), 0x300);
+    // Imports that can't be followed and indirect calls stay where they are
+    assert_eq!(follow_thunks(&memory, 0x70).unwrap().get_address(), 0x70);
+    assert_eq!(follow_thunks(&memory, 0x78).unwrap().get_address(), 0x78);
+    Ok(())
+}
+
+#[test]
+fn follow_linker_and_hot_patch_jumps() -> ReturnType {
+    let memory = get_test_memory();
+    for (address, target) in [(0x100, 0x300), (0x105, 0x380), (0x10a, 0x300)] {
+        let resolution = follow_thunks(&memory, address).unwrap();
+        assert_eq!((resolution.get_address(), resolution.get_thunks()), (target, &[ThunkKind::IncrementalLink][..]));
+    }
+    let resolution = follow_thunks(&memory, 0x200).unwrap();
+    assert_eq!(resolution.get_address(), 0x380);
+    assert_eq!(resolution.get_thunks(), [ThunkKind::HotPatch, ThunkKind::NearJump]);
+    let resolution = follow_thunks(&memory, 0x220).unwrap();
+    assert_eq!(resolution.get_address(), 0x300);
+    assert_eq!(resolution.get_thunks(), [ThunkKind::HotPatch, ThunkKind::NearJump]);
+    // An unpatched prologue is the start of the function
+    assert_eq!(follow_thunks(&memory, 0x240).unwrap().get_address(), 0x240);
+    Ok(())
+}
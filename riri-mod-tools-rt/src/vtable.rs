@@ -1,17 +1,23 @@
 use crate::address::ProcessInfo;
 
-#[cfg(all(feature = "reloaded", target_os = "windows"))]
+#[cfg(all(feature = "reloaded", not(feature = "testing"), target_os = "windows"))]
 #[link(name = "riri_mod_runtime_reloaded", kind = "raw-dylib")]
 unsafe extern "C" {
     pub(crate) fn get_vtable_rtti(name: *const i8, offset: u32) -> *const u8;
 }
 
-#[cfg(all(feature = "reloaded", target_os = "linux"))]
+#[cfg(all(feature = "reloaded", not(feature = "testing"), target_os = "linux"))]
 #[link(name = "riri_mod_runtime_reloaded", kind = "dylib")]
 unsafe extern "C" {
     pub(crate) fn get_vtable_rtti(name: *const i8, offset: u32) -> *const u8;
 }
 
+// testing::Host stands in for the Reloaded runtime
+#[cfg(all(feature = "reloaded", feature = "testing"))]
+pub(crate) unsafe fn get_vtable_rtti(name: *const i8, offset: u32) -> *const u8 {
+    crate::testing::get_vtable_rtti(std::ffi::CStr::from_ptr(name).to_str().unwrap(), offset)
+}
+
 pub fn get_vtable(name: &str) -> *const u8 {
     get_vtable_with_offset(name, 0)
 }
@@ -0,0 +1,37 @@
+//! Find a function from an old version of the game in a new version. See
+//! riri_mod_tools::function_migration.
+use riri_mod_tools::function_migration::{ self, FunctionMigration };
+use riri_mod_tools_rt::pe_image::PeImage;
+use std::{ error::Error, process::ExitCode };
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let old = PeImage::load(&args[1])?;
+    let new = PeImage::load(&args[2])?;
+    let address = args[3].trim_start_matches("0x").trim_start_matches("0X");
+    let mut address = u64::from_str_radix(address, 16)?;
+    // Accept both RVAs and addresses copied from a disassembler
+    if address >= old.get_image_base() {
+        address -= old.get_image_base();
+    }
+    let name = match args.get(4) {
+        Some(v) => v.clone(),
+        None => function_migration::get_version_name(new.get_hash()).unwrap_or_else(|| "NEW_VERSION".to_owned())
+    };
+    println!("{}", FunctionMigration::new(&old, &new).migrate(address as usize, &name)?);
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 || args.len() > 5 {
+        eprintln!("Usage: riri-migrate-fn <old executable> <new executable> <old address> [version name]");
+        return ExitCode::from(2);
+    }
+    match run(&args) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Generate the shortest unique signature for an address in a game executable. See
+//! riri_mod_tools_rt::signature_generator.
+use riri_mod_tools_rt::{
+    pe_image::PeImage,
+    signature_generator::SignatureGenerator
+};
+use std::{ error::Error, process::ExitCode };
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let image = PeImage::load(&args[1])?;
+    let address = args[2].trim_start_matches("0x").trim_start_matches("0X");
+    let mut address = u64::from_str_radix(address, 16)?;
+    // Accept both RVAs and addresses copied from a disassembler
+    if address >= image.get_image_base() {
+        address -= image.get_image_base();
+    }
+    let pattern = SignatureGenerator::from_pe_image(&image).generate(address as usize)?;
+    println!("signature = \"{}\"", pattern);
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: riri-sig-gen <executable> <address>");
+        return ExitCode::from(2);
+    }
+    match run(&args) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
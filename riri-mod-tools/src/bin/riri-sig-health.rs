@@ -0,0 +1,31 @@
+//! Check a mod's signatures against a folder of game executables. See riri_mod_tools::sig_health.
+use riri_mod_tools::sig_health::SignatureHealthCheck;
+use std::{ error::Error, process::ExitCode };
+
+fn run(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    let mut check = SignatureHealthCheck::new()?;
+    if let Some(hashes) = args.get(3) {
+        check.add_hashes(&std::fs::read_to_string(hashes)?)?;
+    }
+    check.add_source_dir(&args[1])?;
+    check.add_executable_dir(&args[2])?;
+    let report = check.run();
+    println!("{}", report);
+    Ok(!report.has_failures())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!("Usage: riri-sig-health <mod source directory> <executable directory> [hashes.toml]");
+        return ExitCode::from(2);
+    }
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Finds a function from one version of the game in another version, so that a hook written for
+//! the old version can be given an arm for the new one without finding the function by hand.
+//!
+//! Both executables' functions are read from their exception directories, and each function is
+//! profiled by:
+//! - Its instructions with every displacement, immediate and relative operand removed. These are
+//!   hashed as a whole and in overlapping windows, so that functions which only changed in a few
+//!   places still score highly.
+//! - The strings that it references, which rarely change between versions.
+//! - The functions that it calls and that call it, compared by their own instruction hashes.
+//!
+//! Every function in the new executable is scored against the old function, and the best match
+//! is printed as a `hashes.toml` entry and a hook arm for the new version. This is available as
+//! the `riri-migrate-fn` binary:
+//!
+//! ```text
+//! riri-migrate-fn <old executable> <new executable> <old address> [version name]
+//! ```
+
+use riri_mod_tools_impl::hook_parse::{
+    CallingConvention,
+    DynamicOffset,
+    HookConditional,
+    HookEntry,
+    HookInfoMultipleCaseCollector,
+    StaticOffset
+};
+use riri_mod_tools_rt::{
+    instruction_decoder,
+    pattern::Pattern,
+    pe_image::PeImage,
+    signature_generator::SignatureGenerator
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{ Display, Formatter },
+    ops::Range
+};
+use twox_hash::XxHash3_64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The old executable doesn't have an exception directory to find functions with.
+    NoFunctions,
+    /// The address isn't inside of any function in the old executable.
+    NotInFunction(usize),
+    /// No function in the new executable was similar enough.
+    NotFound,
+    /// The version name can't be used as a hook arm.
+    InvalidName(String)
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoFunctions => write!(f, "Old executable doesn't have a function table"),
+            Self::NotInFunction(o) => write!(f, "0x{:x} isn't inside of a function in the old executable", o),
+            Self::NotFound => write!(f, "No similar function was found in the new executable"),
+            Self::InvalidName(n) => write!(f, "'{}' isn't a valid identifier for a hook arm", n)
+        }
+    }
+}
+
+impl Error for MigrationError {}
+
+/// Get the name of an executable hash from riri-mod-tools' `hashes.toml`.
+pub fn get_version_name(hash: u64) -> Option<String> {
+    let hashes = include_str!("hash/hashes.toml").parse::<toml::Table>().ok()?;
+    hashes.into_iter().find(|(_, v)| v.as_str()
+        .and_then(|v| v.strip_prefix("0x"))
+        .and_then(|v| u64::from_str_radix(v, 16).ok()) == Some(hash))
+        .map(|(k, _)| k)
+}
+
+/// The parts of a function that are compared between versions.
+#[derive(Debug, Clone)]
+pub struct FunctionProfile {
+    range: Range<usize>,
+    // Hash of every normalized instruction
+    hash: u64,
+    // Hashes of each run of normalized instructions, sorted
+    windows: Vec<u64>,
+    strings: Vec<String>,
+    // Offsets of the functions that this calls and that call this, sorted
+    callees: Vec<usize>,
+    callers: Vec<usize>
+}
+
+impl FunctionProfile {
+    pub fn get_range(&self) -> &Range<usize> { &self.range }
+    pub fn get_hash(&self) -> u64 { self.hash }
+    pub fn get_strings(&self) -> &[String] { &self.strings }
+    pub fn get_callees(&self) -> &[usize] { &self.callees }
+    pub fn get_callers(&self) -> &[usize] { &self.callers }
+}
+
+/// Profiles for every function in an executable.
+#[derive(Debug)]
+pub struct ExecutableProfile<'a> {
+    image: &'a PeImage,
+    functions: Vec<FunctionProfile>,
+    // Function start to its index in functions
+    starts: HashMap<usize, usize>
+}
+
+impl<'a> ExecutableProfile<'a> {
+    const WINDOW_SIZE: usize = 4;
+    const MIN_STRING_LENGTH: usize = 4;
+    const MAX_STRING_LENGTH: usize = 256;
+    const CALL_OPCODE: u8 = 0xe8;
+
+    pub fn new(image: &'a PeImage) -> Self {
+        let mut functions: Vec<FunctionProfile> = image.get_functions().iter()
+            .map(|f| Self::get_profile(image, f.clone())).collect();
+        let starts: HashMap<usize, usize> = functions.iter().enumerate()
+            .map(|(i, f)| (f.range.start, i)).collect();
+        let mut callers = vec![vec![]; functions.len()];
+        for f in &mut functions {
+            // Calls into the middle of a function or outside of the function table are ignored
+            f.callees.retain(|c| starts.contains_key(c));
+            for c in &f.callees {
+                callers[starts[c]].push(f.range.start);
+            }
+        }
+        // Functions are visited in order and callees are unique, so callers are already sorted
+        for (f, c) in functions.iter_mut().zip(callers) {
+            f.callers = c;
+        }
+        Self { image, functions, starts }
+    }
+
+    fn get_profile(image: &PeImage, range: Range<usize>) -> FunctionProfile {
+        let code = &image.get_image()[range.clone()];
+        let mut instructions = vec![];
+        let mut strings = vec![];
+        let mut callees = vec![];
+        let mut pos = 0;
+        // Jump tables and padding can be inside of a function, so stop at the first thing that
+        // isn't an instruction
+        while let Ok(instruction) = instruction_decoder::decode(&code[pos..]) {
+            let bytes = &code[pos..pos + instruction.get_length()];
+            // Operand values change whenever code or data moves, so only the prefixes, opcode and
+            // ModRM are kept
+            let operands = instruction.get_displacement().or(instruction.get_immediate())
+                .map_or(bytes.len(), |o| o.get_offset());
+            instructions.push(XxHash3_64::oneshot(&bytes[..operands]));
+            if let Some(target) = instruction.get_relative_target(range.start + pos, bytes) {
+                if instruction.is_relative_branch() && bytes[instruction.get_opcode_offset()] == Self::CALL_OPCODE {
+                    callees.push(image.get_address_may_thunk(target).unwrap_or(target));
+                } else if instruction.is_rip_relative() {
+                    strings.extend(Self::get_string(image, target));
+                }
+            }
+            pos += instruction.get_length();
+            if pos >= code.len() { break; }
+        }
+        let hash_bytes: Vec<u8> = instructions.iter().flat_map(|h| h.to_le_bytes()).collect();
+        // Functions shorter than a window are hashed as a single window
+        let count = instructions.len().saturating_sub(Self::WINDOW_SIZE - 1).max(instructions.len().min(1));
+        let mut windows: Vec<u64> = (0..count).map(|i| {
+            let end = (i + Self::WINDOW_SIZE).min(instructions.len());
+            XxHash3_64::oneshot(&hash_bytes[i * 8..end * 8])
+        }).collect();
+        windows.sort_unstable();
+        windows.dedup();
+        strings.sort_unstable();
+        strings.dedup();
+        callees.sort_unstable();
+        callees.dedup();
+        FunctionProfile { range, hash: XxHash3_64::oneshot(&hash_bytes), windows, strings, callees, callers: vec![] }
+    }
+
+    // Read a null terminated string of printable ASCII from a section that isn't executable
+    fn get_string(image: &PeImage, offset: usize) -> Option<String> {
+        image.get_sections().iter().find(|s| !s.is_executable()
+            && (s.get_virtual_address()..s.get_virtual_address() + s.get_size()).contains(&offset))?;
+        let data = image.get_image().get(offset..)?;
+        let len = data.iter().take(Self::MAX_STRING_LENGTH).position(|b| *b == 0)?;
+        let text = &data[..len];
+        (len >= Self::MIN_STRING_LENGTH && text.iter().all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace()))
+            .then(|| String::from_utf8_lossy(text).into_owned())
+    }
+
+    pub fn get_functions(&self) -> &[FunctionProfile] { &self.functions }
+
+    /// Get the profile of the function containing `offset`.
+    pub fn get_function(&self, offset: usize) -> Option<&FunctionProfile> {
+        self.image.get_function(offset).and_then(|f| self.starts.get(&f.start)).map(|i| &self.functions[*i])
+    }
+
+    // Instruction hashes of the functions at each offset, sorted
+    fn get_hashes(&self, offsets: &[usize]) -> Vec<u64> {
+        let mut hashes: Vec<u64> = offsets.iter().map(|o| self.functions[self.starts[o]].hash).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes
+    }
+}
+
+// How much of two sorted lists is shared, from 0 to 1
+fn get_similarity<T: Ord>(a: &[T], b: &[T]) -> f32 {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => { shared += 1; i += 1; j += 1; }
+        }
+    }
+    match a.len() + b.len() - shared {
+        0 => 1.0,
+        total => shared as f32 / total as f32
+    }
+}
+
+/// A function in the new executable that might be the old function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationCandidate {
+    offset: usize,
+    score: f32,
+    same_instructions: bool
+}
+
+impl MigrationCandidate {
+    /// The start of the function in the new executable.
+    pub fn get_offset(&self) -> usize { self.offset }
+    /// How similar the function is to the old function, from 0 to 1.
+    pub fn get_score(&self) -> f32 { self.score }
+    /// True if the functions have the same instructions once operands are ignored.
+    pub fn has_same_instructions(&self) -> bool { self.same_instructions }
+}
+
+/// The function that an address from the old executable was found in within the new one.
+#[derive(Debug, Clone)]
+pub struct MigrationResult {
+    name: String,
+    hash: u64,
+    old_offset: usize,
+    offset: usize,
+    candidates: Vec<MigrationCandidate>,
+    signature: Option<Pattern>
+}
+
+impl MigrationResult {
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_old_offset(&self) -> usize { self.old_offset }
+    /// The address in the new executable, relative to the start of the image.
+    pub fn get_offset(&self) -> usize { self.offset }
+    /// Every function that was considered, from most to least similar.
+    pub fn get_candidates(&self) -> &[MigrationCandidate] { &self.candidates }
+    /// A unique signature for the new address, if one could be generated.
+    pub fn get_signature(&self) -> Option<&Pattern> { self.signature.as_ref() }
+
+    /// A line for the mod's `hashes.toml` naming the new executable.
+    pub fn get_hashes_entry(&self) -> String {
+        format!("{} = \"0x{:x}\"", self.name, self.hash)
+    }
+
+    /// An arm to add before the default arm of the hook's attribute.
+    pub fn get_hook_arm(&self) -> String {
+        let entry = match &self.signature {
+            Some(s) => HookEntry::Dyn(DynamicOffset {
+                sig: s.to_string(),
+                resolve_type: None,
+                call_conv: CallingConvention::Microsoft,
+                shared_scan: None,
+                required: true,
+                allow_multiple: false
+            }),
+            None => HookEntry::Static(StaticOffset(self.offset))
+        };
+        HookInfoMultipleCaseCollector::format_arm(&HookConditional::HashNamed(self.name.clone()), &entry)
+    }
+}
+
+impl Display for MigrationResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let best = &self.candidates[0];
+        writeln!(f, "0x{:x} was found at 0x{:x} ({:.0}% similar{})", self.old_offset, self.offset,
+            best.score * 100.0, if best.same_instructions { ", same instructions" } else { "" })?;
+        for c in &self.candidates[1..] {
+            writeln!(f, "    also similar: 0x{:x} ({:.0}%)", c.offset, c.score * 100.0)?;
+        }
+        writeln!(f, "hashes.toml:")?;
+        writeln!(f, "{}", self.get_hashes_entry())?;
+        writeln!(f, "Hook arm:")?;
+        write!(f, "{}", self.get_hook_arm())
+    }
+}
+
+/// Matches functions between two versions of an executable.
+#[derive(Debug)]
+pub struct FunctionMigration<'a> {
+    old: ExecutableProfile<'a>,
+    new: ExecutableProfile<'a>,
+    max_candidates: usize
+}
+
+impl<'a> FunctionMigration<'a> {
+    const DEFAULT_MAX_CANDIDATES: usize = 5;
+    // Functions below this score aren't considered at all
+    const MIN_SCORE: f32 = 0.3;
+    const INSTRUCTION_WEIGHT: f32 = 0.4;
+    const STRING_WEIGHT: f32 = 0.3;
+    const CALLEE_WEIGHT: f32 = 0.15;
+    const CALLER_WEIGHT: f32 = 0.15;
+
+    pub fn new(old: &'a PeImage, new: &'a PeImage) -> Self {
+        Self {
+            old: ExecutableProfile::new(old),
+            new: ExecutableProfile::new(new),
+            max_candidates: Self::DEFAULT_MAX_CANDIDATES
+        }
+    }
+
+    /// Set the number of candidates to keep. Defaults to 5.
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates.max(1);
+        self
+    }
+
+    pub fn get_old_profile(&self) -> &ExecutableProfile<'a> { &self.old }
+    pub fn get_new_profile(&self) -> &ExecutableProfile<'a> { &self.new }
+
+    // Instructions are always compared. Strings, callees and callers only count if the old
+    // function has any, so that small functions aren't penalized for not having them.
+    fn get_score(&self, old: &FunctionProfile, new: &FunctionProfile) -> f32 {
+        let instructions = match old.hash == new.hash {
+            true => 1.0,
+            false => get_similarity(&old.windows, &new.windows)
+        };
+        let mut score = Self::INSTRUCTION_WEIGHT * instructions;
+        let mut total = Self::INSTRUCTION_WEIGHT;
+        if !old.strings.is_empty() {
+            score += Self::STRING_WEIGHT * get_similarity(&old.strings, &new.strings);
+            total += Self::STRING_WEIGHT;
+        }
+        if !old.callees.is_empty() {
+            score += Self::CALLEE_WEIGHT * get_similarity(&self.old.get_hashes(&old.callees), &self.new.get_hashes(&new.callees));
+            total += Self::CALLEE_WEIGHT;
+        }
+        if !old.callers.is_empty() {
+            score += Self::CALLER_WEIGHT * get_similarity(&self.old.get_hashes(&old.callers), &self.new.get_hashes(&new.callers));
+            total += Self::CALLER_WEIGHT;
+        }
+        score / total
+    }
+
+    /// Find the functions in the new executable that are most similar to the function containing
+    /// `offset` in the old executable, from most to least similar.
+    pub fn find_candidates(&self, offset: usize) -> Result<Vec<MigrationCandidate>, MigrationError> {
+        if self.old.functions.is_empty() { return Err(MigrationError::NoFunctions); }
+        let old = self.old.get_function(offset).ok_or(MigrationError::NotInFunction(offset))?;
+        let mut candidates: Vec<MigrationCandidate> = self.new.functions.iter().map(|new| MigrationCandidate {
+            offset: new.range.start,
+            score: self.get_score(old, new),
+            same_instructions: old.hash == new.hash
+        }).filter(|c| c.score >= Self::MIN_SCORE).collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.offset.cmp(&b.offset)));
+        candidates.truncate(self.max_candidates);
+        Ok(candidates)
+    }
+
+    /// Find where `offset` in the old executable is in the new executable, and generate a
+    /// signature for it. `name` is the name of the new version in `hashes.toml`. If the address
+    /// is inside of a function rather than at the start of one, it's only carried over when the
+    /// function's instructions haven't changed.
+    pub fn migrate(&self, offset: usize, name: &str) -> Result<MigrationResult, MigrationError> {
+        syn::parse_str::<syn::Ident>(name).map_err(|_| MigrationError::InvalidName(name.to_owned()))?;
+        let candidates = self.find_candidates(offset)?;
+        let best = candidates.first().ok_or(MigrationError::NotFound)?;
+        let old_start = self.old.get_function(offset).unwrap().range.start;
+        let new_offset = match best.same_instructions {
+            true => best.offset + offset - old_start,
+            false => best.offset
+        };
+        Ok(MigrationResult {
+            name: name.to_owned(),
+            hash: self.new.image.get_hash(),
+            old_offset: offset,
+            offset: new_offset,
+            signature: SignatureGenerator::from_pe_image(self.new.image).generate(new_offset).ok(),
+            candidates
+        })
+    }
+}
@@ -0,0 +1,139 @@
+#![cfg(test)]
+use crate::function_migration::{ FunctionMigration, MigrationError };
+use riri_mod_tools_impl::{
+    hook_parse::{ HookConditional, HookEntry },
+    riri_hook::HookInfo
+};
+use riri_mod_tools_rt::{ pe_builder::{ self, PeBuilder }, pe_image::PeImage };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// PE32+ executable with functions in a .text section at 0x1000, strings in a .data section at
+// 0x2000 and an exception directory listing every function at 0x2100
+fn get_test_executable(functions: &[(usize, Vec<u8>)], strings: &[(usize, &str)]) -> Result<PeImage, Box<dyn std::error::Error>> {
+    let mut text = vec![0xccu8; 0x200];
+    let mut data = vec![0u8; 0x200];
+    for (i, (offset, code)) in functions.iter().enumerate() {
+        text[offset - 0x1000..offset - 0x1000 + code.len()].copy_from_slice(code);
+        let entry = 0x100 + i * 12;
+        data[entry..entry + 4].copy_from_slice(&(*offset as u32).to_le_bytes());
+        data[entry + 4..entry + 8].copy_from_slice(&((offset + code.len()) as u32).to_le_bytes());
+    }
+    for (offset, string) in strings {
+        data[offset - 0x2000..offset - 0x2000 + string.len()].copy_from_slice(string.as_bytes());
+    }
+    let file = PeBuilder::new().text(&text).data(&data)
+        .directory(pe_builder::EXCEPTION_DIRECTORY, 0x2100, functions.len() as u32 * 12).build();
+    Ok(PeImage::from_bytes(&file)?)
+}
+
+// rel32 for an instruction at `next` (the offset after it) pointing to `target`
+fn rel32(next: usize, target: usize) -> [u8; 4] {
+    (target as i32 - next as i32).to_le_bytes()
+}
+
+// push rbx; sub rsp, 0x20; lea rcx, [string]; call callee; mov eax, [rbx+field];
+// add rsp, 0x20; pop rbx; ret. An extra nop can be added after the push.
+fn get_function(offset: usize, string: usize, callee: Option<usize>, field: u32, nop: bool) -> (usize, Vec<u8>) {
+    let mut code = vec![0x53];
+    if nop { code.push(0x90); }
+    code.extend_from_slice(&[0x48, 0x83, 0xec, 0x20, 0x48, 0x8d, 0x0d]);
+    code.extend_from_slice(&rel32(offset + code.len() + 4, string));
+    if let Some(c) = callee {
+        code.push(0xe8);
+        code.extend_from_slice(&rel32(offset + code.len() + 4, c));
+    }
+    code.extend_from_slice(&[0x8b, 0x83]);
+    code.extend_from_slice(&field.to_le_bytes());
+    code.extend_from_slice(&[0x48, 0x83, 0xc4, 0x20, 0x5b, 0xc3]);
+    (offset, code)
+}
+
+// sub rsp, 0x28; call callee; add rsp, 0x28; ret
+fn get_caller(offset: usize, callee: usize) -> (usize, Vec<u8>) {
+    let mut code = vec![0x48, 0x83, 0xec, 0x28, 0xe8];
+    code.extend_from_slice(&rel32(offset + 9, callee));
+    code.extend_from_slice(&[0x48, 0x83, 0xc4, 0x28, 0xc3]);
+    (offset, code)
+}
+
+// The old version has the function being migrated at 0x1000, which reads PlayerData, calls a
+// function at 0x1040 and is called by 0x1060. There's a similar function at 0x1080 which reads
+// EnemyData instead.
+fn get_old_executable() -> Result<PeImage, Box<dyn std::error::Error>> {
+    get_test_executable(&[
+        get_function(0x1000, 0x2000, Some(0x1040), 0x130, false),
+        (0x1040, vec![0x33, 0xc0, 0xc3]),
+        get_caller(0x1060, 0x1000),
+        get_function(0x1080, 0x2010, None, 0x130, false)
+    ], &[(0x2000, "PlayerData"), (0x2010, "EnemyData")])
+}
+
+// In the new version, everything has moved around and the field the function reads has moved
+fn get_new_executable(nop: bool) -> Result<PeImage, Box<dyn std::error::Error>> {
+    get_test_executable(&[
+        get_function(0x1000, 0x2000, None, 0x138, false),
+        get_caller(0x1040, 0x1100),
+        get_function(0x1100, 0x2020, Some(0x1180), 0x138, nop),
+        (0x1180, vec![0x33, 0xc0, 0xc3])
+    ], &[(0x2000, "EnemyData"), (0x2020, "PlayerData")])
+}
+
+#[test]
+fn migrate_function_profiles() -> ReturnType {
+    let old = get_old_executable()?;
+    let new = get_new_executable(false)?;
+    let migration = FunctionMigration::new(&old, &new);
+    let profile = migration.get_old_profile().get_function(0x1010).unwrap();
+    assert_eq!(profile.get_range(), &(0x1000..0x101d));
+    assert_eq!(profile.get_strings(), ["PlayerData"]);
+    assert_eq!(profile.get_callees(), [0x1040]);
+    assert_eq!(profile.get_callers(), [0x1060]);
+    // Only operands changed, so the instructions hash the same
+    let new_profile = migration.get_new_profile().get_function(0x1100).unwrap();
+    assert_eq!(profile.get_hash(), new_profile.get_hash());
+    assert_ne!(profile.get_hash(), migration.get_old_profile().get_function(0x1080).unwrap().get_hash());
+    Ok(())
+}
+
+#[test]
+fn migrate_function() -> ReturnType {
+    let old = get_old_executable()?;
+    let new = get_new_executable(false)?;
+    let migration = FunctionMigration::new(&old, &new);
+    let result = migration.migrate(0x1000, "TEST_V2")?;
+    assert_eq!(result.get_offset(), 0x1100);
+    let best = &result.get_candidates()[0];
+    assert!(best.has_same_instructions());
+    assert_eq!(best.get_score(), 1.0);
+    // The function with the same prologue doesn't reference the same string or functions
+    assert_eq!(result.get_candidates().len(), 1);
+    assert_eq!(result.get_hashes_entry(), format!("TEST_V2 = \"0x{:x}\"", new.get_hash()));
+    let signature = result.get_signature().unwrap().to_string();
+    assert_eq!(result.get_hook_arm(), format!("TEST_V2 => dynamic_offset(signature = \"{}\"),", signature));
+    assert_eq!(new.find_all(result.get_signature().unwrap()), [0x1100]);
+    // The arm can be pasted into an existing hook attribute
+    let info: HookInfo = syn::parse_str(&format!("{{ {} _ => user_defined() }}", result.get_hook_arm()))?;
+    match &info.0[0] {
+        (HookConditional::HashNamed(n), HookEntry::Dyn(d)) => assert_eq!((n.as_str(), d.sig.as_str()), ("TEST_V2", signature.as_str())),
+        v => panic!("Unexpected hook arm {:?}", v)
+    }
+    // Addresses inside of the function are carried over when its instructions are the same
+    assert_eq!(migration.migrate(0x1011, "TEST_V2")?.get_offset(), 0x1111);
+    Ok(())
+}
+
+#[test]
+fn migrate_changed_function() -> ReturnType {
+    let old = get_old_executable()?;
+    let new = get_new_executable(true)?;
+    let migration = FunctionMigration::new(&old, &new);
+    // The string and the functions around it still match after an instruction was added
+    let result = migration.migrate(0x1011, "TEST_V2")?;
+    assert_eq!(result.get_offset(), 0x1100);
+    assert!(!result.get_candidates()[0].has_same_instructions());
+    assert!(result.get_candidates()[0].get_score() > 0.8);
+    assert!(result.to_string().starts_with("0x1011 was found at 0x1100 ("), "{}", result);
+    assert_eq!(migration.migrate(0x1030, "TEST_V2").unwrap_err(), MigrationError::NotInFunction(0x1030));
+    assert_eq!(migration.migrate(0x1000, "1.0.14").unwrap_err(), MigrationError::InvalidName("1.0.14".to_owned()));
+    Ok(())
+}
@@ -16,6 +16,7 @@ using System.Diagnostics.CodeAnalysis;
 using System.Drawing;
 using System.IO.Hashing;
 {{#if csharp_function_invoke}}
+using System.Linq.Expressions;
 using System.Reflection;
 {{/if}}
 using System.Runtime.CompilerServices;
@@ -61,6 +62,12 @@ namespace {{ffi_namespace}}
 		[DllImport(__DllName, EntryPoint = "set_logger_color", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
 		internal static extern bool set_logger_color(nint colorName);
 
+		[DllImport(__DllName, EntryPoint = "set_logger_level", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		internal static extern bool set_logger_level(uint level);
+
+		[DllImport(__DllName, EntryPoint = "set_logger_filter", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		internal static extern bool set_logger_filter(nint filter);
+
 		// Logger callback
 
 		[DllImport(__DllName, EntryPoint = "on_reloaded_logger", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
@@ -95,6 +102,48 @@ namespace {{ffi_namespace}}
 		[DllImport(__DllName, EntryPoint = "set_find_pattern", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
 		internal static extern void set_find_pattern(delegate* unmanaged[Stdcall]<nint, nint, int, nint> offset);
 
+		[DllImport(__DllName, EntryPoint = "scan_ffi_signatures", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		internal static extern nuint scan_ffi_signatures(nint* names, nint* patterns, nuint count, nint* offsets);
+
+		[DllImport(__DllName, EntryPoint = "check_ffi_signature_matches", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool check_ffi_signature_matches(nint name, nint pattern);
+
+		[DllImport(__DllName, EntryPoint = "log_ffi_closest_matches", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		internal static extern void log_ffi_closest_matches(nint name, nint pattern);
+
+		// Signature cache
+		[DllImport(__DllName, EntryPoint = "open_ffi_signature_cache", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool open_ffi_signature_cache(nint path, ulong* mods, uint modCount);
+
+		[DllImport(__DllName, EntryPoint = "get_ffi_cached_signature", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool get_ffi_cached_signature(nint pattern, nuint* offset);
+
+		[DllImport(__DllName, EntryPoint = "add_ffi_cached_signature", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		internal static extern void add_ffi_cached_signature(nint name, nint pattern, nuint offset);
+
+		[DllImport(__DllName, EntryPoint = "save_ffi_signature_cache", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool save_ffi_signature_cache();
+
+		// Hook registry
+		[DllImport(__DllName, EntryPoint = "set_ffi_hook_state", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool set_ffi_hook_state(nint name, nint signature, nuint address, uint state, ulong hash);
+
+		[DllImport(__DllName, EntryPoint = "set_ffi_hook_required", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool set_ffi_hook_required(nint name, [MarshalAs(UnmanagedType.U1)] bool required);
+
+		[DllImport(__DllName, EntryPoint = "set_ffi_hook_allow_multiple", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		[return: MarshalAs(UnmanagedType.U1)]
+		internal static extern bool set_ffi_hook_allow_multiple(nint name, [MarshalAs(UnmanagedType.U1)] bool allowMultiple);
+
+		[DllImport(__DllName, EntryPoint = "log_ffi_hook_summary", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+		internal static extern void log_ffi_hook_summary();
+
         {{#if csharp_function_invoke}}
         // Arbitrary C# function invocation
 		[DllImport(__DllName, EntryPoint = "set_push_parameter", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
@@ -106,6 +155,15 @@ namespace {{ffi_namespace}}
 		[DllImport(__DllName, EntryPoint = "set_free_object", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
     	internal static extern void set_free_object(delegate* unmanaged[Stdcall]<nint, void> offset);
 
+    	[DllImport(__DllName, EntryPoint = "set_clone_object", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+    	internal static extern void set_clone_object(delegate* unmanaged[Stdcall]<nint, nint> offset);
+
+    	[DllImport(__DllName, EntryPoint = "set_downgrade_object", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+    	internal static extern void set_downgrade_object(delegate* unmanaged[Stdcall]<nint, nint> offset);
+
+    	[DllImport(__DllName, EntryPoint = "set_upgrade_object", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+    	internal static extern void set_upgrade_object(delegate* unmanaged[Stdcall]<nint, nint> offset);
+
     	[DllImport(__DllName, EntryPoint = "set_object_as_u8", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
     	internal static extern void set_object_as_u8(delegate* unmanaged[Stdcall]<nint, byte*, byte> offset);
 
@@ -128,12 +186,38 @@ namespace {{ffi_namespace}}
 
    	    [DllImport(__DllName, EntryPoint = "on_mod_loading", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
    	    internal static extern void on_mod_loading(nint pModConfig);
+
+   	    // Events and delegates
+
+   	    [DllImport(__DllName, EntryPoint = "set_subscribe_event", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+   	    internal static extern void set_subscribe_event(delegate* unmanaged[Stdcall]<nint, ulong, nint, nint> offset);
+
+   	    [DllImport(__DllName, EntryPoint = "set_unsubscribe_event", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+   	    internal static extern void set_unsubscribe_event(delegate* unmanaged[Stdcall]<nint, void> offset);
+
+   	    [DllImport(__DllName, EntryPoint = "set_create_delegate", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+   	    internal static extern void set_create_delegate(delegate* unmanaged[Stdcall]<ulong, nint, nint> offset);
+
+   	    [DllImport(__DllName, EntryPoint = "invoke_rust_closure", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+   	    internal static extern nint invoke_rust_closure(nint context, nint* pArgs, nuint count);
+
+   	    [DllImport(__DllName, EntryPoint = "free_rust_closure", CallingConvention = CallingConvention.StdCall, ExactSpelling = true)]
+   	    internal static extern void free_rust_closure(nint context);
      	{{/if}}
     }
 }
 
 namespace {{mod_id}} 
 {
+    // This must stay in sync with HookState in riri_mod_tools_rt::hook_registry!
+    public enum HookState : uint
+    {
+        Pending,
+        Resolved,
+        Failed,
+        Hooked,
+        Removed
+    }
 
     {{#if csharp_function_invoke}}
 
@@ -243,6 +327,19 @@ namespace {{mod_id}}
 				throw new Exception("{{logger_color}} is not a valid color name or hex code!");
 			}
 			Marshal.FreeHGlobal(LoggerColorString);
+			// RIRI_LOG overrides the filter set in package.toml
+			{{#if logger_filter}}
+			var LoggerFilter = Environment.GetEnvironmentVariable("RIRI_LOG") ?? "{{{logger_filter}}}";
+			{{else}}
+			var LoggerFilter = Environment.GetEnvironmentVariable("RIRI_LOG");
+			{{/if}}
+			if (LoggerFilter != null) {
+				var LoggerFilterString = Marshal.StringToHGlobalAnsi(LoggerFilter);
+				if (!{{utility_namespace}}.set_logger_filter(LoggerFilterString)) {
+					_logger!.WriteLineAsync($"[{{logger_prefix}}] '{LoggerFilter}' is not a valid log filter", Color.Red);
+				}
+				Marshal.FreeHGlobal(LoggerFilterString);
+			}
 		}
 
 		public void RegisterModLoaderAPI()
@@ -267,12 +364,18 @@ namespace {{mod_id}}
 			{{utility_namespace}}.set_push_parameter(&PushParameter);
 			{{utility_namespace}}.set_call_function(&CallFunction);
 			{{utility_namespace}}.set_free_object(&FreeObject);
+			{{utility_namespace}}.set_clone_object(&CloneObject);
+			{{utility_namespace}}.set_downgrade_object(&DowngradeObject);
+			{{utility_namespace}}.set_upgrade_object(&UpgradeObject);
 			{{utility_namespace}}.set_object_as_u8(&ObjectAsU8);
 			{{utility_namespace}}.set_object_as_u16(&ObjectAsU16);
 			{{utility_namespace}}.set_object_as_u32(&ObjectAsU32);
 			{{utility_namespace}}.set_object_as_u64(&ObjectAsU64);
 			{{utility_namespace}}.set_object_as_string(&ObjectAsString);
 			{{utility_namespace}}.set_get_object_instance(&GetObjectInstance);
+			{{utility_namespace}}.set_subscribe_event(&SubscribeEvent);
+			{{utility_namespace}}.set_unsubscribe_event(&UnsubscribeEvent);
+			{{utility_namespace}}.set_create_delegate(&CreateDelegate);
 
 			foreach (var BasicType in BasicTypeGenerators)
 			    BasicTypes.Add(CreateTypePath(BasicType, "riri_mod_tools_rt").ToXxh3(), BasicType);
@@ -295,6 +398,9 @@ namespace {{mod_id}}
                         var Namespace = Reloaded2Interfaces.ContainsKey(Type) ? "riri_mod_tools_rt" : null;
                         Types.TryAdd(CreateTypePath(Type, Namespace).ToXxh3(), new(Type));
                     }
+                    foreach (var Type in Assembly.GetTypes()
+                                 .Where(x => x is { IsGenericType: false, FullName: not null } && x.IsSubclassOf(typeof(Delegate))))
+                        DelegateTypes.TryAdd(CreateTypePath(Type).ToXxh3(), Type);
                 }
                 catch (Exception ex)
                 {
@@ -305,6 +411,9 @@ namespace {{mod_id}}
             // To call System.Array methods when converting into Vec<T>
             Types.TryAdd(CreateTypePath(typeof(IEqualityComparer), null).ToXxh3(), new(typeof(IEqualityComparer)));
             Types.TryAdd(CreateTypePath(typeof(Array), null).ToXxh3(), new(typeof(Array)));
+            // Common delegate types from System
+            DelegateTypes.TryAdd(CreateTypePath(typeof(Action)).ToXxh3(), typeof(Action));
+            DelegateTypes.TryAdd(CreateTypePath(typeof(EventHandler)).ToXxh3(), typeof(EventHandler));
             {{/if}}
 
             _logger!.OnWrite += (_, p) => {{utility_namespace}}.on_reloaded_logger(Marshal.StringToHGlobalUni(p.text));
@@ -345,6 +454,54 @@ namespace {{mod_id}}
 			}
 		}
 
+		// Report a hook's state to the hook registry. A hash of 0 keeps the previous hash.
+		public static unsafe void SetHookState(string name, string? signature, nuint address, HookState state, ulong hash = 0)
+		{
+			var NameString = Marshal.StringToHGlobalAnsi(name);
+			var SignatureString = signature != null ? Marshal.StringToHGlobalAnsi(signature) : nint.Zero;
+			{{utility_namespace}}.set_ffi_hook_state(NameString, SignatureString, address, (uint)state, hash);
+			Marshal.FreeHGlobal(NameString);
+			if (SignatureString != nint.Zero) Marshal.FreeHGlobal(SignatureString);
+		}
+
+		// Mark a hook as optional so that failing to find it doesn't disable the mod's hooks
+		public static unsafe void SetHookRequired(string name, bool required)
+		{
+			var NameString = Marshal.StringToHGlobalAnsi(name);
+			{{utility_namespace}}.set_ffi_hook_required(NameString, required);
+			Marshal.FreeHGlobal(NameString);
+		}
+
+		// Stop a signature that's expected to match more than once from being reported as ambiguous
+		public static unsafe void SetHookAllowMultiple(string name, bool allowMultiple)
+		{
+			var NameString = Marshal.StringToHGlobalAnsi(name);
+			{{utility_namespace}}.set_ffi_hook_allow_multiple(NameString, allowMultiple);
+			Marshal.FreeHGlobal(NameString);
+		}
+
+		// Report the signature if it matches more than once. This only scans in debug builds of the
+		// mod or while the signature cache is being regenerated
+		public static unsafe void CheckSignatureMatches(string name, string pattern)
+		{
+			var NameString = Marshal.StringToHGlobalAnsi(name);
+			var PatternString = Marshal.StringToHGlobalAnsi(pattern);
+			{{utility_namespace}}.check_ffi_signature_matches(NameString, PatternString);
+			Marshal.FreeHGlobal(NameString);
+			Marshal.FreeHGlobal(PatternString);
+		}
+
+		// Log the closest matches for a signature that wasn't found, with a suggested replacement
+		// signature for each
+		public static unsafe void LogClosestMatches(string name, string pattern)
+		{
+			var NameString = Marshal.StringToHGlobalAnsi(name);
+			var PatternString = Marshal.StringToHGlobalAnsi(pattern);
+			{{utility_namespace}}.log_ffi_closest_matches(NameString, PatternString);
+			Marshal.FreeHGlobal(NameString);
+			Marshal.FreeHGlobal(PatternString);
+		}
+
         {{#if csharp_function_invoke}}
 
     	private static List<Type> BasicTypeGenerators =
@@ -399,6 +556,21 @@ namespace {{mod_id}}
         public static void FreeObject(nint pObject)
             => GCHandle.FromIntPtr(pObject).Free();
 
+        [UnmanagedCallersOnly(CallConvs = [typeof(CallConvStdcall)])]
+        public static nint CloneObject(nint pObject)
+            => GCHandle.ToIntPtr(GCHandle.Alloc(GCHandle.FromIntPtr(pObject).Target));
+
+        [UnmanagedCallersOnly(CallConvs = [typeof(CallConvStdcall)])]
+        public static nint DowngradeObject(nint pObject)
+            => GCHandle.ToIntPtr(GCHandle.Alloc(GCHandle.FromIntPtr(pObject).Target, GCHandleType.Weak));
+
+        [UnmanagedCallersOnly(CallConvs = [typeof(CallConvStdcall)])]
+        public static nint UpgradeObject(nint pWeak)
+        {
+            var Target = GCHandle.FromIntPtr(pWeak).Target;
+            return Target != null ? GCHandle.ToIntPtr(GCHandle.Alloc(Target)) : nint.Zero;
+        }
+
         [UnmanagedCallersOnly(CallConvs = [typeof(CallConvStdcall)])]
         public static byte ObjectAsU8(nint pObject, byte* pSuccess)
         {
@@ -507,109 +679,159 @@ namespace {{mod_id}}
             }
             return nint.Zero;
         }
-        {{/if}}
-        {{#if cached_signatures}}
 
-        #nullable enable
+        // Delegate types that can be created from a Rust closure
+        private static Dictionary<ulong, Type> DelegateTypes = [];
+        // Event handlers attached by Rust, indexed by subscription ID
+        private static ConcurrentDictionary<nint, (object Target, EventInfo Event, Delegate Handler)> EventSubscriptions = [];
+        private static long NextSubscriptionId = 0;
 
-        private static byte[]? CachedSignature = null;
+        // Owns a closure allocated in Rust. This is captured by the delegate that calls it, so the closure is
+        // freed once nothing in C# references the delegate anymore.
+        internal class RustClosureHandle(nint context)
+        {
+            internal nint Context = context;
+            ~RustClosureHandle() => {{utility_namespace}}.free_rust_closure(Context);
+        }
 
-        private static BinaryWriter? RegenWriter = null;
+        internal static object InvokeRustClosure(RustClosureHandle Closure, object[] Args)
+        {
+            var Handles = Args.Select(x => GCHandle.ToIntPtr(GCHandle.Alloc(x))).ToArray();
+            nint Result;
+            fixed (nint* pHandles = Handles)
+                Result = {{utility_namespace}}.invoke_rust_closure(Closure.Context, pHandles, (nuint)Handles.Length);
+            if (Result == nint.Zero) return null;
+            var Handle = GCHandle.FromIntPtr(Result);
+            var Value = Handle.Target;
+            Handle.Free();
+            return Value;
+        }
 
-        internal struct RegenSigEntry(ulong pattern, ulong offset)
+        private static Delegate CreateRustDelegate(Type DelegateType, nint Context)
         {
-            internal ulong Pattern = pattern;
-            internal ulong Offset = offset;
+            var Invoke = DelegateType.GetMethod("Invoke")!;
+            var Params = Invoke.GetParameters().Select(x => Expression.Parameter(x.ParameterType, x.Name)).ToArray();
+            Expression Body = Expression.Call(
+                typeof(Mod).GetMethod(nameof(InvokeRustClosure), BindingFlags.NonPublic | BindingFlags.Static)!,
+                Expression.Constant(new RustClosureHandle(Context)),
+                Expression.NewArrayInit(typeof(object), Params.Select(x => Expression.Convert(x, typeof(object)))));
+            Body = Invoke.ReturnType == typeof(void)
+                ? Expression.Block(typeof(void), Body)
+                : Expression.Convert(Body, Invoke.ReturnType);
+            return Expression.Lambda(DelegateType, Body, Params).Compile();
         }
 
-        private static List<RegenSigEntry> RegenSigs = new();
+        [UnmanagedCallersOnly(CallConvs = [ typeof(CallConvStdcall) ])]
+        public static nint SubscribeEvent(nint pObject, ulong EventHash, nint Context)
+        {
+            var Object = GCHandle.FromIntPtr(pObject).Target;
+            var Type = Object.GetType();
+            var Event = Type.GetEvents().Concat(Type.GetInterfaces().SelectMany(x => x.GetEvents()))
+                .FirstOrDefault(x => x.Name.ToXxh3() == EventHash && x.EventHandlerType != null);
+            if (Event == null) return nint.Zero;
+            var Handler = CreateRustDelegate(Event.EventHandlerType, Context);
+            Event.AddEventHandler(Object, Handler);
+            var Id = (nint)Interlocked.Increment(ref NextSubscriptionId);
+            EventSubscriptions[Id] = (Object, Event, Handler);
+            return Id;
+        }
 
-        // private static List<Action<nuint>> CacheSigCallbacks = new();
-        private static Action CacheSigCallbacks = () => { };
+        [UnmanagedCallersOnly(CallConvs = [ typeof(CallConvStdcall) ])]
+        public static void UnsubscribeEvent(nint Id)
+        {
+            if (EventSubscriptions.TryRemove(Id, out var Subscription))
+                Subscription.Event.RemoveEventHandler(Subscription.Target, Subscription.Handler);
+        }
 
-        #nullable disable
+        [UnmanagedCallersOnly(CallConvs = [ typeof(CallConvStdcall) ])]
+        public static nint CreateDelegate(ulong TypeHash, nint Context)
+        {
+            if (!DelegateTypes.TryGetValue(TypeHash, out var Type)) return nint.Zero;
+            return GCHandle.ToIntPtr(GCHandle.Alloc(CreateRustDelegate(Type, Context)));
+        }
+        {{/if}}
+        {{#if cached_signatures}}
 
-        private static bool TryCheckSignatureCache()
+        private static bool SignatureCacheLoaded = false;
+
+        private static Action CacheSigCallbacks = () => { };
+
+        // The cache's format is owned by riri_mod_tools_rt::signature_cache. It's invalidated if
+        // the executable or the ID, version or order of the active mods change.
+        private static unsafe void CheckSignatureCache()
         {
-            // signature_cache binary format:
-            // All strings are xxh3 hashed
-            // u64 ExecutableHash
-            // u32 Version
-            // u32 ModCount
-            // ModData[ModCount]:
-            //      u64 ModId;
-            //      u64 ModVer;
-            // u64 SignatureCount
-            // SigData[SignatureCount]
-            //      u64 SigHash;
-            //      u64 Address;
-            try
-            {
-                var CachePath = Path.Join(_modLoader!.GetDirectoryForModId(_modConfig!.ModId), "signature_cache");
-                using (var Reader = new BinaryReader(new FileStream(CachePath, FileMode.Open, FileAccess.Read)))
-                {
-                    CachedSignature = new byte[Reader.BaseStream.Length];
-                    Reader.Read(CachedSignature, 0, (int)Reader.BaseStream.Length);
-                }
-                using (var Reader = new BinaryReader(new MemoryStream(CachedSignature)))
-                {
-                    _ = Reader.ReadUInt64(); // Executable hash
-                    _ = Reader.ReadUInt32(); // Version
-                    var CurrModList = _modLoader!.GetActiveMods().Select(tup => tup.Generic).ToList();
-                    var CacheModCount = (int)Reader.ReadUInt32();
-                    if (
-                        CurrModList.Count != CacheModCount ||
-                        CurrModList.Any(Mod => Mod.ModId.ToXxh3() != Reader.ReadUInt64() || Mod.ModVersion.ToXxh3() != Reader.ReadUInt64()))
-                        return false;
-                }
-            }
-            catch (FileNotFoundException)
+            var CachePath = Marshal.StringToHGlobalAnsi(Path.Join(_modLoader!.GetDirectoryForModId(_modConfig!.ModId), "signature_cache"));
+            var Mods = _modLoader!.GetActiveMods().Select(tup => tup.Generic)
+                .SelectMany(Mod => new ulong[] { Mod.ModId.ToXxh3(), Mod.ModVersion.ToXxh3() }).ToArray();
+            fixed (ulong* pMods = Mods)
             {
-                _logger!.WriteLineAsync("{{mod_name}}: Signature cache does not exist yet, generating...");
-                return false;
-            }
-            catch (IOException ex)
-            {
-                _logger!.WriteLineAsync($"{{mod_name}}: An error occurred while reading signature_cache: '{ex.Message}', regenerating...");
-                return false;
+                SignatureCacheLoaded = {{utility_namespace}}.open_ffi_signature_cache(CachePath, pMods, (uint)(Mods.Length / 2));
             }
+            Marshal.FreeHGlobal(CachePath);
+        }
 
-            return true;
+        private static unsafe bool TryGetCachedSignature(string pattern, out nuint offset)
+        {
+            var PatternString = Marshal.StringToHGlobalAnsi(pattern);
+            nuint Offset = 0;
+            var Found = {{utility_namespace}}.get_ffi_cached_signature(PatternString, &Offset);
+            Marshal.FreeHGlobal(PatternString);
+            offset = Offset;
+            return Found;
         }
 
-        private static void CheckSignatureCache()
+        private static unsafe void AddCachedSignature(string name, string pattern, nuint offset)
         {
-            if (TryCheckSignatureCache()) return;
-            // Have to regenerate
-            CachedSignature = null;
-            var CachePath = Path.Join(_modLoader!.GetDirectoryForModId(_modConfig!.ModId), "signature_cache");
-            RegenWriter = new BinaryWriter(new FileStream(CachePath, FileMode.Create, FileAccess.Write));
-            var CurrModList = _modLoader!.GetActiveMods().Select(tup => tup.Generic).ToList();
-            RegenWriter.Write({{utility_namespace}}.get_executable_hash());
-            RegenWriter.Write(0);
-            RegenWriter.Write((uint)CurrModList.Count);
-            foreach (var Mod in CurrModList)
-            {
-                RegenWriter.Write(Mod.ModId.ToXxh3());
-                RegenWriter.Write(Mod.ModVersion.ToXxh3());
-            }
+            var NameString = Marshal.StringToHGlobalAnsi(name);
+            var PatternString = Marshal.StringToHGlobalAnsi(pattern);
+            {{utility_namespace}}.add_ffi_cached_signature(NameString, PatternString, offset);
+            Marshal.FreeHGlobal(NameString);
+            Marshal.FreeHGlobal(PatternString);
         }
 
         private static void FinishSignatureCache()
         {
             CacheSigCallbacks();
-            if (RegenWriter != null)
+            {{utility_namespace}}.save_ffi_signature_cache();
+        }
+
+        {{/if}}
+        {{#if batched_signatures}}
+
+        private static List<(string Pattern, string Name, Action<nuint> Callback)> BatchedScans = new();
+
+        // Scan for every signature queued by SigScan in one pass, then pass each result to its
+        // resolve function
+        private static unsafe void RunBatchedScans()
+        {
+            if (BatchedScans.Count == 0) return;
+            var Names = BatchedScans.Select(x => Marshal.StringToHGlobalAnsi(x.Name)).ToArray();
+            var Patterns = BatchedScans.Select(x => Marshal.StringToHGlobalAnsi(x.Pattern)).ToArray();
+            var Offsets = new nint[BatchedScans.Count];
+            fixed (nint* pNames = Names)
+            fixed (nint* pPatterns = Patterns)
+            fixed (nint* pOffsets = Offsets)
+            {
+                {{utility_namespace}}.scan_ffi_signatures(pNames, pPatterns, (nuint)Patterns.Length, pOffsets);
+            }
+            foreach (var NameString in Names) Marshal.FreeHGlobal(NameString);
+            foreach (var PatternString in Patterns) Marshal.FreeHGlobal(PatternString);
+            for (var i = 0; i < BatchedScans.Count; i++)
             {
-                RegenWriter.Write((ulong)RegenSigs.Count);
-                foreach (var Entry in RegenSigs)
+                var (Pattern, Name, Callback) = BatchedScans[i];
+                if (Offsets[i] < 0)
                 {
-                    RegenWriter.Write(Entry.Pattern);
-                    RegenWriter.Write(Entry.Offset);
+                    _logger!.WriteLineAsync($"Couldn't find location for {Name}, stuff will break :(", Color.Red);
+                    SetHookState(Name, Pattern, 0, HookState.Failed);
+                    LogClosestMatches(Name, Pattern);
+                    continue;
                 }
-                RegenWriter.Flush();
-                RegenWriter.Close();
-                RegenWriter = null;
+                {{#if cached_signatures}}
+                AddCachedSignature(Name, Pattern, (nuint)Offsets[i]);
+                {{/if}}
+                Callback((nuint)Offsets[i]);
             }
+            BatchedScans.Clear();
         }
 
         {{/if}}
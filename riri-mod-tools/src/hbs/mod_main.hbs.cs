@@ -38,45 +38,49 @@ namespace {{mod_id}}
 	    public void SigScan(string pattern, string name, Action<nuint> hookerCb)
 	    {
 	        {{#if cached_signatures}}
-            if (CachedSignature != null)
+            if (SignatureCacheLoaded)
             {
-                using var Reader = new BinaryReader(new MemoryStream(CachedSignature));
-                Reader.BaseStream.Seek(0xc, SeekOrigin.Begin); // ModCount
-                var ModCount = Reader.ReadUInt32();
-                Reader.BaseStream.Seek(0x10 * ModCount, SeekOrigin.Current);
-                var SigCount = Reader.ReadUInt64();
-                for (ulong i = 0; i < SigCount; i++)
+                if (TryGetCachedSignature(pattern, out var Offset))
                 {
-                    var (Hash, Offset) = (Reader.ReadUInt64(), Reader.ReadUInt64());
-                    if (pattern.ToXxh3() == Hash)
-                    {
-                        CacheSigCallbacks += () => hookerCb((nuint)Offset);
-                        return;
-                    }
+                    CacheSigCallbacks += () => hookerCb(Offset);
+                    return;
                 }
                 _logger!.WriteLineAsync($"Couldn't find location for {name}, stuff will break :(", Color.Red);
+                SetHookState(name, pattern, 0, HookState.Failed);
             }
             else
             {
+                {{#if batched_signatures}}
+                BatchedScans.Add((pattern, name, hookerCb));
+                {{else}}
                 _startupScanner.AddMainModuleScan(pattern, result =>
                 {
                     if (!result.Found)
                     {
                         _logger!.WriteLineAsync($"Couldn't find location for {name}, stuff will break :(", Color.Red);
+                        SetHookState(name, pattern, 0, HookState.Failed);
+                        LogClosestMatches(name, pattern);
                         return;
                     }
-                    RegenSigs.Add(new RegenSigEntry(pattern.ToXxh3(), (ulong)result.Offset));
+                    CheckSignatureMatches(name, pattern);
+                    AddCachedSignature(name, pattern, (nuint)result.Offset);
                     hookerCb((nuint)result.Offset);
                 });
+                {{/if}}
             }
+	        {{else if batched_signatures}}
+            BatchedScans.Add((pattern, name, hookerCb));
 	        {{else}}
             _startupScanner.AddMainModuleScan(pattern, result =>
             {
                 if (!result.Found)
                    {
                        _logger!.WriteLineAsync($"Couldn't find location for {name}, stuff will break :(", Color.Red);
+                       SetHookState(name, pattern, 0, HookState.Failed);
+                       LogClosestMatches(name, pattern);
                        return;
                    }
+                   CheckSignatureMatches(name, pattern);
                    hookerCb((nuint)result.Offset);
             });
 	        {{/if}}
@@ -118,6 +122,9 @@ namespace {{mod_id}}
 	        {{#each register_hook_fn}}
 	        {{this}}();
 	        {{/each}}
+	        {{#if batched_signatures}}
+	        RunBatchedScans();
+	        {{/if}}
 			_instance = this;
 
 			_modLoader!.OnModLoaderInitialized += OnLoaderInit;
@@ -133,6 +140,7 @@ namespace {{mod_id}}
 	        {{#if cached_signatures}}
 	        FinishSignatureCache();
 	        {{/if}}
+	        {{utility_namespace}}.log_ffi_hook_summary();
 
 			_modLoader!.OnModLoaderInitialized -= OnLoaderInit;
 			_modLoader!.ModLoading -= OnModLoading;
@@ -2,6 +2,8 @@
 pub mod config_codegen;
 pub mod ensure_layout;
 pub mod ensure_layout_tests;
+pub mod function_migration; // Find functions from an old executable in a new one
+pub mod function_migration_tests;
 pub mod git_version;
 pub(crate) mod hbs {
     pub(crate) mod ffi_builtin;
@@ -14,7 +16,10 @@ pub mod platform;
 pub mod r2 {
     pub mod hook_assignment;
     pub mod hook_evaluation;
+    pub mod hook_evaluation_tests;
 }
 pub mod reloaded_codegen;
 pub mod riri_hook_tests;
+pub mod sig_health; // Check signatures against game executables offline
+pub mod sig_health_tests;
 pub mod utils;
\ No newline at end of file
@@ -103,6 +103,7 @@ pub mod reloaded3ririext {
         pub Reloaded2Id: Option<String>, // NOT IN SPEC
         pub LoggerPrefix: Option<String>, // NOT IN SPEC
         pub LoggerColor: Option<String>, // NOT IN SPEC
+        pub LoggerFilter: Option<String>, // NOT IN SPEC
         pub Name: String,
         pub Author: String,
         pub Summary: Option<String>, // is required
@@ -125,6 +126,7 @@ pub mod reloaded3ririext {
         pub UseCsharpInvocation: Option<bool>, // NOT IN SPEC
         pub PointersAreUntyped: Option<bool>, // NOT IN SPEC
         pub UseCachedSignatures: Option<bool>, // NOT IN SPEC
+        pub UseBatchedSignatureScan: Option<bool>, // NOT IN SPEC
     }
 
     pub const PACKAGE_FILENAME: &'static str = "package.toml";
@@ -81,6 +81,20 @@ impl HookBootstrapStaticState {
     pub(crate) fn new(static_type: String, static_name: String) -> Self {
         Self { static_type, static_name }
     }
+    pub(crate) fn get_static_name(&self) -> &str { &self.static_name }
+}
+
+// Report a change in a hook's state to riri_mod_tools_rt::hook_registry. See SetHookState in
+// ffi_builtin.hbs.cs
+pub(crate) fn make_hook_state_update(name: &str, signature: Option<&str>, address: &str, state: &str, hash: Option<&str>) -> String {
+    let signature = match signature {
+        Some(v) => format!("\"{}\"", v),
+        None => "null".to_owned()
+    };
+    match hash {
+        Some(h) => format!("SetHookState(\"{}\", {}, {}, HookState.{}, {});\n", name, signature, address, state, h),
+        None => format!("SetHookState(\"{}\", {}, {}, HookState.{});\n", name, signature, address, state)
+    }
 }
 
 pub struct HookBootstrapClassState;
@@ -157,6 +171,8 @@ impl HookAssignCodegen for HookAssignCodegenStaticOffset {
           &Reloaded2CSharpHook::make_hook_set_string(&class.get_fn_name().to_ascii_uppercase()),
           delegate_type,
           class.get_fn_name()));
+        hook_assign.push_str(&format!("            {}", make_hook_state_update(
+            class.get_fn_name(), None, &format!("addr_{}", class.get_fn_name()), "Hooked", None)));
         Ok(hook_assign)
     }
     fn make_function_hook_assign_assembly<P: AsRef<Path>>(
@@ -191,6 +207,8 @@ impl HookAssignCodegen for HookAssignCodegenStaticOffset {
         let exec_mode: &str = assemble_info.execute_mode.into();
         hook_assign.push_str(&format!("            _{}_ASM = _hooks!.CreateAsmHook(function_{}, (long)addr_{}, Reloaded.Hooks.Definitions.Enums.AsmHookBehaviour.{}).Activate();\n",
             class.get_fn_name(), class.get_fn_name(), class.get_fn_name(), exec_mode));
+        hook_assign.push_str(&format!("            {}", make_hook_state_update(
+            class.get_fn_name(), None, &format!("addr_{}", class.get_fn_name()), "Hooked", None)));
         Ok(hook_assign)
     }
     fn make_single_static_hook_assign<P: AsRef<Path>>(
@@ -212,6 +230,8 @@ impl HookAssignCodegen for HookAssignCodegenStaticOffset {
             &state.static_type,
             &state.static_name
         ));
+        hook_assign.push_str(&make_hook_state_update(
+            &state.static_name, None, &format!("addr_{}", &state.static_name), "Resolved", None));
         Ok(hook_assign)
     }
 
@@ -245,6 +265,8 @@ impl<'a> HookAssignCodegen for HookAssignCodegenDynamicOffset<'a> {
           delegate_type,
           class.get_fn_name()
         ));
+        hook_assign.push_str(&format!("                {}", make_hook_state_update(
+            class.get_fn_name(), None, "addr", "Hooked", None)));
         hook_assign.push_str("            \x7d);\n");
         Ok(hook_assign)
     }
@@ -282,6 +304,8 @@ impl<'a> HookAssignCodegen for HookAssignCodegenDynamicOffset<'a> {
         let exec_mode: &str = assemble_info.execute_mode.into();
         hook_assign.push_str(&format!("            _{}_ASM = _hooks!.CreateAsmHook(function, (long)addr, Reloaded.Hooks.Definitions.Enums.AsmHookBehaviour.{}).Activate();\n",
             class.get_fn_name(), exec_mode));
+        hook_assign.push_str(&format!("            {}", make_hook_state_update(
+            class.get_fn_name(), None, "addr", "Hooked", None)));
         hook_assign.push_str("            \x7d);\n");
         Ok(hook_assign)
     }
@@ -292,7 +316,7 @@ impl<'a> HookAssignCodegen for HookAssignCodegenDynamicOffset<'a> {
         let hooks_class = format!("{}.{}", &evaluator.ffi_hook_namespace(), &ffi.csharp_class_name());
         let mut hook_assign = String::new();
         hook_assign.push_str(&format!("SigScan(\"{}\", \"{}\", x => ",
-            self.0.sig, &state.static_name.to_ascii_lowercase()));
+            self.0.sig, &state.static_name));
         hook_assign.push_str("\x7b\n");
         hook_assign.push_str(&format!("                var addr = {}\n",
             get_resolve_function_path(
@@ -305,6 +329,8 @@ impl<'a> HookAssignCodegen for HookAssignCodegenDynamicOffset<'a> {
             &Reloaded2CSharpHook::make_hook_set_string(&state.static_name), 
             &state.static_type
         ));
+        hook_assign.push_str(&format!("                {}", make_hook_state_update(
+            &state.static_name, None, "addr", "Resolved", None)));
         hook_assign.push_str("            \x7d);\n");
         Ok(hook_assign)
     }
@@ -345,6 +371,8 @@ impl<'a> HookAssignCodegen for HookAssignCodegenDynamicOffsetSharedScans<'a> {
           delegate_type,
           class.get_fn_name()
         ));
+        hook_assign.push_str(&format!("                {}", make_hook_state_update(
+            class.get_fn_name(), None, "addr", "Hooked", None)));
         hook_assign.push_str("            \x7d);\n");
         Ok(hook_assign)
     }
@@ -382,6 +410,8 @@ impl<'a> HookAssignCodegen for HookAssignCodegenDynamicOffsetSharedScans<'a> {
             &Reloaded2CSharpHook::make_hook_set_string(&state.static_name), 
             &state.static_type
         ));
+        hook_assign.push_str(&format!("                {}", make_hook_state_update(
+            &state.static_name, None, "addr", "Resolved", None)));
         hook_assign.push_str("            \x7d);\n");
         Ok(hook_assign)
     }
@@ -457,6 +487,11 @@ impl HookAssignCodegen for HookAssignCodegenUserDefined {
             &class.get_fn_name()
 
         ));
+        hook_assign.push_str(&format!("            {}.{}(&UserRemove_{});\n",
+            &hooks_class,
+            Reloaded2CSharpHook::make_user_remove_set_string(&class.fn_name.to_ascii_uppercase()),
+            &class.get_fn_name()
+        ));
         Ok(hook_assign)
     }
     fn make_function_hook_assign_assembly<P: AsRef<Path>>(
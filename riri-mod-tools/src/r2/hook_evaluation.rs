@@ -10,6 +10,7 @@ use crate::{
         HookAssignCodegenUserDefined,
         InitFunction,
         ModEventFunction,
+        make_hook_state_update,
         ModLoadingFunction,
         SourceFileEvaluationResult,
         SourceFileEvaluationParamMapEx,
@@ -250,14 +251,49 @@ where P: AsRef<Path>
         Ok(SourceFileEvaluationResult::new(src_syntax, items))
     } 
 
+    // Register the hook as pending in the hook registry, along with the executable hash for the
+    // arm that was chosen and whether it's required
+    fn generate_hook_pending_state(&self, name: &str, entry: &HookEntry, cond: &HookConditional) -> String {
+        // Shared Scans signatures are C# expressions rather than string literals
+        let signature = match entry {
+            HookEntry::Dyn(d) if d.shared_scan.is_none() => Some(d.sig.as_str()),
+            _ => None
+        };
+        let hash = match cond {
+            HookConditional::HashNamed(s) => Some(format!("{}.Mod.{}", self.package.get_mod_id(), s)),
+            HookConditional::HashNum(v) => Some(v.to_string()),
+            _ => None
+        };
+        let mut out = format!("{}            ", make_hook_state_update(name, signature, "0", "Pending", hash.as_deref()));
+        // Hooks are required by default. User defined hooks are created later by the mod itself,
+        // so they shouldn't stop the rest of the mod from working
+        let required = match entry {
+            HookEntry::Dyn(d) => d.required,
+            HookEntry::Static(_) => true,
+            HookEntry::Delayed => false
+        };
+        if !required {
+            out.push_str(&format!("SetHookRequired(\"{}\", false);\n            ", name));
+        }
+        // Signatures that are expected to match more than once aren't reported as ambiguous
+        if let HookEntry::Dyn(d) = entry {
+            if d.allow_multiple {
+                out.push_str(&format!("SetHookAllowMultiple(\"{}\", true);\n            ", name));
+            }
+        }
+        out
+    }
+
     fn generate_hook_entry_block_function(
         &mut self, 
         ffi: &ReloadedHookClass, 
         class_data: &HookBootstrapFunctionState,
         delegate_type: &str,
-        entry: &HookEntry) 
+        entry: &HookEntry,
+        cond: &HookConditional) 
         -> Result<String, Box<dyn Error>> {
-        Ok(match entry {
+        let pending = self.generate_hook_pending_state(class_data.get_fn_name(), entry, cond);
+        Ok(pending + &match entry {
             HookEntry::Static(s) => {
                 let res = HookAssignCodegenStaticOffset::new(*s);
                 res.make_single_function_hook_assign(self, ffi, &class_data, &delegate_type)?
@@ -286,9 +322,11 @@ where P: AsRef<Path>
         &mut self, 
         ffi: &ReloadedHookClass,
         static_builder: &HookBootstrapStaticState,
-        entry: &HookEntry) 
+        entry: &HookEntry,
+        cond: &HookConditional) 
         -> Result<String, Box<dyn Error>> {
-        Ok(match entry {
+        let pending = self.generate_hook_pending_state(static_builder.get_static_name(), entry, cond);
+        Ok(pending + &match entry {
             HookEntry::Static(s) => {
                 let res = HookAssignCodegenStaticOffset::new(*s);
                 res.make_single_static_hook_assign(self, ffi, static_builder)?
@@ -361,7 +399,7 @@ where P: AsRef<Path>
         let mut hook_assign = String::new();
         if hook_parm.0.len() == 1 {
             hook_assign.push_str(&self.generate_hook_entry_block_function(
-                ffi, &class_data, &delegate_type, &hook_parm.0[0].1)?);
+                ffi, &class_data, &delegate_type, &hook_parm.0[0].1, &hook_parm.0[0].0)?);
         } else {
             let mut add_ending_brace = true;
             for (i, hook_entry) in hook_parm.0.iter().enumerate() {
@@ -393,7 +431,7 @@ where P: AsRef<Path>
                     },
                 }
                 hook_assign.push_str(&self.generate_hook_entry_block_function(
-                    ffi, &class_data, &delegate_type, &hook_entry.1)?);
+                    ffi, &class_data, &delegate_type, &hook_entry.1, &hook_entry.0)?);
             }
             if add_ending_brace {
                 hook_assign.push_str("\x7d\n");
@@ -412,7 +450,8 @@ where P: AsRef<Path>
         assemble: &AssemblyFunctionHookData,
         cond: &HookConditional)
         -> Result<String, Box<dyn Error>> {
-        Ok(match hook {
+        let pending = self.generate_hook_pending_state(class_data.get_fn_name(), hook, cond);
+        Ok(pending + &match hook {
             HookEntry::Static(s) => {
                 let res = HookAssignCodegenStaticOffset::new(*s);
                 res.make_function_hook_assign_assembly(self, ffi, &class_data, &delegate_type, &assemble, cond)?
@@ -490,6 +529,8 @@ where P: AsRef<Path>
         Ok(hook_assign)
     }
 
+    // create_hook! passes in the entry point for a free instance slot. The original function's
+    // address is written before the hook is activated, so that the instance never runs without it.
     pub fn generate_init_function_bootstrap(
         &self,
         class_data: &HookBootstrapFunctionState,
@@ -498,21 +539,64 @@ where P: AsRef<Path>
         let mut user_method = String::new();
         user_method.push_str("[UnmanagedCallersOnly(CallConvs = [ typeof(System.Runtime.CompilerServices.CallConvStdcall) ])]\n");
         user_method.push_str(&format!(
-            "\t\tpublic static unsafe void UserDefined_{}(nuint addr)\n", class_data.get_fn_name()));
+            "\t\tpublic static unsafe byte UserDefined_{}(nuint addr, nuint payload, uint slot, nuint* original)\n", class_data.get_fn_name()));
         user_method.push_str("\t\t\x7b\n");
         user_method.push_str(&format!(
-            "\t\t\t_instance!._{} = _hooks!.CreateHook<{}>({}, (long)addr).Activate();\n",
-            class_data.get_fn_name(), class_data.get_delegate_path(), class_data.get_fn_path()));
-        user_method.push_str(&format!("\t\t\t{}.{}(({})_instance!._{}.OriginalFunctionWrapperAddress);\n",
-          class_data.get_class_path(), 
-          &Reloaded2CSharpHook::make_hook_set_string(&class_data.get_fn_name().to_ascii_uppercase()),
-          delegate_type,
-          class_data.get_fn_name()
-        ));
+            "\t\t\tvar hook = _hooks!.CreateHook<{}>((void*)payload, (long)addr);\n", class_data.get_delegate_path()));
+        user_method.push_str("\t\t\t*original = (nuint)hook.OriginalFunctionWrapperAddress;\n");
+        user_method.push_str(&format!("\t\t\t_instance!._{}_INSTANCES[slot] = hook.Activate();\n", class_data.get_fn_name()));
+        user_method.push_str(&format!("\t\t\t{}", make_hook_state_update(
+            class_data.get_fn_name(), None, "addr", "Hooked", None)));
+        user_method.push_str("\t\t\treturn 1;\n");
+        user_method.push_str("\t\t\x7d\n");
+        // Reloaded can't remove a hook, so disable it instead
+        user_method.push_str("[UnmanagedCallersOnly(CallConvs = [ typeof(System.Runtime.CompilerServices.CallConvStdcall) ])]\n");
+        user_method.push_str(&format!(
+            "\t\tpublic static unsafe byte UserRemove_{}(uint slot)\n", class_data.get_fn_name()));
+        user_method.push_str("\t\t\x7b\n");
+        user_method.push_str(&format!("\t\t\tvar hook = _instance!._{}_INSTANCES[slot];\n", class_data.get_fn_name()));
+        user_method.push_str("\t\t\tif (hook == null) return 0;\n");
+        user_method.push_str("\t\t\thook.Disable();\n");
+        user_method.push_str(&format!("\t\t\t_instance!._{}_INSTANCES[slot] = null;\n", class_data.get_fn_name()));
+        user_method.push_str("\t\t\treturn 1;\n");
         user_method.push_str("\t\t\x7d\n");
         user_method
     }
 
+    // Let Rust enable or disable the hook through the {hook_name} module generated by riri_hook_fn.
+    // field is the IHook or IAsmHook that was created for the function. Instances created with
+    // create_hook! are toggled too.
+    pub fn generate_hook_toggle_function(&self, ffi: &ReloadedHookClass, class_data: &HookBootstrapFunctionState, field: &str, has_instances: bool) -> (String, String) {
+        let mut toggle_method = String::new();
+        toggle_method.push_str("[UnmanagedCallersOnly(CallConvs = [ typeof(System.Runtime.CompilerServices.CallConvStdcall) ])]\n");
+        toggle_method.push_str(&format!(
+            "\t\tpublic static unsafe byte Toggle_{}(byte enable)\n", class_data.get_fn_name()));
+        toggle_method.push_str("\t\t\x7b\n");
+        toggle_method.push_str(&format!("\t\t\tvar hook = _instance!.{};\n", field));
+        if has_instances {
+            toggle_method.push_str("\t\t\tvar found = hook != null;\n");
+            toggle_method.push_str(&format!("\t\t\tforeach (var instance in _instance!._{}_INSTANCES)\n", class_data.get_fn_name()));
+            toggle_method.push_str("\t\t\t\x7b\n");
+            toggle_method.push_str("\t\t\t\tif (instance == null) continue;\n");
+            toggle_method.push_str("\t\t\t\tif (enable != 0) instance.Enable(); else instance.Disable();\n");
+            toggle_method.push_str("\t\t\t\tfound = true;\n");
+            toggle_method.push_str("\t\t\t\x7d\n");
+            toggle_method.push_str("\t\t\tif (!found) return 0;\n");
+            toggle_method.push_str("\t\t\tif (hook == null) return 1;\n");
+        } else {
+            toggle_method.push_str("\t\t\tif (hook == null) return 0;\n");
+        }
+        toggle_method.push_str("\t\t\tif (enable != 0) hook.Enable(); else hook.Disable();\n");
+        toggle_method.push_str("\t\t\treturn 1;\n");
+        toggle_method.push_str("\t\t\x7d\n");
+        let toggle_assign = format!("{}.{}.{}(&Toggle_{});\n            ",
+            &self.ffi_hook_namespace(),
+            &ffi.csharp_class_name(),
+            Reloaded2CSharpHook::make_hook_toggle_set_string(&class_data.get_fn_name().to_ascii_uppercase()),
+            class_data.get_fn_name());
+        (toggle_assign, toggle_method)
+    }
+
     // - Add an IHook<DelegateType> for the target C# hook
     // - Use the SigScan function to set the value of that C# hook, then get the
     // OriginalFunctionWrapperAddress to pass into the Rust hook set function. The Sigscan will
@@ -552,18 +636,30 @@ where P: AsRef<Path>
                         SourceFileEvaluationType::CFunction(hook_parm) => {
                             hook_decl.push_str(&format!("private Reloaded.Hooks.Definitions.IHook<{}>? _{};\n", 
                                 class_data.get_delegate_path(), class_data.get_fn_name()));
+                            let has_instances = hook_parm.0.iter().any(|(_, en)| matches!(en, HookEntry::Delayed));
+                            if has_instances {
+                                // This must stay in sync with MAX_HOOK_INSTANCES in riri_mod_tools_rt::hook_instance!
+                                hook_decl.push_str(&format!("private Reloaded.Hooks.Definitions.IHook<{}>?[] _{}_INSTANCES = new Reloaded.Hooks.Definitions.IHook<{}>?[{}];\n",
+                                    class_data.get_delegate_path(), class_data.get_fn_name(), class_data.get_delegate_path(),
+                                    riri_mod_tools_impl::hook_codegen::MAX_HOOK_INSTANCES));
+                            }
+                            let (toggle_assign, toggle_method) = self.generate_hook_toggle_function(
+                                ffi, &class_data, &format!("_{}", class_data.get_fn_name()), has_instances);
+                            hook_assign.push_str(&toggle_assign);
+                            hook_methods.push_str(&toggle_method);
                             hook_assign.push_str(&self.generate_hook_c_function_for_function(
                                 hook_parm, ffi, &class_data, &delegate_type)?);
-                            for (_, en) in &hook_parm.0 {
-                                match en {
-                                    HookEntry::Delayed => hook_methods.push_str(&self.generate_init_function_bootstrap(&class_data, &delegate_type)),
-                                    _ => ()
-                                }
+                            if has_instances {
+                                hook_methods.push_str(&self.generate_init_function_bootstrap(&class_data, &delegate_type));
                             }
                         },
                         SourceFileEvaluationType::Inline(hook_parm) => {
                             hook_decl.push_str(&format!("private Reloaded.Hooks.Definitions.IAsmHook? _{}_ASM;\n", 
                                 class_data.get_fn_name()));
+                            let (toggle_assign, toggle_method) = self.generate_hook_toggle_function(
+                                ffi, &class_data, &format!("_{}_ASM", class_data.get_fn_name()), false);
+                            hook_assign.push_str(&toggle_assign);
+                            hook_methods.push_str(&toggle_method);
                             for en in &hook_parm.hook_info.0 {
                                 hook_decl.push_str(&format!("private Reloaded.Hooks.Definitions.IReverseWrapper<{}>? _{}_WRAP{};\n", 
                                     class_data.get_delegate_path(), class_data.get_fn_name(), en.0));
@@ -598,7 +694,7 @@ where P: AsRef<Path>
                     if let SourceFileEvaluationType::CFunction(hook_parm) = &hook_parm {
                         if hook_parm.0.len() == 1 {
                             hook_assign.push_str(&self.generate_hook_entry_block_static(
-                                ffi, &static_builder, &hook_parm.0[0].1)?);
+                                ffi, &static_builder, &hook_parm.0[0].1, &hook_parm.0[0].0)?);
                         } else {
                             let mut add_ending_brace = true;
                             for (i, hook_entry) in hook_parm.0.iter().enumerate() {
@@ -627,7 +723,7 @@ where P: AsRef<Path>
                                     },
                                 }
                                 hook_assign.push_str(&self.generate_hook_entry_block_static(
-                                    ffi, &static_builder, &hook_entry.1)?);
+                                    ffi, &static_builder, &hook_entry.1, &hook_entry.0)?);
                             }
                             if add_ending_brace {
                                 hook_assign.push_str("\x7d\n");
@@ -765,18 +861,19 @@ where P: AsRef<Path>
             Some(v) => v.to_owned(),
             None => format!("LimeGreen")
         };
-        let mut hbs = Handlebars::new();
-        hbs.register_template_string("main", crate::hbs::mod_main::FILE)?;
-        hbs.register_template_string("utils", crate::hbs::ffi_builtin::FILE)?;
         let mut data = toml::Table::new();
         data.insert("mod_id".to_owned(), toml::Value::String(self.package.get_mod_id().to_owned()));
         data.insert("mod_name".to_owned(), toml::Value::String(self.package.Name.to_owned()));
         data.insert("dll_name".to_owned(), toml::Value::String(self.get_output_dll_name()?));
         data.insert("logger_prefix".to_owned(), toml::Value::String(logger_prefix));
         data.insert("logger_color".to_owned(), toml::Value::String(logger_color));
+        if let Some(filter) = &self.package.LoggerFilter {
+            set_logger_filter(&mut data, filter);
+        }
         data.insert("uses_shared_scans".to_owned(), toml::Value::Boolean(self.uses_shared_scans));
         data.insert("csharp_function_invoke".to_owned(), toml::Value::Boolean(self.use_csharp_invocation));
         data.insert("cached_signatures".to_owned(), toml::Value::Boolean(self.use_cached_signatures));
+        data.insert("batched_signatures".to_owned(), toml::Value::Boolean(self.use_batched_signature_scan));
         data.insert("utility_namespace".to_owned(), toml::Value::String(self.ffi_utility_class()));
         data.insert("ffi_namespace".to_owned(), toml::Value::String(self.ffi_namespace()));
         data.insert("exports_interfaces".to_owned(), toml::Value::Boolean(false));
@@ -787,8 +884,9 @@ where P: AsRef<Path>
         data.insert("register_hook_fn".to_owned(), register_hook_modules);
         data.insert("loader_init_fn".to_owned(), mod_initialized_modules);
         data.insert("mod_loading_fn".to_owned(), mod_loading_modules);
-        mod_file.write(hbs.render("main", &data)?.as_bytes())?;
-        utils_file.write(hbs.render("utils", &data)?.as_bytes())?;
+        let (mod_main, utils) = render_mod_main(&data)?;
+        mod_file.write(mod_main.as_bytes())?;
+        utils_file.write(utils.as_bytes())?;
         Ok(())
     }
 
@@ -810,4 +908,35 @@ where P: AsRef<Path>
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Escape a value so that it can be placed inside of a C# string literal. Templates insert it with
+// {{{ }}} so that Handlebars doesn't HTML escape it as well.
+pub(crate) fn escape_csharp_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out
+}
+
+/// Set the log filter from package.toml, which Utils.g.cs applies on startup.
+pub(crate) fn set_logger_filter(data: &mut toml::Table, filter: &str) {
+    data.insert("logger_filter".to_owned(), toml::Value::String(escape_csharp_string(filter)));
+}
+
+/// Render Mod.g.cs and Utils.g.cs from the values set in `generate_mod_main`.
+pub(crate) fn render_mod_main(data: &toml::Table) -> Result<(String, String), Box<dyn Error>> {
+    let mut hbs = Handlebars::new();
+    hbs.register_template_string("main", crate::hbs::mod_main::FILE)?;
+    hbs.register_template_string("utils", crate::hbs::ffi_builtin::FILE)?;
+    Ok((hbs.render("main", data)?, hbs.render("utils", data)?))
+}
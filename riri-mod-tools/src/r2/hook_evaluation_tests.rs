@@ -0,0 +1,21 @@
+#![cfg(test)]
+use crate::r2::hook_evaluation::{ render_mod_main, set_logger_filter };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn logger_filter_is_not_escaped() -> ReturnType {
+    let mut data = toml::Table::new();
+    set_logger_filter(&mut data, "riri=debug,mymod::camera=trace,warn");
+    let (_, utils) = render_mod_main(&data)?;
+    assert!(utils.contains("?? \"riri=debug,mymod::camera=trace,warn\";"), "{}", utils);
+    Ok(())
+}
+
+#[test]
+fn logger_filter_is_a_csharp_string() -> ReturnType {
+    let mut data = toml::Table::new();
+    set_logger_filter(&mut data, "a\"b\\c<d>&e");
+    let (_, utils) = render_mod_main(&data)?;
+    assert!(utils.contains(r#"?? "a\"b\\c<d>&e";"#), "{}", utils);
+    Ok(())
+}
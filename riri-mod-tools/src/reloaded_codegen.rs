@@ -33,6 +33,7 @@ pub struct HookEvaluator<'a, P: AsRef<Path>> {
     pub(crate) use_csharp_invocation: bool,
     pub(crate) pointers_are_untyped: bool,
     pub(crate) use_cached_signatures: bool,
+    pub(crate) use_batched_signature_scan: bool,
 }
 
 impl<'a, P> HookEvaluator<'a, P>
@@ -60,6 +61,7 @@ where P: AsRef<Path>
             use_csharp_invocation: package.UseCsharpInvocation.map_or(false, |v| v),
             pointers_are_untyped: package.PointersAreUntyped.map_or(true, |v| v),
             use_cached_signatures: package.UseCachedSignatures.map_or(false, |v| v),
+            use_batched_signature_scan: package.UseBatchedSignatureScan.map_or(false, |v| v),
         })
     }
 }
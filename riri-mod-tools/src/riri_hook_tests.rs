@@ -151,7 +151,7 @@ fn correct_function_hook_static_offset() -> ReturnType {
     let result = riri_hook_fn_impl(attributes, input_function.clone());
     let transformed: syn::File = syn::parse2(result)?; 
     let input_function_ast: syn::ItemFn = syn::parse2(input_function)?;
-    assert!(transformed.items.len() == 3, "Incorrect number of items generated");
+    assert!(transformed.items.len() == 6, "Incorrect number of items generated");
     // First item to check is the pointer to the original function
     check_original_function_pointer(&input_function_ast, &transformed.items[0])?;
     // Second test is setting the pointer
@@ -183,7 +183,7 @@ fn correct_function_hook_dynamic_offset() -> ReturnType {
     let result = riri_hook_fn_impl(attributes, input_function.clone());
     let transformed: syn::File = syn::parse2(result)?; 
     let input_function_ast: syn::ItemFn = syn::parse2(input_function)?;
-    assert!(transformed.items.len() == 3, "Incorrect number of items generated");
+    assert!(transformed.items.len() == 6, "Incorrect number of items generated");
     // First item to check is the pointer to the original function
     check_original_function_pointer(&input_function_ast, &transformed.items[0])?;
     // Second test is setting the pointer
@@ -310,4 +310,220 @@ fn hook_inline_test() -> ReturnType {
     let result = riri_hook_inline_fn_impl(attributes, input_function.clone());
     println!("{}", result.to_string());
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn hook_function_catches_panics() -> ReturnType {
+    use riri_mod_tools_impl::riri_hook::riri_hook_fn_impl;
+    use quote::{ quote, ToTokens };
+    let input_function = quote! {
+        pub unsafe extern "C" fn test_function_panic(a1: u32, _: *mut u8) -> u32 {
+            original_function!(a1, std::ptr::null_mut()) * 2
+        }
+    };
+    let attributes = quote! { static_offset(0x10) };
+    let result = riri_hook_fn_impl(attributes, input_function.clone());
+    let transformed: syn::File = syn::parse2(result)?;
+    assert!(transformed.items.len() == 6, "Incorrect number of items generated");
+    let hooked_function = match &transformed.items[2] {
+        syn::Item::Fn(f) => f,
+        _ => return Err(Box::new(WrongItemType("function")))
+    };
+    let input_function_ast: syn::ItemFn = syn::parse2(input_function)?;
+    check_function_signature_types(hooked_function, &input_function_ast)?;
+    // Unnamed arguments are given a name so they can be passed to the original function
+    match &hooked_function.sig.inputs[1] {
+        syn::FnArg::Typed(t) => assert!(quote!(#t).to_string().starts_with("__riri_arg1"), "Unnamed argument should be renamed"),
+        _ => return Err(Box::new(WrongTypeFormat("Type")))
+    }
+    let body = hooked_function.block.to_token_stream().to_string();
+    assert!(body.contains("riri_mod_tools_rt :: panic_guard :: catch_panic (\"test_function_panic\""),
+        "Function body should run inside of catch_panic");
+    assert!(body.contains("let __riri_og_arg1 = :: std :: clone :: Clone :: clone (& __riri_arg1)"),
+        "Arguments should be cloned before the function body runs");
+    assert!(body.contains("CallOriginal") && body.contains("(__riri_og_arg0 , __riri_og_arg1)"),
+        "Original function should be called with the hook's arguments after a panic");
+    Ok(())
+}
+
+#[test]
+fn optional_hook_falls_back() -> ReturnType {
+    use riri_mod_tools_impl::riri_hook::riri_hook_fn_impl;
+    use quote::{ quote, ToTokens };
+    let input_function = quote! {
+        pub unsafe extern "C" fn test_function_optional(a1: u32) -> u32 {
+            original_function!(a1) + 1
+        }
+    };
+    let attributes = quote! { dynamic_offset(signature = "48 89 5C 24 ?? 57", required = false) };
+    let result = riri_hook_fn_impl(attributes, input_function.clone());
+    let transformed: syn::File = syn::parse2(result)?;
+    let hooked_function = match &transformed.items[2] {
+        syn::Item::Fn(f) => f,
+        _ => return Err(Box::new(WrongItemType("function")))
+    };
+    let body = hooked_function.block.to_token_stream().to_string();
+    assert!(body.contains("riri_mod_tools_rt :: hook_registry :: missing_original (\"test_function_optional\")"),
+        "original_function! should fall back when the original function isn't set");
+    assert!(body.contains("riri_mod_tools_rt :: hook_registry :: required_hook_failed ()"),
+        "Hook should call the original function when a required hook failed");
+    // required only accepts a boolean
+    let attributes = quote! { dynamic_offset(signature = "48 89 5C 24 ?? 57", required = "no") };
+    let result = riri_hook_fn_impl(attributes, input_function).to_string();
+    assert!(result.contains("compile_error") && result.contains("should be true or false"),
+        "Non-boolean value for required should be an error");
+    Ok(())
+}
+
+#[test]
+fn hook_function_toggle_module() -> ReturnType {
+    use riri_mod_tools_impl::riri_hook::riri_hook_fn_impl;
+    use quote::{ quote, ToTokens };
+    let input_function = quote! {
+        pub unsafe extern "C" fn test_function_toggle(a1: u32) -> u32 {
+            original_function!(a1)
+        }
+    };
+    let attributes = quote! { static_offset(0x10) };
+    let result = riri_hook_fn_impl(attributes, input_function);
+    let transformed: syn::File = syn::parse2(result)?;
+    let toggle_ptr = match &transformed.items[3] {
+        syn::Item::Static(s) => s,
+        _ => return Err(Box::new(WrongItemType("static")))
+    };
+    assert!(toggle_ptr.ident == Reloaded2CSharpHook::make_hook_toggle_string("TEST_FUNCTION_TOGGLE"),
+        "Toggle callback has the wrong name");
+    let toggle_set = match &transformed.items[4] {
+        syn::Item::Fn(f) => f,
+        _ => return Err(Box::new(WrongItemType("function")))
+    };
+    assert!(toggle_set.sig.ident == Reloaded2CSharpHook::make_hook_toggle_set_string("TEST_FUNCTION_TOGGLE"),
+        "Toggle callback setter has the wrong name");
+    // A module with the same name as the hook exposes enable, disable and is_enabled
+    let toggle_mod = match &transformed.items[5] {
+        syn::Item::Mod(m) => m,
+        _ => return Err(Box::new(WrongItemType("module")))
+    };
+    assert!(toggle_mod.ident == "test_function_toggle", "Toggle module should be named after the hook");
+    let fns: Vec<String> = toggle_mod.content.as_ref().unwrap().1.iter().filter_map(|i| match i {
+        syn::Item::Fn(f) => Some(f.sig.ident.to_string()),
+        _ => None
+    }).collect();
+    assert!(fns == ["enable", "disable", "is_enabled"], "Toggle module is missing functions");
+    assert!(toggle_mod.to_token_stream().to_string().contains("toggle_hook (\"test_function_toggle\""),
+        "Toggle functions should go through the hook registry");
+    Ok(())
+}
+
+#[test]
+fn user_defined_hook_instances() -> ReturnType {
+    use riri_mod_tools_impl::{
+        hook_codegen::MAX_HOOK_INSTANCES,
+        riri_hook::{ create_hook_impl, riri_hook_fn_impl }
+    };
+    use quote::{ quote, ToTokens };
+    let input_function = quote! {
+        pub unsafe extern "C" fn test_function_user(a1: u32, a2: *mut u8) -> u32 {
+            original_function!(a1, a2)
+        }
+    };
+    let attributes = quote! { user_defined() };
+    let result = riri_hook_fn_impl(attributes, input_function);
+    let transformed: syn::File = syn::parse2(result)?;
+    assert!(transformed.items.len() == 11, "Incorrect number of items generated");
+    // Original functions are stored for each instance
+    let original_function_ptr = match &transformed.items[0] {
+        syn::Item::Static(s) => s,
+        _ => return Err(Box::new(WrongItemType("static")))
+    };
+    assert!(original_function_ptr.ty.to_token_stream().to_string().contains("hook_instance :: HookInstances"),
+        "User defined hooks should store an original function for each instance");
+    let remove_set = match &transformed.items[5] {
+        syn::Item::Fn(f) => f,
+        _ => return Err(Box::new(WrongItemType("function")))
+    };
+    assert!(remove_set.sig.ident == Reloaded2CSharpHook::make_user_remove_set_string("TEST_FUNCTION_USER"),
+        "Remove callback setter has the wrong name");
+    let create_fn = match &transformed.items[6] {
+        syn::Item::Fn(f) => f,
+        _ => return Err(Box::new(WrongItemType("function")))
+    };
+    assert!(create_fn.sig.ident == Reloaded2CSharpHook::make_hook_create_string("TEST_FUNCTION_USER"),
+        "Create function has the wrong name");
+    let create_body = create_fn.block.to_token_stream().to_string();
+    assert!(create_body.matches("hook_instance :: enter").count() == MAX_HOOK_INSTANCES,
+        "There should be an entry point for each instance");
+    // create_hook! calls the create function next to the hook
+    let create_hook = create_hook_impl(quote! { ptr, hooks::test_function_user }).to_string();
+    assert!(create_hook == "hooks :: __HOOK_CREATE_TEST_FUNCTION_USER (ptr)", "create_hook! expanded to {}", create_hook);
+    Ok(())
+}
+
+// The argument isn't Copy, so the hook has to clone it before the body moves it
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct PanicPolicyArg(u32);
+
+#[riri_mod_tools_proc::riri_hook_fn(static_offset(0x10))]
+pub unsafe extern "C" fn panic_policy_hook(a1: u32, a2: PanicPolicyArg) -> u32 {
+    let moved = a2;
+    if a1 == 0 { panic!("panic policy test") }
+    original_function!(a1, moved) + 1
+}
+
+extern "C" fn panic_policy_original(a1: u32, a2: PanicPolicyArg) -> u32 {
+    a1 * 100 + a2.0
+}
+
+// Aborting kills the test process, so the test runs itself again in a child process which sets
+// the abort policy and panics.
+#[test]
+fn hook_function_panic_policies() -> ReturnType {
+    use riri_mod_tools_rt::panic_guard::{ set_panic_policy, PanicPolicy };
+    if std::env::var_os("RIRI_PANIC_POLICY_CHILD").is_some() {
+        set_panic_policy(PanicPolicy::Abort);
+        unsafe { panic_policy_hook(0, PanicPolicyArg(1)) };
+        return Ok(());
+    }
+    unsafe { __HOOK_SET_PANIC_POLICY_HOOK(panic_policy_original) };
+    assert_eq!(unsafe { panic_policy_hook(2, PanicPolicyArg(3)) }, 204);
+    set_panic_policy(PanicPolicy::CallOriginal);
+    assert_eq!(unsafe { panic_policy_hook(0, PanicPolicyArg(3)) }, 3, "Hook should call the original function");
+    set_panic_policy(PanicPolicy::ReturnDefault);
+    assert_eq!(unsafe { panic_policy_hook(0, PanicPolicyArg(3)) }, 0, "Hook should return the fallback value");
+    set_panic_policy(PanicPolicy::CallOriginal);
+    let status = std::process::Command::new(std::env::current_exe()?)
+        .args(["--exact", "riri_hook_tests::hook_function_panic_policies", "--test-threads=1"])
+        .env("RIRI_PANIC_POLICY_CHILD", "1")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()?;
+    assert!(!status.success(), "Hook should abort the process");
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(status.signal(), Some(6), "Process should be stopped by SIGABRT");
+    }
+    Ok(())
+}
+
+#[test]
+fn hook_arms_round_trip() -> ReturnType {
+    use riri_mod_tools_impl::{
+        hook_parse::HookInfoMultipleCaseCollector,
+        riri_hook::HookInfo
+    };
+    let arms = [
+        "TEST_V1 => dynamic_offset(signature = \"48 8B 05 ?? ?? ?? ??\", resolve_type = get_address_may_thunk, required = false),",
+        "TEST_V2 => dynamic_offset(signature = \"90 90\", calling_convention = \"unknown\", allow_multiple = true),",
+        "TEST_V3 => dynamic_offset(shared_scan = \"consumer\"),",
+        "336 => static_offset(0x150),",
+        "_ => user_defined(),"
+    ];
+    let info: HookInfo = syn::parse_str(&format!("{{ {} }}", arms.join(" ")))?;
+    let formatted: Vec<String> = info.0.iter()
+        .map(|(c, e)| HookInfoMultipleCaseCollector::format_arm(c, e)).collect();
+    assert_eq!(formatted, arms);
+    let info: HookInfo = syn::parse_str("static_offset(0x10)")?;
+    assert_eq!(HookInfoMultipleCaseCollector::format_arm(&info.0[0].0, &info.0[0].1), "static_offset(0x10)");
+    Ok(())
+}
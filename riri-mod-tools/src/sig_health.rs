@@ -0,0 +1,367 @@
+//! Checks a mod's signatures against copies of the game's executables without launching them.
+//! Every hook is read from the mod's source, then each executable is mapped from disk, and the
+//! signature and resolver for the arm matching that executable's hash are run on it. The results
+//! are shown as a matrix of hooks against game versions.
+//!
+//! This is available as the `riri-sig-health` binary:
+//!
+//! ```text
+//! riri-sig-health <mod source directory> <executable directory> [hashes.toml]
+//! ```
+//!
+//! Executables are named by the hash names in riri-mod-tools' `hashes.toml` (and optionally, the
+//! mod's own `hashes.toml`), or by their path if their hash isn't known.
+
+use riri_mod_tools_impl::{
+    hook_parse::{ AssemblyFunctionHook, HookConditional, HookEntry },
+    riri_hook::HookInfo
+};
+use riri_mod_tools_rt::{
+    fuzzy_match::{ FuzzyMatch, FuzzyMatcher },
+    pattern::Pattern,
+    pe_image::{ PeImage, ResolveError },
+    sigscan_batch::BatchScanner
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{ Display, Formatter },
+    fs,
+    path::{ Path, PathBuf }
+};
+use walkdir::WalkDir;
+
+/// The hook attribute of a function or static in the mod's source.
+#[derive(Debug)]
+pub struct SourceHook {
+    name: String,
+    path: PathBuf,
+    info: HookInfo
+}
+
+impl SourceHook {
+    pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_path(&self) -> &Path { &self.path }
+    pub fn get_info(&self) -> &HookInfo { &self.info }
+}
+
+/// A copy of the game's executable to check signatures against.
+#[derive(Debug)]
+pub struct GameExecutable {
+    label: String,
+    image: PeImage
+}
+
+impl GameExecutable {
+    pub fn get_label(&self) -> &str { &self.label }
+    pub fn get_image(&self) -> &PeImage { &self.image }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// The signature matched once, and the resolver returned this offset.
+    Found(usize),
+    /// The signature matched once at this offset, but the resolver is defined by the mod so the
+    /// hook's final address can't be checked.
+    FoundUserResolver(usize),
+    Missing { required: bool },
+    /// The signature matched more than once, and the hook doesn't set `allow_multiple`.
+    Ambiguous(Vec<usize>),
+    /// The signature matched at this offset, but the resolver pointed outside of the executable.
+    Unresolved(usize),
+    InvalidSignature(String),
+    /// None of the hook's arms apply to this executable.
+    NoEntry,
+    /// The hook is user defined or consumes a shared scan, so there's nothing to check.
+    Skipped
+}
+
+impl SignatureStatus {
+    pub fn is_failure(&self) -> bool {
+        match self {
+            Self::Missing { required } => *required,
+            Self::Ambiguous(_) | Self::Unresolved(_) | Self::InvalidSignature(_) => true,
+            _ => false
+        }
+    }
+
+    fn get_cell(&self) -> String {
+        match self {
+            Self::Found(_) => "found".to_owned(),
+            Self::FoundUserResolver(_) => "found*".to_owned(),
+            Self::Missing { required: true } => "MISSING".to_owned(),
+            Self::Missing { required: false } => "missing".to_owned(),
+            Self::Ambiguous(m) => format!("AMBIGUOUS ({})", m.len()),
+            Self::Unresolved(_) => "UNRESOLVED".to_owned(),
+            Self::InvalidSignature(_) => "INVALID".to_owned(),
+            Self::NoEntry => "-".to_owned(),
+            Self::Skipped => "skipped".to_owned()
+        }
+    }
+}
+
+impl Display for SignatureStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Found(o) => write!(f, "Found at 0x{:x}", o),
+            Self::FoundUserResolver(o) => write!(f, "Found at 0x{:x}, resolved by the mod", o),
+            Self::Missing { required: true } => write!(f, "Signature was not found"),
+            Self::Missing { required: false } => write!(f, "Signature was not found (optional)"),
+            Self::Ambiguous(m) => {
+                let offsets: Vec<String> = m.iter().map(|o| format!("0x{:x}", o)).collect();
+                write!(f, "Signature matched {} times: {}", m.len(), offsets.join(", "))
+            },
+            Self::Unresolved(o) => write!(f, "Found at 0x{:x}, but it resolved outside of the executable", o),
+            Self::InvalidSignature(e) => write!(f, "Signature is invalid: {}", e),
+            Self::NoEntry => write!(f, "No entry for this executable"),
+            Self::Skipped => write!(f, "Skipped")
+        }
+    }
+}
+
+/// The status of every hook for every executable.
+#[derive(Debug)]
+pub struct HealthReport {
+    executables: Vec<String>,
+    hooks: Vec<String>,
+    // [hook][executable]
+    results: Vec<Vec<SignatureStatus>>,
+    // Closest matches for required signatures that weren't found, by (hook, executable)
+    closest: HashMap<(usize, usize), Vec<FuzzyMatch>>
+}
+
+impl HealthReport {
+    pub fn get_executables(&self) -> &[String] { &self.executables }
+    pub fn get_hooks(&self) -> &[String] { &self.hooks }
+    pub fn get_status(&self, hook: usize, executable: usize) -> &SignatureStatus { &self.results[hook][executable] }
+    pub fn get_status_by_name(&self, hook: &str, executable: &str) -> Option<&SignatureStatus> {
+        let hook = self.hooks.iter().position(|h| h == hook)?;
+        let executable = self.executables.iter().position(|e| e == executable)?;
+        Some(self.get_status(hook, executable))
+    }
+    /// The closest matches for a required signature that wasn't found, with suggested
+    /// replacements.
+    pub fn get_closest_matches(&self, hook: usize, executable: usize) -> &[FuzzyMatch] {
+        self.closest.get(&(hook, executable)).map_or(&[], |v| &v[..])
+    }
+    pub fn failure_count(&self) -> usize {
+        self.results.iter().flatten().filter(|s| s.is_failure()).count()
+    }
+    pub fn has_failures(&self) -> bool { self.failure_count() > 0 }
+}
+
+impl Display for HealthReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name_width = self.hooks.iter().map(|h| h.len()).max().unwrap_or(0).max(4);
+        let widths: Vec<usize> = self.executables.iter().enumerate().map(|(i, e)| {
+            self.results.iter().map(|r| r[i].get_cell().len()).max().unwrap_or(0).max(e.len())
+        }).collect();
+        // Lines are trimmed so that the last column doesn't leave trailing whitespace
+        let mut header = format!("{:<1$}", "Hook", name_width);
+        for (e, w) in self.executables.iter().zip(&widths) {
+            header += &format!("  {:<1$}", e, w);
+        }
+        writeln!(f, "{}", header.trim_end())?;
+        for (hook, row) in self.hooks.iter().zip(&self.results) {
+            let mut line = format!("{:<1$}", hook, name_width);
+            for (s, w) in row.iter().zip(&widths) {
+                line += &format!("  {:<1$}", s.get_cell(), w);
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        // List the details of anything that went wrong below the matrix
+        for (h, (hook, row)) in self.hooks.iter().zip(&self.results).enumerate() {
+            for (i, (e, s)) in self.executables.iter().zip(row).enumerate() {
+                if s.is_failure() {
+                    writeln!(f, "{} on {}: {}", hook, e, s)?;
+                    for m in self.get_closest_matches(h, i) {
+                        writeln!(f, "    closest match {}", m)?;
+                    }
+                }
+            }
+        }
+        write!(f, "{} hooks checked against {} executables, {} failed", self.hooks.len(), self.executables.len(), self.failure_count())
+    }
+}
+
+// Match on the last segment, so that `#[riri_mod_tools_proc::riri_hook_fn]` is found too
+fn find_attribute<'a>(attrs: &'a [syn::Attribute], name: &str) -> Option<&'a syn::Attribute> {
+    attrs.iter().find(|a| a.path().segments.last().is_some_and(|s| s.ident == name))
+}
+
+#[derive(Debug)]
+pub struct SignatureHealthCheck {
+    hooks: Vec<SourceHook>,
+    executables: Vec<GameExecutable>,
+    hashes: HashMap<String, u64>
+}
+
+impl SignatureHealthCheck {
+    /// Create a health check that knows about the executable hashes built into riri-mod-tools.
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let mut new = Self { hooks: vec![], executables: vec![], hashes: HashMap::new() };
+        new.add_hashes(include_str!("hash/hashes.toml"))?;
+        Ok(new)
+    }
+
+    /// Add hash names from a file in the same format as `hashes.toml`.
+    pub fn add_hashes(&mut self, hashes: &str) -> Result<(), Box<dyn Error>> {
+        for (k, v) in hashes.parse::<toml::Table>()? {
+            let hash = v.as_str().and_then(|v| v.strip_prefix("0x"))
+                .ok_or_else(|| format!("Hash for {} should be a hex string", k))?;
+            self.hashes.insert(k, u64::from_str_radix(hash, 16)?);
+        }
+        Ok(())
+    }
+
+    pub fn get_hooks(&self) -> &[SourceHook] { &self.hooks }
+    pub fn get_executables(&self) -> &[GameExecutable] { &self.executables }
+
+    /// Collect every `riri_hook_fn`, `riri_hook_inline_fn` and `riri_hook_static` from Rust
+    /// source code, including hooks inside of inline modules.
+    pub fn add_source<P: AsRef<Path>>(&mut self, path: P, source: &str) -> Result<(), Box<dyn Error>> {
+        self.add_items(path.as_ref(), syn::parse_file(source)?.items)
+    }
+
+    fn add_items(&mut self, path: &Path, items: Vec<syn::Item>) -> Result<(), Box<dyn Error>> {
+        for item in items {
+            let (name, info) = match item {
+                syn::Item::Fn(f) => {
+                    let name = f.sig.ident.to_string();
+                    if let Some(a) = find_attribute(&f.attrs, "riri_hook_fn") {
+                        (name, syn::parse2::<HookInfo>(a.meta.require_list()?.tokens.clone())?)
+                    } else if let Some(a) = find_attribute(&f.attrs, "riri_hook_inline_fn") {
+                        (name, syn::parse2::<AssemblyFunctionHook>(a.meta.require_list()?.tokens.clone())?.hook_info)
+                    } else {
+                        continue
+                    }
+                },
+                syn::Item::Macro(m) => {
+                    let attr = match find_attribute(&m.attrs, "riri_hook_static") {
+                        Some(a) => a,
+                        None => continue
+                    };
+                    // riri_static!(NAME, Type)
+                    let name = match m.mac.tokens.clone().into_iter().next() {
+                        Some(proc_macro2::TokenTree::Ident(i)) => i.to_string(),
+                        _ => return Err(format!("riri_hook_static in {} should annotate riri_static!", path.display()).into())
+                    };
+                    (name, syn::parse2::<HookInfo>(attr.meta.require_list()?.tokens.clone())?)
+                },
+                syn::Item::Mod(m) => {
+                    if let Some((_, items)) = m.content {
+                        self.add_items(path, items)?;
+                    }
+                    continue
+                },
+                _ => continue
+            };
+            self.hooks.push(SourceHook { name, path: path.to_owned(), info });
+        }
+        Ok(())
+    }
+
+    /// Collect hooks from every Rust source file in a directory.
+    pub fn add_source_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "rs") {
+                let source = fs::read_to_string(entry.path())?;
+                self.add_source(entry.path(), &source)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add an executable that's already been mapped. If `label` is None, the executable is named
+    /// by its hash.
+    pub fn add_executable(&mut self, label: Option<&str>, image: PeImage) {
+        let label = match self.hashes.iter().find(|(_, v)| **v == image.get_hash()) {
+            Some((k, _)) => k.clone(),
+            None => label.map_or_else(|| format!("0x{:x}", image.get_hash()), |l| l.to_owned())
+        };
+        self.executables.push(GameExecutable { label, image });
+    }
+
+    /// Map every `.exe` file in a directory and its subdirectories.
+    pub fn add_executable_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        for entry in WalkDir::new(path.as_ref()).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() || !entry.path().extension().is_some_and(|e| e.eq_ignore_ascii_case("exe")) {
+                continue;
+            }
+            let image = PeImage::load(entry.path())
+                .map_err(|e| format!("Couldn't map {}: {}", entry.path().display(), e))?;
+            let label = entry.path().strip_prefix(path.as_ref()).unwrap_or(entry.path()).display().to_string();
+            self.add_executable(Some(&label), image);
+        }
+        Ok(())
+    }
+
+    // Arms are checked in order in the generated C#, so the first arm that matches wins
+    fn get_entry<'a>(&self, info: &'a HookInfo, hash: u64) -> Option<&'a HookEntry> {
+        info.0.iter().find(|(cond, _)| match cond {
+            HookConditional::None | HookConditional::Default => true,
+            HookConditional::HashNamed(n) => self.hashes.get(n) == Some(&hash),
+            HookConditional::HashNum(v) => *v == hash
+        }).map(|(_, e)| e)
+    }
+
+    fn check_executable(&self, executable: &GameExecutable) -> (Vec<SignatureStatus>, HashMap<usize, Vec<FuzzyMatch>>) {
+        let image = &executable.image;
+        let entries: Vec<Option<&HookEntry>> = self.hooks.iter()
+            .map(|h| self.get_entry(&h.info, image.get_hash())).collect();
+        // Scan for every signature in one pass, keeping the index of each hook's pattern
+        let mut scanner = BatchScanner::new();
+        let patterns: Vec<Option<Result<usize, String>>> = entries.iter().map(|e| match e {
+            Some(HookEntry::Dyn(d)) if !d.sig.is_empty() => Some(
+                Pattern::new(&d.sig).map(|p| scanner.add(p)).map_err(|e| e.to_string())),
+            _ => None
+        }).collect();
+        let matches = image.scan_all(&scanner);
+        let statuses: Vec<SignatureStatus> = entries.into_iter().zip(&patterns).map(|(entry, pattern)| match (entry, pattern.clone()) {
+            (None, _) => SignatureStatus::NoEntry,
+            (Some(HookEntry::Static(s)), _) => match image.resolve(Some("get_address"), s.0) {
+                Ok(o) => SignatureStatus::Found(o),
+                Err(_) => SignatureStatus::Unresolved(s.0)
+            },
+            (Some(_), None) => SignatureStatus::Skipped,
+            (Some(_), Some(Err(e))) => SignatureStatus::InvalidSignature(e),
+            (Some(HookEntry::Dyn(d)), Some(Ok(i))) => match &matches[i][..] {
+                [] => SignatureStatus::Missing { required: d.required },
+                m if m.len() > 1 && !d.allow_multiple => SignatureStatus::Ambiguous(m.to_vec()),
+                m => match image.resolve(d.resolve_type.as_deref(), m[0]) {
+                    Ok(o) => SignatureStatus::Found(o),
+                    Err(ResolveError::UserDefined(_)) => SignatureStatus::FoundUserResolver(m[0]),
+                    Err(ResolveError::OutOfBounds) => SignatureStatus::Unresolved(m[0])
+                }
+            },
+            (Some(_), Some(Ok(_))) => SignatureStatus::Skipped
+        }).collect();
+        // Look for where signatures that are missing have moved to
+        let matcher = FuzzyMatcher::from_pe_image(image);
+        let closest = statuses.iter().zip(&patterns).enumerate().filter_map(|(h, (status, pattern))| match (status, pattern) {
+            (SignatureStatus::Missing { required: true }, Some(Ok(i))) => Some((h, matcher.find_closest(&scanner.get_patterns()[*i]))),
+            _ => None
+        }).collect();
+        (statuses, closest)
+    }
+
+    /// Check every hook against every executable.
+    pub fn run(&self) -> HealthReport {
+        let by_executable: Vec<_> = self.executables.iter()
+            .map(|e| self.check_executable(e)).collect();
+        let results = (0..self.hooks.len())
+            .map(|h| by_executable.iter().map(|(e, _)| e[h].clone()).collect())
+            .collect();
+        let closest = by_executable.into_iter().enumerate()
+            .flat_map(|(e, (_, c))| c.into_iter().map(move |(h, m)| ((h, e), m)))
+            .collect();
+        HealthReport {
+            executables: self.executables.iter().map(|e| e.label.clone()).collect(),
+            hooks: self.hooks.iter().map(|h| h.name.clone()).collect(),
+            results,
+            closest
+        }
+    }
+}
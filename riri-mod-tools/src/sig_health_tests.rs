@@ -0,0 +1,115 @@
+#![cfg(test)]
+use crate::sig_health::{ SignatureHealthCheck, SignatureStatus };
+use riri_mod_tools_rt::{ pe_builder::PeBuilder, pe_image::PeImage };
+type ReturnType = Result<(), Box<dyn std::error::Error>>;
+
+// PE32+ executable with the given code in a .text section at 0x1000
+fn get_test_executable(text: &[u8]) -> Result<PeImage, Box<dyn std::error::Error>> {
+    Ok(PeImage::from_bytes(&PeBuilder::new().text(text).build())?)
+}
+
+const TEST_SOURCE: &str = r#"
+#[riri_hook_fn(dynamic_offset(
+    signature = "E9 ?? ?? ?? ?? CC",
+    calling_convention = "microsoft"
+))]
+pub unsafe extern "C" fn thunked_function(a1: usize) -> usize { original_function!(a1) }
+
+#[riri_hook_fn({
+    TEST_V1 => dynamic_offset(signature = "90 90 C3"),
+    _ => dynamic_offset(signature = "48 8B 05 ?? ?? ?? ??", resolve_type = get_indirect_address_long)
+})]
+pub unsafe extern "C" fn changed_function() {}
+
+#[riri_hook_fn(dynamic_offset(signature = "90 90", required = false))]
+pub unsafe extern "C" fn ambiguous_function() {}
+
+#[riri_hook_fn(dynamic_offset(signature = "90 90", allow_multiple = true))]
+pub unsafe extern "C" fn multiple_function() {}
+
+#[riri_hook_fn({ TEST_V2 => static_offset(4096), _ => user_defined() })]
+pub unsafe extern "C" fn static_function() {}
+
+#[riri_hook_static(dynamic_offset(signature = "48 8B 05 ?? ?? ?? ??", resolve_type = get_global_work))]
+riri_static!(GLOBAL_WORK, usize);
+
+mod inner {
+    #[riri_mod_tools_proc::riri_hook_fn(dynamic_offset(signature = "FF FF FF FF", required = false))]
+    pub unsafe extern "C" fn optional_function() {}
+}
+"#;
+
+fn get_test_check() -> Result<SignatureHealthCheck, Box<dyn std::error::Error>> {
+    let mut v1 = vec![0xccu8; 0x200];
+    // 0x1000: jmp 0x1010
+    v1[..6].copy_from_slice(&[0xe9, 0x0b, 0, 0, 0, 0xcc]);
+    v1[0x10..0x13].copy_from_slice(&[0x90, 0x90, 0xc3]);
+    v1[0x20..0x22].copy_from_slice(&[0x90, 0x90]);
+    let mut v2 = v1.clone();
+    // 0x1040: mov rax, [0x1100]
+    v2[0x40..0x47].copy_from_slice(&[0x48, 0x8b, 0x05, 0xb9, 0, 0, 0]);
+    let v1 = get_test_executable(&v1)?;
+    let v2 = get_test_executable(&v2)?;
+    let mut check = SignatureHealthCheck::new()?;
+    check.add_hashes(&format!("TEST_V1 = \"0x{:x}\"\nTEST_V2 = \"0x{:x}\"", v1.get_hash(), v2.get_hash()))?;
+    check.add_source("test.rs", TEST_SOURCE)?;
+    check.add_executable(None, v1);
+    check.add_executable(None, v2);
+    Ok(check)
+}
+
+#[test]
+fn sig_health_collect_hooks() -> ReturnType {
+    let check = get_test_check()?;
+    let names: Vec<&str> = check.get_hooks().iter().map(|h| h.get_name()).collect();
+    assert_eq!(names, ["thunked_function", "changed_function", "ambiguous_function",
+        "multiple_function", "static_function", "GLOBAL_WORK", "optional_function"]);
+    assert_eq!(check.get_hooks()[1].get_info().0.len(), 2);
+    // Executables with a known hash are named after it
+    let labels: Vec<&str> = check.get_executables().iter().map(|e| e.get_label()).collect();
+    assert_eq!(labels, ["TEST_V1", "TEST_V2"]);
+    Ok(())
+}
+
+#[test]
+fn sig_health_report() -> ReturnType {
+    let report = get_test_check()?.run();
+    let status = |hook, exe| report.get_status_by_name(hook, exe).unwrap().clone();
+    assert_eq!(status("thunked_function", "TEST_V1"), SignatureStatus::Found(0x1010));
+    assert_eq!(status("changed_function", "TEST_V1"), SignatureStatus::Found(0x1010));
+    assert_eq!(status("changed_function", "TEST_V2"), SignatureStatus::Found(0x1100));
+    assert_eq!(status("ambiguous_function", "TEST_V1"), SignatureStatus::Ambiguous(vec![0x1010, 0x1020]));
+    assert_eq!(status("multiple_function", "TEST_V1"), SignatureStatus::Found(0x1010));
+    assert_eq!(status("static_function", "TEST_V1"), SignatureStatus::Skipped);
+    assert_eq!(status("static_function", "TEST_V2"), SignatureStatus::Found(0x1000));
+    assert_eq!(status("GLOBAL_WORK", "TEST_V1"), SignatureStatus::Missing { required: true });
+    assert_eq!(status("GLOBAL_WORK", "TEST_V2"), SignatureStatus::FoundUserResolver(0x1040));
+    assert_eq!(status("optional_function", "TEST_V2"), SignatureStatus::Missing { required: false });
+    assert_eq!(report.failure_count(), 3);
+    let text = report.to_string();
+    assert!(text.starts_with(&format!("{:<20}{:<15}TEST_V2\n", "Hook", "TEST_V1")), "{}", text);
+    assert!(text.contains("\nambiguous_function  AMBIGUOUS (2)  AMBIGUOUS (2)\n"), "{}", text);
+    assert!(text.contains("\nGLOBAL_WORK on TEST_V1: Signature was not found\n"), "{}", text);
+    assert!(text.ends_with("7 hooks checked against 2 executables, 3 failed"), "{}", text);
+    Ok(())
+}
+
+#[test]
+fn sig_health_closest_matches() -> ReturnType {
+    let mut text = vec![0xccu8; 0x200];
+    // 0x1040: mov rax, [rip+0x1000]; mov eax, [rcx+0x138]
+    text[0x40..0x4d].copy_from_slice(&[0x48, 0x8b, 0x05, 0, 0x10, 0, 0, 0x8b, 0x81, 0x38, 0x01, 0, 0]);
+    let mut check = SignatureHealthCheck::new()?;
+    check.add_source("test.rs", r#"
+#[riri_hook_fn(dynamic_offset(signature = "48 8B 05 ?? ?? ?? ?? 8B 81 30 01 00 00"))]
+pub unsafe extern "C" fn moved_field() {}
+"#)?;
+    check.add_executable(Some("TEST"), get_test_executable(&text)?);
+    let report = check.run();
+    assert_eq!(report.get_status(0, 0), &SignatureStatus::Missing { required: true });
+    let closest: Vec<usize> = report.get_closest_matches(0, 0).iter().map(|m| m.get_offset()).collect();
+    assert_eq!(closest, [0x1040]);
+    let text = report.to_string();
+    assert!(text.contains("\nmoved_field on TEST: Signature was not found\n    closest match 0x1040 (95% similar), suggested signature: 48\n"), "{}", text);
+    Ok(())
+}